@@ -0,0 +1,141 @@
+use anyhow::{Context, Result, anyhow};
+use std::{
+    path::PathBuf,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use crate::sources::gradient;
+
+/// How often the focused window is looked at
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+/// A color has to stay for this long before it's applied, alt-tabbing
+/// through windows shouldn't flash the desktop
+pub const SETTLE_TIME: Duration = Duration::from_secs(6);
+/// And the wallpaper changes at most this often
+pub const MIN_CHANGE_INTERVAL: Duration = Duration::from_secs(30);
+/// Colors closer than this (sum of channel differences) count as the same
+const MIN_DIFFERENCE: u32 = 48;
+
+fn xprop(args: &[&str]) -> Result<String> {
+    let output = Command::new("xprop")
+        .args(args)
+        .output()
+        .with_context(|| "Failed to run xprop, is it installed?")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "xprop failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `_NET_ACTIVE_WINDOW` of the root window, None when nothing has focus
+fn active_window() -> Result<Option<String>> {
+    let output = xprop(&["-root", "_NET_ACTIVE_WINDOW"])?;
+    // _NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007
+    Ok(output
+        .rsplit('#')
+        .next()
+        .map(|id| id.trim().to_owned())
+        .filter(|id| id.starts_with("0x") && id != "0x0"))
+}
+
+/// Average color of the opaque pixels of `_NET_WM_ICON`, weighted by saturation
+/// so the gray outlines of most icons don't win
+fn icon_color(window: &str) -> Result<Option<[u8; 3]>> {
+    // as plain comma separated cardinals instead of xprop's ascii art
+    let output = xprop(&[
+        "-id",
+        window,
+        "-f",
+        "_NET_WM_ICON",
+        "32c",
+        " = $0+",
+        "_NET_WM_ICON",
+    ])?;
+    let Some((_, values)) = output.split_once(" = ") else {
+        return Ok(None);
+    };
+    let values: Vec<u32> = values
+        .split(',')
+        .filter_map(|value| value.trim().parse().ok())
+        .collect();
+
+    // width, height, then width * height argb pixels, only the first icon is used
+    let [width, height, pixels @ ..] = values.as_slice() else {
+        return Ok(None);
+    };
+    let count = (*width as usize * *height as usize).min(pixels.len());
+
+    let (mut sum, mut total_weight) = ([0f32; 3], 0f32);
+    for &argb in &pixels[..count] {
+        if argb >> 24 < 0x80 {
+            continue;
+        }
+        let rgb = [(argb >> 16) as u8, (argb >> 8) as u8, argb as u8];
+        let (max, min) = (*rgb.iter().max().unwrap(), *rgb.iter().min().unwrap());
+        let weight = 0.05 + (max - min) as f32 / 255.0;
+        for (sum, channel) in sum.iter_mut().zip(rgb) {
+            *sum += channel as f32 * weight;
+        }
+        total_weight += weight;
+    }
+    if total_weight == 0.0 {
+        return Ok(None);
+    }
+    Ok(Some(
+        sum.map(|channel| (channel / total_weight).round() as u8),
+    ))
+}
+
+/// Dominant color of the focused window's icon, None if it has no icon
+pub fn sample() -> Result<Option<[u8; 3]>> {
+    match active_window()? {
+        Some(window) => icon_color(&window),
+        None => Ok(None),
+    }
+}
+
+/// A gradient from `color` to a darker shade of it
+pub fn render(color: [u8; 3]) -> Result<PathBuf> {
+    let dark = color.map(|channel| channel / 5);
+    gradient::render(
+        &format!("active-{:02x}{:02x}{:02x}", color[0], color[1], color[2]),
+        color,
+        dark,
+    )
+}
+
+fn difference(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter().zip(b).map(|(a, b)| a.abs_diff(b) as u32).sum()
+}
+
+/// Turns a stream of samples into the occasional color worth applying
+#[derive(Debug, Default)]
+pub struct Debouncer {
+    pending: Option<([u8; 3], Instant)>,
+    applied: Option<([u8; 3], Instant)>,
+}
+
+impl Debouncer {
+    pub fn observe(&mut self, color: [u8; 3], now: Instant) -> Option<[u8; 3]> {
+        match self.pending {
+            Some((pending, _)) if difference(pending, color) < MIN_DIFFERENCE => {}
+            _ => self.pending = Some((color, now)),
+        }
+        let (pending, since) = self.pending?;
+        if now.duration_since(since) < SETTLE_TIME {
+            return None;
+        }
+        if let Some((applied, at)) = self.applied
+            && (difference(applied, pending) < MIN_DIFFERENCE
+                || now.duration_since(at) < MIN_CHANGE_INTERVAL)
+        {
+            return None;
+        }
+        self.applied = Some((pending, now));
+        Some(pending)
+    }
+}
@@ -0,0 +1,156 @@
+use chrono::{DateTime, Days, Local, NaiveTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Part of every day (quiet hours, night, ...), wraps around midnight if `end`
+/// is before `start`
+///
+/// ```toml
+/// [quiet_hours]
+/// start = "22:00"
+/// end = "07:00"
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    /// From two `HH:MM` text inputs, both empty means no window
+    pub fn parse_inputs(start: &str, end: &str) -> Result<Option<Self>, chrono::ParseError> {
+        match (start.trim(), end.trim()) {
+            ("", "") => Ok(None),
+            (start, end) => Ok(Some(Self {
+                start: start.parse()?,
+                end: end.parse()?,
+            })),
+        }
+    }
+
+    /// The other way around, for filling the text inputs
+    pub fn to_inputs(window: Option<Self>) -> (String, String) {
+        match window {
+            Some(window) => (
+                window.start.format("%H:%M").to_string(),
+                window.end.format("%H:%M").to_string(),
+            ),
+            None => Default::default(),
+        }
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+/// Quick pauses, for when quiet hours weren't planned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseFor {
+    OneHour,
+    TwoHours,
+    Today,
+}
+
+impl PauseFor {
+    pub const ALL: &[Self] = &[Self::OneHour, Self::TwoHours, Self::Today];
+
+    pub fn until(self, now: DateTime<Local>) -> DateTime<Local> {
+        match self {
+            Self::OneHour => now + TimeDelta::hours(1),
+            Self::TwoHours => now + TimeDelta::hours(2),
+            Self::Today => now
+                .date_naive()
+                .checked_add_days(Days::new(1))
+                .and_then(|tomorrow| {
+                    tomorrow
+                        .and_time(NaiveTime::MIN)
+                        .and_local_timezone(Local)
+                        .earliest()
+                })
+                // no midnight because of a dst change, a day is close enough
+                .unwrap_or(now + TimeDelta::days(1)),
+        }
+    }
+}
+
+impl fmt::Display for PauseFor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::OneHour => "1h",
+            Self::TwoHours => "2h",
+            Self::Today => "Today",
+        })
+    }
+}
+
+/// Why automatic changes and notifications are held back right now, if they are
+pub fn paused_reason(
+    quiet_hours: Option<TimeWindow>,
+    paused_until: Option<DateTime<Local>>,
+    now: DateTime<Local>,
+) -> Option<String> {
+    if let Some(until) = paused_until.filter(|until| now < *until) {
+        return Some(format!("paused until {}", until.format("%H:%M")));
+    }
+    quiet_hours
+        .filter(|quiet| quiet.contains(now.time()))
+        .map(|quiet| format!("quiet hours ({quiet})"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn time(hour: u32, min: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, min, 0).unwrap()
+    }
+
+    #[test]
+    fn windows_wrap_around_midnight() {
+        let night = TimeWindow::parse_inputs("22:00", "07:00").unwrap().unwrap();
+        assert!(night.contains(time(22, 0)));
+        assert!(night.contains(time(0, 0)));
+        assert!(night.contains(time(6, 59)));
+        assert!(!night.contains(time(7, 0)));
+        assert!(!night.contains(time(12, 0)));
+
+        let lunch = TimeWindow::parse_inputs("12:00", "13:00").unwrap().unwrap();
+        assert!(lunch.contains(time(12, 30)));
+        assert!(!lunch.contains(time(13, 0)));
+        assert!(!lunch.contains(time(11, 59)));
+    }
+
+    #[test]
+    fn equal_ends_are_an_empty_window() {
+        let window = TimeWindow::parse_inputs("08:00", "08:00").unwrap().unwrap();
+        assert!(!window.contains(time(8, 0)));
+        assert!(!window.contains(time(20, 0)));
+        assert_eq!(TimeWindow::parse_inputs(" ", "").unwrap(), None);
+        assert!(TimeWindow::parse_inputs("8", "").is_err());
+    }
+
+    #[test]
+    fn pausing_for_today_lasts_until_midnight() {
+        let at = |day, hour, min| Local.with_ymd_and_hms(2026, 10, day, hour, min, 0).unwrap();
+        assert_eq!(PauseFor::Today.until(at(16, 22, 30)), at(17, 0, 0));
+        assert_eq!(PauseFor::Today.until(at(16, 0, 0)), at(17, 0, 0));
+        assert_eq!(PauseFor::TwoHours.until(at(16, 23, 0)), at(17, 1, 0));
+    }
+}
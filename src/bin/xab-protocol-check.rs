@@ -0,0 +1,478 @@
+//! Connects to xab and checks that it speaks the protocol the way xab-gui
+//! expects it to, see `ipc_spec`. It only asks for things unless it's told to
+//! change the desktop, so it's safe to run against a daemon that's in use
+
+// shared with xab-gui, which uses the rest of it
+#[allow(dead_code)]
+#[path = "../ipc_spec.rs"]
+mod ipc_spec;
+
+use anyhow::{Context, Result, anyhow};
+use bytes::{Buf, Bytes};
+use clap::Parser;
+use std::{
+    collections::{HashSet, VecDeque},
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
+
+use ipc_spec::*;
+
+/// For every read and write, xab answers right away or not at all
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Checks that an xab daemon speaks the protocol xab-gui expects"
+)]
+struct Args {
+    #[arg(default_value = IPC_PATH)]
+    socket: String,
+    /// Also set this file as the background of every monitor
+    #[arg(long)]
+    background: Option<PathBuf>,
+    /// Also toggle the videos twice, which leaves them the way they were
+    #[arg(long)]
+    playback: bool,
+}
+
+enum Outcome {
+    Pass(String),
+    Fail(String),
+    Skip(String),
+}
+
+#[derive(Default)]
+struct Report {
+    checks: Vec<(&'static str, Outcome)>,
+}
+
+impl Report {
+    fn check(&mut self, name: &'static str, res: Result<String>) {
+        let outcome = match res {
+            Ok(details) => Outcome::Pass(details),
+            Err(err) => Outcome::Fail(format!("{:#}", err)),
+        };
+        self.checks.push((name, outcome));
+    }
+
+    fn skip(&mut self, name: &'static str, why: &str) {
+        self.checks.push((name, Outcome::Skip(why.to_owned())));
+    }
+
+    fn print(&self) -> ExitCode {
+        let width = self.checks.iter().map(|(name, _)| name.len()).max();
+        let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+        for (name, outcome) in &self.checks {
+            let (label, details) = match outcome {
+                Outcome::Pass(details) => {
+                    passed += 1;
+                    ("PASS", details)
+                }
+                Outcome::Fail(details) => {
+                    failed += 1;
+                    ("FAIL", details)
+                }
+                Outcome::Skip(details) => {
+                    skipped += 1;
+                    ("SKIP", details)
+                }
+            };
+            println!(
+                "  {label}  {name:width$}  {details}",
+                width = width.unwrap_or_default()
+            );
+        }
+        println!("{passed} passed, {failed} failed, {skipped} skipped");
+        match failed {
+            0 => ExitCode::SUCCESS,
+            _ => ExitCode::FAILURE,
+        }
+    }
+}
+
+/// A blocking connection, the check reads every byte itself instead of going
+/// through `IpcHandle` so nothing the gui tolerates goes unnoticed
+struct Connection {
+    socket: UnixStream,
+    /// The newest xab speaks
+    version: i32,
+    /// The one agreed on, see `negotiate_version`
+    protocol: i32,
+    /// As sent, including bits this check doesn't know
+    raw_capabilities: u32,
+    capabilities: IpcXabCapabilities,
+    /// Events that came in while waiting for a reply
+    events: VecDeque<IpcEvent>,
+}
+
+impl Connection {
+    fn open(path: &str) -> Result<Self> {
+        let mut socket =
+            UnixStream::connect(path).with_context(|| format!("Failed to connect to {path}"))?;
+        socket.set_read_timeout(Some(TIMEOUT))?;
+        socket.set_write_timeout(Some(TIMEOUT))?;
+
+        let mut buf = [0u8; 4];
+        socket
+            .read_exact(&mut buf)
+            .with_context(|| "xab didn't send its protocol version")?;
+        let version = i32::from_be_bytes(buf);
+        let protocol = negotiate_version(version)?;
+        socket.write_all(&protocol.to_be_bytes())?;
+        socket
+            .read_exact(&mut buf)
+            .with_context(|| format!("xab didn't send its capabilities for protocol {protocol}"))?;
+        let raw_capabilities = u32::from_be_bytes(buf);
+        Ok(Self {
+            socket,
+            version,
+            protocol,
+            raw_capabilities,
+            capabilities: IpcXabCapabilities::from_bits_truncate(raw_capabilities),
+            events: VecDeque::new(),
+        })
+    }
+
+    fn send(&mut self, command: IpcCommands, payload: &[u8]) -> Result<()> {
+        let mut bytes = (command as i32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(payload);
+        self.socket
+            .write_all(&bytes)
+            .with_context(|| format!("Failed to send {command:?}"))
+    }
+
+    fn read_i32(&mut self, what: &str) -> Result<i32> {
+        let mut buf = [0u8; 4];
+        self.socket
+            .read_exact(&mut buf)
+            .with_context(|| format!("No {what}"))?;
+        Ok(i32::from_be_bytes(buf))
+    }
+
+    /// The next reply, on connections that get events the ones in front of
+    /// it are kept for `read_event`
+    fn read_reply(&mut self) -> Result<Bytes> {
+        if self.capabilities.contains(IpcXabCapabilities::Events) {
+            loop {
+                match self.read_i32("reply")? {
+                    REPLY_TAG => break,
+                    id => {
+                        let event = self.read_event_body(id)?;
+                        self.events.push_back(event);
+                    }
+                }
+            }
+        }
+        self.read_frame()
+    }
+
+    fn read_frame(&mut self) -> Result<Bytes> {
+        let mut header = [0u8; FRAME_HEADER_SIZE];
+        self.socket
+            .read_exact(&mut header)
+            .with_context(|| "No reply")?;
+        let mut payload = vec![0u8; decode_frame_header(header)?];
+        self.socket.read_exact(&mut payload).with_context(|| {
+            format!(
+                "Reply is shorter than the {} bytes it claims",
+                payload.len()
+            )
+        })?;
+        Ok(Bytes::from(payload))
+    }
+
+    /// Sends `command` and reads its reply, see `decode_reply`
+    fn request(&mut self, command: IpcCommands) -> Result<Bytes> {
+        self.send(command, &[])?;
+        let reply = self.read_reply()?;
+        decode_reply(reply, self.capabilities)
+    }
+
+    /// Sends `command`, with `IpcXabCapabilities::ErrorReplies` its status is
+    /// read too
+    fn command(&mut self, command: IpcCommands, payload: &[u8]) -> Result<()> {
+        self.send(command, payload)?;
+        if self.capabilities.contains(IpcXabCapabilities::ErrorReplies) {
+            let status = self.read_reply()?;
+            decode_reply(status, self.capabilities)?;
+        }
+        Ok(())
+    }
+
+    /// GetMonitors, read a chunk at a time with `IpcXabCapabilities::MonitorChunks`
+    fn monitors(&mut self) -> Result<Vec<Monitor>> {
+        if !self
+            .capabilities
+            .contains(IpcXabCapabilities::MonitorChunks)
+        {
+            let reply = self.request(IpcCommands::GetMonitors)?;
+            return decode_monitors(reply, self.capabilities);
+        }
+        self.send(IpcCommands::GetMonitors, &[])?;
+        let mut monitors = Vec::new();
+        loop {
+            let chunk = decode_reply(self.read_reply()?, self.capabilities)?;
+            let (chunk, more) = decode_monitor_chunk(chunk, self.capabilities)?;
+            monitors.extend(chunk);
+            if !more {
+                return Ok(monitors);
+            }
+        }
+    }
+
+    /// The next event, including ones read while waiting for a reply
+    fn read_event(&mut self) -> Result<IpcEvent> {
+        if let Some(event) = self.events.pop_front() {
+            return Ok(event);
+        }
+        match self.read_i32("event")? {
+            REPLY_TAG => Err(anyhow!("got a reply nothing was asked for")),
+            id => self.read_event_body(id),
+        }
+    }
+
+    /// Reads the rest of event `id` the way `IpcEvent::decode` expects it
+    fn read_event_body(&mut self, id: i32) -> Result<IpcEvent> {
+        let event = IpcEvents::from_id(id).ok_or_else(|| anyhow!("Unknown event {id}"))?;
+        let mut bytes = id.to_be_bytes().to_vec();
+        let size = match event.payload_size() {
+            Some(size) => size,
+            None => {
+                let mut header = [0u8; FRAME_HEADER_SIZE];
+                self.socket.read_exact(&mut header)?;
+                bytes.extend_from_slice(&header);
+                decode_frame_header(header)?
+            }
+        };
+        let start = bytes.len();
+        bytes.resize(start + size, 0);
+        self.socket
+            .read_exact(&mut bytes[start..])
+            .with_context(|| format!("{event:?} is shorter than it claims"))?;
+        let mut buf = Bytes::from(bytes);
+        let decoded = IpcEvent::decode(&mut buf, self.capabilities)?;
+        if buf.has_remaining() {
+            return Err(anyhow!("{} bytes after {event:?}", buf.remaining()));
+        }
+        Ok(decoded)
+    }
+}
+
+/// Capabilities that don't mean anything without another one
+fn check_claims(capabilities: IpcXabCapabilities) -> Result<String> {
+    const DEPENDS: &[(IpcXabCapabilities, IpcXabCapabilities)] = &[
+        (
+            IpcXabCapabilities::Privileged,
+            IpcXabCapabilities::PrivilegedControl,
+        ),
+        (
+            IpcXabCapabilities::MonitorLists,
+            IpcXabCapabilities::Monitors,
+        ),
+        (
+            IpcXabCapabilities::MonitorNames,
+            IpcXabCapabilities::Monitors,
+        ),
+        (IpcXabCapabilities::Screens, IpcXabCapabilities::Monitors),
+        (
+            IpcXabCapabilities::MonitorChunks,
+            IpcXabCapabilities::Monitors,
+        ),
+        (
+            IpcXabCapabilities::PerMonitorPlayback,
+            IpcXabCapabilities::Monitors,
+        ),
+    ];
+    let broken: Vec<String> = DEPENDS
+        .iter()
+        .filter(|(claim, needs)| capabilities.contains(*claim) && !capabilities.contains(*needs))
+        .map(|(claim, needs)| format!("{claim:?} without {needs:?}"))
+        .collect();
+    match broken.is_empty() {
+        true => Ok("consistent".to_owned()),
+        false => Err(anyhow!("claims {}", broken.join(", "))),
+    }
+}
+
+fn check_monitors(monitors: &[Monitor]) -> Result<String> {
+    if monitors.is_empty() {
+        return Err(anyhow!("no monitors"));
+    }
+    let mut seen = HashSet::new();
+    for monitor in monitors {
+        if !seen.insert(monitor.index) {
+            return Err(anyhow!("monitor {} is listed twice", monitor.index));
+        }
+        if monitor.width == 0 || monitor.height == 0 {
+            return Err(anyhow!(
+                "monitor {} is {}x{}",
+                monitor.index,
+                monitor.width,
+                monitor.height
+            ));
+        }
+    }
+    let labels: Vec<String> = monitors
+        .iter()
+        .map(|monitor| format!("{} {}x{}", monitor.label(), monitor.width, monitor.height))
+        .collect();
+    Ok(labels.join(", "))
+}
+
+/// xab hanging up or going quiet after a command is how it usually fails
+fn check_alive(connection: &mut Connection) -> Result<String> {
+    connection.request(IpcCommands::GetAllBackgrounds)?;
+    Ok("still answering".to_owned())
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    println!("Checking {}", args.socket);
+    let mut report = Report::default();
+
+    let mut connection = match Connection::open(&args.socket) {
+        Ok(connection) => connection,
+        Err(err) => {
+            report.check("handshake", Err(err));
+            return report.print();
+        }
+    };
+    report.check(
+        "handshake",
+        Ok(format!(
+            "protocol {} (xab speaks up to {}), capabilities {:#x}",
+            connection.protocol, connection.version, connection.raw_capabilities
+        )),
+    );
+    let unknown = connection.raw_capabilities & !IpcXabCapabilities::all().bits();
+    report.check(
+        "capabilities",
+        Ok(match unknown {
+            0 => format!("{:?}", connection.capabilities),
+            _ => format!(
+                "{:?}, and {unknown:#x} this check doesn't know",
+                connection.capabilities
+            ),
+        }),
+    );
+    report.check("capability claims", check_claims(connection.capabilities));
+
+    if connection.capabilities.contains(IpcXabCapabilities::Events) {
+        // monitors are pushed right after the handshake
+        report.check(
+            "monitors event",
+            connection.read_event().and_then(|event| match event {
+                IpcEvent::MonitorsChanged(monitors) => check_monitors(&monitors),
+                event => Err(anyhow!("expected the monitors, got {event:?}")),
+            }),
+        );
+    }
+    let capabilities = connection.capabilities;
+    match capabilities
+        .contains(IpcXabCapabilities::Monitors | IpcXabCapabilities::CustomPositioning)
+    {
+        true => report.check(
+            "GetMonitors",
+            connection
+                .monitors()
+                .and_then(|monitors| check_monitors(&monitors)),
+        ),
+        false => report.skip("GetMonitors", "xab doesn't claim monitors"),
+    }
+    report.check(
+        "GetAllBackgrounds",
+        connection
+            .request(IpcCommands::GetAllBackgrounds)
+            .map(|reply| format!("{} byte reply", reply.len())),
+    );
+    let raw_capabilities = connection.raw_capabilities;
+    report.check(
+        "GetCapabilites",
+        connection
+            .request(IpcCommands::GetCapabilites)
+            .and_then(|mut reply| {
+                if reply.len() != 4 {
+                    return Err(anyhow!("{} byte reply, expected 4", reply.len()));
+                }
+                match reply.get_u32() {
+                    claimed if claimed == raw_capabilities => {
+                        Ok("matches the handshake".to_owned())
+                    }
+                    claimed => Err(anyhow!(
+                        "{claimed:#x}, the handshake said {raw_capabilities:#x}"
+                    )),
+                }
+            }),
+    );
+    match capabilities.contains(IpcXabCapabilities::Formats) {
+        true => report.check(
+            "GetFormats",
+            connection
+                .request(IpcCommands::GetFormats)
+                .and_then(decode_formats)
+                .and_then(|formats| match formats.is_empty() {
+                    true => Err(anyhow!("claims formats but lists none")),
+                    false => Ok(formats.join(", ")),
+                }),
+        ),
+        false => report.skip("GetFormats", "xab doesn't claim formats"),
+    }
+    match capabilities.contains(IpcXabCapabilities::BackgroundState) {
+        true => report.check(
+            "GetBackgroundState",
+            connection
+                .request(IpcCommands::GetBackgroundState)
+                .and_then(decode_all::<BackgroundState>)
+                .map(|states| format!("{} monitor(s)", states.len())),
+        ),
+        false => report.skip("GetBackgroundState", "xab doesn't claim background state"),
+    }
+
+    match args.playback {
+        true => {
+            let toggled = connection
+                .command(IpcCommands::TogglePauseVideo, &[])
+                .and_then(|()| connection.command(IpcCommands::TogglePauseVideo, &[]));
+            report.check(
+                "TogglePauseVideo",
+                toggled.and_then(|()| check_alive(&mut connection)),
+            );
+        }
+        false => report.skip("TogglePauseVideo", "only with --playback"),
+    }
+    match &args.background {
+        Some(path) => {
+            let sent = encode_change_background(
+                None,
+                &Screens::new(),
+                path,
+                ScalingMode::default(),
+                VideoOptions::default(),
+                connection.capabilities,
+            )
+            .and_then(|payload| connection.command(IpcCommands::ChangeBackground, &payload));
+            report.check(
+                "ChangeBackground",
+                sent.and_then(|()| check_alive(&mut connection)),
+            );
+        }
+        None => report.skip("ChangeBackground", "only with --background"),
+    }
+    report.skip(
+        "Restart, Shutdown",
+        "never sent, they'd take the desktop down",
+    );
+
+    report.check(
+        "ClientDisconnect",
+        connection
+            .send(IpcCommands::ClientDisconnect, &[])
+            .map(|()| "sent".to_owned()),
+    );
+    report.print()
+}
@@ -0,0 +1,73 @@
+use anyhow::Result;
+use iced::widget::image::Handle;
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use crate::sources::{Location, WallpaperSource, channel::Channel};
+
+/// Thumbnails decoded at a time, the next ones once the user asks for more
+pub const PAGE_SIZE: usize = 48;
+
+/// The library page: the wallpapers in the configured directories and the
+/// thumbnails decoded so far
+#[derive(Debug, Default)]
+pub struct Browser {
+    pub files: Vec<PathBuf>,
+    /// How many of `files` are on the page
+    pub shown: usize,
+    /// None while it's decoded
+    pub thumbnails: HashMap<PathBuf, Option<Result<Handle, String>>>,
+    pub scanning: bool,
+}
+
+impl Browser {
+    /// Swaps in a new scan, keeping the thumbnails of files that are still there
+    pub fn scanned(&mut self, files: Vec<PathBuf>) {
+        self.thumbnails
+            .retain(|path, thumbnail| thumbnail.is_some() && files.contains(path));
+        self.files = files;
+        self.shown = 0;
+        self.scanning = false;
+    }
+
+    /// Puts the next page on, returns the files that need a thumbnail
+    pub fn show_more(&mut self) -> Vec<PathBuf> {
+        let end = (self.shown + PAGE_SIZE).min(self.files.len());
+        let missing: Vec<PathBuf> = self.files[self.shown..end]
+            .iter()
+            .filter(|path| !self.thumbnails.contains_key(*path))
+            .cloned()
+            .collect();
+        for path in &missing {
+            self.thumbnails.insert(path.clone(), None);
+        }
+        self.shown = end;
+        missing
+    }
+
+    pub fn shown(&self) -> &[PathBuf] {
+        &self.files[..self.shown]
+    }
+}
+
+/// Every wallpaper under `dirs`, walked the same way a channel is
+pub fn scan(dirs: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for dir in dirs {
+        files.extend(
+            Channel::new(dir.clone())
+                .list()?
+                .into_iter()
+                .filter_map(|wallpaper| match wallpaper.location {
+                    Location::Local(path) => Some(path),
+                    _ => None,
+                }),
+        );
+    }
+    // directories inside other ones would list files twice
+    let mut seen = HashSet::new();
+    files.retain(|path| seen.insert(path.clone()));
+    Ok(files)
+}
@@ -0,0 +1,335 @@
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, warn};
+
+use crate::{
+    config::Config,
+    hooks,
+    ipc::{IpcHandle, default_socket_path},
+    ipc_spec::MonitorTarget,
+    library::pack,
+    limits, persist,
+    provenance::{self, Provenance},
+};
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "xab gui in rust",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    /// Wallpapers to open, handed over to the running instance if there is one
+    pub files: Vec<PathBuf>,
+
+    /// Print machine readable json instead of text
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Connect to xab and apply the saved backgrounds (meant for wm autostart)
+    ApplySaved {
+        /// Exit after applying instead of opening the gui
+        #[arg(long)]
+        exit: bool,
+    },
+    /// Connect to xab and print the connection status and command latencies
+    Status,
+    /// Import a wallpaper pack (zip or tar archive) into the library
+    ImportPack { archive: PathBuf },
+    /// Print where the wallpapers on the desktop came from, to go with screenshots
+    Credits,
+    /// Set the background of every monitor, or of one of them
+    SetBackground {
+        file: PathBuf,
+        /// Monitor number as list-monitors prints them, output name,
+        /// "primary" or "all"
+        #[arg(long)]
+        monitor: Option<MonitorTarget>,
+    },
+    /// Take the wallpaper off every monitor, or off one of them, leaving the
+    /// configured clear color
+    ClearBackground {
+        /// Like set-background's
+        #[arg(long)]
+        monitor: Option<MonitorTarget>,
+    },
+    /// Print the monitors xab knows about
+    ListMonitors,
+    /// Pause video wallpapers
+    Pause,
+    /// Resume video wallpapers
+    Resume,
+    /// Pause or resume video wallpapers
+    TogglePause,
+}
+
+/// The ipc connection needs a tokio runtime, the gui gets one from iced
+fn block_on<T>(future: impl Future<Output = Result<T>>) -> Result<T> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(future)
+}
+
+fn config() -> Config {
+    Config::load().unwrap_or_else(|err| {
+        warn!("Failed to load config, using the default one: {:?}", err);
+        Config::default()
+    })
+}
+
+/// Connects the way the gui does, to the configured socket if there is one
+async fn connect(config: &Config) -> Result<IpcHandle> {
+    let path = config.socket.clone().unwrap_or_else(default_socket_path);
+    IpcHandle::with_timeouts(&path, config.timeouts()).await
+}
+
+/// Runs `command` on a fresh connection and closes it
+fn with_xab<T>(command: impl AsyncFnOnce(&IpcHandle, &Config) -> Result<T>) -> Result<T> {
+    let config = config();
+    block_on(async {
+        let ipc_handle = connect(&config).await?;
+        let res = command(&ipc_handle, &config).await;
+        ipc_handle.close().await?;
+        res
+    })
+}
+
+pub fn apply_saved() -> Result<()> {
+    let state = persist::state()?;
+    if state.assignments.is_empty() {
+        warn!("No saved backgrounds to apply");
+        return Ok(());
+    }
+
+    with_xab(async |ipc_handle, _| {
+        for assignment in &state.assignments {
+            ipc_handle
+                .change_background_scaled(assignment.monitor, &assignment.path, assignment.scaling)
+                .await?;
+        }
+        debug!("Applied {} saved background(s)", state.assignments.len());
+        Ok(())
+    })
+}
+
+/// The index of `monitor`, every monitor if not given
+async fn resolve(ipc_handle: &IpcHandle, monitor: Option<MonitorTarget>) -> Result<Option<i32>> {
+    match monitor.unwrap_or(MonitorTarget::All) {
+        target @ (MonitorTarget::All | MonitorTarget::Index(_)) => target.resolve(&[]),
+        target => target.resolve(&ipc_handle.get_monitors().await),
+    }
+}
+
+/// Through the apply hooks and remembered like it was set from the gui
+pub fn set_background(file: &Path, monitor: Option<MonitorTarget>) -> Result<()> {
+    let path = fs::canonicalize(file).with_context(|| format!("Can't find {}", file.display()))?;
+    with_xab(async |ipc_handle, config| {
+        let monitor = resolve(ipc_handle, monitor).await?;
+        let path = hooks::apply(ipc_handle, &config.hooks, monitor.as_slice(), path).await?;
+        let scaling = ipc_handle.scaling(monitor);
+        persist::update(|state| state.assign(monitor, path, scaling))
+    })
+}
+
+/// Not saved, `apply-saved` brings the wallpaper back
+pub fn clear_background(monitor: Option<MonitorTarget>) -> Result<()> {
+    with_xab(async |ipc_handle, config| {
+        let monitor = resolve(ipc_handle, monitor).await?;
+        ipc_handle
+            .clear_backgrounds(monitor.as_slice(), config.clear_color())
+            .await
+    })
+}
+
+#[derive(Serialize)]
+struct MonitorStatus {
+    number: i32,
+    name: Option<String>,
+    model: Option<String>,
+    screen: u32,
+    primary: bool,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+pub fn list_monitors(json: bool) -> Result<()> {
+    let monitors: Vec<MonitorStatus> = with_xab(async |ipc_handle, _| {
+        Ok(ipc_handle
+            .get_monitors()
+            .await
+            .into_iter()
+            .map(|monitor| MonitorStatus {
+                number: monitor.index + 1,
+                name: monitor.name,
+                model: monitor.model,
+                screen: monitor.screen,
+                primary: monitor.primary,
+                x: monitor.x,
+                y: monitor.y,
+                width: monitor.width,
+                height: monitor.height,
+            })
+            .collect())
+    })?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&monitors)?);
+        return Ok(());
+    }
+    for monitor in &monitors {
+        let label = [monitor.name.as_deref(), monitor.model.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!(
+            "{}: {}x{}+{}+{}{}{}",
+            monitor.number,
+            monitor.width,
+            monitor.height,
+            monitor.x,
+            monitor.y,
+            if monitor.primary { " primary" } else { "" },
+            if label.is_empty() {
+                String::new()
+            } else {
+                format!(" {label}")
+            }
+        );
+    }
+    Ok(())
+}
+
+/// Some to pause or resume, None to toggle
+pub fn pause_videos(paused: Option<bool>) -> Result<()> {
+    with_xab(async |ipc_handle, _| match paused {
+        Some(paused) => ipc_handle.set_videos_paused(paused).await,
+        None => ipc_handle.toggle_pause_videos().await,
+    })
+}
+
+#[derive(Serialize)]
+struct CommandStatus {
+    command: String,
+    count: u32,
+    last_us: u128,
+    average_us: u128,
+    max_us: u128,
+}
+
+#[derive(Serialize)]
+struct Status {
+    path: String,
+    capabilities: Vec<String>,
+    handshake_us: u128,
+    commands: Vec<CommandStatus>,
+}
+
+pub fn status(json: bool) -> Result<()> {
+    let status = with_xab(async |ipc_handle, _| {
+        Ok(Status {
+            path: ipc_handle.path.clone(),
+            capabilities: ipc_handle
+                .capabilities()
+                .iter_names()
+                .map(|(name, _)| name.to_owned())
+                .collect(),
+            handshake_us: ipc_handle.handshake_latency.as_micros(),
+            commands: ipc_handle
+                .latencies()
+                .into_iter()
+                .map(|(command, latency)| CommandStatus {
+                    command: format!("{:?}", command),
+                    count: latency.count,
+                    last_us: latency.last.as_micros(),
+                    average_us: latency.average().as_micros(),
+                    max_us: latency.max.as_micros(),
+                })
+                .collect(),
+        })
+    })?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+        return Ok(());
+    }
+
+    println!("connected to: {}", status.path);
+    println!("capabilities: {}", status.capabilities.join(", "));
+    println!("handshake: {}us", status.handshake_us);
+    for command in &status.commands {
+        println!(
+            "{}: {} sent, last {}us, avg {}us, max {}us",
+            command.command, command.count, command.last_us, command.average_us, command.max_us
+        );
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Credit {
+    monitor: Option<i32>,
+    path: String,
+    #[serde(flatten)]
+    provenance: Provenance,
+    attribution: String,
+}
+
+pub fn credits(json: bool) -> Result<()> {
+    let mut credits = Vec::new();
+    for assignment in persist::state()?.assignments {
+        // local files are the user's own, nothing to credit
+        let Some(provenance) = provenance::of(&assignment.path)? else {
+            continue;
+        };
+        credits.push(Credit {
+            monitor: assignment.monitor,
+            path: assignment.path.display().to_string(),
+            attribution: provenance.attribution(),
+            provenance,
+        });
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&credits)?);
+        return Ok(());
+    }
+    for credit in &credits {
+        match credit.monitor {
+            Some(monitor) => println!("monitor {}: {}", monitor + 1, credit.attribution),
+            None => println!("{}", credit.attribution),
+        }
+    }
+    Ok(())
+}
+
+pub fn import_pack(archive: &Path) -> Result<()> {
+    limits::set(config().limits);
+    let report = pack::import(archive, |progress| {
+        if progress.total_bytes > 0 {
+            eprint!("\rchecking for duplicates: {}%", progress.percent());
+        }
+    })?;
+    println!(
+        "\rimported {} wallpaper(s) from {} into {} ({} duplicate(s) skipped)",
+        report.imported,
+        report.pack,
+        report.dir.display(),
+        report.duplicates
+    );
+    Ok(())
+}
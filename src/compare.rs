@@ -0,0 +1,110 @@
+use iced::{widget::image, window};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{slideshow, sources::Wallpaper};
+
+/// How many wallpapers are compared at once (if the source has that many)
+pub const CANDIDATES: usize = 3;
+/// How long each candidate stays on the desktop while trying them out
+pub const TRY_DURATION: Duration = Duration::from_secs(4);
+
+#[derive(Debug)]
+pub struct Candidate {
+    pub wallpaper: Wallpaper,
+    /// Set once fetched
+    pub path: Option<PathBuf>,
+    pub handle: Option<image::Handle>,
+    pub error: Option<String>,
+}
+
+impl Candidate {
+    fn new(wallpaper: Wallpaper) -> Self {
+        Self {
+            wallpaper,
+            path: None,
+            handle: None,
+            error: None,
+        }
+    }
+}
+
+/// A window showing a few wallpapers side by side, the one picked is applied
+#[derive(Debug)]
+pub struct Comparison {
+    pub window: window::Id,
+    pub source_id: String,
+    pub candidates: Vec<Candidate>,
+    /// Candidate that's on the desktop while trying them out, as an
+    /// `OverrideKind::Trying`
+    pub trying: Option<usize>,
+}
+
+impl Comparison {
+    pub fn new(window: window::Id, source_id: String, wallpapers: Vec<Wallpaper>) -> Self {
+        let mut comparison = Self {
+            window,
+            source_id,
+            candidates: Vec::new(),
+            trying: None,
+        };
+        comparison.replace(wallpapers);
+        comparison
+    }
+
+    /// Swaps the candidates for new ones
+    pub fn replace(&mut self, wallpapers: Vec<Wallpaper>) {
+        self.candidates = wallpapers.into_iter().map(Candidate::new).collect();
+        self.trying = None;
+    }
+
+    pub fn fetched(&mut self, wallpaper: &Wallpaper, res: Result<PathBuf, String>) {
+        // might've been shuffled away in the meantime
+        let Some(candidate) = self
+            .candidates
+            .iter_mut()
+            .find(|c| c.wallpaper == *wallpaper)
+        else {
+            return;
+        };
+        match res {
+            Ok(path) => {
+                candidate.handle =
+                    slideshow::is_previewable(&path).then(|| image::Handle::from_path(&path));
+                candidate.path = Some(path);
+            }
+            Err(err) => candidate.error = Some(err),
+        }
+    }
+
+    pub fn path(&self, index: usize) -> Option<&Path> {
+        self.candidates.get(index)?.path.as_deref()
+    }
+
+    /// Moves on to the next candidate, `None` once all of them had their turn
+    pub fn try_next(&mut self) -> Option<usize> {
+        self.trying = self
+            .trying
+            .map(|i| i + 1)
+            .filter(|&i| i < self.candidates.len());
+        self.trying
+    }
+}
+
+/// Up to `count` distinct wallpapers, picked at random using `seed`
+pub fn pick_candidates(wallpapers: &[Wallpaper], count: usize, seed: u64) -> Vec<Wallpaper> {
+    // xorshift gets stuck on 0
+    let mut seed = seed | 1;
+    let mut pool: Vec<&Wallpaper> = wallpapers.iter().collect();
+    let mut picked = Vec::new();
+    while picked.len() < count && !pool.is_empty() {
+        // plenty random for this
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        picked.push(pool.swap_remove(seed as usize % pool.len()).clone());
+    }
+    picked
+}
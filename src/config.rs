@@ -0,0 +1,382 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, fs, path::PathBuf, time::Duration};
+use tracing::{debug, error, warn};
+
+use crate::{
+    automation::TimeWindow,
+    coordinator::Precedence,
+    disk_cache::DEFAULT_LIMIT_MB,
+    hooks::Hooks,
+    hotkeys::{HotkeyBindings, MediaKeysOwner},
+    ipc::Timeouts,
+    ipc_spec::MonitorTarget,
+    limits::Limits,
+    net::{Bandwidth, Proxies},
+    nightlight::NightDimming,
+    notify::NotificationMode,
+    panels::Panels,
+    preview::DEFAULT_CACHE_MB,
+    rules::{self, Rule},
+    scheduler::Rotation,
+    sources::filter::ContentFilters,
+    span::Bezels,
+    state::write_atomic,
+    status::StatusPalette,
+    templates::HexColor,
+    tunnel::SshProfile,
+    xdg,
+};
+
+/// Look of the gui, a few of iced's built in themes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppTheme {
+    #[default]
+    Dark,
+    Light,
+    Nord,
+    Dracula,
+    GruvboxDark,
+    TokyoNight,
+}
+
+impl AppTheme {
+    pub const ALL: &[Self] = &[
+        Self::Dark,
+        Self::Light,
+        Self::Nord,
+        Self::Dracula,
+        Self::GruvboxDark,
+        Self::TokyoNight,
+    ];
+
+    pub fn theme(self) -> iced::Theme {
+        match self {
+            Self::Dark => iced::Theme::Dark,
+            Self::Light => iced::Theme::Light,
+            Self::Nord => iced::Theme::Nord,
+            Self::Dracula => iced::Theme::Dracula,
+            Self::GruvboxDark => iced::Theme::GruvboxDark,
+            Self::TokyoNight => iced::Theme::TokyoNight,
+        }
+    }
+}
+
+impl fmt::Display for AppTheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Dark => "Dark",
+            Self::Light => "Light",
+            Self::Nord => "Nord",
+            Self::Dracula => "Dracula",
+            Self::GruvboxDark => "Gruvbox dark",
+            Self::TokyoNight => "Tokyo night",
+        })
+    }
+}
+
+/// Where connecting lands
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartPage {
+    #[default]
+    Monitors,
+    Library,
+    /// Whichever of the two was open last, see `SavedState::last_page`
+    LastUsed,
+}
+
+impl StartPage {
+    pub const ALL: &[Self] = &[Self::Monitors, Self::Library, Self::LastUsed];
+}
+
+impl fmt::Display for StartPage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Monitors => "Monitors",
+            Self::Library => "Library",
+            Self::LastUsed => "Wherever I left off",
+        })
+    }
+}
+
+/// User settings, unlike `SavedState` this is only written when the user
+/// changes a setting
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// Preferred over the other sockets xab leaves around, and connected to
+    /// when none are found
+    #[serde(default)]
+    pub socket: Option<String>,
+    #[serde(default)]
+    pub theme: AppTheme,
+    #[serde(default)]
+    pub start_page: StartPage,
+    /// How the connected page is arranged
+    #[serde(default)]
+    pub panels: Panels,
+    /// Closing the main window leaves hotkeys, rotation and the rest running
+    #[serde(default)]
+    pub close_to_background: bool,
+    /// Colors of the connection, apply and error indicators
+    #[serde(default)]
+    pub palette: StatusPalette,
+    /// Where the file picker opens, follows the last picked file
+    #[serde(default)]
+    pub last_directory: Option<PathBuf>,
+    /// Selected when the gui starts, every monitor if unset
+    #[serde(default)]
+    pub default_monitor: Option<i32>,
+    /// Applied when connecting, by anything `MonitorTarget` takes: a
+    /// monitor number, an output name, `"primary"` or `"all"`
+    #[serde(default)]
+    pub wallpapers: BTreeMap<String, PathBuf>,
+    #[serde(default)]
+    pub hotkeys: HotkeyBindings,
+    #[serde(default)]
+    pub media_keys: MediaKeysOwner,
+    /// No automatic changes or notifications in this window
+    #[serde(default)]
+    pub quiet_hours: Option<TimeWindow>,
+    /// What cleared monitors are left with, black if unset
+    #[serde(default)]
+    pub clear_color: Option<HexColor>,
+    /// Presentation mode also covers the desktop with a plain gray wallpaper
+    #[serde(default)]
+    pub presentation_neutral_wallpaper: bool,
+    #[serde(default)]
+    pub night_dimming: NightDimming,
+    #[serde(default)]
+    pub notifications: NotificationMode,
+    /// Experimental: tint the desktop with the color of the focused window's icon
+    #[serde(default)]
+    pub active_window_color: bool,
+    /// Remote xab daemons, listed next to the local sockets
+    #[serde(default)]
+    pub ssh: Vec<SshProfile>,
+    /// "when <event> and <condition> then <action>" as written, see `Rule`.
+    /// Ones that don't parse are skipped, see `rule_errors`
+    #[serde(default)]
+    pub rules: Vec<String>,
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// Names of the scripts that run, see `scripts::dir`
+    #[serde(default)]
+    pub scripts: Vec<String>,
+    /// Names of the templates that are rendered, see `templates::dir`
+    #[serde(default)]
+    pub templates: Vec<String>,
+    /// Cropped out when spanning a wallpaper, see `span::Bezels`
+    #[serde(default)]
+    pub bezels: Bezels,
+    /// Directory trees followed as sources, see `sources::channel`
+    #[serde(default)]
+    pub channels: Vec<PathBuf>,
+    #[serde(default)]
+    pub rotation: Rotation,
+    /// Which automation wins a monitor several change
+    #[serde(default)]
+    pub precedence: Precedence,
+    /// Shown on the library page, see `browser`
+    #[serde(default)]
+    pub library_dirs: Vec<PathBuf>,
+    /// Seconds, see `ipc::Timeouts` for the defaults
+    #[serde(default)]
+    pub connect_timeout: Option<u64>,
+    #[serde(default)]
+    pub handshake_timeout: Option<u64>,
+    #[serde(default)]
+    pub limits: Limits,
+    #[serde(default)]
+    pub bandwidth: Bandwidth,
+    #[serde(default)]
+    pub proxies: Proxies,
+    /// Online sources and downloads are off, see `net::set_offline`
+    #[serde(default)]
+    pub offline: bool,
+    /// Off unless the user opts in, see `updates`
+    #[serde(default)]
+    pub check_updates: bool,
+    /// Megabytes of previews and thumbnails kept in memory, see
+    /// `preview::DEFAULT_CACHE_MB` for the default
+    #[serde(default)]
+    pub cache_mb: Option<usize>,
+    /// Megabytes of cropped, spanned, dimmed and rendered wallpapers kept on
+    /// disk, see `disk_cache::DEFAULT_LIMIT_MB` for the default
+    #[serde(default)]
+    pub disk_cache_mb: Option<u64>,
+    /// By source id, see `sources::filter`
+    #[serde(default)]
+    pub content_filters: ContentFilters,
+    /// For shared machines (libraries, classrooms, ...), whoever sets it up
+    /// also makes the config read only. Locks the content filters in the gui
+    #[serde(default)]
+    pub kiosk: bool,
+    /// Why the file couldn't be loaded, it isn't saved over until it's
+    /// fixed and reloaded or reset, see `load_or_default`
+    #[serde(skip)]
+    pub load_error: Option<String>,
+}
+
+impl Config {
+    pub fn file_path() -> PathBuf {
+        xdg::config_dir().join("config.toml")
+    }
+
+    pub fn clear_color(&self) -> [u8; 3] {
+        self.clear_color.map(|color| color.0).unwrap_or_default()
+    }
+
+    /// Loads the config, a missing file is treated as the default config
+    pub fn load() -> Result<Self> {
+        let path = Self::file_path();
+        if !path.exists() {
+            debug!("No config at {}", path.display());
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config at {}", path.display()))?;
+        let config: Self = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config at {}", path.display()))?;
+        config
+            .validate()
+            .with_context(|| format!("Invalid config at {}", path.display()))?;
+        for err in config.rule_errors() {
+            warn!("Skipping a rule in {}: {err}", path.display());
+        }
+        Ok(config)
+    }
+
+    /// `load`, the default config if the file is broken. That one remembers
+    /// why so it's never saved over the user's
+    pub fn load_or_default() -> Self {
+        Self::load().unwrap_or_else(|err| {
+            error!("Failed to load config, using the default one: {:?}", err);
+            Self {
+                load_error: Some(format!("{err:#}")),
+                ..Self::default()
+            }
+        })
+    }
+
+    /// Moves the file that failed to load aside (next to it, `.bak`) and
+    /// starts over from the default config
+    pub fn reset() -> Result<Self> {
+        let path = Self::file_path();
+        if path.exists() {
+            let backup = path.with_extension("toml.bak");
+            fs::rename(&path, &backup)
+                .with_context(|| format!("Failed to back up the config to {}", backup.display()))?;
+        }
+        let config = Self::default();
+        config.save()?;
+        Ok(config)
+    }
+
+    /// What serde can't tell, checked before loading and saving
+    pub fn validate(&self) -> Result<()> {
+        if self
+            .socket
+            .as_ref()
+            .is_some_and(|socket| socket.trim().is_empty())
+        {
+            return Err(anyhow!("The socket path is empty, leave it out instead"));
+        }
+        if self.connect_timeout == Some(0) || self.handshake_timeout == Some(0) {
+            return Err(anyhow!(
+                "Timeouts can't be 0 seconds, leave them out instead"
+            ));
+        }
+        self.limits.validate()?;
+        self.precedence.validate()?;
+        if self.cache_mb == Some(0) || self.disk_cache_mb == Some(0) {
+            return Err(anyhow!("Caches can't be 0 MB, leave them out instead"));
+        }
+        if self.default_monitor.is_some_and(|monitor| monitor < 0) {
+            return Err(anyhow!("The default monitor can't be negative"));
+        }
+        for (monitor, path) in &self.wallpapers {
+            if let Err(err) = monitor.parse::<MonitorTarget>() {
+                return Err(anyhow!(
+                    "`{monitor}` isn't a monitor ({err}), wallpapers go by number, output \
+                     name, \"primary\" or \"all\""
+                ));
+            }
+            if path.as_os_str().is_empty() {
+                return Err(anyhow!("The wallpaper of monitor {monitor} is empty"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        let defaults = Timeouts::default();
+        Timeouts {
+            connect: self
+                .connect_timeout
+                .map_or(defaults.connect, Duration::from_secs),
+            handshake: self
+                .handshake_timeout
+                .map_or(defaults.handshake, Duration::from_secs),
+        }
+    }
+
+    /// In bytes
+    pub fn cache_budget(&self) -> usize {
+        self.cache_mb.unwrap_or(DEFAULT_CACHE_MB) * 1024 * 1024
+    }
+
+    /// In bytes
+    pub fn disk_cache_limit(&self) -> u64 {
+        self.disk_cache_mb.unwrap_or(DEFAULT_LIMIT_MB) * 1024 * 1024
+    }
+
+    pub fn parsed_rules(&self) -> Vec<Rule> {
+        rules::parse_all(&self.rules).0
+    }
+
+    /// Why the rules that are skipped don't parse
+    pub fn rule_errors(&self) -> Vec<String> {
+        rules::parse_all(&self.rules).1
+    }
+
+    /// `wallpapers` by the monitor they go to, see `MonitorTarget::resolve`
+    pub fn wallpapers(&self) -> Vec<(MonitorTarget, PathBuf)> {
+        self.wallpapers
+            .iter()
+            .filter_map(|(monitor, path)| Some((monitor.parse().ok()?, path.clone())))
+            .collect()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(err) = &self.load_error {
+            return Err(anyhow!(
+                "Not saving over the config that failed to load ({err}), fix it and reload it or \
+                 reset it in the settings"
+            ));
+        }
+        self.validate()?;
+        let path = Self::file_path();
+        write_atomic(&path, toml::to_string_pretty(self)?.as_bytes())
+            .with_context(|| format!("Failed to write config to {}", path.display()))?;
+        debug!("Saved config to {}", path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_that_failed_to_load_isnt_saved() {
+        let config = Config {
+            load_error: Some("expected `=`".to_owned()),
+            ..Config::default()
+        };
+        let err = config.save().unwrap_err();
+        assert!(format!("{err}").contains("expected `=`"));
+    }
+}
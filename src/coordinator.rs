@@ -0,0 +1,298 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use crate::ipc_spec::{Monitor, MonitorTarget};
+
+/// The automatic ways wallpapers get changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Automation {
+    /// Rules that apply a wallpaper
+    Rules,
+    Templates,
+    Scripts,
+    Rotation,
+}
+
+impl Automation {
+    /// Their usual precedence, first wins
+    pub const ALL: &[Self] = &[Self::Rules, Self::Templates, Self::Scripts, Self::Rotation];
+}
+
+impl fmt::Display for Automation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Rules => "Rules",
+            Self::Templates => "Templates",
+            Self::Scripts => "Scripts",
+            Self::Rotation => "Rotation",
+        })
+    }
+}
+
+/// Which automation keeps a monitor when several change it, kept in the
+/// config
+///
+/// ```toml
+/// [precedence]
+/// order = ["templates", "rotation"]
+///
+/// [precedence.monitors]
+/// DP-1 = ["rotation", "templates"]
+/// ```
+///
+/// Monitors go by anything `MonitorTarget` takes. Automations left out go
+/// after the others in their usual order
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Precedence {
+    #[serde(default)]
+    pub order: Vec<Automation>,
+    /// Over `order` on the monitors they're for
+    #[serde(default)]
+    pub monitors: BTreeMap<String, Vec<Automation>>,
+}
+
+impl Precedence {
+    pub fn validate(&self) -> Result<()> {
+        for monitor in self.monitors.keys() {
+            if let Err(err) = monitor.parse::<MonitorTarget>() {
+                return Err(anyhow!(
+                    "`{monitor}` in [precedence.monitors] isn't a monitor ({err})"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every automation, winner first, on `monitor` (`None` being every
+    /// monitor)
+    pub fn order(&self, monitor: Option<i32>, monitors: &[Monitor]) -> Vec<Automation> {
+        let own = self
+            .monitors
+            .iter()
+            .find(|(target, _)| {
+                target
+                    .parse::<MonitorTarget>()
+                    .ok()
+                    .and_then(|target| target.resolve(monitors).ok())
+                    == Some(monitor)
+            })
+            .map(|(_, order)| order.as_slice())
+            .unwrap_or_default();
+        let mut order: Vec<Automation> = Vec::new();
+        for &automation in own.iter().chain(&self.order).chain(Automation::ALL) {
+            if !order.contains(&automation) {
+                order.push(automation);
+            }
+        }
+        order
+    }
+}
+
+/// Which automation last changed each monitor, `None` being every monitor.
+/// A monitor stays with it until one that goes before it takes over, it
+/// stops, or the user picks something by hand
+#[derive(Debug, Default, Clone)]
+pub struct Claims(HashMap<Option<i32>, Automation>);
+
+impl Claims {
+    /// The running automation that keeps `by` off `monitor`, if any
+    pub fn blocker(
+        &self,
+        by: Automation,
+        monitor: Option<i32>,
+        running: &[Automation],
+        precedence: &Precedence,
+        monitors: &[Monitor],
+    ) -> Option<Automation> {
+        let order = precedence.order(monitor, monitors);
+        let rank = |automation| order.iter().position(|a| *a == automation);
+        self.0
+            .iter()
+            .filter(|(claimed, _)| claimed.is_none() || monitor.is_none() || **claimed == monitor)
+            .map(|(_, automation)| *automation)
+            .filter(|automation| running.contains(automation))
+            .find(|automation| rank(*automation) < rank(by))
+    }
+
+    pub fn claim(&mut self, by: Automation, monitor: Option<i32>) {
+        if monitor.is_none() {
+            self.0.clear();
+        }
+        self.0.insert(monitor, by);
+    }
+
+    /// After the user changed `monitor` by hand. One automation changing
+    /// every monitor still keeps the others
+    pub fn release(&mut self, monitor: Option<i32>) {
+        match monitor {
+            Some(_) => {
+                self.0.remove(&monitor);
+            }
+            None => self.0.clear(),
+        }
+    }
+}
+
+/// Automations set to change the same monitor, winner first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// `None` being every monitor
+    pub monitor: Option<i32>,
+    pub automations: Vec<Automation>,
+}
+
+/// Where `targets`, the automations that are running and the monitors they
+/// change, overlap
+pub fn conflicts(
+    targets: &[(Automation, Option<i32>)],
+    precedence: &Precedence,
+    monitors: &[Monitor],
+) -> Vec<Conflict> {
+    let mut candidates: Vec<Option<i32>> = targets.iter().map(|(_, monitor)| *monitor).collect();
+    candidates.sort();
+    candidates.dedup();
+
+    let mut conflicts = Vec::new();
+    for monitor in candidates {
+        let automations: Vec<Automation> = precedence
+            .order(monitor, monitors)
+            .into_iter()
+            .filter(|automation| {
+                targets
+                    .iter()
+                    .any(|(a, target)| a == automation && (target.is_none() || *target == monitor))
+            })
+            .collect();
+        if automations.len() >= 2 {
+            conflicts.push(Conflict {
+                monitor,
+                automations,
+            });
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitors() -> Vec<Monitor> {
+        [("HDMI-1", true), ("DP-1", false)]
+            .into_iter()
+            .enumerate()
+            .map(|(index, (name, primary))| Monitor {
+                index: index as i32,
+                primary,
+                name: Some(name.to_owned()),
+                ..Monitor::fullscreen()
+            })
+            .collect()
+    }
+
+    fn precedence(order: &[Automation], monitors: &[(&str, &[Automation])]) -> Precedence {
+        Precedence {
+            order: order.to_vec(),
+            monitors: monitors
+                .iter()
+                .map(|(monitor, order)| (monitor.to_string(), order.to_vec()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn orders_by_monitor_then_config_then_default() {
+        use Automation::*;
+        let monitors = monitors();
+        assert_eq!(
+            Precedence::default().order(None, &monitors),
+            Automation::ALL
+        );
+
+        let precedence = precedence(&[Rotation], &[("DP-1", &[Scripts])]);
+        assert_eq!(
+            precedence.order(Some(0), &monitors),
+            [Rotation, Rules, Templates, Scripts]
+        );
+        assert_eq!(
+            precedence.order(Some(1), &monitors),
+            [Scripts, Rotation, Rules, Templates]
+        );
+        // by number and primary too
+        let precedence = self::precedence(&[], &[("2", &[Scripts]), ("primary", &[Rotation])]);
+        assert_eq!(precedence.order(Some(1), &monitors)[0], Scripts);
+        assert_eq!(precedence.order(Some(0), &monitors)[0], Rotation);
+        assert!(precedence.validate().is_ok());
+        assert!(
+            self::precedence(&[], &[("0", &[Scripts])])
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn only_running_automations_that_go_first_block() {
+        use Automation::*;
+        let monitors = monitors();
+        let precedence = Precedence::default();
+        let mut claims = Claims::default();
+        claims.claim(Rotation, Some(0));
+        let running = [Rotation, Templates];
+        assert_eq!(
+            claims.blocker(Templates, Some(0), &running, &precedence, &monitors),
+            None
+        );
+
+        claims.claim(Templates, None);
+        assert_eq!(
+            claims.blocker(Rotation, Some(1), &running, &precedence, &monitors),
+            Some(Templates)
+        );
+        assert_eq!(
+            claims.blocker(Rotation, Some(1), &[Rotation], &precedence, &monitors),
+            None
+        );
+
+        // the user picking something by hand frees the monitor
+        claims.release(None);
+        assert_eq!(
+            claims.blocker(Rotation, Some(1), &running, &precedence, &monitors),
+            None
+        );
+    }
+
+    #[test]
+    fn conflicts_list_the_winner_first() {
+        use Automation::*;
+        let monitors = monitors();
+        let targets = [(Rotation, None), (Templates, Some(1)), (Scripts, Some(0))];
+        assert_eq!(
+            conflicts(&targets, &Precedence::default(), &monitors),
+            [
+                Conflict {
+                    monitor: Some(0),
+                    automations: vec![Scripts, Rotation],
+                },
+                Conflict {
+                    monitor: Some(1),
+                    automations: vec![Templates, Rotation],
+                },
+            ]
+        );
+
+        let precedence = precedence(&[], &[("DP-1", &[Rotation])]);
+        assert_eq!(
+            conflicts(&targets[..2], &precedence, &monitors),
+            [Conflict {
+                monitor: Some(1),
+                automations: vec![Rotation, Templates],
+            }]
+        );
+        assert!(conflicts(&[(Rotation, None)], &precedence, &monitors).is_empty());
+    }
+}
@@ -0,0 +1,450 @@
+use anyhow::{Context, Result};
+use iced::{
+    Color, Point, Rectangle, Renderer, Size, Theme, mouse,
+    widget::{
+        canvas::{self, Event, Frame, Geometry, Path, Stroke},
+        image::Handle,
+    },
+};
+use image::imageops::FilterType;
+use std::{
+    collections::HashMap,
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path as FilePath, PathBuf},
+};
+use tracing::debug;
+
+use crate::{disk_cache, ipc_spec::Monitor, limits, preview, xdg};
+
+/// Bigger images are scaled down for the editor, crops are cut from the original
+const EDITOR_SIZE: u32 = 1600;
+/// How close (0..1 of the image) the crop has to get to a guide to snap to it
+const SNAP_DISTANCE: f32 = 0.02;
+/// Each scroll step zooms by this much
+const ZOOM_STEP: f32 = 1.1;
+
+/// Part of the image going on a monitor, in pixels of the image. Always the
+/// monitor's aspect ratio
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl CropRect {
+    /// The biggest crop with the aspect ratio of `monitor`, centered
+    pub fn fitted(image: (u32, u32), monitor: (u32, u32)) -> Self {
+        let aspect = monitor.1 as f32 / monitor.0.max(1) as f32;
+        let width = max_width(image, aspect);
+        Self::centered_on((image.0 as f32 / 2.0, image.1 as f32 / 2.0), width, aspect)
+            .clamped(image)
+    }
+
+    fn centered_on(center: (f32, f32), width: f32, aspect: f32) -> Self {
+        let height = width * aspect;
+        Self {
+            x: center.0 - width / 2.0,
+            y: center.1 - height / 2.0,
+            width,
+            height,
+        }
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    /// Pushed back inside the image, shrunk if it doesn't fit
+    pub fn clamped(self, image: (u32, u32)) -> Self {
+        let aspect = self.height / self.width.max(1.0);
+        let width = self.width.min(max_width(image, aspect)).max(1.0);
+        let height = width * aspect;
+        Self {
+            // rounding can push the height a hair past the image's
+            x: self.x.clamp(0.0, (image.0 as f32 - width).max(0.0)),
+            y: self.y.clamp(0.0, (image.1 as f32 - height).max(0.0)),
+            width,
+            height,
+        }
+    }
+
+    /// Same aspect ratio and center, `width` wide
+    pub fn resized(self, width: f32, image: (u32, u32)) -> Self {
+        let aspect = self.height / self.width.max(1.0);
+        Self::centered_on(self.center(), width, aspect).clamped(image)
+    }
+
+    /// Zoomed in (or out with a negative `steps`) around its center
+    pub fn zoomed(self, steps: f32, image: (u32, u32)) -> Self {
+        self.resized(self.width / ZOOM_STEP.powf(steps), image)
+    }
+
+    /// Moved to the middle of the image
+    pub fn centered(self, image: (u32, u32)) -> Self {
+        Self {
+            x: (image.0 as f32 - self.width) / 2.0,
+            y: (image.1 as f32 - self.height) / 2.0,
+            ..self
+        }
+        .clamped(image)
+    }
+
+    /// The center pulled onto the image's center or thirds when it's close
+    pub fn snapped(self, image: (u32, u32)) -> Self {
+        let snap = |center: f32, size: u32| {
+            let size = size as f32;
+            [0.5, 1.0 / 3.0, 2.0 / 3.0]
+                .into_iter()
+                .map(|guide| guide * size)
+                .find(|guide| (center - guide).abs() < SNAP_DISTANCE * size)
+                .unwrap_or(center)
+        };
+        let (x, y) = self.center();
+        Self::centered_on(
+            (snap(x, image.0), snap(y, image.1)),
+            self.width,
+            self.height / self.width.max(1.0),
+        )
+        .clamped(image)
+    }
+}
+
+fn max_width(image: (u32, u32), aspect: f32) -> f32 {
+    (image.0 as f32).min(image.1 as f32 / aspect)
+}
+
+/// A crop per monitor of one image, being edited on the crop page
+#[derive(Debug)]
+pub struct CropEditor {
+    pub path: PathBuf,
+    pub handle: Handle,
+    pub image: (u32, u32),
+    /// The monitor being edited
+    pub monitor: i32,
+    pub crops: HashMap<i32, CropRect>,
+    pub x_input: String,
+    pub y_input: String,
+    pub width_input: String,
+}
+
+impl CropEditor {
+    pub fn new(path: PathBuf, handle: Handle, image: (u32, u32), monitor: &Monitor) -> Self {
+        let mut editor = Self {
+            path,
+            handle,
+            image,
+            monitor: monitor.index,
+            crops: HashMap::new(),
+            x_input: String::new(),
+            y_input: String::new(),
+            width_input: String::new(),
+        };
+        editor.select(monitor);
+        editor
+    }
+
+    /// Edits `monitor` next, starting from the biggest crop that fits it
+    pub fn select(&mut self, monitor: &Monitor) {
+        self.monitor = monitor.index;
+        let crop = self
+            .crops
+            .get(&monitor.index)
+            .copied()
+            .unwrap_or_else(|| CropRect::fitted(self.image, preview::monitor_size(monitor)));
+        self.set(crop);
+    }
+
+    pub fn crop(&self) -> CropRect {
+        self.crops[&self.monitor]
+    }
+
+    /// Replaces the crop of the monitor being edited, the inputs follow
+    pub fn set(&mut self, crop: CropRect) {
+        let crop = crop.clamped(self.image);
+        self.x_input = format!("{:.0}", crop.x);
+        self.y_input = format!("{:.0}", crop.y);
+        self.width_input = format!("{:.0}", crop.width);
+        self.crops.insert(self.monitor, crop);
+    }
+
+    /// The crop the inputs describe, None while they aren't numbers
+    pub fn parse_inputs(&self) -> Option<CropRect> {
+        let width: f32 = self.width_input.trim().parse().ok()?;
+        let crop = self.crop().resized(width, self.image);
+        Some(
+            CropRect {
+                x: self.x_input.trim().parse().ok()?,
+                y: self.y_input.trim().parse().ok()?,
+                ..crop
+            }
+            .clamped(self.image),
+        )
+    }
+
+    /// Gives every other monitor of the same resolution the current crop,
+    /// returns how many got it
+    pub fn copy_to_same_resolution(&mut self, monitors: &[Monitor]) -> usize {
+        let crop = self.crop();
+        let Some(size) = monitors
+            .iter()
+            .find(|monitor| monitor.index == self.monitor)
+            .map(preview::monitor_size)
+        else {
+            return 0;
+        };
+        let mut copied = 0;
+        for monitor in monitors {
+            if monitor.index != self.monitor && preview::monitor_size(monitor) == size {
+                self.crops.insert(monitor.index, crop);
+                copied += 1;
+            }
+        }
+        copied
+    }
+}
+
+/// The image scaled down for the editor, with the size of the original
+pub fn load(path: &FilePath) -> Result<(Handle, (u32, u32))> {
+    let _decoding = limits::decode_permit();
+    let image = image::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let size = (image.width(), image.height());
+    let shown = image.thumbnail(EDITOR_SIZE, EDITOR_SIZE).into_rgba8();
+    Ok((
+        Handle::from_rgba(shown.width(), shown.height(), shown.into_raw()),
+        size,
+    ))
+}
+
+/// Cuts `crop` out of `path` and scales it to `monitor`, cached
+pub fn render(path: &FilePath, crop: CropRect, monitor: (u32, u32)) -> Result<PathBuf> {
+    // the modification time too, so an edited wallpaper isn't served stale
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .hash(&mut hasher);
+    [crop.x, crop.y, crop.width, crop.height]
+        .map(f32::to_bits)
+        .hash(&mut hasher);
+    monitor.hash(&mut hasher);
+    let dir = xdg::cache_dir().join("crop");
+    let dest = dir.join(format!("{:016x}.png", hasher.finish()));
+    if disk_cache::hit(&dest) {
+        return Ok(dest);
+    }
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let _decoding = limits::decode_permit();
+    image::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?
+        .crop_imm(
+            crop.x.round() as u32,
+            crop.y.round() as u32,
+            crop.width.round() as u32,
+            crop.height.round() as u32,
+        )
+        .resize_exact(monitor.0, monitor.1, FilterType::Triangle)
+        .save(&dest)
+        .with_context(|| format!("Failed to save {}", dest.display()))?;
+    debug!("Cropped {} to {}", path.display(), dest.display());
+    Ok(dest)
+}
+
+/// The image with the crop on top, drag it around and scroll to zoom
+pub struct CropView<'a, Message> {
+    pub handle: &'a Handle,
+    /// Of the original, crops are in its pixels
+    pub image: (u32, u32),
+    pub crop: CropRect,
+    pub on_change: fn(CropRect) -> Message,
+}
+
+/// Where the crop was grabbed, relative to its corner
+#[derive(Default)]
+pub struct CropState {
+    grab: Option<(f32, f32)>,
+}
+
+impl<Message> CropView<'_, Message> {
+    /// Where the image is drawn in `bounds` and how much it's scaled
+    fn placement(&self, bounds: Size) -> (Rectangle, f32) {
+        let (width, height) = (self.image.0.max(1) as f32, self.image.1.max(1) as f32);
+        let scale = (bounds.width / width).min(bounds.height / height);
+        let size = Size::new(width * scale, height * scale);
+        let position = Point::new(
+            (bounds.width - size.width) / 2.0,
+            (bounds.height - size.height) / 2.0,
+        );
+        (Rectangle::new(position, size), scale)
+    }
+
+    /// The cursor in pixels of the image
+    fn image_position(&self, bounds: Rectangle, cursor: mouse::Cursor) -> Option<(f32, f32)> {
+        let position = cursor.position_in(bounds)?;
+        let (placed, scale) = self.placement(bounds.size());
+        Some((
+            (position.x - placed.x) / scale,
+            (position.y - placed.y) / scale,
+        ))
+    }
+}
+
+impl<Message> canvas::Program<Message> for CropView<'_, Message> {
+    type State = CropState;
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: &Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> Option<canvas::Action<Message>> {
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let (x, y) = self.image_position(bounds, cursor)?;
+                let crop = self.crop;
+                let inside = (crop.x..crop.x + crop.width).contains(&x)
+                    && (crop.y..crop.y + crop.height).contains(&y);
+                inside.then(|| {
+                    state.grab = Some((x - crop.x, y - crop.y));
+                    canvas::Action::capture()
+                })
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                let (grab_x, grab_y) = state.grab?;
+                let (x, y) = self.image_position(bounds, cursor)?;
+                let moved = CropRect {
+                    x: x - grab_x,
+                    y: y - grab_y,
+                    ..self.crop
+                };
+                Some(canvas::Action::publish((self.on_change)(moved)).and_capture())
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                state.grab.take().map(|_| canvas::Action::capture())
+            }
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                cursor.position_in(bounds)?;
+                let steps = match delta {
+                    mouse::ScrollDelta::Lines { y, .. } => *y,
+                    mouse::ScrollDelta::Pixels { y, .. } => *y / 50.0,
+                };
+                let zoomed = self.crop.zoomed(steps, self.image);
+                Some(canvas::Action::publish((self.on_change)(zoomed)).and_capture())
+            }
+            _ => None,
+        }
+    }
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let palette = theme.palette();
+        let (placed, scale) = self.placement(bounds.size());
+        let mut frame = Frame::new(renderer, bounds.size());
+        frame.draw_image(placed, self.handle);
+
+        let crop = Rectangle::new(
+            Point::new(
+                placed.x + self.crop.x * scale,
+                placed.y + self.crop.y * scale,
+            ),
+            Size::new(self.crop.width * scale, self.crop.height * scale),
+        );
+        // everything that's cut off is dimmed
+        let dim = Color {
+            a: 0.6,
+            ..Color::BLACK
+        };
+        frame.fill_rectangle(
+            placed.position(),
+            Size::new(placed.width, crop.y - placed.y),
+            dim,
+        );
+        frame.fill_rectangle(
+            Point::new(placed.x, crop.y + crop.height),
+            Size::new(
+                placed.width,
+                placed.y + placed.height - crop.y - crop.height,
+            ),
+            dim,
+        );
+        frame.fill_rectangle(
+            Point::new(placed.x, crop.y),
+            Size::new(crop.x - placed.x, crop.height),
+            dim,
+        );
+        frame.fill_rectangle(
+            Point::new(crop.x + crop.width, crop.y),
+            Size::new(placed.x + placed.width - crop.x - crop.width, crop.height),
+            dim,
+        );
+
+        // rule of thirds inside the crop
+        let guide = Stroke::default().with_width(1.0).with_color(Color {
+            a: 0.5,
+            ..Color::WHITE
+        });
+        for third in [1.0 / 3.0, 2.0 / 3.0] {
+            let x = crop.x + crop.width * third;
+            let y = crop.y + crop.height * third;
+            frame.stroke(
+                &Path::line(Point::new(x, crop.y), Point::new(x, crop.y + crop.height)),
+                guide,
+            );
+            frame.stroke(
+                &Path::line(Point::new(crop.x, y), Point::new(crop.x + crop.width, y)),
+                guide,
+            );
+        }
+        // the image's center, what the crop snaps to first
+        let center = placed.center();
+        let marker = Stroke::default()
+            .with_width(1.0)
+            .with_color(palette.primary);
+        frame.stroke(
+            &Path::line(
+                Point::new(center.x - 8.0, center.y),
+                Point::new(center.x + 8.0, center.y),
+            ),
+            marker,
+        );
+        frame.stroke(
+            &Path::line(
+                Point::new(center.x, center.y - 8.0),
+                Point::new(center.x, center.y + 8.0),
+            ),
+            marker,
+        );
+
+        frame.stroke_rectangle(
+            crop.position(),
+            crop.size(),
+            Stroke::default()
+                .with_width(2.0)
+                .with_color(palette.primary),
+        );
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        state: &Self::State,
+        _bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        match state.grab {
+            Some(_) => mouse::Interaction::Grabbing,
+            None => mouse::Interaction::Grab,
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    thread,
+};
+use tracing::{debug, warn};
+
+use crate::limits;
+
+/// Read from the start and the end of a file for the quick comparison
+pub const PARTIAL_CHUNK: u64 = 64 * 1024;
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+pub type Hash = [u8; 32];
+
+/// Bytes that have to be read in full, only known once the quick checks are done
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Progress {
+    pub hashed_bytes: u64,
+    pub total_bytes: u64,
+}
+
+impl Progress {
+    pub fn percent(&self) -> u64 {
+        (self.hashed_bytes * 100)
+            .checked_div(self.total_bytes)
+            .unwrap_or(100)
+    }
+}
+
+/// Hash of the size, the first and the last `PARTIAL_CHUNK` bytes, that's
+/// the whole file for small ones
+fn partial_hash(path: &Path, size: u64) -> Result<Hash> {
+    let mut file = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&size.to_le_bytes());
+    let mut chunk = vec![0; PARTIAL_CHUNK.min(size) as usize];
+    file.read_exact(&mut chunk)?;
+    hasher.update(&chunk);
+    if size > PARTIAL_CHUNK {
+        let tail = PARTIAL_CHUNK.min(size - PARTIAL_CHUNK);
+        file.seek(SeekFrom::End(-(tail as i64)))?;
+        chunk.truncate(tail as usize);
+        file.read_exact(&mut chunk)?;
+        hasher.update(&chunk);
+    }
+    Ok(*hasher.finalize().as_bytes())
+}
+
+/// Streams the whole file through the hasher, `on_read` gets the bytes read so far
+pub fn full_hash(path: &Path, mut on_read: impl FnMut(u64)) -> Result<Hash> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0; BUFFER_SIZE];
+    loop {
+        let read = file
+            .read(&mut buf)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        on_read(read as u64);
+    }
+    Ok(*hasher.finalize().as_bytes())
+}
+
+/// `work` on every item from a few threads, results are in the order of `items`
+fn parallel_map<T: Sync, R: Send>(items: &[T], work: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let workers = limits::current().workers().min(items.len());
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(index) else {
+                            break;
+                        };
+                        done.push((index, work(item)));
+                    }
+                    done
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_default())
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Groups `groups` further by `key`, dropping whatever ends up alone (and
+/// files `key` failed on)
+fn split<K: std::hash::Hash + Eq + Send>(
+    groups: Vec<Vec<(PathBuf, u64)>>,
+    key: impl Fn(&Path, u64) -> Result<K> + Sync,
+) -> Vec<Vec<(PathBuf, u64)>> {
+    let files: Vec<(PathBuf, u64)> = groups.into_iter().flatten().collect();
+    let keys = parallel_map(&files, |(path, size)| {
+        key(path, *size)
+            .inspect_err(|err| warn!("Skipping {} for duplicates: {:?}", path.display(), err))
+            .ok()
+    });
+
+    let mut by_key: HashMap<K, Vec<(PathBuf, u64)>> = HashMap::new();
+    let mut order = Vec::new();
+    for (file, key) in files.into_iter().zip(keys) {
+        let Some(key) = key else {
+            continue;
+        };
+        let group = by_key.entry(key).or_default();
+        if group.is_empty() {
+            order.push(file.0.clone());
+        }
+        group.push(file);
+    }
+    // keep the groups in the order they were first seen
+    let mut groups: Vec<Vec<(PathBuf, u64)>> = by_key
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect();
+    groups.sort_by_key(|group| order.iter().position(|first| *first == group[0].0));
+    groups
+}
+
+/// Sets of files with the same content, each in the order of `paths`
+///
+/// Only files sharing their size get their start and end hashed, and only
+/// the ones still alike after that are read in full
+pub fn find_duplicates(paths: &[PathBuf], progress: impl Fn(Progress) + Sync) -> Vec<Vec<PathBuf>> {
+    let sized: Vec<Vec<(PathBuf, u64)>> = vec![
+        paths
+            .iter()
+            .filter_map(|path| match path.metadata() {
+                Ok(metadata) if metadata.is_file() => Some((path.clone(), metadata.len())),
+                Ok(_) => None,
+                Err(err) => {
+                    warn!("Skipping {} for duplicates: {err}", path.display());
+                    None
+                }
+            })
+            .collect(),
+    ];
+
+    let same_size = split(sized, |_, size| Ok(size));
+    let same_ends = split(same_size, partial_hash);
+
+    // the partial hash already covered small files
+    let (small, large): (Vec<_>, Vec<_>) = same_ends
+        .into_iter()
+        .partition(|group| group[0].1 <= 2 * PARTIAL_CHUNK);
+    let total_bytes = large.iter().flatten().map(|(_, size)| size).sum();
+    debug!(
+        "{} possible duplicate group(s), {} bytes to hash",
+        small.len() + large.len(),
+        total_bytes
+    );
+    let hashed_bytes = AtomicU64::new(0);
+    progress(Progress {
+        hashed_bytes: 0,
+        total_bytes,
+    });
+    let same_content = split(large, |path, _| {
+        full_hash(path, |read| {
+            progress(Progress {
+                hashed_bytes: hashed_bytes.fetch_add(read, Ordering::Relaxed) + read,
+                total_bytes,
+            })
+        })
+    });
+
+    small
+        .into_iter()
+        .chain(same_content)
+        .map(|group| {
+            let mut group: Vec<PathBuf> = group.into_iter().map(|(path, _)| path).collect();
+            group.sort_by_key(|path| paths.iter().position(|p| p == path));
+            group
+        })
+        .collect()
+}
@@ -0,0 +1,94 @@
+use anyhow::{Context, Result};
+use std::{env, fs, path::PathBuf, process::Command};
+use tracing::{debug, warn};
+
+use crate::xdg;
+
+/// Basename matches the application id of the main window
+pub const DESKTOP_FILE_NAME: &str = "xab-gui.desktop";
+
+/// What file managers will offer to open with xab-gui
+pub const MIME_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/webp",
+    "image/bmp",
+    "image/gif",
+    "video/mp4",
+    "video/x-matroska",
+    "video/webm",
+    "video/quicktime",
+    "video/x-msvideo",
+];
+
+/// `$XDG_DATA_HOME/applications/xab-gui.desktop`
+pub fn desktop_file_path() -> PathBuf {
+    xdg::data_home()
+        .join("applications")
+        .join(DESKTOP_FILE_NAME)
+}
+
+/// Quotes an argument of `Exec=` as the desktop entry spec wants it
+fn quote_exec_arg(arg: &str) -> String {
+    if !arg.contains(|c: char| c.is_whitespace() || "\"'\\><~|&;$*?#()`".contains(c)) {
+        return arg.to_owned();
+    }
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    // and the whole value is a string, so escape the backslashes once more
+    quoted.replace('\\', "\\\\")
+}
+
+fn desktop_entry(exec: &str) -> String {
+    format!(
+        "[Desktop Entry]
+Type=Application
+Name=xab gui
+Comment=Manage xab wallpapers
+Exec={exec} %F
+Icon=xab-gui
+Terminal=false
+Categories=Utility;DesktopSettings;
+MimeType={mime_types};
+Actions=set-wallpaper;
+
+[Desktop Action set-wallpaper]
+Name=Set as wallpaper
+Exec={exec} %F
+",
+        mime_types = MIME_TYPES.join(";"),
+    )
+}
+
+/// Writes the desktop entry so xab-gui shows up as a handler for images and videos
+pub fn install() -> Result<PathBuf> {
+    let exe = env::current_exe().with_context(|| "Failed to find the xab-gui executable")?;
+    let exec = quote_exec_arg(&exe.to_string_lossy());
+
+    let path = desktop_file_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    fs::write(&path, desktop_entry(&exec))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    debug!("Wrote {}", path.display());
+
+    // optional, most desktops notice the new file on their own
+    if let Some(dir) = path.parent() {
+        match Command::new("update-desktop-database").arg(dir).output() {
+            Ok(output) if !output.status.success() => warn!(
+                "update-desktop-database failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Ok(_) => {}
+            Err(err) => debug!("Not running update-desktop-database: {err}"),
+        }
+    }
+    Ok(path)
+}
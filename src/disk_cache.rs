@@ -0,0 +1,161 @@
+use anyhow::Result;
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, warn};
+
+use crate::{persist, thumbnails, xdg};
+
+/// Space the caches may take when it isn't configured
+pub const DEFAULT_LIMIT_MB: u64 = 512;
+/// How often the background job checks the caches
+pub const CLEANUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Under `xdg::cache_dir`, everything in them can be made again. Downloads
+/// aren't in here, sources evict those themselves (see `sources::sync`)
+const DIRS: &[&str] = &[
+    "crop",
+    "span",
+    "nightlight",
+    "snapshots",
+    "templates",
+    "sources/gradients",
+    thumbnails::DIR,
+];
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CleanupReport {
+    pub evicted: usize,
+    pub freed: u64,
+}
+
+/// Whether `path` is cached, bumping it to the back of the eviction queue
+/// if it is. The modification time is what tells recently used files apart
+pub fn hit(path: &Path) -> bool {
+    if !path.exists() {
+        return false;
+    }
+    if let Err(err) = File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()))
+    {
+        warn!("Failed to mark {} as used: {err}", path.display());
+    }
+    true
+}
+
+/// Every cached file with its size and when it was last used
+fn entries() -> Vec<(PathBuf, u64, SystemTime)> {
+    let cache_dir = xdg::cache_dir();
+    DIRS.iter()
+        .flat_map(|dir| fs::read_dir(cache_dir.join(dir)).into_iter().flatten())
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            Some((
+                entry.path(),
+                metadata.len(),
+                metadata.modified().unwrap_or(UNIX_EPOCH),
+            ))
+        })
+        .collect()
+}
+
+/// Bytes `clear` would free
+pub fn reclaimable() -> Result<u64> {
+    let keep = in_use()?;
+    Ok(entries()
+        .iter()
+        .filter(|(path, _, _)| !keep.contains(path))
+        .map(|(_, size, _)| size)
+        .sum())
+}
+
+/// What's on the desktop right now, xab may need it again after a restart
+fn in_use() -> Result<HashSet<PathBuf>> {
+    Ok(persist::state()?
+        .assignments
+        .into_iter()
+        .map(|assignment| assignment.path)
+        .collect())
+}
+
+fn evict(
+    files: &[(PathBuf, u64, SystemTime)],
+    keep: &HashSet<PathBuf>,
+    mut until: impl FnMut(u64) -> bool,
+) -> CleanupReport {
+    let mut report = CleanupReport::default();
+    for (path, size, _) in files {
+        if until(report.freed) {
+            break;
+        }
+        if keep.contains(path) {
+            continue;
+        }
+        match fs::remove_file(path) {
+            Ok(()) => report.evicted += 1,
+            // gone already, the space is free all the same
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            // one stuck file shouldn't keep the rest
+            Err(err) => {
+                warn!("Failed to evict {}: {err}", path.display());
+                continue;
+            }
+        }
+        report.freed += size;
+    }
+    report
+}
+
+/// Evicts the least recently used files until the caches fit in `limit` bytes
+pub fn cleanup(limit: u64) -> Result<CleanupReport> {
+    let mut files = entries();
+    let total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    if total <= limit {
+        return Ok(CleanupReport::default());
+    }
+    files.sort_by_key(|(_, _, used)| *used);
+    let report = evict(&files, &in_use()?, |freed| total - freed <= limit);
+    debug!("Cache cleanup: {:?}", report);
+    Ok(report)
+}
+
+/// Everything but what's applied right now
+pub fn clear() -> Result<CleanupReport> {
+    let report = evict(&entries(), &in_use()?, |_| false);
+    debug!("Cleared the caches: {:?}", report);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eviction_carries_on_past_failures() {
+        let dir = std::env::temp_dir().join(format!("xab-gui-evict-{}", std::process::id()));
+        fs::create_dir_all(dir.join("stuck")).unwrap();
+        for name in ["old", "applied", "new"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        let files: Vec<(PathBuf, u64, SystemTime)> = ["gone", "stuck", "old", "applied", "new"]
+            .into_iter()
+            .map(|name| (dir.join(name), 10, SystemTime::UNIX_EPOCH))
+            .collect();
+        let keep = HashSet::from([dir.join("applied")]);
+
+        let report = evict(&files, &keep, |freed| freed >= 30);
+        let left = ["stuck", "applied", "new"].map(|name| dir.join(name).exists());
+        let _ = fs::remove_dir_all(&dir);
+        // the one that was gone counts, the stuck one doesn't
+        assert_eq!(report.evicted, 2);
+        assert_eq!(report.freed, 30);
+        assert_eq!(left, [true, true, false]);
+    }
+}
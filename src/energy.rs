@@ -0,0 +1,118 @@
+use anyhow::{Context, Result, anyhow};
+use std::{fmt, fs, path::Path, process::Command};
+use tracing::debug;
+
+/// What decoding a video costs, as far as ffprobe can tell
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoInfo {
+    pub codec: String,
+    pub width: u32,
+    pub height: u32,
+    pub fps: f32,
+}
+
+/// Rough battery impact of a wallpaper, still images have none
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Impact {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Impact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Low => "low battery impact",
+            Self::Medium => "medium battery impact",
+            Self::High => "high battery impact",
+        })
+    }
+}
+
+impl VideoInfo {
+    /// Relative to a 1080p 30fps H.264 video. Codecs GPUs rarely decode, or
+    /// decode slowly, count for more
+    pub fn cost(&self) -> f32 {
+        let codec = match self.codec.as_str() {
+            "h264" => 1.0,
+            "hevc" => 1.2,
+            "vp8" | "vp9" => 1.4,
+            "av1" => 1.6,
+            _ => 1.8,
+        };
+        let pixels = (self.width * self.height) as f32 / (1920.0 * 1080.0);
+        pixels * (self.fps / 30.0) * codec
+    }
+
+    pub fn impact(&self) -> Impact {
+        match self.cost() {
+            cost if cost < 0.75 => Impact::Low,
+            cost if cost < 2.0 => Impact::Medium,
+            _ => Impact::High,
+        }
+    }
+}
+
+/// `30000/1001` or `30` into frames per second
+fn parse_rate(rate: &str) -> Option<f32> {
+    match rate.split_once('/') {
+        Some((frames, seconds)) => {
+            let seconds: f32 = seconds.parse().ok()?;
+            (seconds > 0.0).then_some(frames.parse::<f32>().ok()? / seconds)
+        }
+        None => rate.parse().ok(),
+    }
+}
+
+/// The first video stream of `video`, with ffprobe
+pub fn probe(video: &Path) -> Result<VideoInfo> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args([
+            "-show_entries",
+            "stream=codec_name,width,height,avg_frame_rate",
+        ])
+        .args(["-of", "csv=p=0"])
+        .arg(video)
+        .output()
+        .with_context(|| "Failed to run ffprobe, is it installed?")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to probe {}: {}",
+            video.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let fields: Vec<&str> = stdout.trim().split(',').collect();
+    let [codec, width, height, rate] = fields.as_slice() else {
+        return Err(anyhow!(
+            "{} has no video stream ffprobe understands",
+            video.display()
+        ));
+    };
+    let info = VideoInfo {
+        codec: codec.to_string(),
+        width: width.parse().unwrap_or(1920),
+        height: height.parse().unwrap_or(1080),
+        fps: parse_rate(rate).unwrap_or(30.0),
+    };
+    debug!("{} is {:?}", video.display(), info);
+    Ok(info)
+}
+
+/// Whether a battery is running the machine, false on machines without one
+pub fn discharging() -> bool {
+    let Ok(supplies) = fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    supplies
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            fs::read_to_string(path.join("type")).is_ok_and(|kind| kind.trim() == "Battery")
+        })
+        .any(|path| {
+            fs::read_to_string(path.join("status"))
+                .is_ok_and(|status| status.trim() == "Discharging")
+        })
+}
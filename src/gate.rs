@@ -0,0 +1,122 @@
+use iced::{
+    Element,
+    widget::{Button, container, text, tooltip},
+};
+
+use crate::ipc_spec::{IpcCommands, IpcXabCapabilities, PRIVILEGE_HINT};
+
+/// What a control needs from the connected xab, controls that don't get it
+/// are disabled instead of failing when pressed
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Needs {
+    pub capabilities: IpcXabCapabilities,
+    /// Sent by the control, some daemons lock these away, see
+    /// `IpcXabCapabilities::is_locked`
+    pub command: Option<IpcCommands>,
+}
+
+impl Needs {
+    pub fn capabilities(capabilities: IpcXabCapabilities) -> Self {
+        Self {
+            capabilities,
+            command: None,
+        }
+    }
+
+    pub fn command(command: IpcCommands) -> Self {
+        Self {
+            capabilities: IpcXabCapabilities::None,
+            command: Some(command),
+        }
+    }
+
+    /// What a newer xab would bring, empty when `have` is enough or the
+    /// command is locked away instead
+    pub fn missing(&self, have: IpcXabCapabilities) -> IpcXabCapabilities {
+        if self.command.is_some_and(|command| have.is_locked(command)) {
+            return IpcXabCapabilities::None;
+        }
+        self.capabilities.difference(have)
+    }
+
+    /// Why `have` isn't enough, None if it is
+    pub fn unmet(&self, have: IpcXabCapabilities) -> Option<String> {
+        if self.command.is_some_and(|command| have.is_locked(command)) {
+            return Some(PRIVILEGE_HINT.to_owned());
+        }
+        let missing = self.missing(have);
+        if missing.is_empty() {
+            return None;
+        }
+        let missing: Vec<&str> = missing.iter().map(describe).collect();
+        Some(format!(
+            "This xab is too old, it can't do {}",
+            missing.join(" or ")
+        ))
+    }
+}
+
+/// Every capability this client knows what to do with, in the order the
+/// troubleshooting dialog lists them
+pub const KNOWN: &[IpcXabCapabilities] = &[
+    IpcXabCapabilities::Monitors,
+    IpcXabCapabilities::CustomPositioning,
+    IpcXabCapabilities::MonitorLists,
+    IpcXabCapabilities::MonitorNames,
+    IpcXabCapabilities::MonitorChunks,
+    IpcXabCapabilities::Screens,
+    IpcXabCapabilities::PerMonitorPlayback,
+    IpcXabCapabilities::Scaling,
+    IpcXabCapabilities::Video,
+    IpcXabCapabilities::Clear,
+    IpcXabCapabilities::Formats,
+    IpcXabCapabilities::BackgroundState,
+    IpcXabCapabilities::Events,
+    IpcXabCapabilities::RawPaths,
+    IpcXabCapabilities::ErrorReplies,
+    IpcXabCapabilities::PrivilegedControl,
+];
+
+pub fn describe(capability: IpcXabCapabilities) -> &'static str {
+    match capability {
+        IpcXabCapabilities::CustomPositioning => "positioning per monitor",
+        IpcXabCapabilities::Monitors => "monitor layouts",
+        IpcXabCapabilities::PrivilegedControl | IpcXabCapabilities::Privileged => {
+            "privileged clients"
+        }
+        IpcXabCapabilities::Events => "live updates",
+        IpcXabCapabilities::RawPaths => "file names that aren't UTF-8",
+        IpcXabCapabilities::MonitorLists => "several monitors at once",
+        IpcXabCapabilities::Screens => "multiple X screens",
+        IpcXabCapabilities::PerMonitorPlayback => "pausing videos per monitor",
+        IpcXabCapabilities::MonitorNames => "monitor names",
+        IpcXabCapabilities::MonitorChunks => "listing lots of monitors as they come",
+        IpcXabCapabilities::ErrorReplies => "explaining its errors",
+        IpcXabCapabilities::Scaling => "scaling modes other than fill",
+        IpcXabCapabilities::Video => "looping, muting and speeding up videos",
+        IpcXabCapabilities::Clear => "clearing monitors without a wallpaper",
+        IpcXabCapabilities::Formats => "telling which files it can show",
+        IpcXabCapabilities::BackgroundState => "telling what each monitor shows",
+        _ => "something newer",
+    }
+}
+
+/// `control` pressable with `on_press` when `unmet` is None, otherwise
+/// disabled with the reason in a tooltip
+pub fn gated<'a, Message: Clone + 'a>(
+    control: Button<'a, Message>,
+    on_press: Message,
+    unmet: Option<String>,
+) -> Element<'a, Message> {
+    match unmet {
+        None => control.on_press(on_press).into(),
+        Some(reason) => tooltip(
+            control,
+            container(text(reason))
+                .padding(5)
+                .style(container::rounded_box),
+            tooltip::Position::Top,
+        )
+        .into(),
+    }
+}
@@ -0,0 +1,214 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, warn};
+
+use crate::{ipc::IpcHandle, os_path};
+
+/// Hooks that take longer than this are killed (and can't veto anything)
+pub const HOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Executables run at certain points, they get a json object on stdin
+/// describing what's happening:
+///
+/// ```toml
+/// [hooks]
+/// pre_apply = "/home/me/bin/wallpaper-check"
+/// post_apply = "/home/me/bin/pywal-sync"
+/// ```
+///
+/// `pre_apply` can veto by exiting with an error (stderr is the reason), or
+/// by printing `{"veto": true, "reason": "..."}`. Printing `{"path": "..."}`
+/// applies another file instead. Output of the other hooks is ignored.
+/// Paths that aren't UTF-8 go both ways as `{"raw": [bytes]}`, see `os_path`
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Hooks {
+    /// `{"hook": "pre-apply", "monitor": 1, "monitors": [1], "path": "..."}`,
+    /// monitors is empty for all of them and monitor is null unless there's one
+    pub pre_apply: Option<PathBuf>,
+    /// Same as `pre_apply`, after it went through
+    pub post_apply: Option<PathBuf>,
+    /// `{"hook": "on-connect", "socket": "...", "capabilities": ["Monitors", ...]}`
+    pub on_connect: Option<PathBuf>,
+    /// `{"hook": "on-event", "event": "monitors-changed", ...}`
+    pub on_event: Option<PathBuf>,
+}
+
+/// What a hook printed, everything is optional
+#[derive(Debug, Default, Deserialize)]
+struct HookReply {
+    #[serde(default)]
+    veto: bool,
+    reason: Option<String>,
+    #[serde(default, deserialize_with = "crate::os_path::option::deserialize")]
+    path: Option<PathBuf>,
+}
+
+struct Finished {
+    success: bool,
+    stdout: String,
+    stderr: String,
+}
+
+fn read_all(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut out = Vec::new();
+        let _ = pipe.read_to_end(&mut out);
+        String::from_utf8_lossy(&out).into_owned()
+    })
+}
+
+/// Runs `command` with `input` on stdin, killing it after `HOOK_TIMEOUT`
+fn run(command: &Path, input: &serde_json::Value) -> Result<Finished> {
+    debug!("Running hook {}: {}", command.display(), input);
+    let mut child = Command::new(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run hook {}", command.display()))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // a hook not reading its input is fine
+        let _ = stdin.write_all(input.to_string().as_bytes());
+    }
+    // read on the side so a chatty hook can't fill the pipe and hang
+    let stdout = child.stdout.take().map(read_all);
+    let stderr = child.stderr.take().map(read_all);
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() > HOOK_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!(
+                "Hook {} didn't finish within {:?}",
+                command.display(),
+                HOOK_TIMEOUT
+            ));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let joined = |pipe: Option<thread::JoinHandle<String>>| {
+        pipe.and_then(|pipe| pipe.join().ok()).unwrap_or_default()
+    };
+    Ok(Finished {
+        success: status.success(),
+        stdout: joined(stdout),
+        stderr: joined(stderr),
+    })
+}
+
+/// For hooks that can't change anything, failures are only logged
+pub async fn notify(command: Option<PathBuf>, input: serde_json::Value) {
+    let Some(command) = command else {
+        return;
+    };
+    let res = tokio::task::spawn_blocking(move || run(&command, &input)).await;
+    match res {
+        Ok(Ok(finished)) if !finished.success => {
+            warn!("Hook failed: {}", finished.stderr.trim())
+        }
+        Ok(Ok(_)) => {}
+        Ok(Err(err)) => error!("{:?}", err),
+        Err(err) => error!("Hook panicked: {err}"),
+    }
+}
+
+/// The monitor if there's only one, for hooks written before monitor lists
+fn single(monitors: &[i32]) -> Option<i32> {
+    match monitors {
+        [monitor] => Some(*monitor),
+        _ => None,
+    }
+}
+
+/// Asks the pre-apply hook about `path`, returns what should be applied instead
+async fn pre_apply(command: PathBuf, monitors: &[i32], path: PathBuf) -> Result<PathBuf> {
+    let input = serde_json::json!({
+        "hook": "pre-apply",
+        "monitor": single(monitors),
+        "monitors": monitors,
+        "path": os_path::stored(&path),
+    });
+    let finished = tokio::task::spawn_blocking(move || run(&command, &input)).await??;
+    if !finished.success {
+        return Err(anyhow!(
+            "Vetoed by the pre-apply hook: {}",
+            finished.stderr.trim()
+        ));
+    }
+
+    let reply: HookReply = match finished.stdout.trim() {
+        "" => HookReply::default(),
+        stdout => serde_json::from_str(stdout).with_context(|| {
+            format!("Pre-apply hook printed something that isn't json: {stdout}")
+        })?,
+    };
+    if reply.veto {
+        return Err(anyhow!(
+            "Vetoed by the pre-apply hook: {}",
+            reply.reason.as_deref().unwrap_or("no reason given")
+        ));
+    }
+    Ok(reply.path.unwrap_or(path))
+}
+
+/// Applies `path` to `monitors` (every one if empty) with the apply hooks around it, returns
+/// what was actually applied
+pub async fn apply(
+    ipc_handle: &IpcHandle,
+    hooks: &Hooks,
+    monitors: &[i32],
+    path: PathBuf,
+) -> Result<PathBuf> {
+    let path = match hooks.pre_apply.clone() {
+        Some(command) => pre_apply(command, monitors, path).await?,
+        None => path,
+    };
+    ipc_handle.change_backgrounds(monitors, &path).await?;
+    notify(
+        hooks.post_apply.clone(),
+        serde_json::json!({
+            "hook": "post-apply",
+            "monitor": single(monitors),
+            "monitors": monitors,
+            "path": os_path::stored(&path),
+        }),
+    )
+    .await;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    #[test]
+    fn paths_that_arent_utf8_go_through() {
+        let latin1 = PathBuf::from(OsStr::from_bytes(b"/caf\xe9.png"));
+        let input = serde_json::json!({ "path": os_path::stored(&latin1) });
+        assert_eq!(
+            input.to_string(),
+            r#"{"path":{"raw":[47,99,97,102,233,46,112,110,103]}}"#
+        );
+
+        let reply: HookReply = serde_json::from_str(&input.to_string()).unwrap();
+        assert_eq!(reply.path, Some(latin1));
+        let reply: HookReply = serde_json::from_str(r#"{"path": "/b.png"}"#).unwrap();
+        assert_eq!(reply.path, Some(PathBuf::from("/b.png")));
+        let reply: HookReply = serde_json::from_str(r#"{"veto": true}"#).unwrap();
+        assert_eq!(reply.path, None);
+    }
+}
@@ -0,0 +1,272 @@
+use anyhow::{Result, anyhow};
+use global_hotkey::{
+    GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState,
+    hotkey::{Code, HotKey},
+};
+use iced::futures::{SinkExt, Stream, StreamExt, channel::mpsc};
+use serde::{Deserialize, Serialize};
+use std::{fmt, process::Command, str::FromStr, thread};
+use tracing::{debug, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HotkeyAction {
+    NextWallpaper,
+    PreviousWallpaper,
+    PauseVideos,
+    ApplyRandom,
+}
+
+impl HotkeyAction {
+    pub const ALL: &[Self] = &[
+        Self::NextWallpaper,
+        Self::PreviousWallpaper,
+        Self::PauseVideos,
+        Self::ApplyRandom,
+    ];
+}
+
+/// Who gets XF86AudioPlay/Next/Prev
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKeysOwner {
+    /// Leave them alone for the regular media players
+    #[default]
+    Players,
+    /// Always grab them for the wallpaper
+    Wallpaper,
+    /// Grab them only while no MPRIS media player is running
+    WhenNoPlayer,
+}
+
+impl MediaKeysOwner {
+    pub const ALL: &[Self] = &[Self::Players, Self::Wallpaper, Self::WhenNoPlayer];
+}
+
+impl fmt::Display for MediaKeysOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Players => "Media players",
+            Self::Wallpaper => "Wallpaper",
+            Self::WhenNoPlayer => "Wallpaper, unless a media player is running",
+        })
+    }
+}
+
+/// Media key -> what it does to the wallpaper
+const MEDIA_KEYS: &[(Code, HotkeyAction)] = &[
+    (Code::MediaPlay, HotkeyAction::PauseVideos),
+    (Code::MediaPlayPause, HotkeyAction::PauseVideos),
+    (Code::MediaTrackNext, HotkeyAction::NextWallpaper),
+    (Code::MediaTrackPrevious, HotkeyAction::PreviousWallpaper),
+];
+
+/// Asks the session bus if any `org.mpris.MediaPlayer2.*` name is taken
+pub fn mpris_player_running() -> bool {
+    let output = Command::new("dbus-send")
+        .args([
+            "--session",
+            "--dest=org.freedesktop.DBus",
+            "--type=method_call",
+            "--print-reply",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus.ListNames",
+        ])
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).contains("org.mpris.MediaPlayer2.")
+        }
+        // no bus, no players
+        _ => false,
+    }
+}
+
+impl fmt::Display for HotkeyAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::NextWallpaper => "Next wallpaper",
+            Self::PreviousWallpaper => "Previous wallpaper",
+            Self::PauseVideos => "Pause videos",
+            Self::ApplyRandom => "Apply random",
+        })
+    }
+}
+
+/// Accelerators like `super+alt+n`, empty means unbound
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HotkeyBindings {
+    #[serde(default)]
+    pub next_wallpaper: String,
+    #[serde(default)]
+    pub previous_wallpaper: String,
+    #[serde(default)]
+    pub pause_videos: String,
+    #[serde(default)]
+    pub apply_random: String,
+}
+
+impl HotkeyBindings {
+    pub fn get(&self, action: HotkeyAction) -> &str {
+        match action {
+            HotkeyAction::NextWallpaper => &self.next_wallpaper,
+            HotkeyAction::PreviousWallpaper => &self.previous_wallpaper,
+            HotkeyAction::PauseVideos => &self.pause_videos,
+            HotkeyAction::ApplyRandom => &self.apply_random,
+        }
+    }
+
+    pub fn set(&mut self, action: HotkeyAction, accelerator: String) {
+        match action {
+            HotkeyAction::NextWallpaper => self.next_wallpaper = accelerator,
+            HotkeyAction::PreviousWallpaper => self.previous_wallpaper = accelerator,
+            HotkeyAction::PauseVideos => self.pause_videos = accelerator,
+            HotkeyAction::ApplyRandom => self.apply_random = accelerator,
+        }
+    }
+
+    /// Parses every binding, failing on invalid accelerators and on two actions
+    /// sharing the same one
+    pub fn validate(&self) -> Result<Vec<(HotkeyAction, HotKey)>> {
+        let mut parsed: Vec<(HotkeyAction, HotKey)> = Vec::new();
+        for &action in HotkeyAction::ALL {
+            let accelerator = self.get(action).trim();
+            if accelerator.is_empty() {
+                continue;
+            }
+
+            let hotkey = HotKey::from_str(accelerator).map_err(|err| anyhow!("{action}: {err}"))?;
+            if let Some((other, _)) = parsed.iter().find(|(_, h)| h.id() == hotkey.id()) {
+                return Err(anyhow!("{other} and {action} both use `{accelerator}`"));
+            }
+            parsed.push((action, hotkey));
+        }
+        Ok(parsed)
+    }
+}
+
+/// Owns the X11 key grabs
+pub struct Hotkeys {
+    manager: GlobalHotKeyManager,
+    registered: Vec<(HotkeyAction, HotKey)>,
+    /// Grabbed media keys, separate so they can come and go with media players
+    media_keys: Vec<(HotkeyAction, HotKey)>,
+}
+
+impl Hotkeys {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            manager: GlobalHotKeyManager::new()
+                .map_err(|err| anyhow!("Global hotkeys aren't available: {err}"))?,
+            registered: Vec::new(),
+            media_keys: Vec::new(),
+        })
+    }
+
+    pub fn has_media_keys(&self) -> bool {
+        !self.media_keys.is_empty()
+    }
+
+    /// Grabs or releases the media keys, keys the user bound explicitly are left alone
+    pub fn set_media_keys(&mut self, grab: bool) -> Result<()> {
+        if grab == self.has_media_keys() {
+            return Ok(());
+        }
+
+        if !grab {
+            debug!("Releasing media keys");
+            let keys: Vec<HotKey> = self.media_keys.drain(..).map(|(_, h)| h).collect();
+            return self
+                .manager
+                .unregister_all(&keys)
+                .map_err(|err| anyhow!("Failed to release media keys: {err}"));
+        }
+
+        debug!("Grabbing media keys");
+        let mut errors = Vec::new();
+        for &(code, action) in MEDIA_KEYS {
+            let hotkey = HotKey::new(None, code);
+            if self.registered.iter().any(|(_, h)| h.id() == hotkey.id()) {
+                continue;
+            }
+            match self.manager.register(hotkey) {
+                Ok(()) => self.media_keys.push((action, hotkey)),
+                // some keyboards don't have all of them, that's fine
+                Err(err) => errors.push(format!("{}: {err}", hotkey.into_string())),
+            }
+        }
+
+        match self.media_keys.is_empty() && !errors.is_empty() {
+            true => Err(anyhow!(errors.join("\n"))),
+            false => Ok(()),
+        }
+    }
+
+    /// Replaces the registered hotkeys with `bindings`, if another program
+    /// already grabbed one of them the rest are still registered
+    pub fn apply(&mut self, bindings: &HotkeyBindings) -> Result<()> {
+        let wanted = bindings.validate()?;
+
+        let old: Vec<HotKey> = self.registered.drain(..).map(|(_, h)| h).collect();
+        if let Err(err) = self.manager.unregister_all(&old) {
+            warn!("Failed to unregister hotkeys: {err}");
+        }
+
+        // explicit bindings win over grabbed media keys
+        let grabbed_media_keys = self.has_media_keys();
+        self.set_media_keys(false)?;
+
+        let mut errors = Vec::new();
+        for (action, hotkey) in wanted {
+            match self.manager.register(hotkey) {
+                Ok(()) => {
+                    debug!("Registered {} for {action}", hotkey.into_string());
+                    self.registered.push((action, hotkey));
+                }
+                Err(global_hotkey::Error::AlreadyRegistered(_)) => errors.push(format!(
+                    "{action}: `{}` is already taken by another program",
+                    hotkey.into_string()
+                )),
+                Err(err) => errors.push(format!("{action}: {err}")),
+            }
+        }
+        if grabbed_media_keys && let Err(err) = self.set_media_keys(true) {
+            errors.push(format!("{err}"));
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!(errors.join("\n"))),
+        }
+    }
+
+    pub fn action(&self, id: u32) -> Option<HotkeyAction> {
+        self.registered
+            .iter()
+            .chain(&self.media_keys)
+            .find(|(_, hotkey)| hotkey.id() == id)
+            .map(|(action, _)| *action)
+    }
+}
+
+/// Ids of pressed hotkeys
+///
+/// NOTE: only run this once, the events come from a single global channel
+pub fn events() -> impl Stream<Item = u32> {
+    iced::stream::channel(16, async |mut output| {
+        // the global-hotkey receiver is blocking, so forward from a thread
+        let (sender, mut receiver) = mpsc::unbounded();
+        thread::spawn(move || {
+            while let Ok(event) = GlobalHotKeyEvent::receiver().recv() {
+                if sender.unbounded_send(event).is_err() {
+                    break;
+                }
+            }
+        });
+
+        while let Some(event) = receiver.next().await {
+            if event.state == HotKeyState::Pressed {
+                let _ = output.send(event.id).await;
+            }
+        }
+    })
+}
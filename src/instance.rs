@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use iced::futures::{SinkExt, Stream, StreamExt, channel::mpsc};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+use tracing::{debug, error, warn};
+
+use crate::xdg;
+
+/// Set by `claim()`, taken by `events()`
+static LISTENER: Mutex<Option<UnixListener>> = Mutex::new(None);
+
+/// What a second launch hands over to the running instance
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Activation {
+    #[serde(with = "crate::os_path::vec")]
+    pub files: Vec<PathBuf>,
+    /// `DESKTOP_STARTUP_ID` (x11) or `XDG_ACTIVATION_TOKEN` (wayland) of the launch
+    pub startup_id: Option<String>,
+}
+
+impl Activation {
+    pub fn from_env(files: &[PathBuf]) -> Self {
+        Self {
+            // the running instance has a different working directory
+            files: files
+                .iter()
+                .map(|file| file.canonicalize().unwrap_or_else(|_| file.clone()))
+                .collect(),
+            startup_id: env::var("DESKTOP_STARTUP_ID")
+                .or_else(|_| env::var("XDG_ACTIVATION_TOKEN"))
+                .ok()
+                .filter(|id| !id.is_empty()),
+        }
+    }
+}
+
+pub fn socket_path() -> PathBuf {
+    xdg::runtime_dir().join("xab-gui.sock")
+}
+
+/// Hands `activation` to an already running instance, returns false if there is none
+pub fn forward(activation: &Activation) -> Result<bool> {
+    let path = socket_path();
+    let mut stream = match UnixStream::connect(&path) {
+        Ok(stream) => stream,
+        Err(err)
+            if matches!(
+                err.kind(),
+                ErrorKind::NotFound | ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(false);
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to connect to {}", path.display()));
+        }
+    };
+    let mut line = serde_json::to_string(activation)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    debug!("Forwarded {:?} to the running instance", activation);
+    Ok(true)
+}
+
+/// Becomes the running instance, later launches will be forwarded here
+pub fn claim() -> Result<()> {
+    let path = socket_path();
+    // we already know nobody is listening, so it's left over from a crash
+    if path.exists() {
+        debug!("Removing stale {}", path.display());
+        let _ = fs::remove_file(&path);
+    }
+    let listener =
+        UnixListener::bind(&path).with_context(|| format!("Failed to bind {}", path.display()))?;
+    *LISTENER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(listener);
+    Ok(())
+}
+
+/// Removes the socket so the next launch doesn't try to forward to us
+pub fn release() {
+    let listener = LISTENER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take();
+    if listener.is_some() {
+        let _ = fs::remove_file(socket_path());
+    }
+}
+
+/// Activations forwarded by later launches
+///
+/// NOTE: only run this once, the listener can only be taken once
+pub fn events() -> impl Stream<Item = Activation> {
+    iced::stream::channel(16, async |mut output| {
+        let Some(listener) = LISTENER
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take()
+        else {
+            warn!("Not the primary instance, ignoring activations");
+            return;
+        };
+
+        // accept() blocks, so forward from a thread
+        let (sender, mut receiver) = mpsc::unbounded();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        error!("Failed to accept an activation: {err}");
+                        continue;
+                    }
+                };
+                // a launcher that connects and then hangs shouldn't block the next one
+                let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+                let mut line = String::new();
+                if let Err(err) = BufReader::new(stream).read_line(&mut line) {
+                    error!("Failed to read an activation: {err}");
+                    continue;
+                }
+                match serde_json::from_str::<Activation>(&line) {
+                    Ok(activation) => {
+                        if sender.unbounded_send(activation).is_err() {
+                            break;
+                        }
+                    }
+                    Err(err) => error!("Invalid activation {line:?}: {err}"),
+                }
+            }
+        });
+
+        while let Some(activation) = receiver.next().await {
+            let _ = output.send(activation).await;
+        }
+    })
+}
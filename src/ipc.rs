@@ -1,143 +1,1386 @@
 use anyhow::{Context, Result, anyhow};
-use bytes::{Bytes, BytesMut};
-use iced::futures::lock::{Mutex, MutexGuard};
+use bytes::{Buf, Bytes};
+use iced::futures::{SinkExt, Stream, StreamExt, channel::mpsc};
 use std::{
-    io::{Read, Write},
-    os::unix::net::UnixStream,
+    collections::{HashMap, VecDeque},
+    env, fmt, fs, io,
+    os::unix::fs::FileTypeExt,
+    path::Path,
+    sync::{
+        self, Arc,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    },
+    time::{Duration, Instant},
 };
-use tracing::{debug, error};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{UnixStream, unix::OwnedReadHalf},
+    sync::oneshot,
+    task::JoinHandle,
+    time::timeout,
+};
+use tracing::{debug, error, warn};
 
+use crate::ipc_outbox::*;
 use crate::ipc_spec::*;
+use crate::sources::gradient;
+use crate::tunnel::SshProfile;
+
+/// How long to wait for a reply before giving up on it
+pub const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long connecting can take before it's given up on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// For the socket to accept the connection
+    pub connect: Duration,
+    /// For xab to go through the version and capabilities exchange after that
+    pub handshake: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(5),
+            handshake: Duration::from_secs(5),
+        }
+    }
+}
+
+type Latencies = Arc<sync::Mutex<HashMap<IpcCommands, CommandLatency>>>;
+/// Whoever is waiting for a reply, in the order their commands were queued.
+/// `None` once the connection is gone
+type Replies = Arc<sync::Mutex<Option<VecDeque<Waiter>>>>;
+
+#[derive(Debug)]
+enum Waiter {
+    /// For the next reply
+    Reply(oneshot::Sender<Bytes>),
+    /// For replies until the last of a GetMonitors, see `chunk_continues`
+    Chunks(mpsc::UnboundedSender<Bytes>, IpcXabCapabilities),
+    /// For the status of a command nobody waits on, failures are only logged
+    Status(IpcCommands),
+}
+
+fn lock<T>(mutex: &sync::Mutex<T>) -> sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Round-trip times of a single command, for commands without a reply
+/// it's the time it took to write the command
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CommandLatency {
+    pub count: u32,
+    pub last: Duration,
+    pub max: Duration,
+    pub total: Duration,
+}
+
+impl CommandLatency {
+    pub fn average(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => self.total / count,
+        }
+    }
+
+    fn record(&mut self, elapsed: Duration) {
+        self.count += 1;
+        self.last = elapsed;
+        self.max = self.max.max(elapsed);
+        self.total += elapsed;
+    }
+}
+
+fn record_latency(latencies: &Latencies, command: IpcCommands, elapsed: Duration) {
+    debug!("{:?} took {:?}", command, elapsed);
+    lock(latencies).entry(command).or_default().record(elapsed);
+}
+
+/// An xab socket, there's one per X display when more than one xab runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XabSocket {
+    pub path: String,
+    /// `:1` for `xab_uds.:1`, None for the plain `xab_uds`
+    pub display: Option<String>,
+}
+
+impl fmt::Display for XabSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.display {
+            Some(display) => write!(f, "Display {display}"),
+            None => f.write_str("Default display"),
+        }
+    }
+}
+
+/// Something the connect page can connect to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    Local(XabSocket),
+    /// Typed in by the user, somewhere `find_sockets` doesn't look
+    Custom(String),
+    Ssh(SshProfile),
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local(socket) => socket.fmt(f),
+            Self::Custom(path) => f.write_str(path),
+            Self::Ssh(profile) => profile.fmt(f),
+        }
+    }
+}
+
+/// Every xab socket next to `IPC_PATH`, sorted by display
+pub fn find_sockets() -> Vec<XabSocket> {
+    let default = Path::new(IPC_PATH);
+    let (Some(dir), Some(prefix)) = (default.parent(), default.file_name()) else {
+        return Vec::new();
+    };
+    let prefix = prefix.to_string_lossy();
+
+    let mut sockets: Vec<XabSocket> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_socket()))
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let display = match name.strip_prefix(prefix.as_ref())? {
+                "" => None,
+                suffix => Some(suffix.strip_prefix('.')?.to_owned()),
+            };
+            Some(XabSocket {
+                path: entry.path().to_string_lossy().into_owned(),
+                display,
+            })
+        })
+        .collect();
+    sockets.sort_by(|a, b| a.display.cmp(&b.display));
+    sockets
+}
+
+/// The socket of our own `$DISPLAY` if there's one, otherwise the plain one
+pub fn default_socket(sockets: &[XabSocket]) -> Option<&XabSocket> {
+    let display = env::var("DISPLAY").ok();
+    sockets
+        .iter()
+        .find(|socket| display.is_some() && socket.display == display)
+        .or_else(|| sockets.iter().find(|socket| socket.display.is_none()))
+        .or_else(|| sockets.first())
+}
+
+/// Whether `path` could be an xab socket, it doesn't have to exist yet
+pub fn check_socket_path(path: &str) -> Result<()> {
+    if !Path::new(path).is_absolute() {
+        return Err(anyhow!(
+            "The socket path should be absolute, like {IPC_PATH}"
+        ));
+    }
+    // sun_path is 108 bytes, the terminating nul included
+    if path.len() > 107 {
+        return Err(anyhow!(
+            "The socket path is {} bytes long, sockets can't be longer than 107",
+            path.len()
+        ));
+    }
+    match fs::metadata(path) {
+        Ok(metadata) if !metadata.file_type().is_socket() => {
+            Err(anyhow!("{path} exists but isn't a socket"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// For the cli, falls back to `IPC_PATH` so the error message makes sense
+pub fn default_socket_path() -> String {
+    default_socket(&find_sockets())
+        .map(|socket| socket.path.clone())
+        .unwrap_or_else(|| IPC_PATH.to_owned())
+}
 
 #[derive(Debug)]
 pub struct IpcHandle {
     pub path: String,
-    socket: Mutex<UnixStream>,
-    pub capabilities: IpcXabCapabilities,
+    /// All writes go through the outbox, its task owns the writing side
+    outbox: Arc<Outbox>,
+    /// Owns the reading side, reads either replies or events
+    reader: JoinHandle<()>,
+    replies: Replies,
+    /// Bits of `IpcXabCapabilities`, see `refresh_capabilities`
+    capabilities: AtomicU32,
+    pub handshake_latency: Duration,
+    /// Agreed on in the handshake, see `negotiate_version`
+    pub protocol: i32,
+    // std mutex because view() needs to read it synchronously
+    latencies: Latencies,
+    /// Taken by whoever listens for events, None if xab doesn't send any
+    events: sync::Mutex<Option<mpsc::UnboundedReceiver<IpcEvent>>>,
+    /// Set once xab said it's going away, nothing is sent after that
+    shutting_down: AtomicBool,
+    /// From the last `get_monitors`, backgrounds are addressed with them
+    screens: sync::Mutex<Screens>,
+    /// From the last `IpcEvent::MonitorsChanged`, saves asking for them
+    pushed_monitors: sync::Mutex<Vec<Monitor>>,
+    /// Sent with every background, see `PerMonitor`
+    scaling: sync::Mutex<PerMonitor<ScalingMode>>,
+    video: sync::Mutex<PerMonitor<VideoOptions>>,
+    /// From `probe_formats`, None until xab said
+    formats: sync::Mutex<Option<Vec<String>>>,
+}
+
+/// By monitor, `None` being every monitor
+type PerMonitor<T> = HashMap<Option<i32>, T>;
+
+/// What was set for `monitor`, or else for every monitor
+fn for_monitor<T: Copy + Default>(values: &PerMonitor<T>, monitor: Option<i32>) -> T {
+    values
+        .get(&monitor)
+        .or_else(|| values.get(&None))
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Every monitor if `monitors` is empty
+fn set_for_monitors<T: Copy>(values: &mut PerMonitor<T>, monitors: &[i32], value: T) {
+    if monitors.is_empty() {
+        values.clear();
+        values.insert(None, value);
+    }
+    for &monitor in monitors {
+        values.insert(Some(monitor), value);
+    }
+}
+
+/// What the user is shown for `err`: xab's own errors are explained, anything
+/// else is its chain of contexts on one line
+pub fn describe_error(err: &anyhow::Error) -> String {
+    match err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<IpcError>())
+    {
+        Some(ipc_err) => ipc_err.to_string(),
+        None => format!("{:#}", err),
+    }
+}
+
+/// Reads `socket` into `decoder` until a whole message is in, None once the
+/// connection is gone or out of sync
+async fn next_message(socket: &mut OwnedReadHalf, decoder: &mut FrameDecoder) -> Option<Incoming> {
+    loop {
+        match decoder.decode_next() {
+            Ok(Some(message)) => return Some(message),
+            Ok(None) => {}
+            Err(err) => {
+                error!("{:?}, ignoring what xab sends from now on", err);
+                return None;
+            }
+        }
+        match socket.read_buf(decoder.buffer()).await {
+            Ok(0) if decoder.pending() > 0 => {
+                debug!(
+                    "Stopped reading: closed {} bytes into a message",
+                    decoder.pending()
+                );
+                return None;
+            }
+            Ok(0) => {
+                debug!("Stopped reading: closed");
+                return None;
+            }
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => {
+                debug!("Stopped reading: {err}");
+                return None;
+            }
+        }
+    }
+}
+
+/// Hands `reply` to whoever's been waiting the longest, false once the
+/// connection is closed
+fn route_reply(replies: &Replies, reply: Bytes) -> bool {
+    let mut queue = lock(replies);
+    let Some(queue) = queue.as_mut() else {
+        return false;
+    };
+    // the receiver might've given up, the reply is still theirs though
+    match queue.front() {
+        Some(Waiter::Chunks(waiting, capabilities)) => {
+            let last = !chunk_continues(&reply, *capabilities);
+            let _ = waiting.unbounded_send(reply);
+            if last {
+                queue.pop_front();
+            }
+        }
+        Some(Waiter::Reply(_)) => {
+            if let Some(Waiter::Reply(waiting)) = queue.pop_front() {
+                let _ = waiting.send(reply);
+            }
+        }
+        Some(Waiter::Status(command)) => {
+            if let Err(err) = decode_reply(reply, IpcXabCapabilities::ErrorReplies) {
+                warn!("xab refused {command:?}: {}", describe_error(&err));
+            }
+            queue.pop_front();
+        }
+        None => warn!("Got a reply nobody asked for, ignoring it"),
+    }
+    true
+}
+
+/// Reads until the socket closes, replies go to whoever's waiting for them
+/// and events (only sent with `IpcXabCapabilities::Events`) to `events`
+async fn read_messages(
+    mut socket: OwnedReadHalf,
+    capabilities: IpcXabCapabilities,
+    replies: Replies,
+    events: Option<mpsc::UnboundedSender<IpcEvent>>,
+) {
+    let kind = match events {
+        Some(_) => StreamKind::Events,
+        None => StreamKind::Replies,
+    };
+    let mut decoder = FrameDecoder::new(kind);
+    while let Some(incoming) = next_message(&mut socket, &mut decoder).await {
+        match incoming {
+            Incoming::Reply(reply) => {
+                if !route_reply(&replies, reply) {
+                    break;
+                }
+            }
+            Incoming::Event(mut event) => match IpcEvent::decode(&mut event, capabilities) {
+                Ok(event) => {
+                    debug!("Event: {:?}", event);
+                    // nobody listening anymore, replies still matter
+                    if let Some(events) = &events {
+                        let _ = events.unbounded_send(event);
+                    }
+                }
+                Err(err) => error!("Failed to decode event: {:?}", err),
+            },
+        }
+    }
+    // lets everyone still waiting know it's not coming
+    lock(&replies).take();
 }
 
 impl IpcHandle {
-    pub async fn new(path: &str) -> Result<Self> {
+    /// Has to be called from within a tokio runtime, the connection's tasks
+    /// are spawned on it
+    pub async fn with_timeouts(path: &str, timeouts: Timeouts) -> Result<Self> {
         debug!("Initializing Unix Domain Socket");
-        let mut socket = UnixStream::connect(path)
+        let handshake_start = Instant::now();
+        let socket = timeout(timeouts.connect, UnixStream::connect(path))
+            .await
+            .map_err(|_| {
+                anyhow!(
+                    "xab at {path} didn't accept the connection in {:?}",
+                    timeouts.connect
+                )
+            })?
             .with_context(|| format!("Failed to connect to socket at {path}"))?;
+        // xab accepting and then saying nothing would hang here forever
+        timeout(
+            timeouts.handshake,
+            Self::handshake(path, socket, handshake_start),
+        )
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "xab at {path} accepted the connection but didn't finish the handshake in {:?}, \
+                 it might be stuck",
+                timeouts.handshake
+            )
+        })?
+    }
 
+    async fn handshake(
+        path: &str,
+        mut socket: UnixStream,
+        handshake_start: Instant,
+    ) -> Result<Self> {
         // get xab IPC protocol version from server
         let mut buf = [0u8; std::mem::size_of::<i32>()]; // rust is so weird 0_0
         socket
             .read_exact(&mut buf)
+            .await
             .with_context(|| "Failed to read IPC protocol version")?;
 
         // version from buf - uses native-endianness
         let version: i32 = i32::from_be_bytes(buf);
         debug!("Server IPC version: {version}");
 
-        // send version back
-        buf = IPC_PROTO_VERSION.to_be_bytes();
+        let protocol = match negotiate_version(version) {
+            Ok(protocol) => protocol,
+            Err(err) => {
+                error!("{err}");
+                socket.shutdown().await?;
+                return Err(err);
+            }
+        };
+        debug!("Speaking IPC protocol {protocol}");
+
+        // send the version we'll both speak back
+        buf = protocol.to_be_bytes();
         socket
             .write_all(&buf)
+            .await
             .with_context(|| "Failed to send IPC protocol version")?;
 
-        // if version is mismatched - disconnect
-        if version != IPC_PROTO_VERSION {
-            error!(
-                "Mismatch between client and server xab IPC protocol version! (server: {} | client| {})",
-                version, IPC_PROTO_VERSION
-            );
-            socket.shutdown(std::net::Shutdown::Both)?;
-            return Err(anyhow!(
-                "Mismatch between client and server xab IPC protocol version! (server: {} | client: {})",
-                version,
-                IPC_PROTO_VERSION
-            ));
-        } else {
-            debug!("Server and Client xab IPC protocol versions match!");
-        }
-
         // read capabilities
         debug!("Getting XAB capabilities");
-        socket
-            .read_exact(&mut buf)
-            .with_context(|| "Failed to read XAB capabilities")?;
+        socket.read_exact(&mut buf).await.with_context(|| {
+            format!("xab hung up instead of agreeing on IPC protocol {protocol}")
+        })?;
         let capabilities = IpcXabCapabilities::from_bits_truncate(u32::from_be_bytes(buf));
         debug!(
             "capabilities: {:?} {:b}",
             capabilities,
             u32::from_be_bytes(buf)
         );
+        let handshake_latency = handshake_start.elapsed();
+        debug!("Handshake took {:?}", handshake_latency);
+
+        let (reader, writer) = socket.into_split();
+        let latencies = Latencies::default();
+        let writer_latencies = latencies.clone();
+        let outbox = Outbox::spawn(writer, move |command, elapsed| {
+            record_latency(&writer_latencies, command, elapsed)
+        });
+
+        let replies = Replies::new(sync::Mutex::new(Some(VecDeque::new())));
+        let (sender, events) = match capabilities.contains(IpcXabCapabilities::Events) {
+            true => {
+                let (sender, receiver) = mpsc::unbounded();
+                (Some(sender), Some(receiver))
+            }
+            false => (None, None),
+        };
+        let reader = tokio::spawn(read_messages(reader, capabilities, replies.clone(), sender));
 
         Ok(Self {
             path: path.to_owned(),
-            socket: Mutex::from(socket),
-            capabilities,
+            outbox,
+            reader,
+            replies,
+            capabilities: AtomicU32::new(capabilities.bits()),
+            handshake_latency,
+            protocol,
+            latencies,
+            events: sync::Mutex::new(events),
+            shutting_down: AtomicBool::new(false),
+            screens: sync::Mutex::new(Screens::new()),
+            pushed_monitors: sync::Mutex::new(Vec::new()),
+            scaling: sync::Mutex::new(HashMap::new()),
+            video: sync::Mutex::new(HashMap::new()),
+            formats: sync::Mutex::new(None),
         })
     }
 
-    /// NOTE: try not deadlocking yourself - by using the guard argument
-    pub async fn send_commands<'a>(
-        &'a self,
-        commands: u32,
-        guard: Option<MutexGuard<'a, UnixStream>>,
-    ) -> Result<MutexGuard<'a, UnixStream>> {
-        let mut socket = if let Some(guard) = guard {
-            guard
-        } else {
-            self.socket.lock().await
+    /// From the handshake, or the last `refresh_capabilities`
+    pub fn capabilities(&self) -> IpcXabCapabilities {
+        IpcXabCapabilities::from_bits_truncate(self.capabilities.load(Ordering::Relaxed))
+    }
+
+    /// Asks xab what it can do again, true if that changed
+    pub async fn refresh_capabilities(&self) -> Result<bool> {
+        let mut reply = self
+            .send_recv_command(IpcCommands::GetCapabilites)
+            .await?
+            .ok_or_else(|| anyhow!("xab didn't send its capabilities"))?;
+        ensure_remaining(&reply, 4, "capabilities")?;
+        let refreshed = IpcXabCapabilities::from_bits_truncate(reply.get_u32());
+        let old = self.capabilities();
+        // the reader was started for one or the other, that can't change now
+        let refreshed = refreshed.difference(IpcXabCapabilities::Events)
+            | old.intersection(IpcXabCapabilities::Events);
+        if refreshed == old {
+            return Ok(false);
+        }
+        debug!("Capabilities changed from {:?} to {:?}", old, refreshed);
+        self.capabilities.store(refreshed.bits(), Ordering::Relaxed);
+        Ok(true)
+    }
+
+    /// Asks xab which extensions it can show, see `formats`. Nothing to ask
+    /// without `IpcXabCapabilities::Formats`
+    pub async fn probe_formats(&self) -> Result<()> {
+        if !self.capabilities().contains(IpcXabCapabilities::Formats) {
+            return Ok(());
+        }
+        let formats = match self.send_recv_command(IpcCommands::GetFormats).await? {
+            Some(reply) => decode_formats(reply)?,
+            None => Vec::new(),
+        };
+        debug!("xab can show {:?}", formats);
+        *lock(&self.formats) = Some(formats);
+        Ok(())
+    }
+
+    /// What xab said it can show, None if it wasn't asked or couldn't be
+    pub fn formats(&self) -> Option<Vec<String>> {
+        lock(&self.formats).clone()
+    }
+
+    /// Snapshot of the recorded round-trip times, sorted by command
+    pub fn latencies(&self) -> Vec<(IpcCommands, CommandLatency)> {
+        let mut latencies: Vec<_> = lock(&self.latencies)
+            .iter()
+            .map(|(command, latency)| (*command, *latency))
+            .collect();
+        latencies.sort_by_key(|(command, _)| *command as i32);
+        latencies
+    }
+
+    /// Events xab sends on its own, can only be taken once
+    pub fn take_events(&self) -> Option<mpsc::UnboundedReceiver<IpcEvent>> {
+        lock(&self.events).take()
+    }
+
+    /// Stops sending commands, xab announced it's going away
+    pub fn mark_shutting_down(&self) {
+        self.shutting_down.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Relaxed)
+    }
+
+    /// Queues a command without waiting for it to be written, fails if the
+    /// daemon stopped reading and the outbox is full. With
+    /// `IpcXabCapabilities::ErrorReplies` its status is waited for (and
+    /// logged if it failed) so the replies after it line up
+    pub fn send_command(&self, outgoing: Outgoing) -> Result<()> {
+        if self
+            .capabilities()
+            .contains(IpcXabCapabilities::ErrorReplies)
+        {
+            let command = outgoing.command;
+            return self.send_expecting(outgoing, Waiter::Status(command));
+        }
+        self.queue(outgoing)
+    }
+
+    fn queue(&self, outgoing: Outgoing) -> Result<()> {
+        if self.is_shutting_down() {
+            return Err(anyhow!("xab is shutting down, try again once it's back"));
+        }
+        if self.capabilities().is_locked(outgoing.command) {
+            return Err(anyhow!("Can't {:?}: {PRIVILEGE_HINT}", outgoing.command));
+        }
+        self.outbox.push(outgoing)
+    }
+
+    /// Sends `outgoing` with `waiter` in line for what comes back. It isn't
+    /// merged with the commands queued before it, each gets its own reply
+    fn send_expecting(&self, mut outgoing: Outgoing, waiter: Waiter) -> Result<()> {
+        // queued under the same lock so replies line up with the waiters
+        let mut replies = lock(&self.replies);
+        let Some(replies) = replies.as_mut() else {
+            return Err(anyhow!("Connection is closed"));
         };
-        socket.write_all(&commands.to_be_bytes())?;
-        Ok(socket)
+        outgoing.merge_key = None;
+        self.queue(outgoing)?;
+        replies.push_back(waiter);
+        Ok(())
     }
 
-    /// NOTE: try not deadlocking yourself
+    /// Sends `command` and waits for its reply, `None` if the reply is empty.
+    /// Safe to drop halfway, the reply is thrown away once it arrives
     pub async fn send_recv_command(&self, command: IpcCommands) -> Result<Option<Bytes>> {
-        // TODO: guard thingy like i did with send_commands
-        let mut socket = self.socket.lock().await;
-        socket.write_all(&(command as i32).to_be_bytes())?;
-
-        let mut demz_bytes = BytesMut::new();
-        socket.read_exact(&mut demz_bytes)?;
-        let demz_bytes: Bytes = demz_bytes.freeze();
-        Ok(match !demz_bytes.is_empty() {
-            true => Some(demz_bytes),
-            false => None,
-        })
+        let reply = self
+            .send_recv(Outgoing::new(command, None).expects_reply())
+            .await?;
+        Ok((!reply.is_empty()).then_some(reply))
+    }
+
+    /// Like `send_command`, waiting for xab to take it with
+    /// `IpcXabCapabilities::ErrorReplies` so a failure comes back as an
+    /// `IpcError`
+    async fn send_checked(&self, outgoing: Outgoing) -> Result<()> {
+        match self
+            .capabilities()
+            .contains(IpcXabCapabilities::ErrorReplies)
+        {
+            true => self.send_recv(outgoing).await.map(drop),
+            false => self.queue(outgoing),
+        }
+    }
+
+    async fn send_recv(&self, outgoing: Outgoing) -> Result<Bytes> {
+        let command = outgoing.command;
+        let start = Instant::now();
+        let (sender, receiver) = oneshot::channel();
+        self.send_expecting(outgoing, Waiter::Reply(sender))?;
+
+        let reply = match timeout(REPLY_TIMEOUT, receiver).await {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(_)) => return Err(anyhow!("Connection closed before {command:?} got a reply")),
+            Err(_) => {
+                return Err(anyhow!(
+                    "xab didn't reply to {command:?} in {REPLY_TIMEOUT:?}"
+                ));
+            }
+        };
+        record_latency(&self.latencies, command, start.elapsed());
+        decode_reply(reply, self.capabilities()).with_context(|| format!("xab refused {command:?}"))
+    }
+
+    /// How backgrounds sent to `monitor` from now on are scaled, falls back
+    /// to what was set for every monitor
+    pub fn scaling(&self, monitor: Option<i32>) -> ScalingMode {
+        for_monitor(&lock(&self.scaling), monitor)
+    }
+
+    /// Takes effect with the next background, every monitor if `monitors` is
+    /// empty
+    pub fn set_scaling(&self, monitors: &[i32], mode: ScalingMode) {
+        set_for_monitors(&mut lock(&self.scaling), monitors, mode);
+    }
+
+    /// Like `scaling`, for videos
+    pub fn video_options(&self, monitor: Option<i32>) -> VideoOptions {
+        for_monitor(&lock(&self.video), monitor)
+    }
+
+    pub fn set_video_options(&self, monitors: &[i32], options: VideoOptions) {
+        set_for_monitors(&mut lock(&self.video), monitors, options);
+    }
+
+    pub async fn change_background(&self, monitor: Option<i32>, path: &Path) -> Result<()> {
+        self.change_background_scaled(monitor, path, self.scaling(monitor))
+            .await
+    }
+
+    /// `change_background` with its own scaling, for saved wallpapers put
+    /// back the way they were
+    pub async fn change_background_scaled(
+        &self,
+        monitor: Option<i32>,
+        path: &Path,
+        scaling: ScalingMode,
+    ) -> Result<()> {
+        debug!(
+            "Changing background of {:?} to `{}`",
+            monitor,
+            path.display()
+        );
+        let payload = encode_change_background(
+            monitor,
+            &lock(&self.screens),
+            path,
+            scaling,
+            self.video_options(monitor),
+            self.capabilities(),
+        )?;
+        self.send_checked(
+            Outgoing::new(IpcCommands::ChangeBackground, Some(payload))
+                .merge_key(monitor.unwrap_or(-1)),
+        )
+        .await
+        .with_context(|| "Failed to send background options")
+    }
+
+    /// Same background on several monitors, every monitor if `monitors` is
+    /// empty. xab without `MonitorLists` gets a command per monitor
+    pub async fn change_backgrounds(&self, monitors: &[i32], path: &Path) -> Result<()> {
+        match monitors {
+            [] => self.change_background(None, path).await,
+            [monitor] => self.change_background(Some(*monitor), path).await,
+            _ if self
+                .capabilities()
+                .contains(IpcXabCapabilities::MonitorLists) =>
+            {
+                debug!(
+                    "Changing background of {:?} to `{}`",
+                    monitors,
+                    path.display()
+                );
+                let payload = encode_change_backgrounds(
+                    monitors,
+                    &lock(&self.screens),
+                    path,
+                    // set for all of them at once from the ui
+                    self.scaling(Some(monitors[0])),
+                    self.video_options(Some(monitors[0])),
+                    self.capabilities(),
+                )?;
+                self.send_checked(Outgoing::new(IpcCommands::ChangeBackgrounds, Some(payload)))
+                    .await
+                    .with_context(|| "Failed to send background options")
+            }
+            _ => {
+                for &monitor in monitors {
+                    self.change_background(Some(monitor), path).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Takes the wallpaper off `monitors` (every monitor if it's empty),
+    /// leaving `color`. xab without `Clear` gets a wallpaper of that color
+    pub async fn clear_backgrounds(&self, monitors: &[i32], color: [u8; 3]) -> Result<()> {
+        if !self.capabilities().contains(IpcXabCapabilities::Clear) {
+            let path = gradient::solid(color)?;
+            return self.change_backgrounds(monitors, &path).await;
+        }
+        let targets: Vec<Option<i32>> = match monitors {
+            [] => vec![None],
+            monitors => monitors.iter().copied().map(Some).collect(),
+        };
+        for monitor in targets {
+            debug!("Clearing the background of {:?}", monitor);
+            let payload =
+                encode_clear_background(monitor, &lock(&self.screens), color, self.capabilities());
+            self.send_checked(
+                Outgoing::new(IpcCommands::ClearBackground, Some(payload))
+                    .merge_key(monitor.unwrap_or(-1)),
+            )
+            .await
+            .with_context(|| "Failed to clear the background")?;
+        }
+        Ok(())
+    }
+
+    pub async fn toggle_pause_videos(&self) -> Result<()> {
+        debug!("Toggling video pause");
+        self.send_command(Outgoing::new(IpcCommands::TogglePauseVideo, None))
+    }
+
+    /// Pauses (`Some(true)`), resumes or toggles the videos on `monitors`,
+    /// every monitor if it's empty
+    pub async fn video_playback(&self, monitors: &[i32], paused: Option<bool>) -> Result<()> {
+        let command = match paused {
+            Some(true) => IpcCommands::PauseVideo,
+            Some(false) => IpcCommands::UnpauseVideo,
+            None => IpcCommands::TogglePauseVideo,
+        };
+        if monitors.is_empty() {
+            debug!("{:?} on every monitor", command);
+            return self.send_command(Outgoing::new(command, None));
+        }
+        if !self
+            .capabilities()
+            .contains(IpcXabCapabilities::PerMonitorPlayback)
+        {
+            return Err(anyhow!(
+                "This xab can only pause the videos of every monitor at once"
+            ));
+        }
+        for &monitor in monitors {
+            debug!("{:?} on monitor {}", command, monitor);
+            self.send_command(Outgoing::new(command, Some(encode_video_monitor(monitor))))?;
+        }
+        Ok(())
+    }
+
+    pub async fn set_videos_paused(&self, paused: bool) -> Result<()> {
+        debug!("Setting video pause to {paused}");
+        self.send_command(Outgoing::new(
+            match paused {
+                true => IpcCommands::PauseVideo,
+                false => IpcCommands::UnpauseVideo,
+            },
+            None,
+        ))
+    }
+
+    /// Asks xab to restart, it comes back on the same socket
+    pub async fn restart_xab(&self) -> Result<()> {
+        debug!("Restarting xab");
+        self.send_command(Outgoing::new(IpcCommands::Restart, None))
+    }
+
+    pub async fn shutdown_xab(&self) -> Result<()> {
+        debug!("Shutting xab down");
+        self.send_command(Outgoing::new(IpcCommands::Shutdown, None))
     }
 
     pub async fn close(&self) -> Result<()> {
         debug!("Closing connection: {}", self.path);
 
-        let socket = self.socket.lock().await;
-        let socket = self
-            .send_commands(IpcCommands::ClientDisconnect as u32, Some(socket))
-            .await?;
-        socket.shutdown(std::net::Shutdown::Both)?;
-        Ok(())
+        self.send_command(Outgoing::new(IpcCommands::ClientDisconnect, None))?;
+        // give the queued commands a chance to make it out before hanging up
+        let flushed = self.outbox.flush(WRITE_TIMEOUT).await;
+        self.outbox.close();
+        self.reader.abort();
+        flushed
+    }
+
+    /// Keeps what `IpcEvent::MonitorsChanged` brought for `get_monitors`
+    pub fn monitors_pushed(&self, monitors: &[Monitor]) {
+        self.remember_screens(monitors);
+        *lock(&self.pushed_monitors) = monitors.to_vec();
+    }
+
+    fn remember_screens(&self, monitors: &[Monitor]) {
+        *lock(&self.screens) = monitors
+            .iter()
+            .map(|monitor| (monitor.index, monitor.screen))
+            .collect();
+    }
+
+    /// GetMonitors answered a few at a time, `arrived` gets the monitors so
+    /// far after every reply but the last
+    async fn get_monitor_chunks(
+        &self,
+        mut arrived: impl FnMut(&[Monitor]),
+    ) -> Result<Vec<Monitor>> {
+        let start = Instant::now();
+        let capabilities = self.capabilities();
+        let (sender, mut receiver) = mpsc::unbounded();
+        self.send_expecting(
+            Outgoing::new(IpcCommands::GetMonitors, None).expects_reply(),
+            Waiter::Chunks(sender, capabilities),
+        )?;
+        let mut monitors = Vec::new();
+        loop {
+            let chunk = match timeout(REPLY_TIMEOUT, receiver.next()).await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => {
+                    return Err(anyhow!("Connection closed before every monitor arrived"));
+                }
+                Err(_) => {
+                    return Err(anyhow!(
+                        "xab stopped sending monitors after {}",
+                        monitors.len()
+                    ));
+                }
+            };
+            let chunk =
+                decode_reply(chunk, capabilities).with_context(|| "xab refused GetMonitors")?;
+            let (chunk, more) = decode_monitor_chunk(chunk, capabilities)
+                .with_context(|| "Failed to decode monitors")?;
+            monitors.extend(chunk);
+            if !more {
+                record_latency(&self.latencies, IpcCommands::GetMonitors, start.elapsed());
+                return Ok(monitors);
+            }
+            arrived(&monitors);
+        }
+    }
+
+    async fn get_monitor_list(&self) -> Result<Vec<Monitor>> {
+        match self.send_recv_command(IpcCommands::GetMonitors).await? {
+            Some(monitors) => decode_monitors(monitors, self.capabilities())
+                .with_context(|| "Failed to decode monitors"),
+            None => Err(anyhow!("xab didn't send any monitors")),
+        }
+    }
+
+    /// What every monitor shows right now, whoever put it up. Connections
+    /// that get events also follow `IpcEvent::BackgroundChanged`
+    pub async fn get_background_state(&self) -> Result<Vec<BackgroundState>> {
+        if !self
+            .capabilities()
+            .contains(IpcXabCapabilities::BackgroundState)
+        {
+            return Err(anyhow!("xab can't tell what the monitors show"));
+        }
+        match self
+            .send_recv_command(IpcCommands::GetBackgroundState)
+            .await?
+        {
+            Some(reply) => decode_all(reply),
+            None => Ok(Vec::new()),
+        }
     }
 
     pub async fn get_monitors(&self) -> Vec<Monitor> {
+        self.get_monitors_as_they_arrive(|_| {}).await
+    }
+
+    /// `get_monitors`, with `arrived` called with the monitors so far while
+    /// xab is still sending them (`IpcXabCapabilities::MonitorChunks`)
+    pub async fn get_monitors_as_they_arrive(
+        &self,
+        arrived: impl FnMut(&[Monitor]),
+    ) -> Vec<Monitor> {
+        if self.capabilities().contains(IpcXabCapabilities::Events) {
+            // xab pushes them, they're only asked for until it did
+            let pushed = lock(&self.pushed_monitors).clone();
+            if !pushed.is_empty() {
+                return pushed;
+            }
+        }
         // if xab isn't capable then return fullscreen
         if self
-            .capabilities
+            .capabilities()
             .contains(IpcXabCapabilities::Monitors | IpcXabCapabilities::CustomPositioning)
         {
-            let monitors_bytes = self
-                .send_recv_command(IpcCommands::GetMonitors)
+            let monitors = match self
+                .capabilities()
+                .contains(IpcXabCapabilities::MonitorChunks)
+            {
+                true => self.get_monitor_chunks(arrived).await,
+                false => self.get_monitor_list().await,
+            };
+            match monitors {
+                Ok(monitors) => {
+                    self.remember_screens(&monitors);
+                    return monitors;
+                }
+                Err(err) => error!("Failed to get monitors: {}", describe_error(&err)),
+            }
+        }
+        vec![Monitor::fullscreen()]
+    }
+
+    /// `get_monitors` for the ui: the monitors so far and `true` while xab is
+    /// still sending them, then all of them and `false`
+    pub fn monitor_stream(self: Arc<Self>) -> impl Stream<Item = (Vec<Monitor>, bool)> {
+        iced::stream::channel(4, async move |mut output| {
+            let monitors = self
+                .get_monitors_as_they_arrive(|so_far| {
+                    // only the latest matters, a full channel skips one
+                    let _ = output.try_send((so_far.to_vec(), true));
+                })
+                .await;
+            let _ = output.send((monitors, false)).await;
+        })
+    }
+}
+
+impl Drop for IpcHandle {
+    fn drop(&mut self) {
+        // lets the writer task exit
+        self.outbox.close();
+        self.reader.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BufMut;
+    use iced::futures::{StreamExt, poll};
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    /// Plays xab on the other end of a socket pair: does the handshake,
+    /// then answers every command with the next of `replies`
+    async fn fake_xab(capabilities: IpcXabCapabilities, replies: Vec<Vec<u8>>) -> IpcHandle {
+        let replies = replies.into_iter().map(|reply| vec![reply]).collect();
+        fake_xab_chunked(capabilities, replies).await
+    }
+
+    /// `fake_xab` answering every command with several replies
+    async fn fake_xab_chunked(
+        capabilities: IpcXabCapabilities,
+        replies: Vec<Vec<Vec<u8>>>,
+    ) -> IpcHandle {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        tokio::spawn(async move {
+            server
+                .write_all(&IPC_PROTO_VERSION.to_be_bytes())
+                .await
+                .unwrap();
+            let mut buf = [0u8; 4];
+            server.read_exact(&mut buf).await.unwrap();
+            server
+                .write_all(&capabilities.bits().to_be_bytes())
                 .await
-                .unwrap()
                 .unwrap();
 
-            // NOTE: remember to set step size and the other stuff
-            // to the same size at Monitor::from_bytes
-            return (0..monitors_bytes.len())
-                .step_by(21)
-                .filter_map(|i| {
-                    if i + 21 <= monitors_bytes.len() {
-                        return Monitor::from_bytes(&monitors_bytes.slice(i..i + 21)).ok();
+            for answer in replies {
+                server.read_exact(&mut buf).await.unwrap();
+                for reply in answer {
+                    // replies trickle in to make sure partial reads are handled
+                    let frame = encode_frame(&reply).unwrap();
+                    for chunk in frame.chunks(3) {
+                        server.write_all(chunk).await.unwrap();
+                        tokio::time::sleep(Duration::from_millis(1)).await;
                     }
-                    None
+                }
+            }
+            // wait for the client to hang up
+            let _ = server.read(&mut buf).await;
+        });
+        IpcHandle::handshake("fake", client, Instant::now())
+            .await
+            .unwrap()
+    }
+
+    fn monitor_bytes(index: i32, width: u32) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.put_i32(index);
+        buf.put_u8((index == 0) as u8);
+        buf.put_u32(0);
+        buf.put_u32(0);
+        buf.put_u32(width);
+        buf.put_u32(1080);
+        buf
+    }
+
+    #[test]
+    fn gets_monitors_from_framed_replies() {
+        runtime().block_on(async {
+            let two_monitors = [monitor_bytes(0, 1920), monitor_bytes(1, 2560)].concat();
+            let handle = fake_xab(
+                IpcXabCapabilities::Monitors | IpcXabCapabilities::CustomPositioning,
+                vec![two_monitors, monitor_bytes(0, 1280)],
+            )
+            .await;
+
+            let monitors = handle.get_monitors().await;
+            assert_eq!(monitors.len(), 2);
+            assert_eq!(monitors[1].index, 1);
+            assert_eq!(monitors[1].width, 2560);
+
+            // the first reply was read whole, so the second one lines up
+            let monitors = handle.get_monitors().await;
+            assert_eq!(
+                monitors,
+                vec![Monitor {
+                    width: 1280,
+                    ..monitors[0].clone()
+                }]
+            );
+        });
+    }
+
+    #[test]
+    fn gets_monitors_in_chunks() {
+        runtime().block_on(async {
+            let chunk =
+                |more: bool, monitors: &[Vec<u8>]| [vec![more as u8], monitors.concat()].concat();
+            let handle = fake_xab_chunked(
+                IpcXabCapabilities::Monitors
+                    | IpcXabCapabilities::CustomPositioning
+                    | IpcXabCapabilities::MonitorChunks,
+                vec![
+                    vec![
+                        chunk(true, &[monitor_bytes(0, 1920), monitor_bytes(1, 2560)]),
+                        chunk(true, &[]),
+                        chunk(false, &[monitor_bytes(2, 1280)]),
+                    ],
+                    vec![Vec::new()],
+                ],
+            )
+            .await;
+
+            let mut arrived = Vec::new();
+            let monitors = handle
+                .get_monitors_as_they_arrive(|so_far| arrived.push(so_far.len()))
+                .await;
+            assert_eq!(arrived, [2, 2]);
+            assert_eq!(monitors.len(), 3);
+            assert_eq!(monitors[2].width, 1280);
+
+            // every chunk went to the first request, this one gets the next reply
+            let reply = handle
+                .send_recv_command(IpcCommands::GetAllBackgrounds)
+                .await;
+            assert!(reply.unwrap().is_none());
+        });
+    }
+
+    #[test]
+    fn probes_formats_only_when_xab_can_tell() {
+        runtime().block_on(async {
+            let handle = fake_xab(IpcXabCapabilities::None, Vec::new()).await;
+            handle.probe_formats().await.unwrap();
+            assert_eq!(handle.formats(), None);
+
+            let reply = [&3u16.to_be_bytes()[..], b"png"].concat();
+            let handle = fake_xab(IpcXabCapabilities::Formats, vec![reply]).await;
+            handle.probe_formats().await.unwrap();
+            assert_eq!(handle.formats(), Some(vec!["png".to_owned()]));
+        });
+    }
+
+    #[test]
+    fn empty_reply_is_none() {
+        runtime().block_on(async {
+            let handle = fake_xab(IpcXabCapabilities::None, vec![Vec::new()]).await;
+            let reply = handle
+                .send_recv_command(IpcCommands::GetAllBackgrounds)
+                .await;
+            assert!(reply.unwrap().is_none());
+        });
+    }
+
+    #[test]
+    fn error_replies_are_explained() {
+        runtime().block_on(async {
+            let mut failed = Vec::new();
+            failed.put_i32(IpcErrorCode::FileNotFound as i32);
+            failed.put_u16(0);
+            let handle = fake_xab(IpcXabCapabilities::ErrorReplies, vec![failed]).await;
+            let err = handle
+                .send_recv_command(IpcCommands::GetAllBackgrounds)
+                .await
+                .unwrap_err();
+            assert_eq!(describe_error(&err), IpcErrorCode::FileNotFound.describe());
+        });
+    }
+
+    #[test]
+    fn failed_commands_keep_replies_in_line() {
+        runtime().block_on(async {
+            let mut failed = Vec::new();
+            failed.put_i32(IpcErrorCode::FileNotFound as i32);
+            failed.put_u16(0);
+            let mut monitors = Vec::new();
+            monitors.put_i32(0);
+            monitors.extend(monitor_bytes(0, 1920));
+            monitors.extend(monitor_bytes(1, 2560));
+            let handle = fake_xab(
+                IpcXabCapabilities::ErrorReplies
+                    | IpcXabCapabilities::MonitorLists
+                    | IpcXabCapabilities::Monitors
+                    | IpcXabCapabilities::CustomPositioning,
+                vec![failed, monitors],
+            )
+            .await;
+            let (changed, monitors) = iced::futures::join!(
+                handle.change_backgrounds(&[0, 1], Path::new("/missing.png")),
+                handle.get_monitors(),
+            );
+            assert_eq!(
+                describe_error(&changed.unwrap_err()),
+                IpcErrorCode::FileNotFound.describe()
+            );
+            assert_eq!(monitors.len(), 2);
+        });
+    }
+
+    #[test]
+    fn refreshes_capabilities() {
+        runtime().block_on(async {
+            let upgraded = IpcXabCapabilities::Monitors | IpcXabCapabilities::Video;
+            let handle = fake_xab(
+                IpcXabCapabilities::Monitors,
+                vec![
+                    upgraded.bits().to_be_bytes().to_vec(),
+                    upgraded.bits().to_be_bytes().to_vec(),
+                ],
+            )
+            .await;
+            assert!(handle.refresh_capabilities().await.unwrap());
+            assert_eq!(handle.capabilities(), upgraded);
+            assert!(!handle.refresh_capabilities().await.unwrap());
+        });
+    }
+
+    #[test]
+    fn abandoned_request_keeps_replies_in_line() {
+        runtime().block_on(async {
+            let handle = fake_xab(
+                IpcXabCapabilities::None,
+                vec![b"first".to_vec(), b"second".to_vec()],
+            )
+            .await;
+
+            // gives up before its reply arrives
+            let mut abandoned = Box::pin(handle.send_recv_command(IpcCommands::GetAllBackgrounds));
+            assert!(poll!(abandoned.as_mut()).is_pending());
+            drop(abandoned);
+
+            let reply = handle
+                .send_recv_command(IpcCommands::GetAllBackgrounds)
+                .await;
+            assert_eq!(reply.unwrap().unwrap(), &b"second"[..]);
+        });
+    }
+
+    #[test]
+    fn silent_daemon_times_out() {
+        runtime().block_on(async {
+            let path = std::env::temp_dir().join(format!("xab-gui-silent-{}", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            let listener = tokio::net::UnixListener::bind(&path).unwrap();
+            tokio::spawn(async move {
+                // accepts, then never says a word
+                let (_socket, _) = listener.accept().await.unwrap();
+                std::future::pending::<()>().await;
+            });
+            let timeouts = Timeouts {
+                connect: Duration::from_secs(1),
+                handshake: Duration::from_millis(50),
+            };
+            let err = IpcHandle::with_timeouts(path.to_str().unwrap(), timeouts)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("handshake"), "{err}");
+            let _ = std::fs::remove_file(&path);
+        });
+    }
+
+    #[test]
+    fn checks_socket_paths() {
+        assert!(check_socket_path(IPC_PATH).is_ok());
+        assert!(check_socket_path("xab_uds").is_err());
+        assert!(check_socket_path(&format!("/tmp/{}", "x".repeat(120))).is_err());
+        let file =
+            std::env::temp_dir().join(format!("xab-gui-not-a-socket-{}", std::process::id()));
+        std::fs::write(&file, b"").unwrap();
+        assert!(check_socket_path(file.to_str().unwrap()).is_err());
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn newer_xab_speaks_our_protocol() {
+        runtime().block_on(async {
+            let (client, mut server) = UnixStream::pair().unwrap();
+            let agreed = tokio::spawn(async move {
+                server
+                    .write_all(&(IPC_PROTO_VERSION + 1).to_be_bytes())
+                    .await
+                    .unwrap();
+                let agreed = server.read_i32().await.unwrap();
+                server.write_all(&0u32.to_be_bytes()).await.unwrap();
+                let _ = server.read(&mut [0u8; 4]).await;
+                agreed
+            });
+            let handle = IpcHandle::handshake("fake", client, Instant::now())
+                .await
+                .unwrap();
+            assert_eq!(handle.protocol, IPC_PROTO_VERSION);
+            drop(handle);
+            assert_eq!(agreed.await.unwrap(), IPC_PROTO_VERSION);
+        });
+    }
+
+    #[test]
+    fn reads_framed_events() {
+        runtime().block_on(async {
+            let (client, mut server) = UnixStream::pair().unwrap();
+            tokio::spawn(async move {
+                server
+                    .write_all(&IPC_PROTO_VERSION.to_be_bytes())
+                    .await
+                    .unwrap();
+                let mut buf = [0u8; 4];
+                server.read_exact(&mut buf).await.unwrap();
+                server
+                    .write_all(&IpcXabCapabilities::Events.bits().to_be_bytes())
+                    .await
+                    .unwrap();
+
+                let monitor = monitor_bytes(0, 1920);
+                let screen_monitor = [&monitor[..4], &0u32.to_be_bytes(), &monitor[4..]].concat();
+                let mut events = Vec::new();
+                events.put_i32(IpcEvents::MonitorsChanged as i32);
+                events.put_slice(&encode_frame(&screen_monitor).unwrap());
+                events.put_i32(IpcEvents::ServerShuttingDown as i32);
+                events.put_u32(5);
+                for chunk in events.chunks(3) {
+                    server.write_all(chunk).await.unwrap();
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+                let _ = server.read(&mut buf).await;
+            });
+            let handle = IpcHandle::handshake("fake", client, Instant::now())
+                .await
+                .unwrap();
+            let mut events = handle.take_events().unwrap();
+
+            let Some(IpcEvent::MonitorsChanged(monitors)) = events.next().await else {
+                panic!("expected the monitors first");
+            };
+            assert_eq!(handle.get_monitors().await, vec![Monitor::fullscreen()]);
+            handle.monitors_pushed(&monitors);
+            assert_eq!(handle.get_monitors().await, monitors);
+            assert_eq!(monitors[0].width, 1920);
+            assert_eq!(
+                events.next().await,
+                Some(IpcEvent::ServerShuttingDown {
+                    grace: Duration::from_secs(5)
                 })
-                .collect();
-        }
-        vec![Monitor::fullscreen()]
+            );
+        });
+    }
+
+    #[test]
+    fn events_connections_get_replies_too() {
+        runtime().block_on(async {
+            let (client, mut server) = UnixStream::pair().unwrap();
+            tokio::spawn(async move {
+                server
+                    .write_all(&IPC_PROTO_VERSION.to_be_bytes())
+                    .await
+                    .unwrap();
+                let mut buf = [0u8; 4];
+                server.read_exact(&mut buf).await.unwrap();
+                let capabilities = IpcXabCapabilities::Events
+                    | IpcXabCapabilities::Monitors
+                    | IpcXabCapabilities::CustomPositioning;
+                server
+                    .write_all(&capabilities.bits().to_be_bytes())
+                    .await
+                    .unwrap();
+
+                server.read_exact(&mut buf).await.unwrap();
+                assert_eq!(i32::from_be_bytes(buf), IpcCommands::GetMonitors as i32);
+                // an event sneaks in ahead of the reply
+                let mut changed = bytes::BytesMut::new();
+                changed.put_i32(-1);
+                encode_path(&mut changed, Path::new("/a.png"));
+                let mut out = Vec::new();
+                out.put_i32(IpcEvents::BackgroundChanged as i32);
+                out.put_slice(&encode_frame(&changed).unwrap());
+                out.put_i32(REPLY_TAG);
+                out.put_slice(&encode_frame(&monitor_bytes(0, 2560)).unwrap());
+                for chunk in out.chunks(3) {
+                    server.write_all(chunk).await.unwrap();
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+                let _ = server.read(&mut buf).await;
+            });
+            let handle = IpcHandle::handshake("fake", client, Instant::now())
+                .await
+                .unwrap();
+            let mut events = handle.take_events().unwrap();
+
+            let monitors = handle.get_monitors().await;
+            assert_eq!(monitors.len(), 1);
+            assert_eq!(monitors[0].width, 2560);
+            assert_eq!(
+                events.next().await,
+                Some(IpcEvent::BackgroundChanged {
+                    monitor: None,
+                    path: "/a.png".into(),
+                })
+            );
+        });
+    }
+
+    #[test]
+    fn per_monitor_playback_needs_the_capability() {
+        runtime().block_on(async {
+            let handle = fake_xab(IpcXabCapabilities::None, Vec::new()).await;
+            assert!(handle.video_playback(&[1], Some(true)).await.is_err());
+            assert!(handle.video_playback(&[], Some(true)).await.is_ok());
+
+            let handle = fake_xab(IpcXabCapabilities::PerMonitorPlayback, Vec::new()).await;
+            assert!(handle.video_playback(&[0, 1], None).await.is_ok());
+        });
+    }
+
+    #[test]
+    fn truncated_reply_stops_reading() {
+        runtime().block_on(async {
+            let (client, mut server) = UnixStream::pair().unwrap();
+            server.write_all(&10u32.to_be_bytes()).await.unwrap();
+            server.write_all(b"short").await.unwrap();
+            drop(server);
+            let (mut client, _) = client.into_split();
+            let mut decoder = FrameDecoder::new(StreamKind::Replies);
+            assert!(next_message(&mut client, &mut decoder).await.is_none());
+            assert_eq!(decoder.pending(), FRAME_HEADER_SIZE + 5);
+        });
     }
 }
@@ -0,0 +1,351 @@
+use anyhow::{Result, anyhow};
+use bytes::Bytes;
+use std::{
+    collections::VecDeque,
+    pin::pin,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt, BufWriter},
+    sync::Notify,
+    time::timeout,
+};
+use tracing::{debug, error, warn};
+
+use crate::ipc_spec::IpcCommands;
+
+/// Max amount of commands waiting to be written before new ones are rejected
+pub const OUTBOX_CAPACITY: usize = 64;
+/// A daemon that doesn't read for this long is considered stalled
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A command (with its payload) waiting to be written to the socket
+#[derive(Debug)]
+pub struct Outgoing {
+    pub command: IpcCommands,
+    /// Commands with the same command and merge key supersede each other while
+    /// they're still queued, e.g. two background changes for the same monitor
+    pub merge_key: Option<i32>,
+    /// Replies are timed by whoever reads them, not by the writer
+    pub expects_reply: bool,
+    pub bytes: Bytes,
+    queued_at: Instant,
+}
+
+impl Outgoing {
+    pub fn new(command: IpcCommands, payload: Option<Bytes>) -> Self {
+        let mut bytes = (command as i32).to_be_bytes().to_vec();
+        if let Some(payload) = payload {
+            bytes.extend_from_slice(&payload);
+        }
+
+        Self {
+            command,
+            merge_key: None,
+            expects_reply: false,
+            bytes: Bytes::from(bytes),
+            queued_at: Instant::now(),
+        }
+    }
+
+    pub fn merge_key(mut self, merge_key: i32) -> Self {
+        self.merge_key = Some(merge_key);
+        self
+    }
+
+    pub fn expects_reply(mut self) -> Self {
+        self.expects_reply = true;
+        self
+    }
+
+    fn supersedes(&self, other: &Outgoing) -> bool {
+        self.merge_key.is_some()
+            && self.command == other.command
+            && self.merge_key == other.merge_key
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    items: VecDeque<Outgoing>,
+    writing: bool,
+    closed: bool,
+    error: Option<String>,
+}
+
+type OnWritten = Box<dyn Fn(IpcCommands, Duration) + Send + Sync>;
+
+/// Bounded queue of outgoing commands, drained by a writer task so a
+/// stalled daemon blocks that task instead of the ui
+///
+/// NOTE: the lock is never held across an await, so dropping a future
+/// halfway through can't leave it locked
+pub struct Outbox {
+    queue: Mutex<Queue>,
+    changed: Notify,
+    on_written: OnWritten,
+}
+
+impl std::fmt::Debug for Outbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Outbox")
+            .field("queued", &self.lock().items.len())
+            .finish()
+    }
+}
+
+impl Outbox {
+    /// `on_written` is called with the time a command spent queued + written,
+    /// has to be called from within a tokio runtime
+    pub fn spawn(
+        writer: impl AsyncWrite + Unpin + Send + 'static,
+        on_written: impl Fn(IpcCommands, Duration) + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        let outbox = Arc::new(Self {
+            queue: Mutex::default(),
+            changed: Notify::new(),
+            on_written: Box::new(on_written),
+        });
+
+        let writer_outbox = outbox.clone();
+        tokio::spawn(async move { writer_outbox.run(writer).await });
+        outbox
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn push(&self, outgoing: Outgoing) -> Result<()> {
+        let mut queue = self.lock();
+        if let Some(err) = &queue.error {
+            return Err(anyhow!("Connection is broken: {err}"));
+        }
+        if queue.closed {
+            return Err(anyhow!("Connection is closed"));
+        }
+
+        if let Some(at) = queue.items.iter().position(|q| outgoing.supersedes(q)) {
+            debug!("Merging superseded {:?}", outgoing.command);
+            // it goes last, commands queued in between may depend on what's
+            // before it. The original queue time keeps the latency honest
+            let queued_at = queue.items.remove(at).map(|q| q.queued_at);
+            queue.items.push_back(Outgoing {
+                queued_at: queued_at.unwrap_or(outgoing.queued_at),
+                ..outgoing
+            });
+        } else if queue.items.len() >= OUTBOX_CAPACITY {
+            warn!("Outgoing queue is full, dropping {:?}", outgoing.command);
+            return Err(anyhow!(
+                "xab isn't keeping up ({} commands waiting), try again later",
+                OUTBOX_CAPACITY
+            ));
+        } else {
+            queue.items.push_back(outgoing);
+        }
+
+        self.changed.notify_waiters();
+        Ok(())
+    }
+
+    /// Waits until `done` is true for the queue, checked whenever it changes
+    async fn wait_until(&self, mut done: impl FnMut(&mut Queue) -> bool) {
+        loop {
+            // registered before checking so a change in between isn't missed
+            let mut changed = pin!(self.changed.notified());
+            changed.as_mut().enable();
+            if done(&mut self.lock()) {
+                return;
+            }
+            changed.await;
+        }
+    }
+
+    /// Waits until everything queued so far is written, or `limit` passes
+    pub async fn flush(&self, limit: Duration) -> Result<()> {
+        let drained = self.wait_until(|q| q.error.is_some() || (q.items.is_empty() && !q.writing));
+        let timed_out = timeout(limit, drained).await.is_err();
+
+        if let Some(err) = &self.lock().error {
+            return Err(anyhow!("Connection is broken: {err}"));
+        }
+        if timed_out {
+            return Err(anyhow!("Timed out flushing outgoing commands"));
+        }
+        Ok(())
+    }
+
+    /// Stops the writer task once the queue is drained
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.changed.notify_waiters();
+    }
+
+    async fn run(&self, writer: impl AsyncWrite + Unpin) {
+        let mut writer = BufWriter::new(writer);
+        loop {
+            let mut next = None;
+            self.wait_until(|q| {
+                next = q.items.pop_front();
+                q.writing = next.is_some();
+                next.is_some() || q.closed
+            })
+            .await;
+            let Some(outgoing) = next else {
+                break; // closed and drained
+            };
+
+            let written = timeout(WRITE_TIMEOUT, async {
+                writer.write_all(&outgoing.bytes).await?;
+                // only pay for the syscall once nothing else is waiting
+                if self.lock().items.is_empty() {
+                    writer.flush().await?;
+                }
+                std::io::Result::Ok(())
+            })
+            .await;
+            let result = match written {
+                Ok(result) => result.map_err(|err| err.to_string()),
+                Err(_) => Err(format!("xab didn't read anything for {WRITE_TIMEOUT:?}")),
+            };
+            let mut queue = self.lock();
+            queue.writing = false;
+
+            match result {
+                Ok(()) => {
+                    if !outgoing.expects_reply {
+                        (self.on_written)(outgoing.command, outgoing.queued_at.elapsed());
+                    }
+                }
+                Err(err) => {
+                    error!("Failed to write {:?}: {err}", outgoing.command);
+                    queue.error = Some(err);
+                    queue.items.clear();
+                    drop(queue);
+                    self.changed.notify_waiters();
+                    break;
+                }
+            }
+            drop(queue);
+            self.changed.notify_waiters();
+        }
+        let _ = writer.shutdown().await;
+        debug!("IPC writer task stopped");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    /// Without a writer task, so what's queued stays there
+    fn idle() -> Outbox {
+        Outbox {
+            queue: Mutex::default(),
+            changed: Notify::new(),
+            on_written: Box::new(|_, _| {}),
+        }
+    }
+
+    fn command(command: IpcCommands, payload: u8) -> Outgoing {
+        Outgoing::new(command, Some(Bytes::from(vec![payload])))
+    }
+
+    fn queued(outbox: &Outbox) -> Vec<(IpcCommands, u8)> {
+        outbox
+            .lock()
+            .items
+            .iter()
+            .map(|q| (q.command, q.bytes[4]))
+            .collect()
+    }
+
+    #[test]
+    fn superseding_commands_go_last() {
+        use IpcCommands::*;
+        let outbox = idle();
+        outbox
+            .push(command(ChangeBackground, 1).merge_key(0))
+            .unwrap();
+        outbox
+            .push(command(ChangeBackground, 2).merge_key(1))
+            .unwrap();
+        outbox.push(command(PauseVideo, 3)).unwrap();
+        outbox
+            .push(command(ChangeBackground, 4).merge_key(0))
+            .unwrap();
+        // without a merge key nothing is merged
+        outbox.push(command(PauseVideo, 5)).unwrap();
+        assert_eq!(
+            queued(&outbox),
+            [
+                (ChangeBackground, 2),
+                (PauseVideo, 3),
+                (ChangeBackground, 4),
+                (PauseVideo, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn full_outboxes_only_take_merges() {
+        let outbox = idle();
+        for i in 0..OUTBOX_CAPACITY {
+            outbox
+                .push(command(IpcCommands::ChangeBackground, 0).merge_key(i as i32))
+                .unwrap();
+        }
+        assert!(outbox.push(command(IpcCommands::PauseVideo, 0)).is_err());
+        outbox
+            .push(command(IpcCommands::ChangeBackground, 1).merge_key(0))
+            .unwrap();
+        assert_eq!(outbox.lock().items.len(), OUTBOX_CAPACITY);
+    }
+
+    #[test]
+    fn writes_in_order_until_closed() {
+        runtime().block_on(async {
+            let (writer, mut reader) = tokio::io::duplex(64);
+            let outbox = Outbox::spawn(writer, |_, _| {});
+            for payload in 1..=3 {
+                outbox
+                    .push(command(IpcCommands::PauseVideo, payload))
+                    .unwrap();
+            }
+            outbox.flush(Duration::from_secs(1)).await.unwrap();
+            outbox.close();
+            assert!(outbox.push(command(IpcCommands::PauseVideo, 4)).is_err());
+
+            let mut written = Vec::new();
+            reader.read_to_end(&mut written).await.unwrap();
+            let pause = (IpcCommands::PauseVideo as i32).to_be_bytes();
+            assert_eq!(
+                written,
+                [&pause[..], &[1], &pause, &[2], &pause, &[3]].concat()
+            );
+        });
+    }
+
+    #[test]
+    fn broken_connections_refuse_commands() {
+        runtime().block_on(async {
+            let (writer, reader) = tokio::io::duplex(64);
+            drop(reader);
+            let outbox = Outbox::spawn(writer, |_, _| {});
+            outbox.push(command(IpcCommands::PauseVideo, 1)).unwrap();
+            assert!(outbox.flush(Duration::from_secs(1)).await.is_err());
+            assert!(outbox.push(command(IpcCommands::PauseVideo, 2)).is_err());
+        });
+    }
+}
@@ -1,13 +1,23 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use bitflags::bitflags;
-use bytes::Bytes;
-use std::io::{Cursor, Read};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
+/// The newest protocol this client speaks, see `negotiate_version`
 pub const IPC_PROTO_VERSION: i32 = 1;
+/// The oldest one it still speaks
+pub const IPC_PROTO_VERSION_MIN: i32 = 1;
 pub const IPC_PATH: &str = "/tmp/xab/xab_uds";
 
 #[repr(i32)]
-#[derive(Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
 pub enum IpcCommands {
     // stuff
     #[default]
@@ -28,62 +38,881 @@ pub enum IpcCommands {
     GetMonitors = 9,
     GetAllBackgrounds = 10,
     GetCapabilites = 11,
+
+    // set state again, only with `IpcXabCapabilities::MonitorLists`
+    ChangeBackgrounds = 12,
+    // only with `IpcXabCapabilities::Clear`
+    ClearBackground = 13,
+    // only with `IpcXabCapabilities::Formats`
+    GetFormats = 14,
+    // only with `IpcXabCapabilities::BackgroundState`
+    GetBackgroundState = 15,
+}
+
+impl IpcCommands {
+    /// Commands a daemon can reserve for privileged clients, see
+    /// `IpcXabCapabilities::PrivilegedControl`
+    pub fn is_privileged(self) -> bool {
+        matches!(self, Self::Restart | Self::Shutdown)
+    }
+}
+
+/// Sent by xab on its own instead of as a reply, only when it advertises
+/// `IpcXabCapabilities::Events`
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IpcEvents {
+    ServerShuttingDown = 1,
+    /// Monitors were plugged in or out, also sent right after the handshake
+    MonitorsChanged = 2,
+    /// Someone else changed a background
+    BackgroundChanged = 3,
+}
+
+impl IpcEvents {
+    pub fn from_id(id: i32) -> Option<Self> {
+        match id {
+            1 => Some(Self::ServerShuttingDown),
+            2 => Some(Self::MonitorsChanged),
+            3 => Some(Self::BackgroundChanged),
+            _ => None,
+        }
+    }
+
+    /// Bytes following the event id, `None` if they're a frame (see
+    /// `encode_frame`)
+    pub fn payload_size(self) -> Option<usize> {
+        match self {
+            // seconds until it goes away (u32)
+            Self::ServerShuttingDown => Some(4),
+            // every monitor, laid out like `decode_monitors` with
+            // `IpcXabCapabilities::Screens`
+            Self::MonitorsChanged => None,
+            // the monitor (i32, -1 for every monitor) and the path, see
+            // `encode_path`
+            Self::BackgroundChanged => None,
+        }
+    }
+}
+
+/// A decoded `IpcEvents`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IpcEvent {
+    ServerShuttingDown {
+        grace: Duration,
+    },
+    MonitorsChanged(Vec<Monitor>),
+    BackgroundChanged {
+        /// `None` for every monitor
+        monitor: Option<i32>,
+        path: PathBuf,
+    },
+}
+
+impl IpcEvent {
+    /// Needs the capabilities for the monitor names, see `decode_monitors`
+    pub fn decode(buf: &mut impl Buf, capabilities: IpcXabCapabilities) -> Result<Self> {
+        ensure_remaining(buf, 4, "event id")?;
+        let id = buf.get_i32();
+        let event = IpcEvents::from_id(id).ok_or_else(|| anyhow!("Unknown event {id}"))?;
+        let mut payload = match event.payload_size() {
+            Some(size) => {
+                ensure_remaining(buf, size, "event")?;
+                buf.copy_to_bytes(size)
+            }
+            None => {
+                ensure_remaining(buf, FRAME_HEADER_SIZE, "event frame")?;
+                let len = decode_frame_header(buf.get_u32().to_be_bytes())?;
+                ensure_remaining(buf, len, "event")?;
+                buf.copy_to_bytes(len)
+            }
+        };
+        Ok(match event {
+            IpcEvents::ServerShuttingDown => Self::ServerShuttingDown {
+                grace: Duration::from_secs(payload.get_u32().into()),
+            },
+            IpcEvents::MonitorsChanged => Self::MonitorsChanged(decode_monitors(
+                payload,
+                capabilities | IpcXabCapabilities::Screens,
+            )?),
+            IpcEvents::BackgroundChanged => {
+                ensure_remaining(&payload, 4, "monitor")?;
+                let monitor = payload.get_i32();
+                Self::BackgroundChanged {
+                    monitor: (monitor >= 0).then_some(monitor),
+                    path: decode_path(&mut payload)?,
+                }
+            }
+        })
+    }
+}
+
+/// Why xab refused a command, see `IpcError`
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IpcErrorCode {
+    UnknownCommand = 1,
+    InvalidArguments = 2,
+    NoSuchMonitor = 3,
+    FileNotFound = 4,
+    UnsupportedFormat = 5,
+    NotPermitted = 6,
+    Internal = 7,
+}
+
+impl IpcErrorCode {
+    pub fn from_id(id: i32) -> Option<Self> {
+        match id {
+            1 => Some(Self::UnknownCommand),
+            2 => Some(Self::InvalidArguments),
+            3 => Some(Self::NoSuchMonitor),
+            4 => Some(Self::FileNotFound),
+            5 => Some(Self::UnsupportedFormat),
+            6 => Some(Self::NotPermitted),
+            7 => Some(Self::Internal),
+            _ => None,
+        }
+    }
+
+    /// What the user is shown
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::UnknownCommand => {
+                "xab doesn't know this command, it's probably older than xab-gui"
+            }
+            Self::InvalidArguments => "xab didn't understand what it was sent",
+            Self::NoSuchMonitor => "That monitor isn't connected anymore",
+            Self::FileNotFound => {
+                "xab can't open the file, it was moved or xab's user can't read it"
+            }
+            Self::UnsupportedFormat => "xab can't show this kind of file",
+            Self::NotPermitted => PRIVILEGE_HINT,
+            Self::Internal => "Something went wrong inside xab, its log should say what",
+        }
+    }
+}
+
+/// A failed command, sent instead of the reply with
+/// `IpcXabCapabilities::ErrorReplies`, see `decode_reply`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpcError {
+    pub code: i32,
+    /// xab's own words, for the details
+    pub message: Option<String>,
+}
+
+impl IpcError {
+    /// `None` for codes newer than this client
+    pub fn kind(&self) -> Option<IpcErrorCode> {
+        IpcErrorCode::from_id(self.code)
+    }
+}
+
+impl std::fmt::Display for IpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind() {
+            Some(kind) => f.write_str(kind.describe())?,
+            None => write!(f, "xab failed with error {}", self.code)?,
+        }
+        match &self.message {
+            Some(message) => write!(f, " ({message})"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for IpcError {}
+
+/// With `IpcXabCapabilities::ErrorReplies` every command gets a reply, the
+/// ones without a reply of their own just the status. It starts with that
+/// status (i32): 0 followed by the reply itself, or an `IpcErrorCode`
+/// followed by a message (see `decode_name`). The error is an `IpcError`
+pub fn decode_reply(mut reply: Bytes, capabilities: IpcXabCapabilities) -> Result<Bytes> {
+    if !capabilities.contains(IpcXabCapabilities::ErrorReplies) {
+        return Ok(reply);
+    }
+    ensure_remaining(&reply, 4, "reply status")?;
+    let code = reply.get_i32();
+    if code == 0 {
+        return Ok(reply);
+    }
+    Err(IpcError {
+        code,
+        message: decode_name(&mut reply, "error message")?,
+    }
+    .into())
+}
+
+/// xab opens with the newest version it speaks and the client answers with
+/// the one they'll both use: the highest in common, xab speaks every version
+/// up to its newest. Wire formats that change between versions are picked by
+/// it, see `IpcHandle::protocol`
+pub fn negotiate_version(server: i32) -> Result<i32> {
+    if server < IPC_PROTO_VERSION_MIN {
+        return Err(anyhow!(
+            "xab speaks IPC protocol {server}, this client needs at least \
+             {IPC_PROTO_VERSION_MIN}. Upgrade xab"
+        ));
+    }
+    Ok(server.min(IPC_PROTO_VERSION))
+}
+
+/// Replies are framed as payload length (u32) followed by the payload
+pub const FRAME_HEADER_SIZE: usize = 4;
+/// Bigger than any real reply, a length like that means we're out of sync
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+pub fn encode_frame(payload: &[u8]) -> Result<Bytes> {
+    if payload.len() > MAX_FRAME_SIZE {
+        return Err(anyhow!("Frame of {} bytes is too big", payload.len()));
+    }
+    let mut buf = BytesMut::with_capacity(FRAME_HEADER_SIZE + payload.len());
+    buf.put_u32(payload.len() as u32);
+    buf.put_slice(payload);
+    Ok(buf.freeze())
+}
+
+/// Payload length from a frame header
+pub fn decode_frame_header(header: [u8; FRAME_HEADER_SIZE]) -> Result<usize> {
+    let len = u32::from_be_bytes(header) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(anyhow!(
+            "Reply claims to be {len} bytes, the connection is probably out of sync"
+        ));
+    }
+    Ok(len)
+}
+
+/// On connections that get events a reply is this (i32) followed by its
+/// frame, events start with their `IpcEvents` id instead
+pub const REPLY_TAG: i32 = 0;
+
+/// What a connection carries, depending on `IpcXabCapabilities::Events`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind {
+    /// Frames, see `encode_frame`
+    Replies,
+    /// `IpcEvents` (see `IpcEvent::decode`) and replies, told apart by
+    /// `REPLY_TAG`
+    Events,
+}
+
+/// A message off a connection, see `FrameDecoder::decode_next`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Incoming {
+    /// Without its tag and length, for `decode_reply`
+    Reply(Bytes),
+    /// Whole, for `IpcEvent::decode`
+    Event(Bytes),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecoderState {
+    /// The message at the front isn't long enough to know its length yet
+    Header,
+    /// The message at the front is this many bytes, headers included
+    Body(usize),
+}
+
+/// Splits what's read off a connection into messages, however the reads
+/// happen to cut them up or glue them together
+#[derive(Debug)]
+pub struct FrameDecoder {
+    kind: StreamKind,
+    state: DecoderState,
+    buf: BytesMut,
+}
+
+impl FrameDecoder {
+    pub fn new(kind: StreamKind) -> Self {
+        Self {
+            kind,
+            state: DecoderState::Header,
+            buf: BytesMut::with_capacity(4096),
+        }
+    }
+
+    /// Where reads go, `AsyncReadExt::read_buf` appends to it
+    pub fn buffer(&mut self) -> &mut BytesMut {
+        &mut self.buf
+    }
+
+    /// Bytes of a message that's still incomplete
+    pub fn pending(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Length of the message at the front, None until its headers arrived
+    fn message_len(&self) -> Result<Option<usize>> {
+        let header = |at: usize| -> Option<[u8; 4]> { self.buf.get(at..at + 4)?.try_into().ok() };
+        let Some(first) = header(0) else {
+            return Ok(None);
+        };
+        match self.kind {
+            StreamKind::Replies => Ok(Some(FRAME_HEADER_SIZE + decode_frame_header(first)?)),
+            StreamKind::Events => {
+                let id = i32::from_be_bytes(first);
+                let size = match id {
+                    REPLY_TAG => None,
+                    // no idea how long it is, so nothing after it can be read
+                    id => IpcEvents::from_id(id)
+                        .ok_or_else(|| anyhow!("Unknown event {id}"))?
+                        .payload_size(),
+                };
+                match size {
+                    Some(size) => Ok(Some(4 + size)),
+                    None => match header(4) {
+                        Some(frame) => {
+                            Ok(Some(4 + FRAME_HEADER_SIZE + decode_frame_header(frame)?))
+                        }
+                        None => Ok(None),
+                    },
+                }
+            }
+        }
+    }
+
+    /// The next complete message, None until more bytes arrive. Errors leave
+    /// the stream out of sync, nothing after them can be trusted
+    pub fn decode_next(&mut self) -> Result<Option<Incoming>> {
+        if self.state == DecoderState::Header {
+            match self.message_len()? {
+                Some(len) => self.state = DecoderState::Body(len),
+                None => return Ok(None),
+            }
+        }
+        let DecoderState::Body(len) = self.state else {
+            return Ok(None);
+        };
+        if self.buf.len() < len {
+            return Ok(None);
+        }
+        self.state = DecoderState::Header;
+        let mut message = self.buf.split_to(len).freeze();
+        let reply = match self.kind {
+            StreamKind::Replies => true,
+            StreamKind::Events if message.starts_with(&REPLY_TAG.to_be_bytes()) => {
+                message.advance(4);
+                true
+            }
+            StreamKind::Events => false,
+        };
+        Ok(Some(match reply {
+            true => {
+                message.advance(FRAME_HEADER_SIZE);
+                Incoming::Reply(message)
+            }
+            false => Incoming::Event(message),
+        }))
+    }
+}
+
+/// Something that can be decoded from a daemon reply, the buffer is advanced
+/// past the decoded item so several can be read back to back
+pub trait Decode: Sized {
+    fn decode(buf: &mut impl Buf) -> Result<Self>;
+}
+
+/// Fails instead of letting `Buf::get_*` panic on truncated input
+pub fn ensure_remaining(buf: &impl Buf, needed: usize, what: &str) -> Result<()> {
+    if buf.remaining() < needed {
+        return Err(anyhow!(
+            "Truncated {what}: need {needed} bytes, got {}",
+            buf.remaining()
+        ));
+    }
+    Ok(())
+}
+
+/// Decodes items until the buffer is empty
+pub fn decode_all<T: Decode>(mut buf: impl Buf) -> Result<Vec<T>> {
+    let mut items = Vec::new();
+    while buf.has_remaining() {
+        items.push(T::decode(&mut buf)?);
+    }
+    Ok(items)
+}
+
+/// A path with `IpcXabCapabilities::RawPaths`: utf-8 flag (u8), length (u32),
+/// bytes. The bytes are the path as is, the flag only tells xab if it can
+/// show it without converting it
+pub fn encode_path(buf: &mut BytesMut, path: &Path) {
+    let bytes = path.as_os_str().as_bytes();
+    buf.put_u8(path.to_str().is_some() as u8);
+    buf.put_u32(bytes.len() as u32);
+    buf.put_slice(bytes);
+}
+
+pub fn decode_path(buf: &mut impl Buf) -> Result<PathBuf> {
+    ensure_remaining(buf, 5, "path header")?;
+    let _utf8 = buf.get_u8();
+    let len = buf.get_u32() as usize;
+    ensure_remaining(buf, len, "path")?;
+    let mut bytes = vec![0; len];
+    buf.copy_to_slice(&mut bytes);
+    Ok(PathBuf::from(OsStr::from_bytes(&bytes)))
+}
+
+/// The path of a background change, see `encode_path`. Older xab takes path
+/// length (u32) + bytes and expects UTF-8
+fn put_background_path(
+    buf: &mut BytesMut,
+    path: &Path,
+    capabilities: IpcXabCapabilities,
+) -> Result<()> {
+    let bytes = path.as_os_str().as_bytes();
+    if capabilities.contains(IpcXabCapabilities::RawPaths) {
+        encode_path(buf, path);
+    } else if path.to_str().is_some() {
+        buf.put_u32(bytes.len() as u32);
+        buf.put_slice(bytes);
+    } else {
+        return Err(anyhow!(
+            "This xab is too old for file names that aren't UTF-8: {}",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Which X screen each monitor is on, by monitor index
+pub type Screens = HashMap<i32, u32>;
+
+/// A monitor index (i32, -1 for all monitors), with `IpcXabCapabilities::Screens`
+/// preceded by the X screen it's on (i32, -1 for every screen or when it isn't
+/// known, xab looks the monitor up then)
+fn put_monitor(
+    buf: &mut BytesMut,
+    monitor: Option<i32>,
+    screens: &Screens,
+    capabilities: IpcXabCapabilities,
+) {
+    if capabilities.contains(IpcXabCapabilities::Screens) {
+        let screen = monitor.and_then(|monitor| screens.get(&monitor));
+        buf.put_i32(screen.map_or(-1, |&screen| screen as i32));
+    }
+    buf.put_i32(monitor.unwrap_or(-1));
+}
+
+/// Which monitor a command is about, as users write it: `all`, `primary`, a
+/// number (from 1, like list-monitors prints them) or an output name like
+/// `DP-1`. xab only takes indices, see `MonitorTarget::resolve`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MonitorTarget {
+    All,
+    Primary,
+    /// Index, from 0
+    Index(u32),
+    Named(String),
+}
+
+impl MonitorTarget {
+    /// The index xab knows it by, `None` being every monitor. Needs the
+    /// monitors for anything but `All` and `Index`
+    pub fn resolve(&self, monitors: &[Monitor]) -> Result<Option<i32>> {
+        let found = match self {
+            Self::All => return Ok(None),
+            Self::Index(index) => {
+                return i32::try_from(*index)
+                    .map(Some)
+                    .map_err(|_| anyhow!("There's no monitor {}", self));
+            }
+            Self::Primary => monitors.iter().find(|monitor| monitor.primary),
+            Self::Named(name) => monitors.iter().find(|monitor| {
+                monitor
+                    .name
+                    .as_deref()
+                    .is_some_and(|output| output.eq_ignore_ascii_case(name))
+            }),
+        };
+        found
+            .map(|monitor| Some(monitor.index))
+            .ok_or_else(|| anyhow!("xab doesn't know a {} monitor", self))
+    }
+}
+
+impl From<Option<i32>> for MonitorTarget {
+    fn from(monitor: Option<i32>) -> Self {
+        match monitor {
+            Some(index) => Self::Index(index.max(0) as u32),
+            None => Self::All,
+        }
+    }
+}
+
+impl std::fmt::Display for MonitorTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::All => f.write_str("all"),
+            Self::Primary => f.write_str("primary"),
+            Self::Index(index) => write!(f, "{}", *index as u64 + 1),
+            Self::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+impl std::str::FromStr for MonitorTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(Self::All);
+        }
+        if s.eq_ignore_ascii_case("primary") {
+            return Ok(Self::Primary);
+        }
+        match s.parse::<i64>() {
+            Ok(number) if number < 1 => Err("Monitors are numbered from 1".to_owned()),
+            Ok(number) => u32::try_from(number - 1)
+                .map(Self::Index)
+                .map_err(|_| format!("There's no monitor {number}")),
+            Err(_) if s.is_empty() => Err("No monitor given".to_owned()),
+            Err(_) => Ok(Self::Named(s.to_owned())),
+        }
+    }
+}
+
+/// With `IpcXabCapabilities::Scaling` the path is followed by the scaling
+/// mode (u8), older xab always fills
+fn put_scaling(buf: &mut BytesMut, scaling: ScalingMode, capabilities: IpcXabCapabilities) {
+    if capabilities.contains(IpcXabCapabilities::Scaling) {
+        buf.put_u8(scaling as u8);
+    }
+}
+
+/// How a video wallpaper plays, ignored by xab for images
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VideoOptions {
+    pub looping: bool,
+    pub muted: bool,
+    /// 0 to 100
+    pub volume: u8,
+    /// Percent of the normal speed, see `VideoOptions::RATES`
+    pub rate: u16,
+}
+
+impl VideoOptions {
+    pub const RATES: std::ops::RangeInclusive<u16> = 25..=400;
+}
+
+impl Default for VideoOptions {
+    fn default() -> Self {
+        Self {
+            looping: true,
+            // wallpapers shouldn't start talking
+            muted: true,
+            volume: 100,
+            rate: 100,
+        }
+    }
+}
+
+/// With `IpcXabCapabilities::Video` the scaling mode is followed by flags (u8,
+/// 1 to loop and 2 to mute), the volume (u8) and the rate (u16)
+fn put_video_options(buf: &mut BytesMut, video: VideoOptions, capabilities: IpcXabCapabilities) {
+    if capabilities.contains(IpcXabCapabilities::Video) {
+        buf.put_u8(video.looping as u8 | (video.muted as u8) << 1);
+        buf.put_u8(video.volume.min(100));
+        buf.put_u16(
+            video
+                .rate
+                .clamp(*VideoOptions::RATES.start(), *VideoOptions::RATES.end()),
+        );
+    }
+}
+
+/// Payload of `IpcCommands::ChangeBackground`: the monitor (see `put_monitor`)
+/// followed by the path (see `put_background_path`), the scaling mode (see
+/// `put_scaling`) and the video options (see `put_video_options`)
+pub fn encode_change_background(
+    monitor: Option<i32>,
+    screens: &Screens,
+    path: &Path,
+    scaling: ScalingMode,
+    video: VideoOptions,
+    capabilities: IpcXabCapabilities,
+) -> Result<Bytes> {
+    let mut buf = BytesMut::with_capacity(18 + path.as_os_str().len());
+    put_monitor(&mut buf, monitor, screens, capabilities);
+    put_background_path(&mut buf, path, capabilities)?;
+    put_scaling(&mut buf, scaling, capabilities);
+    put_video_options(&mut buf, video, capabilities);
+    Ok(buf.freeze())
 }
 
-// im too lazy to implement monitor names (coming soon TM)
-#[repr(C)]
-#[derive(Default, Debug, Clone, Copy)]
+/// Payload of `IpcCommands::ChangeBackgrounds`: monitor count (u32), that
+/// many monitors, then the path, scaling mode and video options like
+/// `encode_change_background`
+pub fn encode_change_backgrounds(
+    monitors: &[i32],
+    screens: &Screens,
+    path: &Path,
+    scaling: ScalingMode,
+    video: VideoOptions,
+    capabilities: IpcXabCapabilities,
+) -> Result<Bytes> {
+    let mut buf = BytesMut::with_capacity(14 + 8 * monitors.len() + path.as_os_str().len());
+    buf.put_u32(monitors.len() as u32);
+    for &monitor in monitors {
+        put_monitor(&mut buf, Some(monitor), screens, capabilities);
+    }
+    put_background_path(&mut buf, path, capabilities)?;
+    put_scaling(&mut buf, scaling, capabilities);
+    put_video_options(&mut buf, video, capabilities);
+    Ok(buf.freeze())
+}
+
+/// Payload of `IpcCommands::ClearBackground`: the monitor (see `put_monitor`)
+/// followed by the color it's left with (r, g, b as u8s)
+pub fn encode_clear_background(
+    monitor: Option<i32>,
+    screens: &Screens,
+    color: [u8; 3],
+    capabilities: IpcXabCapabilities,
+) -> Bytes {
+    let mut buf = BytesMut::with_capacity(11);
+    put_monitor(&mut buf, monitor, screens, capabilities);
+    buf.put_slice(&color);
+    buf.freeze()
+}
+
+/// Payload of `IpcCommands::PauseVideo`, `UnpauseVideo` and `TogglePauseVideo`
+/// with `IpcXabCapabilities::PerMonitorPlayback`: the monitor index (i32).
+/// Without a payload they're about every monitor
+pub fn encode_video_monitor(monitor: i32) -> Bytes {
+    Bytes::copy_from_slice(&monitor.to_be_bytes())
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct Monitor {
     pub index: i32,
+    /// X screen (`:0.<screen>`), only ever not 0 with `IpcXabCapabilities::Screens`.
+    /// Positions are relative to the screen, indices are unique across screens
+    pub screen: u32,
     pub primary: bool,
     pub x: u32,
     pub y: u32,
     pub width: u32,
     pub height: u32,
+    /// The output, like `DP-1`. Only sent with `IpcXabCapabilities::MonitorNames`
+    pub name: Option<String>,
+    /// From the EDID, like `Dell U2720Q`
+    pub model: Option<String>,
 }
 
 impl Monitor {
+    /// index (i32), primary (u8), x, y, width, height (u32)
+    pub const WIRE_SIZE: usize = 4 + 1 + 4 * 4;
+
     pub fn fullscreen() -> Self {
         Self {
             index: 0,
+            screen: 0,
             primary: true,
             x: 0,
             y: 0,
             // width and height of 0 means fullscreen
             width: 0,
             height: 0,
+            name: None,
+            model: None,
         }
     }
-    pub fn from_bytes(bytes: &Bytes) -> Result<Self> {
-        let mut cursor = Cursor::new(&bytes[..]);
-        let mut buf4 = [0u8; 4];
-        let mut buf1 = [0u8; 1];
 
-        cursor.read_exact(&mut buf4)?;
-        let index = i32::from_be_bytes(buf4);
+    /// Like `DP-1 (Dell U2720Q)`, the number when xab didn't say
+    pub fn label(&self) -> String {
+        match (&self.name, &self.model) {
+            (Some(name), Some(model)) => format!("{name} ({model})"),
+            (Some(name), None) | (None, Some(name)) => name.clone(),
+            (None, None) => (self.index + 1).to_string(),
+        }
+    }
+}
 
-        cursor.read_exact(&mut buf1)?;
-        let primary = buf1[0] != 0;
+impl Decode for Monitor {
+    fn decode(buf: &mut impl Buf) -> Result<Self> {
+        ensure_remaining(buf, Self::WIRE_SIZE, "monitor")?;
+        Ok(Self {
+            index: buf.get_i32(),
+            screen: 0,
+            primary: buf.get_u8() != 0,
+            x: buf.get_u32(),
+            y: buf.get_u32(),
+            width: buf.get_u32(),
+            height: buf.get_u32(),
+            name: None,
+            model: None,
+        })
+    }
+}
 
-        cursor.read_exact(&mut buf4)?;
-        let x = u32::from_be_bytes(buf4);
+/// A monitor as sent by xab with `IpcXabCapabilities::Screens`: the screen
+/// (u32) comes right after the index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenMonitor(pub Monitor);
 
-        cursor.read_exact(&mut buf4)?;
-        let y = u32::from_be_bytes(buf4);
+impl ScreenMonitor {
+    pub const WIRE_SIZE: usize = Monitor::WIRE_SIZE + 4;
+}
 
-        cursor.read_exact(&mut buf4)?;
-        let width = u32::from_be_bytes(buf4);
+impl Decode for ScreenMonitor {
+    fn decode(buf: &mut impl Buf) -> Result<Self> {
+        ensure_remaining(buf, Self::WIRE_SIZE, "monitor")?;
+        Ok(Self(Monitor {
+            index: buf.get_i32(),
+            screen: buf.get_u32(),
+            primary: buf.get_u8() != 0,
+            x: buf.get_u32(),
+            y: buf.get_u32(),
+            width: buf.get_u32(),
+            height: buf.get_u32(),
+            name: None,
+            model: None,
+        }))
+    }
+}
 
-        cursor.read_exact(&mut buf4)?;
-        let height = u32::from_be_bytes(buf4);
+/// Length (u16) and that many bytes of UTF-8, None when empty
+fn decode_name(buf: &mut impl Buf, what: &str) -> Result<Option<String>> {
+    ensure_remaining(buf, 2, what)?;
+    let len = buf.get_u16() as usize;
+    ensure_remaining(buf, len, what)?;
+    let name = String::from_utf8_lossy(&buf.copy_to_bytes(len)).into_owned();
+    Ok((!name.is_empty()).then_some(name))
+}
+
+/// A list of monitors: `ScreenMonitor`s with `IpcXabCapabilities::Screens`,
+/// plain `Monitor`s otherwise. With `IpcXabCapabilities::MonitorNames` each
+/// is followed by its name and model, see `decode_name`
+pub fn decode_monitors(
+    mut buf: impl Buf,
+    capabilities: IpcXabCapabilities,
+) -> Result<Vec<Monitor>> {
+    let mut monitors = Vec::new();
+    while buf.has_remaining() {
+        let mut monitor = match capabilities.contains(IpcXabCapabilities::Screens) {
+            true => ScreenMonitor::decode(&mut buf)?.0,
+            false => Monitor::decode(&mut buf)?,
+        };
+        if capabilities.contains(IpcXabCapabilities::MonitorNames) {
+            monitor.name = decode_name(&mut buf, "monitor name")?;
+            monitor.model = decode_name(&mut buf, "monitor model")?;
+        }
+        monitors.push(monitor);
+    }
+    Ok(monitors)
+}
+
+/// Reply of `IpcCommands::GetFormats`: the extensions xab can show, each
+/// one like a name (see `decode_name`), lowercase and without the dot
+pub fn decode_formats(mut buf: impl Buf) -> Result<Vec<String>> {
+    let mut formats = Vec::new();
+    while buf.has_remaining() {
+        if let Some(format) = decode_name(&mut buf, "format")? {
+            formats.push(format.trim_start_matches('.').to_lowercase());
+        }
+    }
+    Ok(formats)
+}
 
+/// With `IpcXabCapabilities::MonitorChunks` GetMonitors is answered in
+/// several replies, each one a flag (u8, 1 if more follow) and some of the
+/// monitors (see `decode_monitors`). Returns them and the flag
+pub fn decode_monitor_chunk(
+    mut buf: impl Buf,
+    capabilities: IpcXabCapabilities,
+) -> Result<(Vec<Monitor>, bool)> {
+    ensure_remaining(&buf, 1, "monitor chunk flag")?;
+    let more = buf.get_u8() != 0;
+    Ok((decode_monitors(buf, capabilities)?, more))
+}
+
+/// Whether more replies to the same GetMonitors follow `reply`, without
+/// decoding it. An error reply is always the last
+pub fn chunk_continues(mut reply: &[u8], capabilities: IpcXabCapabilities) -> bool {
+    if capabilities.contains(IpcXabCapabilities::ErrorReplies)
+        && (reply.len() < 4 || reply.get_i32() != 0)
+    {
+        return false;
+    }
+    reply.first().is_some_and(|more| *more != 0)
+}
+
+/// What a monitor shows, as xab sees it. Reply of
+/// `IpcCommands::GetBackgroundState` is one of these per monitor: the index
+/// (i32), paused (u8), scaling (u8, see `ScalingMode`) and the path (see
+/// `encode_path`), empty when nothing is up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackgroundState {
+    pub monitor: i32,
+    pub path: Option<PathBuf>,
+    pub paused: bool,
+    pub scaling: ScalingMode,
+}
+
+impl Decode for BackgroundState {
+    fn decode(buf: &mut impl Buf) -> Result<Self> {
+        ensure_remaining(buf, 6, "background state")?;
+        let monitor = buf.get_i32();
+        let paused = buf.get_u8() != 0;
+        let scaling = ScalingMode::from_wire(buf.get_u8());
+        let path = decode_path(buf)?;
         Ok(Self {
-            index,
-            primary,
-            x,
-            y,
-            width,
-            height,
+            monitor,
+            path: (!path.as_os_str().is_empty()).then_some(path),
+            paused,
+            scaling,
+        })
+    }
+}
+
+/// How a wallpaper is fit into a monitor, the discriminant is what's sent
+/// with `IpcXabCapabilities::Scaling`
+#[repr(u8)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScalingMode {
+    /// Cover the monitor, cropping what doesn't fit
+    #[default]
+    Fill = 0,
+    /// Show everything, letterboxed
+    Fit = 1,
+    Stretch = 2,
+    /// Original size in the middle
+    Center = 3,
+    /// Original size, repeated from the top left
+    Tile = 4,
+}
+
+impl ScalingMode {
+    pub const ALL: &[Self] = &[
+        Self::Fill,
+        Self::Fit,
+        Self::Stretch,
+        Self::Center,
+        Self::Tile,
+    ];
+
+    /// The discriminant sent with `IpcXabCapabilities::Scaling`, fill for
+    /// anything newer
+    pub fn from_wire(mode: u8) -> Self {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|scaling| *scaling as u8 == mode)
+            .unwrap_or_default()
+    }
+}
+
+impl std::fmt::Display for ScalingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Fill => "Fill",
+            Self::Fit => "Fit",
+            Self::Stretch => "Stretch",
+            Self::Center => "Center",
+            Self::Tile => "Tile",
         })
     }
 }
@@ -95,6 +924,57 @@ bitflags! {
         const None = 0;
         const CustomPositioning = 1 << 0;
         const Monitors = 1 << 1;
+        /// Restart and shutdown are reserved for privileged clients
+        const PrivilegedControl = 1 << 2;
+        /// This client is privileged (only meaningful with `PrivilegedControl`)
+        const Privileged = 1 << 3;
+        /// xab sends `IpcEvents` without being asked
+        const Events = 1 << 4;
+        /// Paths are sent with a UTF-8 flag, see `encode_path`
+        const RawPaths = 1 << 5;
+        /// One background can go to several monitors at once, see
+        /// `encode_change_backgrounds`
+        const MonitorLists = 1 << 6;
+        /// Zaphod setups, monitors come with their X screen and backgrounds
+        /// are addressed to one, see `ScreenMonitor` and `put_monitor`
+        const Screens = 1 << 7;
+        /// The pause commands can be about a single monitor, see
+        /// `encode_video_monitor`
+        const PerMonitorPlayback = 1 << 8;
+        /// Monitors come with their output name and EDID model, see
+        /// `decode_monitors`
+        const MonitorNames = 1 << 9;
+        /// Every command gets a status back, failed ones an `IpcError`, see
+        /// `decode_reply`
+        const ErrorReplies = 1 << 10;
+        /// Backgrounds come with a `ScalingMode`, see `put_scaling`
+        const Scaling = 1 << 11;
+        /// Backgrounds come with `VideoOptions`, see `put_video_options`
+        const Video = 1 << 12;
+        /// GetMonitors is answered a few monitors at a time, see
+        /// `decode_monitor_chunk`
+        const MonitorChunks = 1 << 13;
+        /// Monitors can be left without a wallpaper, see
+        /// `encode_clear_background`
+        const Clear = 1 << 14;
+        /// GetFormats tells what files xab can show, see `decode_formats`
+        const Formats = 1 << 15;
+        /// GetBackgroundState tells what each monitor shows, see
+        /// `BackgroundState`
+        const BackgroundState = 1 << 16;
+    }
+}
+
+/// Shown next to locked commands
+pub const PRIVILEGE_HINT: &str = "xab only accepts this from privileged clients, \
+     run xab-gui as the user running xab or ask them to grant you access";
+
+impl IpcXabCapabilities {
+    /// Commands the daemon would refuse, so they shouldn't even be sent
+    pub fn is_locked(self, command: IpcCommands) -> bool {
+        command.is_privileged()
+            && self.contains(Self::PrivilegedControl)
+            && !self.contains(Self::Privileged)
     }
 }
 
@@ -103,3 +983,617 @@ impl Default for IpcXabCapabilities {
         Self::None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_monitor(monitor: &Monitor) -> BytesMut {
+        let mut buf = BytesMut::new();
+        buf.put_i32(monitor.index);
+        buf.put_u8(monitor.primary as u8);
+        buf.put_u32(monitor.x);
+        buf.put_u32(monitor.y);
+        buf.put_u32(monitor.width);
+        buf.put_u32(monitor.height);
+        buf
+    }
+
+    #[test]
+    fn decodes_monitor_names() {
+        let mut buf = encode_monitor(&Monitor::fullscreen());
+        for name in ["DP-1", "Dell U2720Q"] {
+            buf.put_u16(name.len() as u16);
+            buf.put_slice(name.as_bytes());
+        }
+        buf.extend_from_slice(&encode_monitor(&Monitor {
+            index: 1,
+            ..Monitor::fullscreen()
+        }));
+        // xab doesn't know either
+        buf.put_u16(0);
+        buf.put_u16(0);
+
+        let monitors =
+            decode_monitors(buf.clone().freeze(), IpcXabCapabilities::MonitorNames).unwrap();
+        assert_eq!(monitors[0].name.as_deref(), Some("DP-1"));
+        assert_eq!(monitors[0].model.as_deref(), Some("Dell U2720Q"));
+        assert_eq!(monitors[0].label(), "DP-1 (Dell U2720Q)");
+        assert_eq!(monitors[1].name, None);
+        assert_eq!(monitors[1].label(), "2");
+
+        // a name cut short
+        buf.truncate(buf.len() - 1);
+        assert!(decode_monitors(buf.freeze(), IpcXabCapabilities::MonitorNames).is_err());
+    }
+
+    #[test]
+    fn resolves_monitor_targets() {
+        let monitors = [
+            Monitor {
+                index: 0,
+                primary: false,
+                name: Some("HDMI-1".to_owned()),
+                ..Monitor::fullscreen()
+            },
+            Monitor {
+                index: 1,
+                primary: true,
+                name: Some("DP-1".to_owned()),
+                ..Monitor::fullscreen()
+            },
+        ];
+        let encode = |target: &str| {
+            let target: MonitorTarget = target.parse().unwrap();
+            let mut buf = BytesMut::new();
+            put_monitor(
+                &mut buf,
+                target.resolve(&monitors)?,
+                &Screens::from([(1, 2)]),
+                IpcXabCapabilities::Screens,
+            );
+            anyhow::Ok(buf.to_vec())
+        };
+        let bytes =
+            |screen: i32, monitor: i32| [screen.to_be_bytes(), monitor.to_be_bytes()].concat();
+
+        assert_eq!(encode("all").unwrap(), bytes(-1, -1));
+        assert_eq!(encode("Primary").unwrap(), bytes(2, 1));
+        assert_eq!(encode("1").unwrap(), bytes(-1, 0));
+        assert_eq!(encode("dp-1").unwrap(), bytes(2, 1));
+        // indices past what an i8 (or the monitors xab listed) holds
+        assert_eq!(encode("300").unwrap(), bytes(-1, 299));
+        assert!(encode("DP-2").is_err());
+        assert!(encode("4294967296").is_err());
+        assert!("0".parse::<MonitorTarget>().is_err());
+
+        for target in ["all", "primary", "3", "DP-1"] {
+            assert_eq!(target.parse::<MonitorTarget>().unwrap().to_string(), target);
+        }
+        assert_eq!(MonitorTarget::from(Some(2)), MonitorTarget::Index(2));
+        assert_eq!(MonitorTarget::from(None), MonitorTarget::All);
+    }
+
+    #[test]
+    fn decodes_background_state() {
+        let mut buf = BytesMut::new();
+        buf.put_i32(0);
+        buf.put_u8(1);
+        buf.put_u8(ScalingMode::Fit as u8);
+        encode_path(&mut buf, Path::new("/wallpapers/h.mp4"));
+        // nothing up on the second one, and a scaling mode from the future
+        buf.put_i32(1);
+        buf.put_u8(0);
+        buf.put_u8(42);
+        encode_path(&mut buf, Path::new(""));
+
+        let states: Vec<BackgroundState> = decode_all(buf.freeze()).unwrap();
+        assert_eq!(
+            states,
+            [
+                BackgroundState {
+                    monitor: 0,
+                    path: Some(PathBuf::from("/wallpapers/h.mp4")),
+                    paused: true,
+                    scaling: ScalingMode::Fit,
+                },
+                BackgroundState {
+                    monitor: 1,
+                    path: None,
+                    paused: false,
+                    scaling: ScalingMode::Fill,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_formats() {
+        let mut buf = BytesMut::new();
+        for format in ["png", ".JPG", "", "webm"] {
+            buf.put_u16(format.len() as u16);
+            buf.put_slice(format.as_bytes());
+        }
+        assert_eq!(
+            decode_formats(buf.freeze()).unwrap(),
+            ["png", "jpg", "webm"]
+        );
+    }
+
+    #[test]
+    fn decodes_back_to_back_monitors() {
+        let mut buf = encode_monitor(&Monitor {
+            index: 0,
+            screen: 0,
+            primary: true,
+            x: 0,
+            y: 0,
+            width: 1920,
+            height: 1080,
+            name: None,
+            model: None,
+        });
+        buf.extend_from_slice(&encode_monitor(&Monitor {
+            index: 1,
+            screen: 0,
+            primary: false,
+            x: 1920,
+            y: 0,
+            width: 2560,
+            height: 1440,
+            name: None,
+            model: None,
+        }));
+
+        let monitors: Vec<Monitor> = decode_all(buf.freeze()).unwrap();
+        assert_eq!(monitors.len(), 2);
+        assert!(monitors[0].primary);
+        assert_eq!(monitors[1].index, 1);
+        assert_eq!(monitors[1].x, 1920);
+        assert_eq!(monitors[1].height, 1440);
+    }
+
+    #[test]
+    fn truncated_monitor_is_an_error() {
+        let buf = encode_monitor(&Monitor::fullscreen()).freeze();
+        for len in 0..Monitor::WIRE_SIZE {
+            assert!(Monitor::decode(&mut buf.slice(..len)).is_err());
+        }
+    }
+
+    #[test]
+    fn trailing_garbage_is_an_error() {
+        let mut buf = encode_monitor(&Monitor::fullscreen());
+        buf.put_u8(0);
+        assert!(decode_all::<Monitor>(buf.freeze()).is_err());
+    }
+
+    #[test]
+    fn empty_reply_decodes_to_nothing() {
+        assert!(decode_all::<Monitor>(Bytes::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn frames_split_on_their_length() {
+        let reply = |payload: &'static [u8]| Some(Incoming::Reply(Bytes::from_static(payload)));
+        let mut decoder = FrameDecoder::new(StreamKind::Replies);
+        decoder.buffer().put_slice(&encode_frame(b"first").unwrap());
+        decoder.buffer().put_slice(&encode_frame(b"").unwrap());
+        let second = encode_frame(b"second").unwrap();
+        // only part of the second one arrived so far
+        decoder.buffer().put_slice(&second[..6]);
+
+        assert_eq!(decoder.decode_next().unwrap(), reply(b"first"));
+        assert_eq!(decoder.decode_next().unwrap(), reply(b""));
+        assert_eq!(decoder.decode_next().unwrap(), None);
+        decoder.buffer().put_slice(&second[6..]);
+        assert_eq!(decoder.decode_next().unwrap(), reply(b"second"));
+        assert_eq!(decoder.pending(), 0);
+    }
+
+    #[test]
+    fn decodes_error_replies() {
+        let mut ok = BytesMut::new();
+        ok.put_i32(0);
+        ok.put_slice(b"reply");
+        assert_eq!(
+            decode_reply(ok.freeze(), IpcXabCapabilities::ErrorReplies).unwrap(),
+            &b"reply"[..]
+        );
+
+        let mut failed = BytesMut::new();
+        failed.put_i32(IpcErrorCode::NoSuchMonitor as i32);
+        failed.put_u16(4);
+        failed.put_slice(b"HDMI");
+        let err = decode_reply(failed.freeze(), IpcXabCapabilities::ErrorReplies).unwrap_err();
+        let err = err.downcast::<IpcError>().unwrap();
+        assert_eq!(err.kind(), Some(IpcErrorCode::NoSuchMonitor));
+        assert_eq!(err.message.as_deref(), Some("HDMI"));
+
+        // older xab doesn't send a status at all
+        assert_eq!(
+            decode_reply(Bytes::from_static(b"\0\0\0\x03"), IpcXabCapabilities::None).unwrap(),
+            &b"\0\0\0\x03"[..]
+        );
+        assert!(decode_reply(Bytes::new(), IpcXabCapabilities::ErrorReplies).is_err());
+    }
+
+    #[test]
+    fn decoder_handles_any_split() {
+        let mut stream = Vec::new();
+        for payload in [&b"first"[..], b"", b"third one"] {
+            stream.extend_from_slice(&encode_frame(payload).unwrap());
+        }
+        // a byte at a time, then everything coalesced into one read
+        for chunk in [1, stream.len()] {
+            let mut decoder = FrameDecoder::new(StreamKind::Replies);
+            let mut frames = Vec::new();
+            for bytes in stream.chunks(chunk) {
+                decoder.buffer().put_slice(bytes);
+                while let Some(Incoming::Reply(frame)) = decoder.decode_next().unwrap() {
+                    frames.push(frame);
+                }
+            }
+            assert_eq!(frames, [&b"first"[..], b"", b"third one"]);
+            assert_eq!(decoder.pending(), 0);
+        }
+
+        let mut huge = FrameDecoder::new(StreamKind::Replies);
+        huge.buffer().put_slice(&u32::MAX.to_be_bytes());
+        assert!(huge.decode_next().is_err());
+    }
+
+    #[test]
+    fn decoder_splits_events() {
+        let mut stream = BytesMut::new();
+        stream.put_i32(IpcEvents::ServerShuttingDown as i32);
+        stream.put_u32(10);
+        let mut changed = BytesMut::new();
+        changed.put_i32(1);
+        encode_path(&mut changed, Path::new("/wallpapers/g.png"));
+        // a reply in between
+        stream.put_i32(REPLY_TAG);
+        stream.extend_from_slice(&encode_frame(b"reply").unwrap());
+        stream.put_i32(IpcEvents::BackgroundChanged as i32);
+        stream.extend_from_slice(&encode_frame(&changed).unwrap());
+
+        let mut decoder = FrameDecoder::new(StreamKind::Events);
+        let mut events = Vec::new();
+        let mut replies = Vec::new();
+        for bytes in stream.chunks(3) {
+            decoder.buffer().put_slice(bytes);
+            while let Some(incoming) = decoder.decode_next().unwrap() {
+                match incoming {
+                    Incoming::Event(mut event) => {
+                        events
+                            .push(IpcEvent::decode(&mut event, IpcXabCapabilities::None).unwrap());
+                        assert!(!event.has_remaining());
+                    }
+                    Incoming::Reply(reply) => replies.push(reply),
+                }
+            }
+        }
+        assert_eq!(replies, [&b"reply"[..]]);
+        assert_eq!(
+            events,
+            [
+                IpcEvent::ServerShuttingDown {
+                    grace: Duration::from_secs(10)
+                },
+                IpcEvent::BackgroundChanged {
+                    monitor: Some(1),
+                    path: PathBuf::from("/wallpapers/g.png"),
+                },
+            ]
+        );
+
+        let mut unknown = FrameDecoder::new(StreamKind::Events);
+        unknown.buffer().put_slice(&(-5i32).to_be_bytes());
+        assert!(unknown.decode_next().is_err());
+    }
+
+    #[test]
+    fn negotiates_highest_common_version() {
+        assert_eq!(
+            negotiate_version(IPC_PROTO_VERSION).unwrap(),
+            IPC_PROTO_VERSION
+        );
+        assert_eq!(
+            negotiate_version(IPC_PROTO_VERSION + 3).unwrap(),
+            IPC_PROTO_VERSION
+        );
+        assert!(negotiate_version(IPC_PROTO_VERSION_MIN - 1).is_err());
+    }
+
+    #[test]
+    fn error_reply_ends_monitor_chunks() {
+        let capabilities = IpcXabCapabilities::MonitorChunks | IpcXabCapabilities::ErrorReplies;
+        let mut chunk = BytesMut::new();
+        chunk.put_i32(0);
+        chunk.put_u8(1);
+        assert!(chunk_continues(&chunk, capabilities));
+        chunk[4] = 0;
+        assert!(!chunk_continues(&chunk, capabilities));
+
+        let mut failed = BytesMut::new();
+        failed.put_i32(IpcErrorCode::Internal as i32);
+        failed.put_u8(1);
+        assert!(!chunk_continues(&failed, capabilities));
+        assert!(!chunk_continues(&[], IpcXabCapabilities::MonitorChunks));
+    }
+
+    #[test]
+    fn decodes_shutdown_notice() {
+        let mut buf = BytesMut::new();
+        buf.put_i32(IpcEvents::ServerShuttingDown as i32);
+        buf.put_u32(30);
+        assert_eq!(
+            IpcEvent::decode(&mut buf.freeze(), IpcXabCapabilities::None).unwrap(),
+            IpcEvent::ServerShuttingDown {
+                grace: Duration::from_secs(30)
+            }
+        );
+
+        let mut unknown = BytesMut::new();
+        unknown.put_i32(-5);
+        assert!(IpcEvent::decode(&mut unknown.freeze(), IpcXabCapabilities::None).is_err());
+    }
+
+    #[test]
+    fn decodes_framed_events() {
+        let mut monitors = BytesMut::new();
+        monitors.put_i32(1);
+        monitors.put_u32(0);
+        monitors.put_u8(1);
+        for value in [1920, 0, 2560, 1440] {
+            monitors.put_u32(value);
+        }
+        let mut buf = BytesMut::new();
+        buf.put_i32(IpcEvents::MonitorsChanged as i32);
+        buf.put_slice(&encode_frame(&monitors).unwrap());
+
+        let mut path = BytesMut::new();
+        path.put_i32(-1);
+        encode_path(&mut path, Path::new("/wallpapers/sea.png"));
+        buf.put_i32(IpcEvents::BackgroundChanged as i32);
+        buf.put_slice(&encode_frame(&path).unwrap());
+
+        let mut buf = buf.freeze();
+        let mut events = Vec::new();
+        while buf.has_remaining() {
+            events.push(IpcEvent::decode(&mut buf, IpcXabCapabilities::None).unwrap());
+        }
+        assert_eq!(
+            events,
+            [
+                IpcEvent::MonitorsChanged(vec![Monitor {
+                    index: 1,
+                    screen: 0,
+                    primary: true,
+                    x: 1920,
+                    y: 0,
+                    width: 2560,
+                    height: 1440,
+                    name: None,
+                    model: None,
+                }]),
+                IpcEvent::BackgroundChanged {
+                    monitor: None,
+                    path: PathBuf::from("/wallpapers/sea.png"),
+                },
+            ]
+        );
+
+        let mut truncated = BytesMut::new();
+        truncated.put_i32(IpcEvents::BackgroundChanged as i32);
+        truncated.put_u32(100);
+        truncated.put_i32(0);
+        assert!(IpcEvent::decode(&mut truncated.freeze(), IpcXabCapabilities::None).is_err());
+    }
+
+    #[test]
+    fn non_utf8_paths_survive_the_wire() {
+        let path = Path::new(OsStr::from_bytes(b"/wallpapers/caf\xe9.png"));
+        let mut buf = encode_change_background(
+            Some(1),
+            &Screens::new(),
+            path,
+            ScalingMode::Fill,
+            VideoOptions::default(),
+            IpcXabCapabilities::RawPaths,
+        )
+        .unwrap();
+        assert_eq!(buf.get_i32(), 1);
+        assert_eq!(buf[0], 0, "utf-8 flag should be off");
+        assert_eq!(decode_path(&mut buf).unwrap(), path);
+        assert!(!buf.has_remaining());
+
+        // old daemons would mangle it, so it's refused instead
+        assert!(
+            encode_change_background(
+                None,
+                &Screens::new(),
+                path,
+                ScalingMode::Fill,
+                VideoOptions::default(),
+                IpcXabCapabilities::None
+            )
+            .is_err()
+        );
+        assert!(
+            encode_change_background(
+                None,
+                &Screens::new(),
+                Path::new("/ok.png"),
+                ScalingMode::Fill,
+                VideoOptions::default(),
+                IpcXabCapabilities::None
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn change_background_carries_the_monitor_and_path() {
+        let mut buf = encode_change_background(
+            None,
+            &Screens::new(),
+            Path::new("/wallpapers/a.png"),
+            ScalingMode::Fill,
+            VideoOptions::default(),
+            IpcXabCapabilities::None,
+        )
+        .unwrap();
+        assert_eq!(buf.get_i32(), -1, "no monitor means all of them");
+        assert_eq!(buf.get_u32() as usize, "/wallpapers/a.png".len());
+        assert_eq!(&buf[..], b"/wallpapers/a.png");
+
+        let mut buf = encode_change_background(
+            Some(2),
+            &Screens::new(),
+            Path::new("/wallpapers/b.png"),
+            ScalingMode::Fill,
+            VideoOptions::default(),
+            IpcXabCapabilities::RawPaths,
+        )
+        .unwrap();
+        assert_eq!(buf.get_i32(), 2);
+        assert_eq!(buf[0], 1, "utf-8 flag should be on");
+        assert_eq!(
+            decode_path(&mut buf).unwrap(),
+            Path::new("/wallpapers/b.png")
+        );
+        assert!(!buf.has_remaining());
+    }
+
+    #[test]
+    fn change_backgrounds_lists_every_monitor() {
+        let mut buf = encode_change_backgrounds(
+            &[0, 2],
+            &Screens::new(),
+            Path::new("/wallpapers/c.png"),
+            ScalingMode::Fill,
+            VideoOptions::default(),
+            IpcXabCapabilities::MonitorLists | IpcXabCapabilities::RawPaths,
+        )
+        .unwrap();
+        assert_eq!(buf.get_u32(), 2);
+        assert_eq!(buf.get_i32(), 0);
+        assert_eq!(buf.get_i32(), 2);
+        assert_eq!(
+            decode_path(&mut buf).unwrap(),
+            Path::new("/wallpapers/c.png")
+        );
+        assert!(!buf.has_remaining());
+    }
+
+    #[test]
+    fn scaling_follows_the_path() {
+        let path = Path::new("/wallpapers/e.png");
+        let buf = encode_change_background(
+            Some(0),
+            &Screens::new(),
+            path,
+            ScalingMode::Tile,
+            VideoOptions::default(),
+            IpcXabCapabilities::Scaling,
+        )
+        .unwrap();
+        assert_eq!(buf.last(), Some(&(ScalingMode::Tile as u8)));
+
+        let old = encode_change_background(
+            Some(0),
+            &Screens::new(),
+            path,
+            ScalingMode::Tile,
+            VideoOptions::default(),
+            IpcXabCapabilities::None,
+        )
+        .unwrap();
+        assert_eq!(old.len(), buf.len() - 1, "older xab doesn't get it");
+    }
+
+    #[test]
+    fn video_options_follow_the_scaling() {
+        let video = VideoOptions {
+            looping: false,
+            muted: true,
+            volume: 250,
+            rate: 150,
+        };
+        let mut buf = encode_change_background(
+            None,
+            &Screens::new(),
+            Path::new("/wallpapers/f.mp4"),
+            ScalingMode::Fit,
+            video,
+            IpcXabCapabilities::Scaling | IpcXabCapabilities::Video,
+        )
+        .unwrap();
+        let mut options = buf.split_off(buf.len() - 4);
+        assert_eq!(buf.last(), Some(&(ScalingMode::Fit as u8)));
+        assert_eq!(options.get_u8(), 2, "muted without looping");
+        assert_eq!(options.get_u8(), 100, "the volume is capped");
+        assert_eq!(options.get_u16(), 150);
+    }
+
+    #[test]
+    fn privileged_commands_are_locked_only_when_advertised() {
+        let old_daemon = IpcXabCapabilities::Monitors;
+        assert!(!old_daemon.is_locked(IpcCommands::Shutdown));
+
+        let locked = IpcXabCapabilities::PrivilegedControl;
+        assert!(locked.is_locked(IpcCommands::Shutdown));
+        assert!(locked.is_locked(IpcCommands::Restart));
+        assert!(!locked.is_locked(IpcCommands::ChangeBackground));
+
+        let granted = IpcXabCapabilities::PrivilegedControl | IpcXabCapabilities::Privileged;
+        assert!(!granted.is_locked(IpcCommands::Shutdown));
+    }
+
+    #[test]
+    fn screens_address_every_monitor() {
+        let buf = encode_monitor(&Monitor {
+            index: 1,
+            ..Monitor::fullscreen()
+        });
+        // xab with screens puts the screen right after the index
+        let mut screened = BytesMut::new();
+        screened.put_slice(&buf[..4]);
+        screened.put_u32(1);
+        screened.put_slice(&buf[4..]);
+        let monitors: Vec<ScreenMonitor> = decode_all(screened.freeze()).unwrap();
+        assert_eq!(monitors[0].0.index, 1);
+        assert_eq!(monitors[0].0.screen, 1);
+        assert!(monitors[0].0.primary);
+
+        let screens = Screens::from([(1, 1)]);
+        let mut buf = encode_change_backgrounds(
+            &[0, 1],
+            &screens,
+            Path::new("/wallpapers/d.png"),
+            ScalingMode::Fill,
+            VideoOptions::default(),
+            IpcXabCapabilities::MonitorLists | IpcXabCapabilities::Screens,
+        )
+        .unwrap();
+        assert_eq!(buf.get_u32(), 2);
+        assert_eq!(buf.get_i32(), -1, "a monitor on no known screen");
+        assert_eq!(buf.get_i32(), 0);
+        assert_eq!(buf.get_i32(), 1);
+        assert_eq!(buf.get_i32(), 1);
+
+        // without the capability the screen isn't sent at all
+        let mut buf = encode_change_background(
+            Some(1),
+            &screens,
+            Path::new("/wallpapers/d.png"),
+            ScalingMode::Fill,
+            VideoOptions::default(),
+            IpcXabCapabilities::None,
+        )
+        .unwrap();
+        assert_eq!(buf.get_i32(), 1);
+        assert_eq!(buf.get_u32() as usize, "/wallpapers/d.png".len());
+    }
+}
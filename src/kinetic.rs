@@ -0,0 +1,88 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Only movement this recent counts towards the speed of a flick
+const SAMPLE_WINDOW: Duration = Duration::from_millis(100);
+/// Pixels per second, anything slower just stops
+const MIN_SPEED: f32 = 50.0;
+/// Part of the speed left after coasting for a second
+const FRICTION: f32 = 0.05;
+
+/// Keeps a list scrolling after it's flicked with a finger, slowing down
+/// until it stops. The dragging itself is left to the scrollable
+#[derive(Debug, Default)]
+pub struct Kinetic {
+    fingers: usize,
+    /// Offsets of the list while a finger is down, oldest first
+    samples: VecDeque<(Instant, f32)>,
+    /// Pixels per second, 0 unless coasting
+    speed: f32,
+    last_frame: Option<Instant>,
+}
+
+impl Kinetic {
+    /// A finger anywhere stops the list, like grabbing it
+    pub fn finger_down(&mut self) {
+        self.fingers += 1;
+        self.speed = 0.0;
+        self.samples.clear();
+    }
+
+    pub fn finger_up(&mut self, now: Instant) {
+        self.fingers = self.fingers.saturating_sub(1);
+        if self.fingers > 0 {
+            return;
+        }
+        self.speed = match (self.samples.front(), self.samples.back()) {
+            (Some((start, from)), Some((end, to)))
+                if end > start && now.duration_since(*end) < SAMPLE_WINDOW =>
+            {
+                (to - from) / end.duration_since(*start).as_secs_f32()
+            }
+            _ => 0.0,
+        };
+        if self.speed.abs() < MIN_SPEED {
+            self.speed = 0.0;
+        }
+        self.samples.clear();
+        self.last_frame = Some(now);
+    }
+
+    /// The list moved to `offset`, only counts while a finger is down
+    pub fn scrolled(&mut self, offset: f32, now: Instant) {
+        if self.fingers == 0 {
+            return;
+        }
+        self.samples.push_back((now, offset));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > SAMPLE_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn is_coasting(&self) -> bool {
+        self.speed != 0.0
+    }
+
+    /// How far to scroll for the frame drawn at `now`, None once it stopped
+    pub fn frame(&mut self, now: Instant) -> Option<f32> {
+        if !self.is_coasting() {
+            return None;
+        }
+        let elapsed = self
+            .last_frame
+            .map_or(0.0, |last| now.duration_since(last).as_secs_f32());
+        self.last_frame = Some(now);
+        let distance = self.speed * elapsed;
+        self.speed *= FRICTION.powf(elapsed);
+        if self.speed.abs() < MIN_SPEED {
+            self.speed = 0.0;
+        }
+        Some(distance)
+    }
+}
@@ -0,0 +1,468 @@
+use iced::{
+    Color, Point, Rectangle, Renderer, Size, Theme, Vector, keyboard, mouse, touch,
+    widget::{
+        canvas::{self, Cache, Event, Frame, Geometry, Path, Stroke, Text},
+        image::Handle,
+    },
+    window,
+};
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use crate::{energy::Impact, ipc_spec::Monitor, preview};
+
+/// Space around the monitors, in pixels of the canvas
+const PADDING: f32 = 10.0;
+/// Between X screens, in pixels of the desktop
+const SCREEN_GAP: f32 = 200.0;
+/// Monitors drawn shorter than this only get their number
+const DETAILS_MIN_HEIGHT: f32 = 48.0;
+/// Held at least this long, a tap opens the monitor's menu
+const LONG_PRESS: Duration = Duration::from_millis(500);
+/// A finger moving further than this is dragging, not tapping
+const TAP_SLOP: f32 = 10.0;
+/// How far in a pinch can zoom
+const MAX_ZOOM: f32 = 4.0;
+
+/// Number, name and size of a monitor, drawn in its top left corner
+fn describe(monitor: &Monitor) -> String {
+    let name = match monitor.name.is_some() || monitor.model.is_some() {
+        true => format!(" {}", monitor.label()),
+        false => String::new(),
+    };
+    let primary = if monitor.primary { " (primary)" } else { "" };
+    match (monitor.width, monitor.height) {
+        (0, 0) => format!("{}{name}\nWhole desktop", monitor.index + 1),
+        (width, height) => format!(
+            "{}{name}{primary}\n{width}x{height} at {},{}",
+            monitor.index + 1,
+            monitor.x,
+            monitor.y
+        ),
+    }
+}
+
+/// Layers of the layout canvas, each one only redrawn when cleared (or resized)
+#[derive(Default)]
+pub struct LayoutCaches {
+    /// Monitor outlines, changes only with the monitor list
+    geometry: Cache,
+    /// Selection and thumbnails
+    selection: Cache,
+}
+
+impl LayoutCaches {
+    pub fn monitors_changed(&self) {
+        self.geometry.clear();
+        self.selection.clear();
+    }
+
+    pub fn selection_changed(&self) {
+        self.selection.clear();
+    }
+}
+
+/// The monitors as xab sees them, click (or tap) one to select it, ctrl+click
+/// to add it to the selection, right click (or long press) for its menu.
+/// Pinching zooms in on the previews
+pub struct LayoutView<'a, Message> {
+    pub monitors: Vec<Monitor>,
+    /// Every monitor if empty
+    pub selected: &'a HashSet<i32>,
+    /// Rendered previews by monitor index
+    pub previews: Vec<(i32, Handle)>,
+    /// What each monitor shows by monitor index, written under it
+    pub captions: Vec<(i32, String)>,
+    /// Battery impact of the saved wallpaper by monitor index, videos only
+    pub impacts: Vec<(i32, Impact)>,
+    pub caches: &'a LayoutCaches,
+    pub on_select: fn(i32) -> Message,
+    pub on_toggle: fn(i32) -> Message,
+    pub on_menu: fn(i32) -> Message,
+    /// A file dropped onto a monitor
+    pub on_drop: fn(i32, PathBuf) -> Message,
+}
+
+pub struct LayoutState {
+    hovered: Option<i32>,
+    modifiers: keyboard::Modifiers,
+    /// Fingers on the canvas, relative to it
+    fingers: HashMap<touch::Finger, Point>,
+    /// Where and when a lone finger went down, until it starts dragging
+    press: Option<(Point, Instant)>,
+    /// Pinch zoom, a point `p` of the layout is drawn at `p * scale + offset`
+    scale: f32,
+    offset: Vector,
+}
+
+impl Default for LayoutState {
+    fn default() -> Self {
+        Self {
+            hovered: None,
+            modifiers: keyboard::Modifiers::default(),
+            fingers: HashMap::new(),
+            press: None,
+            scale: 1.0,
+            offset: Vector::ZERO,
+        }
+    }
+}
+
+impl LayoutState {
+    /// Scales the layout by `ratio` around `from`, moving that point to `to`
+    fn pinch(&mut self, from: Point, to: Point, ratio: f32) {
+        let scale = (self.scale * ratio).clamp(1.0, MAX_ZOOM);
+        if scale == 1.0 {
+            self.scale = 1.0;
+            self.offset = Vector::ZERO;
+            return;
+        }
+        // the point of the layout under `from` ends up under `to`
+        let anchor = Vector::new(
+            (from.x - self.offset.x) / self.scale,
+            (from.y - self.offset.y) / self.scale,
+        );
+        self.offset = (to - Point::ORIGIN) - anchor * scale;
+        self.scale = scale;
+    }
+}
+
+impl<Message> LayoutView<'_, Message> {
+    /// Where each X screen starts, they don't share coordinates so they're
+    /// put next to each other
+    fn screen_offsets(&self) -> BTreeMap<u32, f32> {
+        let mut widths: BTreeMap<u32, f32> = BTreeMap::new();
+        for monitor in &self.monitors {
+            let right = (monitor.x + preview::monitor_size(monitor).0) as f32;
+            let width = widths.entry(monitor.screen).or_default();
+            *width = width.max(right);
+        }
+        let mut offset = 0.0;
+        widths
+            .into_iter()
+            .map(|(screen, width)| {
+                let start = offset;
+                offset += width + SCREEN_GAP;
+                (screen, start)
+            })
+            .collect()
+    }
+
+    /// Monitor rectangles scaled into `bounds`
+    fn rects(&self, bounds: Size) -> Vec<(i32, Rectangle)> {
+        let screens = self.screen_offsets();
+        let sized: Vec<(i32, Rectangle)> = self
+            .monitors
+            .iter()
+            .map(|monitor| {
+                let (width, height) = preview::monitor_size(monitor);
+                (
+                    monitor.index,
+                    Rectangle::new(
+                        Point::new(
+                            screens[&monitor.screen] + monitor.x as f32,
+                            monitor.y as f32,
+                        ),
+                        Size::new(width as f32, height as f32),
+                    ),
+                )
+            })
+            .collect();
+        let Some(desktop) = sized
+            .iter()
+            .map(|(_, rect)| *rect)
+            .reduce(|a, b| a.union(&b))
+        else {
+            return Vec::new();
+        };
+
+        let scale = ((bounds.width - 2.0 * PADDING) / desktop.width)
+            .min((bounds.height - 2.0 * PADDING) / desktop.height);
+        let offset = Point::new(
+            (bounds.width - desktop.width * scale) / 2.0,
+            (bounds.height - desktop.height * scale) / 2.0,
+        );
+        sized
+            .into_iter()
+            .map(|(index, rect)| {
+                (
+                    index,
+                    Rectangle::new(
+                        Point::new(
+                            offset.x + (rect.x - desktop.x) * scale,
+                            offset.y + (rect.y - desktop.y) * scale,
+                        ),
+                        rect.size() * scale,
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    /// `rects` with the pinch zoom applied
+    fn zoomed_rects(&self, bounds: Size, state: &LayoutState) -> Vec<(i32, Rectangle)> {
+        self.rects(bounds)
+            .into_iter()
+            .map(|(index, rect)| {
+                (
+                    index,
+                    Rectangle::new(
+                        Point::new(
+                            rect.x * state.scale + state.offset.x,
+                            rect.y * state.scale + state.offset.y,
+                        ),
+                        rect.size() * state.scale,
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    /// `position` is relative to the canvas
+    fn monitor_at(&self, bounds: Size, state: &LayoutState, position: Point) -> Option<i32> {
+        self.zoomed_rects(bounds, state)
+            .into_iter()
+            .find(|(_, rect)| rect.contains(position))
+            .map(|(index, _)| index)
+    }
+
+    fn touch(
+        &self,
+        state: &mut LayoutState,
+        event: &touch::Event,
+        bounds: Rectangle,
+    ) -> Option<canvas::Action<Message>> {
+        let origin = bounds.position() - Point::ORIGIN;
+        match *event {
+            touch::Event::FingerPressed { id, position } => {
+                if !bounds.contains(position) {
+                    return None;
+                }
+                state.fingers.insert(id, position - origin);
+                // a second finger starts a pinch instead
+                let now = Instant::now();
+                state.press = (state.fingers.len() == 1).then_some((position - origin, now));
+                Some(canvas::Action::request_redraw_at(now + LONG_PRESS).and_capture())
+            }
+            touch::Event::FingerMoved { id, position } => {
+                let position = position - origin;
+                let previous = state.fingers.insert(id, position)?;
+                if state
+                    .press
+                    .is_some_and(|(start, _)| start.distance(position) > TAP_SLOP)
+                {
+                    state.press = None;
+                }
+                let others: Vec<Point> = state
+                    .fingers
+                    .iter()
+                    .filter(|(finger, _)| **finger != id)
+                    .map(|(_, position)| *position)
+                    .collect();
+                match others.as_slice() {
+                    // a zoomed in layout pans along
+                    [] if state.press.is_none() && state.scale > 1.0 => {
+                        state.pinch(previous, position, 1.0);
+                    }
+                    [other] if previous.distance(*other) > 0.0 => {
+                        let middle =
+                            |p: Point, q: Point| Point::new((p.x + q.x) / 2.0, (p.y + q.y) / 2.0);
+                        state.pinch(
+                            middle(previous, *other),
+                            middle(position, *other),
+                            position.distance(*other) / previous.distance(*other),
+                        );
+                    }
+                    _ => return Some(canvas::Action::capture()),
+                }
+                self.caches.monitors_changed();
+                Some(canvas::Action::request_redraw().and_capture())
+            }
+            touch::Event::FingerLifted { id, .. } => {
+                state.fingers.remove(&id)?;
+                // long presses already opened the menu
+                let (start, _) = state.press.take()?;
+                self.monitor_at(bounds.size(), state, start)
+                    .map(|index| canvas::Action::publish((self.on_select)(index)).and_capture())
+            }
+            touch::Event::FingerLost { id, .. } => {
+                state.fingers.remove(&id);
+                state.press = None;
+                None
+            }
+        }
+    }
+
+    /// Opens the menu of the monitor under a finger held still long enough
+    fn long_press(&self, state: &mut LayoutState, bounds: Rectangle) -> Option<Message> {
+        let (start, at) = state.press?;
+        if at.elapsed() < LONG_PRESS {
+            return None;
+        }
+        state.press = None;
+        self.monitor_at(bounds.size(), state, start)
+            .map(self.on_menu)
+    }
+}
+
+impl<Message> canvas::Program<Message> for LayoutView<'_, Message> {
+    type State = LayoutState;
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: &Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> Option<canvas::Action<Message>> {
+        match event {
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                let now = cursor
+                    .position_in(bounds)
+                    .and_then(|position| self.monitor_at(bounds.size(), state, position));
+                // hovering only touches the uncached layer
+                (now != state.hovered).then(|| {
+                    state.hovered = now;
+                    canvas::Action::request_redraw()
+                })
+            }
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                state.modifiers = *modifiers;
+                None
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(button)) => {
+                let on_press = match (button, state.modifiers.control()) {
+                    (mouse::Button::Right, _) => self.on_menu,
+                    (mouse::Button::Left, true) => self.on_toggle,
+                    (mouse::Button::Left, false) => self.on_select,
+                    _ => return None,
+                };
+                let position = cursor.position_in(bounds)?;
+                self.monitor_at(bounds.size(), state, position)
+                    .map(|index| canvas::Action::publish(on_press(index)).and_capture())
+            }
+            Event::Touch(event) => self.touch(state, event, bounds),
+            Event::Window(window::Event::FileDropped(path)) => {
+                // the cursor isn't always tracked while dragging, where it
+                // was last seen is close enough then
+                let index = match cursor.position_in(bounds) {
+                    Some(position) => self.monitor_at(bounds.size(), state, position),
+                    None => state.hovered,
+                }?;
+                let on_drop = self.on_drop;
+                Some(canvas::Action::publish(on_drop(index, path.clone())).and_capture())
+            }
+            Event::Window(window::Event::RedrawRequested(_)) => self
+                .long_press(state, bounds)
+                .map(|message| canvas::Action::publish(message).and_capture()),
+            _ => None,
+        }
+    }
+
+    fn draw(
+        &self,
+        state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let palette = theme.palette();
+        let rects = self.zoomed_rects(bounds.size(), state);
+
+        let geometry = self.caches.geometry.draw(renderer, bounds.size(), |frame| {
+            for (_, rect) in &rects {
+                frame.fill_rectangle(rect.position(), rect.size(), Color::BLACK);
+                frame.stroke_rectangle(
+                    rect.position(),
+                    rect.size(),
+                    Stroke::default().with_width(1.0).with_color(palette.text),
+                );
+            }
+        });
+
+        let selection = self
+            .caches
+            .selection
+            .draw(renderer, bounds.size(), |frame| {
+                for (index, rect) in &rects {
+                    if let Some((_, handle)) = self.previews.iter().find(|(i, _)| i == index) {
+                        frame.draw_image(*rect, handle);
+                    }
+                    let monitor = self.monitors.iter().find(|m| m.index == *index);
+                    let details = rect.height >= DETAILS_MIN_HEIGHT;
+                    frame.fill_text(Text {
+                        content: match monitor {
+                            Some(monitor) if details => describe(monitor),
+                            _ => (index + 1).to_string(),
+                        },
+                        position: rect.position() + iced::Vector::new(6.0, 4.0),
+                        max_width: rect.width - 12.0,
+                        color: Color::WHITE,
+                        ..Text::default()
+                    });
+                    if let Some((_, name)) = self.captions.iter().find(|(i, _)| i == index)
+                        && details
+                    {
+                        let impact = self.impacts.iter().find(|(i, _)| i == index);
+                        frame.fill_text(Text {
+                            content: match impact {
+                                Some((_, impact)) => format!("{name} · {impact}"),
+                                None => name.clone(),
+                            },
+                            position: Point::new(rect.x + 6.0, rect.y + rect.height - 4.0),
+                            max_width: rect.width - 12.0,
+                            color: match impact {
+                                Some((_, Impact::High)) => Color::from_rgb(1.0, 0.6, 0.4),
+                                _ => Color::from_rgb(0.8, 0.8, 0.8),
+                            },
+                            size: 12.into(),
+                            align_y: iced::alignment::Vertical::Bottom,
+                            ..Text::default()
+                        });
+                    }
+                    if self.selected.is_empty() || self.selected.contains(index) {
+                        frame.stroke_rectangle(
+                            rect.position(),
+                            rect.size(),
+                            Stroke::default()
+                                .with_width(3.0)
+                                .with_color(palette.primary),
+                        );
+                    }
+                }
+            });
+
+        let mut hover = Frame::new(renderer, bounds.size());
+        if let Some((_, rect)) = rects
+            .iter()
+            .find(|(index, _)| Some(*index) == state.hovered)
+        {
+            hover.fill(
+                &Path::rectangle(rect.position(), rect.size()),
+                Color {
+                    a: 0.15,
+                    ..palette.text
+                },
+            );
+        }
+
+        vec![geometry, selection, hover.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        state: &Self::State,
+        _bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        match state.hovered {
+            Some(_) => mouse::Interaction::Pointer,
+            None => mouse::Interaction::default(),
+        }
+    }
+}
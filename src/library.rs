@@ -0,0 +1,82 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+use crate::xdg;
+
+pub mod pack;
+
+/// A wallpaper the user imported into the library
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    #[serde(with = "crate::os_path")]
+    pub path: PathBuf,
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Monitors the wallpaper was made for, empty means any
+    #[serde(default)]
+    pub monitors: Vec<i32>,
+    /// Name of the pack it came from
+    pub pack: Option<String>,
+}
+
+/// Index of everything in the library directory
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Library {
+    #[serde(default)]
+    pub entries: Vec<LibraryEntry>,
+}
+
+impl Library {
+    /// Where imported wallpapers are stored
+    pub fn dir() -> PathBuf {
+        xdg::data_dir().join("library")
+    }
+
+    pub fn index_path() -> PathBuf {
+        xdg::data_dir().join("library.toml")
+    }
+
+    /// Loads the index, a missing file is treated as an empty library
+    pub fn load() -> Result<Self> {
+        let path = Self::index_path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read library index at {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse library index at {}", path.display()))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::index_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        fs::write(&path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write library index to {}", path.display()))?;
+        debug!("Saved library index to {}", path.display());
+        Ok(())
+    }
+
+    /// Takes the entry of `path` out of the library
+    pub fn remove(&mut self, path: &Path) -> Option<LibraryEntry> {
+        let index = self.entries.iter().position(|e| e.path == path)?;
+        Some(self.entries.remove(index))
+    }
+
+    /// Adds `entry`, replacing any entry with the same path
+    pub fn add(&mut self, entry: LibraryEntry) {
+        self.entries.retain(|e| e.path != entry.path);
+        self.entries.push(entry);
+    }
+}
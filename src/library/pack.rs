@@ -0,0 +1,416 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tracing::{debug, warn};
+
+use super::{Library, LibraryEntry};
+use crate::{
+    dedupe::{self, Progress},
+    sources::local::WALLPAPER_EXTENSIONS,
+};
+
+/// Optional file in the root of a pack describing its contents
+pub const MANIFEST_NAME: &str = "manifest.toml";
+
+/// ```toml
+/// name = "Nature"
+/// tags = ["nature"]
+///
+/// [[wallpapers]]
+/// file = "forest.jpg"
+/// name = "Misty forest"
+/// tags = ["trees"]
+/// monitors = [0]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct Manifest {
+    pub name: Option<String>,
+    /// Added to every wallpaper of the pack
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Listing wallpapers is optional, without it every media file is imported
+    #[serde(default)]
+    pub wallpapers: Vec<ManifestWallpaper>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ManifestWallpaper {
+    /// Relative to the manifest
+    pub file: PathBuf,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub monitors: Vec<i32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportReport {
+    pub pack: String,
+    pub dir: PathBuf,
+    pub imported: usize,
+    /// Already in the library (or twice in the pack), not imported
+    pub duplicates: usize,
+}
+
+/// `nature.tar.gz` -> `nature`
+fn pack_name(archive: &Path) -> String {
+    let file_name = archive
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let stem = [
+        ".tar.gz", ".tar.xz", ".tar.zst", ".tar.bz2", ".tgz", ".tar", ".zip",
+    ]
+    .iter()
+    .find_map(|ext| file_name.strip_suffix(ext))
+    .unwrap_or(&file_name);
+
+    let name: String = stem
+        .chars()
+        .map(|c| if c == '/' || c.is_control() { '_' } else { c })
+        .collect();
+    match name.trim_start_matches('.') {
+        "" => "pack".to_owned(),
+        name => name.to_owned(),
+    }
+}
+
+fn is_wallpaper(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| WALLPAPER_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+fn find_wallpapers(dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let entry = entry?;
+        let path = entry.path();
+        // a pack's symlinks could point anywhere, they're left alone
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            continue;
+        }
+        if file_type.is_dir() {
+            find_wallpapers(&path, found)?;
+        } else if is_wallpaper(&path) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn extract(archive: &Path, dest: &Path) -> Result<()> {
+    let is_zip = archive
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+
+    // both refuse to write outside of `dest` (absolute paths and `..` members)
+    let output = if is_zip {
+        Command::new("unzip")
+            .arg("-q")
+            .arg(archive)
+            .arg("-d")
+            .arg(dest)
+            .output()
+            .with_context(|| "Failed to run unzip, is it installed?")?
+    } else {
+        Command::new("tar")
+            .arg("-xf")
+            .arg(archive)
+            .arg("-C")
+            .arg(dest)
+            .output()
+            .with_context(|| "Failed to run tar, is it installed?")?
+    };
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to extract {}: {}",
+            archive.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Archives often wrap everything in a single top level directory. One that's
+/// a symlink could point anywhere, the pack is refused then
+fn pack_root(dir: &Path) -> Result<PathBuf> {
+    let entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    let [only] = entries.as_slice() else {
+        return Ok(dir.to_path_buf());
+    };
+    let file_type = fs::symlink_metadata(only)?.file_type();
+    if file_type.is_symlink() {
+        return Err(anyhow!(
+            "The pack is only a symlink ({}), not importing it",
+            only.display()
+        ));
+    }
+    match file_type.is_dir() {
+        true => Ok(only.clone()),
+        false => Ok(dir.to_path_buf()),
+    }
+}
+
+fn entries_from_manifest(
+    root: &Path,
+    manifest: &Manifest,
+    pack: &str,
+) -> Result<Vec<LibraryEntry>> {
+    let root = root.canonicalize()?;
+    let mut entries = Vec::new();
+    for wallpaper in &manifest.wallpapers {
+        let path = match root.join(&wallpaper.file).canonicalize() {
+            // a manifest has no business pointing outside of its pack
+            Ok(path) if path.starts_with(&root) => path,
+            _ => {
+                warn!(
+                    "Skipping {} from the manifest, it's not in the pack",
+                    wallpaper.file.display()
+                );
+                continue;
+            }
+        };
+
+        entries.push(LibraryEntry {
+            name: wallpaper.name.clone().unwrap_or_else(|| {
+                path.file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            }),
+            tags: manifest
+                .tags
+                .iter()
+                .chain(&wallpaper.tags)
+                .cloned()
+                .collect(),
+            monitors: wallpaper.monitors.clone(),
+            pack: Some(pack.to_owned()),
+            path,
+        });
+    }
+    Ok(entries)
+}
+
+/// Drops entries whose file is already in `library`, or earlier in `entries`,
+/// and deletes their extracted copy. Only what's really in `pack_dir` is
+/// deleted
+fn remove_duplicates(
+    library: &Library,
+    entries: &mut Vec<LibraryEntry>,
+    pack_dir: &Path,
+    progress: impl Fn(Progress) + Sync,
+) -> usize {
+    let pack_dir = pack_dir.canonicalize().ok();
+    let known: Vec<PathBuf> = library
+        .entries
+        .iter()
+        .map(|entry| entry.path.clone())
+        .collect();
+    let paths: Vec<PathBuf> = known
+        .iter()
+        .cloned()
+        .chain(entries.iter().map(|entry| entry.path.clone()))
+        .collect();
+
+    let mut duplicates = 0;
+    for group in dedupe::find_duplicates(&paths, progress) {
+        // the first one is the oldest, keep that one
+        for path in group.iter().skip(1).filter(|path| !known.contains(path)) {
+            debug!(
+                "{} is a duplicate of {}",
+                path.display(),
+                group[0].display()
+            );
+            entries.retain(|entry| entry.path != *path);
+            duplicates += 1;
+            let extracted = path
+                .canonicalize()
+                .ok()
+                .zip(pack_dir.as_ref())
+                .is_some_and(|(path, pack_dir)| path.starts_with(pack_dir));
+            if !extracted {
+                warn!(
+                    "Not removing duplicate {}, it's not in the pack",
+                    path.display()
+                );
+                continue;
+            }
+            if let Err(err) = fs::remove_file(path) {
+                warn!("Failed to remove duplicate {}: {err}", path.display());
+            }
+        }
+    }
+    duplicates
+}
+
+/// Extracts `archive` into the library directory and registers its wallpapers,
+/// `progress` follows the duplicate check
+pub fn import(archive: &Path, progress: impl Fn(Progress) + Sync) -> Result<ImportReport> {
+    let library_dir = Library::dir();
+    fs::create_dir_all(&library_dir)
+        .with_context(|| format!("Failed to create {}", library_dir.display()))?;
+
+    let mut pack = pack_name(archive);
+    let mut dir = library_dir.join(&pack);
+    let mut suffix = 2;
+    while dir.exists() {
+        dir = library_dir.join(format!("{pack}-{suffix}"));
+        suffix += 1;
+    }
+
+    // extract somewhere hidden first so a failure doesn't leave half a pack behind
+    let staging = library_dir.join(format!(".importing-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&staging);
+    fs::create_dir_all(&staging)?;
+    let root = match extract(archive, &staging).and_then(|()| pack_root(&staging)) {
+        Ok(root) => root,
+        Err(err) => {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(err);
+        }
+    };
+    fs::rename(&root, &dir).with_context(|| format!("Failed to move pack to {}", dir.display()))?;
+    let _ = fs::remove_dir_all(&staging);
+    debug!("Extracted {} to {}", archive.display(), dir.display());
+
+    let manifest_path = dir.join(MANIFEST_NAME);
+    let manifest: Manifest = if manifest_path.exists() {
+        toml::from_str(&fs::read_to_string(&manifest_path)?)
+            .with_context(|| format!("Failed to parse {}", manifest_path.display()))?
+    } else {
+        Manifest::default()
+    };
+    if let Some(name) = &manifest.name {
+        pack = name.clone();
+    }
+
+    let mut entries = if manifest.wallpapers.is_empty() {
+        let mut found = Vec::new();
+        find_wallpapers(&dir, &mut found)?;
+        found.sort();
+        found
+            .into_iter()
+            .map(|path| LibraryEntry {
+                name: path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                tags: manifest.tags.clone(),
+                monitors: Vec::new(),
+                pack: Some(pack.clone()),
+                path,
+            })
+            .collect()
+    } else {
+        entries_from_manifest(&dir, &manifest, &pack)?
+    };
+
+    let mut library = Library::load()?;
+    let duplicates = remove_duplicates(&library, &mut entries, &dir, progress);
+    let imported = entries.len();
+    if imported == 0 {
+        // nothing new in it, don't leave an empty pack behind
+        let _ = fs::remove_dir_all(&dir);
+    }
+    for entry in entries {
+        library.add(entry);
+    }
+    library.save()?;
+
+    Ok(ImportReport {
+        pack,
+        dir,
+        imported,
+        duplicates,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn symlinked_roots_are_refused() {
+        let dir = std::env::temp_dir().join(format!("xab-gui-pack-root-{}", std::process::id()));
+        let outside = dir.join("outside");
+        let staging = dir.join("staging");
+        fs::create_dir_all(&outside).unwrap();
+        fs::create_dir_all(&staging).unwrap();
+        fs::write(outside.join("mine.png"), b"").unwrap();
+        symlink(&outside, staging.join("pack")).unwrap();
+        let symlinked = pack_root(&staging);
+
+        fs::remove_file(staging.join("pack")).unwrap();
+        fs::create_dir(staging.join("pack")).unwrap();
+        let wrapped = pack_root(&staging);
+        fs::write(staging.join("sea.png"), b"").unwrap();
+        let flat = pack_root(&staging);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(symlinked.is_err());
+        assert_eq!(wrapped.unwrap(), staging.join("pack"));
+        assert_eq!(flat.unwrap(), staging);
+    }
+
+    fn entry(path: PathBuf) -> LibraryEntry {
+        LibraryEntry {
+            name: String::new(),
+            tags: Vec::new(),
+            monitors: Vec::new(),
+            pack: None,
+            path,
+        }
+    }
+
+    #[test]
+    fn only_duplicates_in_the_pack_are_deleted() {
+        let dir = std::env::temp_dir().join(format!("xab-gui-pack-dupes-{}", std::process::id()));
+        let pack = dir.join("pack");
+        fs::create_dir_all(&pack).unwrap();
+        for path in [pack.join("a.png"), pack.join("b.png"), dir.join("mine.png")] {
+            fs::write(path, b"same").unwrap();
+        }
+
+        let mut entries = vec![
+            entry(pack.join("a.png")),
+            entry(pack.join("b.png")),
+            entry(pack.join("../mine.png")),
+        ];
+        let duplicates = remove_duplicates(&Library::default(), &mut entries, &pack, |_| {});
+        let left = (pack.join("b.png").exists(), dir.join("mine.png").exists());
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(duplicates, 2);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(left, (false, true));
+    }
+
+    #[test]
+    fn symlinks_are_left_out() {
+        let dir = std::env::temp_dir().join(format!("xab-gui-pack-{}", std::process::id()));
+        fs::create_dir_all(dir.join("forest")).unwrap();
+        fs::write(dir.join("sea.png"), b"").unwrap();
+        fs::write(dir.join("forest/pines.jpg"), b"").unwrap();
+        symlink(dir.join("forest"), dir.join("more")).unwrap();
+        symlink("/etc/hostname", dir.join("host.png")).unwrap();
+
+        let mut found = Vec::new();
+        let result = find_wallpapers(&dir, &mut found);
+        let _ = fs::remove_dir_all(&dir);
+        result.unwrap();
+        found.sort();
+        assert_eq!(found, [dir.join("forest/pines.jpg"), dir.join("sea.png")]);
+    }
+}
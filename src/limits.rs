@@ -0,0 +1,106 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{
+    num::NonZeroUsize,
+    sync::{Condvar, Mutex, MutexGuard},
+    thread,
+};
+
+/// How much heavy work runs at once, unset ones follow the cpu count
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Limits {
+    /// Threads hashing files while looking for duplicates
+    #[serde(default)]
+    pub workers: Option<usize>,
+    /// Wallpapers fetched at once while syncing a source
+    #[serde(default)]
+    pub downloads: Option<usize>,
+    /// Images decoded at once for previews, thumbnails, spanning and such
+    #[serde(default)]
+    pub decodes: Option<usize>,
+}
+
+fn cpus() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+impl Limits {
+    pub fn validate(&self) -> Result<()> {
+        if [self.workers, self.downloads, self.decodes].contains(&Some(0)) {
+            return Err(anyhow!("Limits can't be 0, leave them out instead"));
+        }
+        Ok(())
+    }
+
+    /// Hashing is mostly waiting on the disk, more than 4 threads just seek around
+    pub fn workers(&self) -> usize {
+        self.workers.unwrap_or_else(|| cpus().min(4))
+    }
+
+    pub fn downloads(&self) -> usize {
+        self.downloads.unwrap_or_else(|| cpus().clamp(2, 4))
+    }
+
+    /// Half the cpus, decoding a big image eats a core and plenty of memory
+    pub fn decodes(&self) -> usize {
+        self.decodes.unwrap_or_else(|| (cpus() / 2).max(1))
+    }
+}
+
+static CURRENT: Mutex<Limits> = Mutex::new(Limits {
+    workers: None,
+    downloads: None,
+    decodes: None,
+});
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Whatever the config says, set when it's loaded or saved
+pub fn set(limits: Limits) {
+    *lock(&CURRENT) = limits;
+    // a raised limit lets waiting decodes in right away
+    let _running = lock(&DECODES.running);
+    DECODES.freed.notify_all();
+}
+
+pub fn current() -> Limits {
+    *lock(&CURRENT)
+}
+
+struct Gate {
+    running: Mutex<usize>,
+    freed: Condvar,
+}
+
+static DECODES: Gate = Gate {
+    running: Mutex::new(0),
+    freed: Condvar::new(),
+};
+
+/// Lets the next decode in when dropped
+pub struct Permit(());
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        *lock(&DECODES.running) -= 1;
+        DECODES.freed.notify_one();
+    }
+}
+
+/// Blocks until fewer than `Limits::decodes` images are being decoded, hold
+/// on to it while decoding
+pub fn decode_permit() -> Permit {
+    let mut running = lock(&DECODES.running);
+    while *running >= current().decodes() {
+        running = DECODES
+            .freed
+            .wait(running)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+    *running += 1;
+    Permit(())
+}
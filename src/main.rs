@@ -1,63 +1,836 @@
 use anyhow::Result;
+use clap::Parser;
+use iced::futures::channel::mpsc;
 use iced::{
-    ContentFit, Length, Subscription, Task,
-    widget::{Column, Row, button, column, horizontal_rule, image, row, text},
+    Color, ContentFit, Element, Length, Size, Subscription, Task, task, touch,
+    widget::{
+        Button, Column, Row, button, canvas, center, checkbox, column, container, grid, image,
+        mouse_area, opaque, operation, pick_list, row, rule, scrollable, slider, stack, text,
+        text_input,
+    },
     window,
 };
 use iced_aw::card;
 use rfd::FileDialog;
-use std::{path::PathBuf, sync::Arc};
-use tracing::{debug, error};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, error, warn};
 use tracing_subscriber::{filter::EnvFilter, fmt::Subscriber};
 
+pub mod active_color;
+pub mod automation;
+pub mod browser;
+pub mod cli;
+pub mod compare;
+pub mod config;
+pub mod coordinator;
+pub mod crop;
+pub mod dedupe;
+pub mod desktop;
+pub mod disk_cache;
+pub mod energy;
+pub mod gate;
+pub mod hooks;
+pub mod hotkeys;
+pub mod instance;
 pub mod ipc;
+pub mod ipc_outbox;
 pub mod ipc_spec;
+pub mod kinetic;
+pub mod layout;
+pub mod library;
+pub mod limits;
+pub mod media;
+pub mod net;
+pub mod nightlight;
+pub mod notify;
+pub mod os_path;
+pub mod overrides;
+pub mod panels;
+pub mod persist;
+pub mod playlists;
+pub mod presentation;
+pub mod preview;
+pub mod provenance;
+pub mod rules;
+pub mod scheduler;
+pub mod scripts;
+pub mod session;
+pub mod shared;
+pub mod slideshow;
+pub mod snapshot;
+pub mod sources;
+pub mod span;
+pub mod state;
+pub mod status;
+pub mod templates;
+pub mod thumbnails;
+pub mod timeline;
+pub mod trash;
+pub mod tunnel;
+pub mod updates;
+pub mod xdg;
 
+use active_color::Debouncer;
+use automation::{PauseFor, TimeWindow};
+use browser::Browser;
+use chrono::{DateTime, Local, Timelike};
+use cli::{Cli, Command};
+use compare::Comparison;
+use config::{AppTheme, Config, StartPage};
+use coordinator::{Automation, Claims};
+use crop::{CropEditor, CropRect, CropView};
+use disk_cache::CleanupReport;
+use energy::{Impact, VideoInfo};
+use gate::Needs;
+use hotkeys::{HotkeyAction, HotkeyBindings, Hotkeys, MediaKeysOwner};
+use instance::Activation;
 use ipc::*;
 use ipc_spec::*;
+use kinetic::Kinetic;
+use layout::{LayoutCaches, LayoutView};
+use limits::Limits;
+use nightlight::NightDimming;
+use notify::NotificationMode;
+use overrides::{OverrideKind, Overrides};
+use panels::{Panel, Panels};
+use playlists::Playlists;
+use presentation::Presentation;
+use preview::{PreviewCache, PreviewKey, Thumbnail};
+use provenance::Provenance;
+use rules::{Facts, Rule, RuleAction, RuleEvent};
+use scheduler::{RotationOrder, Scheduler};
+use scripts::{Outcome, Script, ScriptAction, ScriptEvent};
+use session::SessionKind;
+use shared::Shared;
+use slideshow::{SlideInterval, Slideshow, Transition};
+use snapshot::Snapshot;
+use sources::{
+    Location, Registry, Wallpaper, WallpaperSource,
+    channel::Channel,
+    filter::{ContentFilter, ContentFilters},
+    health::{Scan, SourceHealth},
+    playlist::PlaylistSource,
+    sync::{SourceSync, SyncReport},
+};
+use state::Pins;
+use status::{Status, StatusPalette};
+use templates::{HexColor, TemplateFile};
+use timeline::{Horizon, Inputs, Plan, TemplateSchedule};
+use trash::Trashed;
+use tunnel::Tunnel;
 
-#[derive(Default, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 enum Page {
     #[default]
     Connect,
     Connecting,
     Connected,
+    /// xab shut down and we're waiting for it to come back
+    WaitingForXab,
+    Settings,
+    Scripts,
+    Templates,
+    /// Thumbnails of everything in the library directories
+    Library,
+    /// Where each monitor's part of the selected file is picked
+    Crop,
 }
 
 #[derive(Debug, Clone)]
 enum Message {
+    WindowOpened(window::Id),
+    WindowClosed(window::Id),
+    /// Hung up on xab and wrote the saved state, see `App::exit`
+    ReadyToExit(Result<(), String>),
+    Activated(Activation),
+    RefreshSockets,
+    SelectEndpoint(Endpoint),
+    /// Gives up on the connection being made
+    CancelConnect,
+    TunnelOpened(Arc<Tunnel>),
+    TunnelFailed(String),
     Connect,
     Connected(Arc<IpcHandle>),
     ConnectionFailed(String),
-    SelectMonitor, // TODO save monitors in context and select from them with index or smh
+    TogglePin,
+    PinSaved(Result<(), String>),
+    MonitorsChanged(Vec<Monitor>),
+    /// Some of the monitors, xab is still sending the rest
+    MonitorsArriving(Vec<Monitor>),
+    DaemonEvent(IpcEvent),
+    ShutdownTick,
+    EditRestoreWhenBack(bool),
+    Reconnect,
+    ReconnectFailed(String),
+    StopWaiting,
+    WallpapersRestored(Result<(), String>),
+    FormatsProbed(Result<(), String>),
+    SelectMonitor(i32),
+    ToggleMonitor(i32),
+    /// Right click or long press on a monitor of the layout
+    OpenMonitorMenu(i32),
+    CloseMonitorMenu,
+    Touch(touch::Event),
+    WallpapersScrolled(scrollable::Viewport),
+    /// Frame of a flicked wallpaper list coasting
+    WallpapersFrame(Instant),
+    RefreshMonitors,
+    RefreshCapabilities,
+    CapabilitiesRefreshed(Result<bool, String>),
     SelectFileForBackground,
     SelectedFileForBackground(Option<PathBuf>),
+    /// Dropped from a file manager, anywhere but on a monitor
+    FileDropped(window::Id, PathBuf),
+    /// Applied to that monitor right away
+    DroppedOnMonitor(i32, PathBuf),
+    SelectScaling(ScalingMode),
+    ToggleVideoOptions,
+    EditVideo(VideoOptions),
+    PreviewRendered(PreviewKey, Result<image::Handle, String>),
+    ThumbnailRendered(PathBuf, Result<Thumbnail, String>),
+    CopyCredit,
     SendBackgroundOptions,
+    /// Takes the wallpaper off the selected monitors
+    ClearBackground,
+    SpanBackground,
+    OpenCropEditor,
+    CropLoaded(PathBuf, Result<(image::Handle, (u32, u32)), String>),
+    CropSelectMonitor(i32),
+    CropChanged(CropRect),
+    EditCropX(String),
+    EditCropY(String),
+    EditCropWidth(String),
+    CenterCrop,
+    CopyCropToSameResolution,
+    ApplyCrops,
+    BackgroundApplied(Vec<i32>, PathBuf),
+    BackgroundSent,
+    BackgroundSendFailed(String),
+    /// Pause, resume (`Some(false)`) or toggle the selected monitors' videos
+    VideoPlayback(Option<bool>),
+    VideoPlaybackSent(Vec<i32>, Option<bool>, Result<(), String>),
+    SelectSource(SourceChoice),
+    SourceListed(String, Result<Vec<Wallpaper>, String>),
+    /// None shows every wallpaper of the source
+    SelectSourceTag(Option<String>),
+    FollowChannel,
+    SelectedChannel(Option<PathBuf>),
+    UnfollowChannel(PathBuf),
+    ScanSources,
+    SourceScanned(String, Result<usize, String>),
+    RetrySource(String),
+    SelectSourceWallpaper(Wallpaper),
+    DeleteWallpaper(PathBuf),
+    WallpaperDeleted(Result<Trashed, String>),
+    RestoreTrashed(Trashed),
+    TrashedRestored(Result<Trashed, String>),
+    StartPurge(Trashed),
+    EditPurgeConfirm(String),
+    CancelPurge,
+    ConfirmPurge,
+    Purged(Result<Trashed, String>),
+    ApplySourceWallpaper(Wallpaper),
+    SelectPlaylist(String),
+    EditPlaylistName(String),
+    CreatePlaylist,
+    DeletePlaylist,
+    /// Adds the selected file to the selected playlist
+    AddToPlaylist,
+    RemoveFromPlaylist(usize),
+    ShiftInPlaylist(usize, bool),
+    /// Applies the next (or previous) file of the selected playlist
+    StepPlaylist(bool),
+    FetchedForApply(Result<PathBuf, String>),
+    HotkeyPressed(u32),
+    OpenSettings,
+    CloseSettings,
+    /// After the user fixed the config that failed to load
+    ReloadConfig,
+    /// Backs up the config that failed to load and starts over
+    ResetConfig,
+    EditHotkey(HotkeyAction, String),
+    SelectMediaKeysOwner(MediaKeysOwner),
+    SelectNotificationMode(NotificationMode),
+    CheckMediaKeys,
+    MediaPlayerRunning(bool),
+    EditQuietStart(String),
+    EditQuietEnd(String),
+    PauseAutomation(PauseFor),
+    EditRotationInterval(String),
+    SelectRotationOrder(RotationOrder),
+    EditRotationPerMonitor(bool),
+    /// Rotates through the selected source (and tag)
+    StartRotation,
+    StopRotation,
+    /// Queued wallpapers first, then the rotation's
+    Rotate,
+    /// Plays a wallpaper of the selected source next, see `App::queue`
+    QueueWallpaper(Wallpaper),
+    UnqueueWallpaper(usize),
+    ClearQueue,
+    RotationFetched(Option<i32>, Result<PathBuf, String>),
+    ResumeAutomation,
+    TogglePresentation,
+    PresentationStarted(Result<Presentation, String>),
+    PresentationEnded(Result<(), String>),
+    EditPresentationNeutral(bool),
+    EditCloseToBackground(bool),
+    ShowPanel(Panel, bool),
+    /// Up if true
+    ShiftPanel(Panel, bool),
+    ToggleSnapshot,
+    EditActiveWindowColor(bool),
+    EditNightDimming(bool),
+    EditNightStart(String),
+    EditNightEnd(String),
+    CheckNightLight,
+    NightLightChecked(bool),
+    SampleActiveWindow,
+    ActiveWindowSampled(Result<Option<[u8; 3]>, String>),
+    ActiveColorRendered(Result<PathBuf, String>),
+    SnapshotTaken(Result<Snapshot, String>),
+    SnapshotRestored(Result<(), String>),
+    EditRule(usize, String),
+    AddRule,
+    RemoveRule(usize),
+    CheckBattery,
+    BatteryChecked(Option<u8>),
+    PowerChecked(bool),
+    RefreshLiveState,
+    LiveStateReceived(Result<Vec<BackgroundState>, String>),
+    EnergyEstimated(PathBuf, Result<VideoInfo, String>),
+    EditSocket(String),
+    /// Connects to `socket_input` from now on
+    UseSocket,
+    ResetSocket,
+    SelectTheme(AppTheme),
+    SelectStartPage(StartPage),
+    SelectPalette(StatusPalette),
+    EditDefaultMonitor(String),
+    EditClearColor(String),
+    EditConnectTimeout(String),
+    EditHandshakeTimeout(String),
+    EditWorkers(String),
+    EditDownloads(String),
+    EditDecodes(String),
+    EditCacheBudget(String),
+    EditDiskCacheLimit(String),
+    EditBandwidth(String),
+    EditSyncOnMetered(bool),
+    EditProxy(String),
+    ToggleOffline(bool),
+    EditCheckUpdates(bool),
+    CheckUpdates,
+    UpdateChecked(Result<Option<updates::Release>, String>),
+    OpenRelease,
+    DismissUpdate,
+    EditCaBundle(String),
+    ToggleSfwOnly(String, bool),
+    EditBlocked(String, String),
+    /// The background job, evicts down to the configured size
+    CleanupCaches,
+    CachesCleaned(Result<CleanupReport, String>),
+    Reclaimable(Result<u64, String>),
+    ClearCaches,
+    CachesCleared(Result<CleanupReport, String>),
+    AddStartupWallpaper,
+    RemoveStartupWallpaper(String),
+    SaveSettings,
+    OpenScripts,
+    OpenLibrary,
+    LibraryScanned(Result<Vec<PathBuf>, String>),
+    RescanLibrary,
+    ShowMoreLibrary,
+    AddLibraryDir,
+    SelectedLibraryDir(Option<PathBuf>),
+    RemoveLibraryDir(PathBuf),
+    /// Applies to the selected monitors
+    ApplyFromLibrary(PathBuf),
+    ApplyRecent(PathBuf),
+    ReloadScripts,
+    NewScript,
+    ToggleScript(String, bool),
+    RunScript(String),
+    ScriptTick,
+    ScriptFinished(Outcome),
+    OpenTemplates,
+    ReloadTemplates,
+    NewTemplate,
+    ToggleTemplate(String, bool),
+    RenderTemplate(String),
+    TemplateTick,
+    TemplateRendered(String, Option<i32>, Result<PathBuf, String>),
+    ImportPack,
+    SelectedPack(Option<PathBuf>),
+    PackImportProgress(dedupe::Progress),
+    PackImported(Result<String, String>),
+    InstallDesktopEntry,
+    DesktopEntryInstalled(Result<PathBuf, String>),
+    DetachPreview,
+    TogglePreviewFullscreen,
+    SelectSlideInterval(SlideInterval),
+    SelectTransition(Transition),
+    StartSlideshow,
+    SlideshowNext,
+    SlideshowFetched(window::Id, Result<PathBuf, String>),
+    SlideshowFrame,
+    StartComparison,
+    ShuffleComparison,
+    ComparisonFetched(window::Id, Wallpaper, Result<PathBuf, String>),
+    TryCandidates,
+    TryNextCandidate,
+    StopTrying,
+    PickCandidate(usize),
+    SyncSources,
+    SourcesSynced(Result<Vec<(String, SyncReport)>, String>),
+    RestartXab,
+    /// Opens the confirmation dialog
+    AskShutdownXab,
+    CancelShutdownXab,
+    ExplainCapabilities(IpcXabCapabilities),
+    OpenSchedule,
+    SelectScheduleHorizon(Horizon),
+    CloseSchedule,
+    CopyCapabilities,
+    CloseCapabilities,
+    ShutdownXab,
+    DaemonControlSent(IpcCommands, Result<(), String>),
     Disconnect,
     Disconnected,
 }
 
+/// xab announced it's going away
+#[derive(Debug)]
+struct ShutdownNotice {
+    deadline: Instant,
+    /// Reconnected to once xab is back
+    path: String,
+}
+
 #[derive(Default)]
 struct BackgroundOpts {
     path: PathBuf,
-    // when I'll add support for assiging a singe bakcground to multiple monitors ill have to
-    // change this to a vec or smh
-    monitor: Option<i8>, // if u have more than 128 monitors hit me up
+    /// Every monitor if empty
+    monitors: HashSet<i32>,
+    scaling: ScalingMode,
+    video: VideoOptions,
+}
+
+impl BackgroundOpts {
+    /// The selected monitors in order, empty for every monitor
+    fn monitor_list(&self) -> Vec<i32> {
+        let mut monitors: Vec<i32> = self.monitors.iter().copied().collect();
+        monitors.sort_unstable();
+        monitors
+    }
+
+    /// What pins and saved state are keyed by, `None` being every monitor
+    fn targets(&self) -> Vec<Option<i32>> {
+        match self.monitors.is_empty() {
+            true => vec![None],
+            false => self.monitor_list().into_iter().map(Some).collect(),
+        }
+    }
+}
+
+/// Entry of the source picker
+#[derive(Debug, Clone, PartialEq)]
+struct SourceChoice {
+    id: String,
+    name: String,
+}
+
+impl std::fmt::Display for SourceChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
 }
 
 #[derive(Default)]
 struct App {
+    main_window: Option<window::Id>,
     page: Page,
     user_error: Option<String>,
+    user_notice: Option<String>,
     ipc_handle: Option<Arc<IpcHandle>>,
+    /// Shown until the whole list is in, when there wasn't one before
+    arriving_monitors: Vec<Monitor>,
     background_opts: BackgroundOpts,
+    sources: Registry,
+    selected_source: Option<SourceChoice>,
+    source_wallpapers: Vec<Wallpaper>,
+    /// Only wallpapers with this tag are listed and picked from
+    source_tag: Option<String>,
+    browser: Browser,
+    /// From `SavedState::recent`, for the selected monitor
+    recent: Vec<PathBuf>,
+    /// File names of the saved wallpapers by monitor index, for the layout
+    assigned: Vec<(i32, PathBuf)>,
+    /// What xab says the monitors show, see `IpcHandle::get_background_state`
+    live: Vec<BackgroundState>,
+    /// Video wallpapers that were looked at for `energy::Impact`
+    energy: HashMap<PathBuf, Result<VideoInfo, String>>,
+    /// Running on battery, see `energy::discharging`
+    discharging: bool,
+    recent_thumbnails: HashMap<PathBuf, Option<Result<image::Handle, String>>>,
+    /// Keyed by source id
+    source_scans: HashMap<String, Scan>,
+    source_sync: BTreeMap<String, SourceSync>,
+    /// Newest last
+    trashed: Vec<Trashed>,
+    playlists: Playlists,
+    selected_playlist: Option<String>,
+    playlist_name_input: String,
+    /// Where `StepPlaylist` is in the selected playlist
+    playlist_at: Option<usize>,
+    /// Permanent deletion waiting for the name to be typed
+    purge_confirm: Option<(Trashed, String)>,
+    syncing_sources: bool,
+    slide_interval: SlideInterval,
+    transition: Transition,
+    slideshow: Option<Slideshow>,
+    comparison: Option<Comparison>,
+    /// Always on top window showing the selected background
+    preview_window: Option<window::Id>,
+    preview_fullscreen: bool,
+    config: Config,
+    /// `None` if global hotkeys aren't available (e.g. no X11)
+    hotkeys: Option<Hotkeys>,
+    /// Being edited on the settings page
+    hotkey_inputs: HotkeyBindings,
+    media_keys_input: MediaKeysOwner,
+    /// Where to go back to from the settings, scripts and templates pages
+    settings_return: Page,
+    scripts: Vec<Script>,
+    templates: Vec<TemplateFile>,
+    /// When each template was last rendered, see `TemplateFile::is_due`
+    template_renders: HashMap<String, DateTime<Local>>,
+    /// A file was opened from outside, waiting for the user to pick a monitor
+    choosing_monitor: bool,
+    session: SessionKind,
+    /// xab sockets found on the connect page (one per display) and ssh profiles
+    endpoints: Vec<Endpoint>,
+    selected_endpoint: Option<Endpoint>,
+    /// Kept open for as long as we're connected through it
+    tunnel: Option<Arc<Tunnel>>,
+    /// Aborts the connection being made when dropped
+    connecting: Option<task::Handle>,
+    /// Copy of the saved pins, automatic changes check them before applying
+    pins: Pins,
+    /// Which automation each monitor is with, see `Config::precedence`
+    claims: Claims,
+    quiet_start_input: String,
+    quiet_end_input: String,
+    socket_input: String,
+    /// Minutes
+    rotation_interval_input: String,
+    scheduler: Option<Scheduler>,
+    /// Wallpapers to play next and the sources they're from, drained by
+    /// `Message::Rotate` before the rotation carries on
+    queue: VecDeque<(String, Wallpaper)>,
+    theme_input: AppTheme,
+    start_page_input: StartPage,
+    /// Mirrors `SavedState::last_page`
+    last_page: StartPage,
+    palette_input: StatusPalette,
+    /// Monitor number, empty for every monitor
+    default_monitor_input: String,
+    clear_color_input: String,
+    /// Seconds, empty for the default
+    connect_timeout_input: String,
+    handshake_timeout_input: String,
+    workers_input: String,
+    downloads_input: String,
+    decodes_input: String,
+    cache_mb_input: String,
+    disk_cache_mb_input: String,
+    /// KB/s, empty for no limit
+    bandwidth_input: String,
+    sync_on_metered_input: bool,
+    check_updates_input: bool,
+    /// Empty for the environment's
+    proxy_input: String,
+    ca_bundle_input: String,
+    /// Safe for work only and the blocked tags, by source id
+    filter_inputs: BTreeMap<String, (bool, String)>,
+    /// Bytes "Clear caches" would free, None until it's been looked at
+    reclaimable: Option<u64>,
+    wallpapers_input: BTreeMap<String, PathBuf>,
+    automation_paused_until: Option<DateTime<Local>>,
+    /// Only what we toggled, xab can't be asked yet
+    videos_paused: bool,
+    /// Paused one by one, while the rest keep playing
+    paused_monitors: HashSet<i32>,
+    /// Temporary wallpapers of the modes below, see `overrides`
+    overrides: Overrides,
+    presentation: Option<Presentation>,
+    presentation_neutral_input: bool,
+    close_to_background_input: bool,
+    panels_input: Panels,
+    /// Video wallpapers currently frozen for screen sharing
+    snapshot: Option<Snapshot>,
+    active_color: Debouncer,
+    active_color_input: bool,
+    /// Whether the dimmed wallpapers are on screen
+    night_active: bool,
+    night_dimming_input: bool,
+    night_start_input: String,
+    night_end_input: String,
+    notifications_input: NotificationMode,
+    rule_inputs: Vec<String>,
+    /// Percent, for rules
+    battery: Option<u8>,
+    previews: PreviewCache,
+    layout: LayoutCaches,
+    /// Of the selected file, None inside while it's decoded
+    thumbnail: Option<(PathBuf, Option<Result<Thumbnail, String>>)>,
+    /// Of the selected file, for files a source downloaded
+    credit: Option<Provenance>,
+    /// A newer release, until it's dismissed
+    update: Option<updates::Release>,
+    crop: Option<CropEditor>,
+    /// Monitor whose menu is open under the layout
+    monitor_menu: Option<i32>,
+    wallpapers_kinetic: Kinetic,
+    /// Of the last press of Apply (or Span)
+    apply_status: Option<Status>,
+    /// What subscriptions can see of the above
+    shared: Shared,
+    shutdown: Option<ShutdownNotice>,
+    restore_when_back: bool,
+    /// The shutdown confirmation dialog is open
+    confirm_shutdown: bool,
+    /// What the control the user asked about needs, see
+    /// `build_capabilities_dialog`
+    explain_missing: Option<IpcXabCapabilities>,
+    /// The automation's plan, shown over everything while it's open
+    schedule: Option<(Horizon, Plan)>,
+    video_options_open: bool,
 }
 
-// TODO: closed events - https://docs.rs/iced/latest/iced/window/fn.close_events.html
+/// Id of the wallpaper list, scrolled along after a flick
+const WALLPAPER_LIST: &str = "wallpaper-list";
+
+/// How often to look for xab while it's restarting
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a restart we asked for is given before we stop talking to xab,
+/// for daemons that don't announce it
+const RESTART_GRACE: Duration = Duration::from_secs(1);
+
+/// How often to ask xab what it can do, it may have been upgraded in place
+const CAPABILITY_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often to check if a media player appeared (or went away)
+const MEDIA_PLAYER_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often to ask xab what the monitors show, another client might've
+/// changed them
+const LIVE_STATE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often to check if an online source is due for a sync
+const SOURCE_SYNC_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
 
 impl App {
+    fn open_main_window() -> (window::Id, Task<window::Id>) {
+        window::open(window::Settings {
+            platform_specific: window::settings::PlatformSpecific {
+                // matches the desktop entry
+                application_id: "xab-gui".to_owned(),
+                ..Default::default()
+            },
+            ..window::Settings::default()
+        })
+    }
+
+    fn new(activation: Activation) -> (Self, Task<Message>) {
+        let (main_window, open) = Self::open_main_window();
+
+        let config = Config::load_or_default();
+        limits::set(config.limits);
+        net::set_bandwidth(config.bandwidth);
+        net::set_proxies(config.proxies.clone());
+        net::set_offline(config.offline);
+        sources::filter::set(config.content_filters.clone());
+        let session = SessionKind::detect();
+        debug!("Session: {}", session);
+        // grabs don't work on the wayland desktop, only on xwayland windows
+        let hotkeys = match session.has_x11_desktop().then(Hotkeys::new) {
+            None => None,
+            Some(Ok(mut hotkeys)) => {
+                if let Err(err) = hotkeys.apply(&config.hotkeys) {
+                    error!("Failed to register hotkeys: {:?}", err);
+                }
+                Some(hotkeys)
+            }
+            Some(Err(err)) => {
+                error!("{:?}", err);
+                None
+            }
+        };
+
+        let state = persist::state().unwrap_or_else(|err| {
+            error!("Failed to load saved state: {:?}", err);
+            Default::default()
+        });
+
+        let background_opts = BackgroundOpts {
+            monitors: config.default_monitor.into_iter().collect(),
+            ..BackgroundOpts::default()
+        };
+        let playlists = Playlists::load_or_default();
+        let mut sources = Registry::with_channels(&config.channels);
+        for name in playlists.names() {
+            sources.register(PlaylistSource::new(&name));
+        }
+        let mut app = Self {
+            background_opts,
+            sources,
+            playlists,
+            pins: state.pinned,
+            last_page: state.last_page,
+            source_sync: state.source_sync,
+            // the trash might've been emptied in the meantime
+            trashed: state.trashed.into_iter().filter(Trashed::exists).collect(),
+            scripts: scripts::load_all(),
+            templates: templates::load_all(),
+            socket_input: config.socket.clone().unwrap_or_default(),
+            rotation_interval_input: config.rotation.interval_mins.to_string(),
+            hotkey_inputs: config.hotkeys.clone(),
+            media_keys_input: config.media_keys,
+            config,
+            hotkeys,
+            session,
+            main_window: Some(main_window),
+            ..Self::default()
+        };
+        app.refresh_endpoints();
+        app.previews.set_budget(app.config.cache_budget());
+        app.share();
+        if app.config.load_error.is_some() {
+            app.user_error = Some(
+                "The config failed to load, settings won't be saved until it's fixed or reset"
+                    .to_owned(),
+            );
+        }
+        let media_keys = app.sync_media_keys();
+        (
+            app,
+            Task::batch([
+                open.map(Message::WindowOpened),
+                media_keys,
+                Task::done(Message::Activated(activation)),
+                Task::done(Message::ScanSources),
+                // catch up on syncs that were due while we weren't running
+                Task::done(Message::SyncSources),
+                Task::done(Message::CheckBattery),
+                Task::done(Message::CheckUpdates),
+            ]),
+        )
+    }
+
     fn update(&mut self, message: Message) -> Task<Message> {
+        let task = self.handle(message);
+        self.share();
+        task
+    }
+
+    /// Publishes what subscriptions need to know
+    fn share(&self) {
+        let mut shared = self.shared.write();
+        shared.ipc = self.ipc_handle.clone();
+        shared.pins = self.pins.clone();
+        shared.quiet_hours = self.config.quiet_hours;
+        shared.automation_paused_until = self.automation_paused_until;
+        shared.presenting = self.presentation.is_some();
+    }
+
+    fn handle(&mut self, message: Message) -> Task<Message> {
         match message {
+            Message::WindowOpened(id) => {
+                debug!("Window {:?} opened", id);
+                Task::none()
+            }
+            Message::Activated(activation) => {
+                debug!("Activated: {:?}", activation);
+                let focus = match self.main_window {
+                    Some(id) => window::gain_focus(id),
+                    // closed, but kept running in the background
+                    None => {
+                        let (id, open) = Self::open_main_window();
+                        self.main_window = Some(id);
+                        open.map(Message::WindowOpened)
+                    }
+                };
+                let Some(file) = activation.files.first() else {
+                    return focus;
+                };
+                if activation.files.len() > 1 {
+                    warn!("Only one file can be selected, ignoring the rest");
+                }
+                self.background_opts.path = file.clone();
+                self.choosing_monitor = true;
+
+                match (&self.ipc_handle, self.page) {
+                    (Some(_), _) => {
+                        self.set_page(Page::Connected);
+                        self.notify_choose_monitor();
+                        Task::batch([focus, self.render_previews()])
+                    }
+                    (None, Page::Connecting) => focus,
+                    (None, _) => Task::batch([focus, self.update(Message::Connect)]),
+                }
+            }
+            Message::RefreshSockets => {
+                self.refresh_endpoints();
+                Task::none()
+            }
+            Message::SelectEndpoint(endpoint) => {
+                self.selected_endpoint = Some(endpoint);
+                Task::none()
+            }
+            Message::ReadyToExit(res) => {
+                if let Err(err) = res {
+                    error!("Failed to exit cleanly: {}", err);
+                }
+                iced::exit()
+            }
+            Message::WindowClosed(id) => {
+                if self.main_window == Some(id) {
+                    self.main_window = None;
+                    if self.config.close_to_background {
+                        debug!("Main window closed, running in the background");
+                        return Task::none();
+                    }
+                    debug!("Main window closed, exiting");
+                    return self.exit();
+                }
+                if self.slideshow.as_ref().is_some_and(|s| s.window == id) {
+                    debug!("Slideshow closed");
+                    self.slideshow = None;
+                }
+                if self.comparison.as_ref().is_some_and(|c| c.window == id) {
+                    debug!("Comparison closed");
+                    // nothing was picked, put back whatever was there
+                    let restore = self.stop_trying();
+                    self.comparison = None;
+                    return restore;
+                }
+                if self.preview_window == Some(id) {
+                    debug!("Detached preview closed");
+                    self.preview_window = None;
+                }
+                Task::none()
+            }
             Message::Connect => match self.ipc_handle {
                 Some(_) => {
                     error!("Can't create a new connection, already connected!");
@@ -65,31 +838,406 @@ impl App {
                 }
                 None => {
                     self.set_page(Page::Connecting);
-                    Task::perform(IpcHandle::new(IPC_PATH), |res| match res {
-                        Ok(ipc_handle) => Message::Connected(Arc::new(ipc_handle)),
-                        Err(err) => Message::ConnectionFailed(format!("{:?}", err)),
-                    })
+                    let timeouts = self.config.timeouts();
+                    let connect = match &self.selected_endpoint {
+                        Some(Endpoint::Ssh(profile)) => {
+                            let profile = profile.clone();
+                            // ssh is waited on, so off the gui thread. Canceled,
+                            // the tunnel is closed once it's up
+                            Task::perform(
+                                async move {
+                                    tokio::task::spawn_blocking(move || Tunnel::open(&profile))
+                                        .await?
+                                },
+                                |res| match res {
+                                    Ok(tunnel) => Message::TunnelOpened(Arc::new(tunnel)),
+                                    Err(err) => Message::TunnelFailed(ipc::describe_error(&err)),
+                                },
+                            )
+                        }
+                        Some(Endpoint::Local(socket)) => {
+                            Self::connect_ipc(socket.path.clone(), timeouts)
+                        }
+                        Some(Endpoint::Custom(path)) => Self::connect_ipc(path.clone(), timeouts),
+                        None => Self::connect_ipc(
+                            self.config
+                                .socket
+                                .clone()
+                                .unwrap_or_else(|| IPC_PATH.to_owned()),
+                            timeouts,
+                        ),
+                    };
+                    self.cancelable_connect(connect)
                 }
             },
+            Message::CancelConnect => {
+                debug!("Connecting canceled");
+                // dropping the handle aborts the task
+                self.connecting = None;
+                self.tunnel = None;
+                self.set_page(Page::Connect);
+                Task::none()
+            }
+            Message::TunnelOpened(tunnel) => {
+                let path = tunnel.local_socket.to_string_lossy().into_owned();
+                self.tunnel = Some(tunnel);
+                let connect = Self::connect_ipc(path, self.config.timeouts());
+                self.cancelable_connect(connect)
+            }
+            Message::TunnelFailed(err) => {
+                error!("Failed to open the ssh tunnel: {:?}", err);
+                self.connecting = None;
+                self.set_page(Page::Connect);
+                self.user_error = Some(format!("SSH tunnel failed, xab wasn't reached: {err}"));
+                Task::none()
+            }
             Message::ConnectionFailed(err) => {
                 error!("Failed to connect: {:?}", err);
-                self.user_error = Some(err);
+                self.connecting = None;
+                self.tunnel = None;
+                self.set_page(Page::Connect);
+                self.user_error = Some(match self.session.warning() {
+                    Some(warning) => format!("{err}\n\n{warning}"),
+                    None => err,
+                });
                 Task::none()
             }
             Message::Connected(ipc_handle) => {
                 debug!("Connected to server!");
-                self.ipc_handle = Some(ipc_handle);
-                self.set_page(Page::Connected);
+                self.connecting = None;
+                self.ipc_handle = Some(ipc_handle.clone());
+                // picked while disconnected, or on the connection before this one
+                ipc_handle.set_scaling(&[], self.background_opts.scaling);
+                ipc_handle.set_video_options(&[], self.background_opts.video);
+                // a file waiting for its monitor, or xab coming back, stays
+                // on the monitors
+                let start_page = match self.config.start_page {
+                    _ if self.choosing_monitor || self.shutdown.is_some() => StartPage::Monitors,
+                    StartPage::LastUsed => self.last_page,
+                    page => page,
+                };
+                let landing = match start_page {
+                    StartPage::Library => {
+                        self.settings_return = Page::Connected;
+                        self.set_page(Page::Library);
+                        self.scan_library()
+                    }
+                    _ => {
+                        self.set_page(Page::Connected);
+                        Task::none()
+                    }
+                };
+                if self.choosing_monitor {
+                    self.notify_choose_monitor();
+                }
 
+                let on_connect = hooks::notify(
+                    self.config.hooks.on_connect.clone(),
+                    serde_json::json!({
+                        "hook": "on-connect",
+                        "socket": ipc_handle.path,
+                        "capabilities": ipc_handle
+                            .capabilities()
+                            .iter_names()
+                            .map(|(name, _)| name)
+                            .collect::<Vec<_>>(),
+                    }),
+                );
+                let probing = ipc_handle.clone();
+                let restore = match self.shutdown.take() {
+                    Some(_) if self.restore_when_back => {
+                        self.user_notice = Some("xab is back, restoring wallpapers".to_owned());
+                        Task::perform(
+                            async move {
+                                let state = persist::state()?;
+                                for assignment in &state.assignments {
+                                    ipc_handle
+                                        .change_background_scaled(
+                                            assignment.monitor,
+                                            &assignment.path,
+                                            assignment.scaling,
+                                        )
+                                        .await?;
+                                }
+                                Ok(())
+                            },
+                            |res: Result<()>| {
+                                Message::WallpapersRestored(
+                                    res.map_err(|err| ipc::describe_error(&err)),
+                                )
+                            },
+                        )
+                    }
+                    Some(_) => {
+                        self.user_notice = Some("xab is back".to_owned());
+                        Task::none()
+                    }
+                    None => self.apply_startup_wallpapers(),
+                };
+                Task::batch([
+                    landing,
+                    Task::done(Message::CheckNightLight),
+                    restore,
+                    self.run_rules(RuleEvent::Connected),
+                    self.run_scripts(ScriptEvent::Connected),
+                    Task::done(Message::TemplateTick),
+                    Task::future(on_connect).discard(),
+                    self.refresh_recent(),
+                    Task::perform(
+                        async move { probing.probe_formats().await },
+                        |res: Result<()>| {
+                            Message::FormatsProbed(res.map_err(|err| ipc::describe_error(&err)))
+                        },
+                    ),
+                ])
+            }
+            Message::DaemonEvent(IpcEvent::ServerShuttingDown { grace }) => {
+                let Some(ipc_handle) = &self.ipc_handle else {
+                    return Task::none();
+                };
+                warn!("xab is shutting down in {:?}", grace);
+                ipc_handle.mark_shutting_down();
+                self.shutdown = Some(ShutdownNotice {
+                    deadline: Instant::now() + grace,
+                    path: ipc_handle.path.clone(),
+                });
+                self.event_hook(serde_json::json!({
+                    "event": "server-shutting-down",
+                    "grace_secs": grace.as_secs(),
+                }))
+            }
+            Message::DaemonEvent(IpcEvent::MonitorsChanged(monitors)) => {
+                let Some(ipc_handle) = &self.ipc_handle else {
+                    return Task::none();
+                };
+                ipc_handle.monitors_pushed(&monitors);
+                self.update(Message::MonitorsChanged(monitors))
+            }
+            Message::DaemonEvent(IpcEvent::BackgroundChanged { monitor, path }) => {
+                debug!("Background of {:?} changed to {}", monitor, path.display());
+                self.live_changed(monitor, &path);
+                self.user_notice = Some(format!(
+                    "{} changed to {} outside the gui",
+                    match monitor {
+                        Some(monitor) => format!("Monitor {}", monitor + 1),
+                        None => "Every monitor".to_owned(),
+                    },
+                    path.display()
+                ));
+                self.event_hook(serde_json::json!({
+                    "event": "background-changed",
+                    "monitor": monitor,
+                    "path": path.to_string_lossy(),
+                }))
+            }
+            Message::ShutdownTick => {
+                if self
+                    .shutdown
+                    .as_ref()
+                    .is_some_and(|notice| Instant::now() >= notice.deadline)
+                    && self.ipc_handle.is_some()
+                {
+                    debug!("xab should be gone by now, waiting for it to come back");
+                    self.update(Message::Disconnected)
+                } else {
+                    Task::none()
+                }
+            }
+            Message::EditRestoreWhenBack(restore) => {
+                self.restore_when_back = restore;
+                Task::none()
+            }
+            Message::Reconnect => match &self.shutdown {
+                Some(notice) if self.ipc_handle.is_none() => {
+                    let path = notice.path.clone();
+                    let timeouts = self.config.timeouts();
+                    Task::perform(
+                        async move { IpcHandle::with_timeouts(&path, timeouts).await },
+                        |res| match res {
+                            Ok(ipc_handle) => Message::Connected(Arc::new(ipc_handle)),
+                            Err(err) => Message::ReconnectFailed(ipc::describe_error(&err)),
+                        },
+                    )
+                }
+                _ => Task::none(),
+            },
+            Message::ReconnectFailed(err) => {
+                // expected until xab is back up
+                debug!("xab isn't back yet: {}", err);
+                Task::none()
+            }
+            Message::StopWaiting => {
+                self.shutdown = None;
+                self.tunnel = None;
+                self.set_page(Page::Connect);
+                Task::none()
+            }
+            Message::WallpapersRestored(res) => {
+                if let Err(err) = res {
+                    error!("Failed to restore wallpapers: {:?}", err);
+                    self.user_error = Some(err);
+                }
+                Task::none()
+            }
+            Message::FormatsProbed(res) => {
+                // files are still checked by what they look like
+                if let Err(err) = res {
+                    warn!("Failed to ask xab what it can show: {}", err);
+                }
+                Task::none()
+            }
+            Message::TogglePin => {
+                let pinned = !self.selection_pinned();
+                for monitor in self.background_opts.targets() {
+                    self.pins.set(monitor, pinned);
+                }
+                let pins = self.pins.clone();
+                Task::perform(
+                    async move { persist::update(|state| state.pinned = pins) },
+                    |res| Message::PinSaved(res.map_err(|err| ipc::describe_error(&err))),
+                )
+            }
+            Message::PinSaved(res) => {
+                if let Err(err) = res {
+                    error!("Failed to save pins: {:?}", err);
+                    self.user_error = Some(err);
+                }
+                Task::none()
+            }
+            Message::RefreshCapabilities => {
+                let Some(ipc_handle) = self.ipc_handle.clone() else {
+                    return Task::none();
+                };
+                Task::perform(
+                    async move {
+                        ipc_handle
+                            .refresh_capabilities()
+                            .await
+                            .map_err(|err| ipc::describe_error(&err))
+                    },
+                    Message::CapabilitiesRefreshed,
+                )
+            }
+            Message::CapabilitiesRefreshed(res) => match res {
+                Ok(true) => {
+                    self.user_notice = Some("xab's capabilities changed".to_owned());
+                    // monitors may be reported differently now
+                    self.update(Message::RefreshMonitors)
+                }
+                Ok(false) => Task::none(),
+                Err(err) => {
+                    warn!("Failed to refresh capabilities: {}", err);
+                    Task::none()
+                }
+            },
+            Message::RefreshMonitors => {
+                let Some(ipc_handle) = self.ipc_handle.clone() else {
+                    return Task::none();
+                };
+                Task::run(ipc_handle.monitor_stream(), |(monitors, more)| match more {
+                    true => Message::MonitorsArriving(monitors),
+                    false => Message::MonitorsChanged(monitors),
+                })
+            }
+            Message::MonitorsArriving(monitors) => {
+                // rules and hooks wait for the whole list
+                if self.shared.read().monitors.is_empty() {
+                    self.arriving_monitors = monitors;
+                }
                 Task::none()
             }
-            Message::SelectMonitor => Task::none(),
+            Message::MonitorsChanged(monitors) => {
+                self.arriving_monitors.clear();
+                let unchanged = monitors == self.shared.read().monitors;
+                let previous = std::mem::replace(&mut self.shared.write().monitors, monitors);
+                if unchanged {
+                    return Task::none();
+                }
+                self.layout.monitors_changed();
+                let estimate = self.refresh_assigned();
+                // the first list after connecting isn't a change
+                let changed = match previous.is_empty() {
+                    true => Task::none(),
+                    false => Task::batch([
+                        self.run_rules(RuleEvent::MonitorsChanged),
+                        self.run_scripts(ScriptEvent::MonitorsChanged),
+                        self.event_hook(serde_json::json!({
+                            "event": "monitors-changed",
+                            "monitors": self.monitors().len(),
+                        })),
+                    ]),
+                };
+                Task::batch([self.render_previews(), changed, estimate])
+            }
+            Message::SelectMonitor(index) => {
+                self.choosing_monitor = false;
+                self.monitor_menu = None;
+                self.background_opts.monitors = HashSet::from([index]);
+                self.show_monitor_options();
+                Task::batch([self.render_previews(), self.refresh_recent()])
+            }
+            Message::ToggleMonitor(index) => {
+                self.choosing_monitor = false;
+                self.monitor_menu = None;
+                // deselecting the last one goes back to every monitor
+                if !self.background_opts.monitors.remove(&index) {
+                    self.background_opts.monitors.insert(index);
+                }
+                self.show_monitor_options();
+                Task::batch([self.render_previews(), self.refresh_recent()])
+            }
+            Message::OpenMonitorMenu(index) => {
+                self.monitor_menu = Some(index);
+                Task::none()
+            }
+            Message::CloseMonitorMenu => {
+                self.monitor_menu = None;
+                Task::none()
+            }
+            Message::Touch(event) => {
+                match event {
+                    touch::Event::FingerPressed { .. } => self.wallpapers_kinetic.finger_down(),
+                    touch::Event::FingerLifted { .. } | touch::Event::FingerLost { .. } => {
+                        self.wallpapers_kinetic.finger_up(Instant::now())
+                    }
+                    touch::Event::FingerMoved { .. } => {}
+                }
+                Task::none()
+            }
+            Message::WallpapersScrolled(viewport) => {
+                self.wallpapers_kinetic
+                    .scrolled(viewport.absolute_offset().y, Instant::now());
+                Task::none()
+            }
+            Message::WallpapersFrame(now) => match self.wallpapers_kinetic.frame(now) {
+                Some(distance) => operation::scroll_by(
+                    WALLPAPER_LIST,
+                    operation::AbsoluteOffset {
+                        x: 0.0,
+                        y: distance,
+                    },
+                ),
+                None => Task::none(),
+            },
             Message::SelectFileForBackground => {
                 debug!("Selecting a background file...");
+                let directory = self
+                    .config
+                    .last_directory
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from("~"));
+                let formats = self.formats().unwrap_or_else(|| {
+                    sources::local::WALLPAPER_EXTENSIONS
+                        .iter()
+                        .map(|ext| ext.to_string())
+                        .collect()
+                });
                 Task::perform(
                     async move {
                         FileDialog::new()
-                            .set_directory("~")
+                            .set_directory(directory)
+                            .add_filter("Wallpapers", &formats)
+                            .add_filter("All files", &["*"])
                             .pick_file()
                             .map(|file| file.to_path_buf())
                     },
@@ -98,131 +1246,5698 @@ impl App {
             }
             Message::SelectedFileForBackground(path) => {
                 if let Some(path_ok) = path {
+                    if !self.vet_wallpaper(&path_ok) {
+                        return Task::none();
+                    }
                     self.background_opts.path = path_ok;
                     debug!(
                         "Background file selected: `{}`",
-                        self.background_opts.path.to_str().unwrap_or_default()
+                        self.background_opts.path.display()
                     );
+                    self.remember_directory();
+                }
+                self.render_previews()
+            }
+            Message::FileDropped(id, path) => {
+                if self.main_window != Some(id) || self.ipc_handle.is_none() {
+                    return Task::none();
+                }
+                debug!("Dropped `{}`", path.display());
+                self.update(Message::SelectedFileForBackground(Some(path)))
+            }
+            Message::DroppedOnMonitor(index, path) => {
+                if !self.vet_wallpaper(&path) {
+                    return Task::none();
+                }
+                debug!("Dropped `{}` on monitor {}", path.display(), index + 1);
+                self.background_opts.path = path;
+                self.remember_directory();
+                Task::batch([
+                    self.update(Message::SelectMonitor(index)),
+                    self.update(Message::SendBackgroundOptions),
+                ])
+            }
+            Message::SelectScaling(scaling) => {
+                self.background_opts.scaling = scaling;
+                if let Some(ipc_handle) = &self.ipc_handle {
+                    ipc_handle.set_scaling(&self.background_opts.monitor_list(), scaling);
+                }
+                self.invalidate_selected_previews();
+                self.render_previews()
+            }
+            Message::ToggleVideoOptions => {
+                self.video_options_open = !self.video_options_open;
+                Task::none()
+            }
+            Message::EditVideo(video) => {
+                self.background_opts.video = video;
+                if let Some(ipc_handle) = &self.ipc_handle {
+                    ipc_handle.set_video_options(&self.background_opts.monitor_list(), video);
+                }
+                Task::none()
+            }
+            Message::CopyCredit => match &self.credit {
+                Some(credit) => iced::clipboard::write(credit.attribution()),
+                None => Task::none(),
+            },
+            Message::ThumbnailRendered(path, res) => {
+                if let Ok(thumbnail) = &res {
+                    self.previews
+                        .insert_thumbnail(path.clone(), thumbnail.clone());
+                }
+                for thumbnails in [&mut self.browser.thumbnails, &mut self.recent_thumbnails] {
+                    if let Some(thumbnail) = thumbnails.get_mut(&path) {
+                        *thumbnail = Some(
+                            res.as_ref()
+                                .map(|thumbnail| thumbnail.handle.clone())
+                                .map_err(Clone::clone),
+                        );
+                    }
                 }
+                if let Some((selected, thumbnail)) = &mut self.thumbnail
+                    && *selected == path
+                {
+                    if let Err(err) = &res {
+                        warn!(
+                            "Failed to render a thumbnail of {}: {}",
+                            path.display(),
+                            err
+                        );
+                    }
+                    *thumbnail = Some(res);
+                }
+                Task::none()
+            }
+            Message::PreviewRendered(key, res) => {
+                let handle = res
+                    .inspect_err(|err| {
+                        warn!(
+                            "Failed to render preview of {}: {}",
+                            key.path.display(),
+                            err
+                        )
+                    })
+                    .ok();
+                self.previews.finish(key, handle);
+                self.layout.selection_changed();
                 Task::none()
             }
 
-            Message::SendBackgroundOptions => Task::none(),
-            Message::Disconnect => match &self.ipc_handle {
+            Message::SendBackgroundOptions => match &self.ipc_handle {
                 Some(ipc_handle) => {
+                    if self.background_opts.path.as_os_str().is_empty() {
+                        self.user_error = Some("Select a file first!".to_owned());
+                        return Task::none();
+                    }
+                    // picked before xab said what it can show
+                    if let Err(err) =
+                        media::check(&self.background_opts.path, ipc_handle.formats().as_deref())
+                    {
+                        self.user_error = Some(format!("{err:#}"));
+                        return Task::none();
+                    }
+
+                    self.choosing_monitor = false;
+                    self.apply_status = Some(Status::Pending);
                     let ipc_clone = ipc_handle.clone();
-                    Task::perform(async move { ipc_clone.close().await }, |_| {
-                        Message::Disconnected
-                    })
+                    let monitors = self.background_opts.monitor_list();
+                    let targets = self.background_opts.targets();
+                    for monitor in &targets {
+                        self.claims.release(*monitor);
+                    }
+                    let path = self.background_opts.path.clone();
+                    let hooks = self.config.hooks.clone();
+                    Task::perform(
+                        async move {
+                            // the pre-apply hook might've swapped it
+                            let path = hooks::apply(&ipc_clone, &hooks, &monitors, path).await?;
+
+                            // remember it for `xab-gui apply-saved`
+                            persist::update(|state| {
+                                for monitor in targets {
+                                    let scaling = ipc_clone.scaling(monitor);
+                                    state.assign(monitor, path.clone(), scaling);
+                                }
+                            })?;
+                            anyhow::Ok((monitors, path))
+                        },
+                        |res| match res {
+                            Ok((monitors, path)) => Message::BackgroundApplied(monitors, path),
+                            Err(err) => Message::BackgroundSendFailed(ipc::describe_error(&err)),
+                        },
+                    )
                 }
                 None => {
-                    error!("Can't disconnect, connected to nothing!");
+                    error!("Can't send background options, connected to nothing!");
                     Task::none()
                 }
             },
-            Message::Disconnected => {
-                self.ipc_handle = None;
-                self.set_page(Page::Connect);
-                Task::none()
-            }
-        }
-    }
-
-    async fn build_monitors_widgets(&self) -> Row<'_, Message> {
-        let mut monitors_widgets = Row::new();
-        if let Some(ipc_handle) = self.ipc_handle.as_ref() {
-            for monitor in ipc_handle.get_monitors().await {
-                monitors_widgets = monitors_widgets.push(
-                    button(text!("{}", monitor.index).center())
-                        .width(Length::Fill)
-                        .on_press(Message::SelectMonitor),
-                );
+            Message::ClearBackground => {
+                let Some(ipc_handle) = self.ipc_handle.clone() else {
+                    error!("Can't clear the background, connected to nothing!");
+                    return Task::none();
+                };
+                self.choosing_monitor = false;
+                let monitors = self.background_opts.monitor_list();
+                let color = self.config.clear_color();
+                // not saved, applying the saved backgrounds brings them back
+                Task::perform(
+                    async move { ipc_handle.clear_backgrounds(&monitors, color).await },
+                    |res| match res {
+                        Ok(()) => Message::BackgroundSent,
+                        Err(err) => Message::BackgroundSendFailed(ipc::describe_error(&err)),
+                    },
+                )
             }
-        }
-        monitors_widgets
-    }
-
-    // TODO: stuff like this:
-
-    // fn build_monitors_widgets_subscription(&self) -> Subscription<_> {
-    //     Subscription::run(self.build_monitors_widgets())
-    // }
+            Message::SpanBackground => match &self.ipc_handle {
+                Some(ipc_handle) => {
+                    if self.background_opts.path.as_os_str().is_empty() {
+                        self.user_error = Some("Select a file first!".to_owned());
+                        return Task::none();
+                    }
+                    // picked before xab said what it can show
+                    if let Err(err) =
+                        media::check(&self.background_opts.path, ipc_handle.formats().as_deref())
+                    {
+                        self.user_error = Some(format!("{err:#}"));
+                        return Task::none();
+                    }
 
-    fn set_page(&mut self, page: Page) {
-        self.page = page;
-        self.user_error = None
-    }
+                    self.choosing_monitor = false;
+                    self.apply_status = Some(Status::Pending);
+                    let ipc_clone = ipc_handle.clone();
+                    // across the selected monitors, or all of them
+                    let selected = &self.background_opts.monitors;
+                    let monitors: Vec<Monitor> = self
+                        .monitors()
+                        .into_iter()
+                        .filter(|monitor| selected.is_empty() || selected.contains(&monitor.index))
+                        .collect();
+                    let path = self.background_opts.path.clone();
+                    let bezels = self.config.bezels.clone();
+                    let hooks = self.config.hooks.clone();
+                    Task::perform(
+                        async move {
+                            let pieces = span::render(&monitors, &path, &bezels)?;
+                            let mut applied = Vec::new();
+                            for (monitor, piece) in pieces {
+                                let piece =
+                                    hooks::apply(&ipc_clone, &hooks, &[monitor], piece).await?;
+                                applied.push((monitor, piece));
+                            }
 
-    fn view(&self) -> Column<Message> {
-        match self.page {
-            Page::Connect => column![
-                image("res/logo.webp").content_fit(ContentFit::Cover),
-                button("Connect")
-                    .width(Length::Fill)
-                    .on_press(Message::Connect),
-            ]
-            .push_maybe(
-                self.user_error
-                    .as_ref()
-                    .map(|e| column![horizontal_rule(50), text(e)]),
-            )
-            .padding(20),
-            Page::Connecting => column![text!["Connecting..."]]
-                .push_maybe(
-                    self.user_error
-                        .as_ref()
-                        .map(|e| column![horizontal_rule(50), text(e)]),
+                            persist::update(|state| {
+                                for (monitor, piece) in &applied {
+                                    let scaling = ipc_clone.scaling(Some(*monitor));
+                                    state.assign(Some(*monitor), piece.clone(), scaling);
+                                }
+                            })?;
+                            let monitors = applied.iter().map(|(monitor, _)| *monitor).collect();
+                            anyhow::Ok((monitors, path))
+                        },
+                        |res| match res {
+                            Ok((monitors, path)) => Message::BackgroundApplied(monitors, path),
+                            Err(err) => Message::BackgroundSendFailed(ipc::describe_error(&err)),
+                        },
+                    )
+                }
+                None => {
+                    error!("Can't span the background, connected to nothing!");
+                    Task::none()
+                }
+            },
+            Message::OpenCropEditor => {
+                let path = self.background_opts.path.clone();
+                if path.as_os_str().is_empty() {
+                    self.user_error = Some("Select a file first!".to_owned());
+                    return Task::none();
+                }
+                if snapshot::is_video(&path) {
+                    self.user_error = Some("Videos can't be cropped".to_owned());
+                    return Task::none();
+                }
+                Task::perform(
+                    {
+                        let path = path.clone();
+                        async move { crop::load(&path).map_err(|err| ipc::describe_error(&err)) }
+                    },
+                    move |res| Message::CropLoaded(path.clone(), res),
                 )
-                .padding(20),
-            Page::Connected => column![
-                image("res/logo.webp").content_fit(ContentFit::Cover),
-                button("Select file")
-                    .width(Length::Fill)
-                    .on_press(Message::SelectFileForBackground),
-                card(
-                    text!["Monitors"],
-                    row![
-                        button(text("1").center())
-                            .width(Length::Fill)
-                            .on_press(Message::SelectMonitor),
-                        button(text("2").center())
-                            .width(Length::Fill)
-                            .on_press(Message::SelectMonitor),
-                        button(text("3").center())
-                            .width(Length::Fill)
-                            .on_press(Message::SelectMonitor),
-                    ]
-                ),
-                button("Disconnect")
-                    .width(Length::Fill)
-                    .on_press(Message::Disconnect),
-            ]
-            .push_maybe(
-                self.user_error
-                    .as_ref()
-                    .map(|e| column![horizontal_rule(50), text(e)]),
-            )
-            .padding(20),
-        }
-    }
-    fn theme(&self) -> iced::Theme {
-        iced::Theme::Dark
-    }
-}
-
-fn main() -> Result<()> {
-    // only enable debug for our crate
-    Subscriber::builder()
-        .with_env_filter(EnvFilter::new("xab_gui=debug"))
-        .init();
+            }
+            Message::CropLoaded(path, res) => {
+                let (handle, size) = match res {
+                    Ok(loaded) => loaded,
+                    Err(err) => {
+                        error!("Failed to open the crop editor: {}", err);
+                        self.user_error = Some(err);
+                        return Task::none();
+                    }
+                };
+                let monitors = self.monitors();
+                // starts on the first selected monitor
+                let monitor = monitors
+                    .iter()
+                    .find(|monitor| self.background_opts.monitors.contains(&monitor.index))
+                    .unwrap_or(&monitors[0]);
+                self.crop = Some(CropEditor::new(path, handle, size, monitor));
+                self.settings_return = self.page;
+                self.set_page(Page::Crop);
+                Task::none()
+            }
+            Message::CropSelectMonitor(index) => {
+                let monitors = self.monitors();
+                if let (Some(editor), Some(monitor)) = (
+                    &mut self.crop,
+                    monitors.iter().find(|monitor| monitor.index == index),
+                ) {
+                    editor.select(monitor);
+                }
+                Task::none()
+            }
+            Message::CropChanged(crop) => {
+                if let Some(editor) = &mut self.crop {
+                    editor.set(crop.snapped(editor.image));
+                }
+                Task::none()
+            }
+            Message::EditCropX(input) => self.edit_crop_inputs(|editor| editor.x_input = input),
+            Message::EditCropY(input) => self.edit_crop_inputs(|editor| editor.y_input = input),
+            Message::EditCropWidth(input) => {
+                self.edit_crop_inputs(|editor| editor.width_input = input)
+            }
+            Message::CenterCrop => {
+                if let Some(editor) = &mut self.crop {
+                    editor.set(editor.crop().centered(editor.image));
+                }
+                Task::none()
+            }
+            Message::CopyCropToSameResolution => {
+                let monitors = self.monitors();
+                if let Some(editor) = &mut self.crop {
+                    self.user_notice = Some(match editor.copy_to_same_resolution(&monitors) {
+                        0 => "No other monitor has this resolution".to_owned(),
+                        copied => format!("Copied the crop to {copied} more monitor(s)"),
+                    });
+                }
+                Task::none()
+            }
+            Message::ApplyCrops => {
+                let (Some(ipc_handle), Some(editor)) = (&self.ipc_handle, &self.crop) else {
+                    error!("Can't apply crops, connected to nothing!");
+                    return Task::none();
+                };
+                let ipc_clone = ipc_handle.clone();
+                let path = editor.path.clone();
+                let crops: Vec<(i32, CropRect, (u32, u32))> = self
+                    .monitors()
+                    .iter()
+                    .filter_map(|monitor| {
+                        let crop = editor.crops.get(&monitor.index)?;
+                        Some((monitor.index, *crop, preview::monitor_size(monitor)))
+                    })
+                    .collect();
+                let hooks = self.config.hooks.clone();
+                Task::perform(
+                    async move {
+                        let mut applied = Vec::new();
+                        for (monitor, crop, size) in crops {
+                            let piece = crop::render(&path, crop, size)?;
+                            let piece = hooks::apply(&ipc_clone, &hooks, &[monitor], piece).await?;
+                            applied.push((monitor, piece));
+                        }
 
-    debug!("Initializing iced application");
-    iced::application("xab gui", App::update, App::view)
-        .window(window::Settings {
-            ..window::Settings::default()
-        })
-        .theme(App::theme)
-        .run()?;
+                        persist::update(|state| {
+                            for (monitor, piece) in &applied {
+                                let scaling = ipc_clone.scaling(Some(*monitor));
+                                state.assign(Some(*monitor), piece.clone(), scaling);
+                            }
+                        })?;
+                        let monitors = applied.iter().map(|(monitor, _)| *monitor).collect();
+                        anyhow::Ok((monitors, path))
+                    },
+                    |res| match res {
+                        Ok((monitors, path)) => Message::BackgroundApplied(monitors, path),
+                        Err(err) => Message::BackgroundSendFailed(ipc::describe_error(&err)),
+                    },
+                )
+            }
+            Message::BackgroundApplied(monitors, path) => {
+                self.apply_status = Some(Status::Ok);
+                self.user_notice = Some(format!(
+                    "Applied {} to {}",
+                    path.display(),
+                    match monitors.as_slice() {
+                        [] => "every monitor".to_owned(),
+                        [index] => format!("monitor {}", index + 1),
+                        indices => format!(
+                            "monitors {}",
+                            indices
+                                .iter()
+                                .map(|index| (index + 1).to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                    }
+                ));
+                self.update(Message::BackgroundSent)
+            }
+            Message::BackgroundSent => {
+                debug!("Background options sent!");
+                self.user_error = None;
+                // whatever was cached for the monitors is stale now
+                self.invalidate_selected_previews();
+                Task::batch([self.render_previews(), self.refresh_recent()])
+            }
+            Message::BackgroundSendFailed(err) => {
+                error!("Failed to send background options: {:?}", err);
+                if self.apply_status == Some(Status::Pending) {
+                    self.apply_status = Some(Status::Error);
+                }
+                self.user_error = Some(err);
+                Task::none()
+            }
+            Message::SelectSource(choice) => {
+                let Some(source) = self.sources.get(&choice.id) else {
+                    error!("Unknown source: {}", choice.id);
+                    return Task::none();
+                };
+                if self.config.offline && source.is_online() {
+                    self.user_error =
+                        Some(format!("{} isn't available in offline mode", source.name()));
+                    return Task::none();
+                }
+                debug!("Listing wallpapers of {}", choice.id);
+                self.selected_source = Some(choice.clone());
+                self.source_wallpapers.clear();
+                self.source_tag = None;
+                Task::perform(
+                    async move { source.list().map_err(|err| ipc::describe_error(&err)) },
+                    move |res| Message::SourceListed(choice.id.clone(), res),
+                )
+            }
+            Message::SourceListed(id, res) => {
+                self.source_scans.insert(
+                    id.clone(),
+                    Scan {
+                        at: SystemTime::now(),
+                        items: res.as_ref().map(Vec::len).map_err(Clone::clone),
+                    },
+                );
+                // the user might've picked another source in the meantime
+                if self.selected_source.as_ref().map(|s| &s.id) != Some(&id) {
+                    return Task::none();
+                }
+                match res {
+                    Ok(wallpapers) => {
+                        // a tag that's gone (folder renamed, say) would hide everything
+                        if let Some(tag) = &self.source_tag
+                            && !wallpapers.iter().any(|w| w.tags.contains(tag))
+                        {
+                            self.source_tag = None;
+                        }
+                        self.source_wallpapers = wallpapers;
+                    }
+                    Err(err) => {
+                        error!("Failed to list {}: {:?}", id, err);
+                        self.user_error = Some(err);
+                    }
+                }
+                Task::none()
+            }
+            Message::SelectSourceTag(tag) => {
+                self.source_tag = tag;
+                Task::none()
+            }
+            Message::FollowChannel => Task::perform(
+                async move { FileDialog::new().set_directory("~").pick_folder() },
+                Message::SelectedChannel,
+            ),
+            Message::SelectedChannel(dir) => {
+                let Some(dir) = dir else {
+                    return Task::none();
+                };
+                if !self.config.channels.contains(&dir) {
+                    self.config.channels.push(dir.clone());
+                    if let Err(err) = self.config.save() {
+                        self.user_error = Some(ipc::describe_error(&err));
+                    }
+                }
+                let channel = Channel::new(dir);
+                let choice = SourceChoice {
+                    id: channel.id().to_owned(),
+                    name: channel.name().to_owned(),
+                };
+                self.sources.register(channel);
+                self.update(Message::SelectSource(choice))
+            }
+            Message::UnfollowChannel(dir) => {
+                self.config.channels.retain(|d| *d != dir);
+                if let Err(err) = self.config.save() {
+                    self.user_error = Some(ipc::describe_error(&err));
+                }
+                let id = Channel::new(dir).id().to_owned();
+                self.sources.unregister(&id);
+                if self.selected_source.as_ref().is_some_and(|s| s.id == id) {
+                    self.selected_source = None;
+                    self.source_wallpapers.clear();
+                    self.source_tag = None;
+                }
+                Task::none()
+            }
+            Message::ScanSources => Task::batch(
+                self.sources
+                    .iter()
+                    .filter(|source| !(self.config.offline && source.is_online()))
+                    .map(|source| {
+                        let source = source.clone();
+                        let id = source.id().to_owned();
+                        Task::perform(
+                            async move {
+                                source
+                                    .list()
+                                    .map(|wallpapers| wallpapers.len())
+                                    .map_err(|err| format!("{:#}", err))
+                            },
+                            move |res| Message::SourceScanned(id.clone(), res),
+                        )
+                    }),
+            ),
+            Message::SourceScanned(id, items) => {
+                if let Err(err) = &items {
+                    warn!("Source {} is unhealthy: {}", id, err);
+                }
+                self.source_scans.insert(
+                    id,
+                    Scan {
+                        at: SystemTime::now(),
+                        items,
+                    },
+                );
+                Task::none()
+            }
+            Message::RetrySource(id) => {
+                let Some(source) = self.sources.get(&id) else {
+                    return Task::none();
+                };
+                let rescan = match self.selected_source.clone() {
+                    // also refreshes the wallpaper list
+                    Some(choice) if choice.id == id => self.update(Message::SelectSource(choice)),
+                    _ => {
+                        let id = id.clone();
+                        Task::perform(
+                            async move {
+                                source
+                                    .list()
+                                    .map(|wallpapers| wallpapers.len())
+                                    .map_err(|err| format!("{:#}", err))
+                            },
+                            move |res| Message::SourceScanned(id.clone(), res),
+                        )
+                    }
+                };
+                let failed_sync = self
+                    .source_sync
+                    .get(&id)
+                    .is_some_and(|sync| sync.ledger.last_error.is_some());
+                if !failed_sync || self.syncing_sources {
+                    return rescan;
+                }
+                self.syncing_sources = true;
+                let registry = self.sources.clone();
+                Task::batch([
+                    rescan,
+                    Task::perform(
+                        async move {
+                            sources::sync::sync_now(&registry, &id)
+                                .map_err(|err| ipc::describe_error(&err))
+                        },
+                        Message::SourcesSynced,
+                    ),
+                ])
+            }
+            Message::DeleteWallpaper(path) => Task::perform(
+                async move { trash::delete_wallpaper(&path).map_err(|err| ipc::describe_error(&err)) },
+                Message::WallpaperDeleted,
+            ),
+            Message::WallpaperDeleted(res) => {
+                match res {
+                    Ok(trashed) => {
+                        self.source_wallpapers.retain(|wallpaper| {
+                            wallpaper.location != Location::Local(trashed.original.clone())
+                        });
+                        self.user_notice =
+                            Some(format!("Moved {} to the trash", trashed.original.display()));
+                        self.trashed.push(trashed);
+                    }
+                    Err(err) => {
+                        error!("Failed to delete wallpaper: {:?}", err);
+                        self.user_error = Some(err);
+                    }
+                }
+                Task::none()
+            }
+            Message::RestoreTrashed(trashed) => Task::perform(
+                async move {
+                    trash::restore(&trashed)
+                        .map(|()| trashed)
+                        .map_err(|err| ipc::describe_error(&err))
+                },
+                Message::TrashedRestored,
+            ),
+            Message::TrashedRestored(res) => match res {
+                Ok(trashed) => {
+                    self.trashed.retain(|t| *t != trashed);
+                    self.user_notice = Some(format!("Restored {}", trashed.original.display()));
+                    match self.selected_source.clone() {
+                        Some(choice) => self.update(Message::SelectSource(choice)),
+                        None => Task::none(),
+                    }
+                }
+                Err(err) => {
+                    error!("Failed to restore: {:?}", err);
+                    self.user_error = Some(err);
+                    Task::none()
+                }
+            },
+            Message::StartPurge(trashed) => {
+                self.purge_confirm = Some((trashed, String::new()));
+                Task::none()
+            }
+            Message::EditPurgeConfirm(typed) => {
+                if let Some((_, confirm)) = &mut self.purge_confirm {
+                    *confirm = typed;
+                }
+                Task::none()
+            }
+            Message::CancelPurge => {
+                self.purge_confirm = None;
+                Task::none()
+            }
+            Message::ConfirmPurge => {
+                let Some((trashed, typed)) = self.purge_confirm.take() else {
+                    return Task::none();
+                };
+                // the button is disabled until it matches, but better safe than sorry
+                if typed != trashed.name.to_string_lossy() {
+                    self.purge_confirm = Some((trashed, typed));
+                    return Task::none();
+                }
+                Task::perform(
+                    async move {
+                        trash::purge(&trashed)
+                            .map(|()| trashed)
+                            .map_err(|err| ipc::describe_error(&err))
+                    },
+                    Message::Purged,
+                )
+            }
+            Message::Purged(res) => {
+                match res {
+                    Ok(trashed) => {
+                        self.trashed.retain(|t| *t != trashed);
+                        self.user_notice =
+                            Some(format!("Deleted {} for good", trashed.original.display()));
+                    }
+                    Err(err) => {
+                        error!("Failed to delete permanently: {:?}", err);
+                        self.user_error = Some(err);
+                    }
+                }
+                Task::none()
+            }
+            Message::SelectSourceWallpaper(wallpaper) => {
+                let Some(source) = self
+                    .selected_source
+                    .as_ref()
+                    .and_then(|choice| self.sources.get(&choice.id))
+                else {
+                    return Task::none();
+                };
+                debug!("Fetching {}", wallpaper.name);
+                Task::perform(async move { source.fetch(&wallpaper) }, |res| match res {
+                    Ok(path) => Message::SelectedFileForBackground(Some(path)),
+                    Err(err) => Message::BackgroundSendFailed(ipc::describe_error(&err)),
+                })
+            }
+            Message::ApplySourceWallpaper(wallpaper) => {
+                let Some(source) = self
+                    .selected_source
+                    .as_ref()
+                    .and_then(|choice| self.sources.get(&choice.id))
+                else {
+                    return Task::none();
+                };
+                debug!("Fetching {} to apply it", wallpaper.name);
+                Task::perform(
+                    async move {
+                        source
+                            .fetch(&wallpaper)
+                            .map_err(|err| ipc::describe_error(&err))
+                    },
+                    Message::FetchedForApply,
+                )
+            }
+            Message::SelectPlaylist(name) => {
+                self.selected_playlist = Some(name);
+                self.playlist_at = None;
+                Task::none()
+            }
+            Message::EditPlaylistName(name) => {
+                self.playlist_name_input = name;
+                Task::none()
+            }
+            Message::CreatePlaylist => {
+                if let Err(err) = self.playlists.create(&self.playlist_name_input) {
+                    self.user_error = Some(err.to_string());
+                    return Task::none();
+                }
+                let name = self.playlist_name_input.trim().to_owned();
+                self.sources.register(PlaylistSource::new(&name));
+                self.selected_playlist = Some(name);
+                self.playlist_at = None;
+                self.playlist_name_input.clear();
+                self.save_playlists();
+                Task::none()
+            }
+            Message::DeletePlaylist => {
+                let Some(name) = self.selected_playlist.take() else {
+                    return Task::none();
+                };
+                self.playlists.delete(&name);
+                self.sources.unregister(&PlaylistSource::id_of(&name));
+                self.save_playlists();
+                Task::none()
+            }
+            Message::AddToPlaylist => {
+                let path = self.background_opts.path.clone();
+                if path.as_os_str().is_empty() {
+                    self.user_error = Some("Select a file first!".to_owned());
+                    return Task::none();
+                }
+                let Some(playlist) = self
+                    .selected_playlist
+                    .as_deref()
+                    .and_then(|name| self.playlists.get_mut(name))
+                else {
+                    return Task::none();
+                };
+                if playlist.add(path) {
+                    self.save_playlists();
+                }
+                Task::none()
+            }
+            Message::RemoveFromPlaylist(at) => {
+                if let Some(playlist) = self
+                    .selected_playlist
+                    .as_deref()
+                    .and_then(|name| self.playlists.get_mut(name))
+                {
+                    playlist.remove(at);
+                    self.playlist_at = None;
+                    self.save_playlists();
+                }
+                Task::none()
+            }
+            Message::ShiftInPlaylist(at, up) => {
+                if let Some(playlist) = self
+                    .selected_playlist
+                    .as_deref()
+                    .and_then(|name| self.playlists.get_mut(name))
+                {
+                    playlist.shift(at, up);
+                    self.playlist_at = None;
+                    self.save_playlists();
+                }
+                Task::none()
+            }
+            Message::StepPlaylist(forward) => {
+                let Some(playlist) = self
+                    .selected_playlist
+                    .as_deref()
+                    .and_then(|name| self.playlists.get(name))
+                else {
+                    return Task::none();
+                };
+                // the first step lands on the first (or last) file
+                let from = self.playlist_at.unwrap_or(match forward {
+                    true => playlist.files.len().saturating_sub(1),
+                    false => 0,
+                });
+                let Some((at, path)) = playlist.step(from, forward) else {
+                    return Task::none();
+                };
+                let path = path.to_path_buf();
+                self.playlist_at = Some(at);
+                self.update(Message::FetchedForApply(Ok(path)))
+            }
+            Message::FetchedForApply(res) => match res {
+                Ok(path) => {
+                    self.background_opts.path = path;
+                    self.update(Message::SendBackgroundOptions)
+                }
+                Err(err) => self.update(Message::BackgroundSendFailed(err)),
+            },
+            Message::HotkeyPressed(id) => {
+                let Some(action) = self.hotkeys.as_ref().and_then(|h| h.action(id)) else {
+                    return Task::none();
+                };
+                debug!("Hotkey pressed: {action}");
+                self.run_hotkey_action(action)
+            }
+            Message::OpenSettings => {
+                self.settings_return = self.page;
+                self.hotkey_inputs = self.config.hotkeys.clone();
+                self.presentation_neutral_input = self.config.presentation_neutral_wallpaper;
+                self.close_to_background_input = self.config.close_to_background;
+                self.panels_input = self.config.panels.clone();
+                self.active_color_input = self.config.active_window_color;
+                self.notifications_input = self.config.notifications;
+                self.rule_inputs = self.config.rules.clone();
+                self.night_dimming_input = self.config.night_dimming.enabled;
+                (self.night_start_input, self.night_end_input) =
+                    TimeWindow::to_inputs(self.config.night_dimming.schedule);
+                (self.quiet_start_input, self.quiet_end_input) =
+                    TimeWindow::to_inputs(self.config.quiet_hours);
+                self.socket_input = self.config.socket.clone().unwrap_or_default();
+                self.theme_input = self.config.theme;
+                self.start_page_input = self.config.start_page;
+                self.palette_input = self.config.palette;
+                self.default_monitor_input = self
+                    .config
+                    .default_monitor
+                    .map(|monitor| (monitor + 1).to_string())
+                    .unwrap_or_default();
+                self.clear_color_input = self
+                    .config
+                    .clear_color
+                    .map(|color| color.to_string())
+                    .unwrap_or_default();
+                self.wallpapers_input = self.config.wallpapers.clone();
+                self.connect_timeout_input = self
+                    .config
+                    .connect_timeout
+                    .map(|secs| secs.to_string())
+                    .unwrap_or_default();
+                self.handshake_timeout_input = self
+                    .config
+                    .handshake_timeout
+                    .map(|secs| secs.to_string())
+                    .unwrap_or_default();
+                let limits = self.config.limits;
+                [self.workers_input, self.downloads_input, self.decodes_input] =
+                    [limits.workers, limits.downloads, limits.decodes]
+                        .map(|limit| limit.map(|limit| limit.to_string()).unwrap_or_default());
+                self.cache_mb_input = self
+                    .config
+                    .cache_mb
+                    .map(|mb| mb.to_string())
+                    .unwrap_or_default();
+                self.disk_cache_mb_input = self
+                    .config
+                    .disk_cache_mb
+                    .map(|mb| mb.to_string())
+                    .unwrap_or_default();
+                self.bandwidth_input = self
+                    .config
+                    .bandwidth
+                    .limit_kbps
+                    .map(|kbps| kbps.to_string())
+                    .unwrap_or_default();
+                self.sync_on_metered_input = self.config.bandwidth.sync_on_metered;
+                self.check_updates_input = self.config.check_updates;
+                self.proxy_input = self.config.proxies.proxy.clone().unwrap_or_default();
+                self.ca_bundle_input = self
+                    .config
+                    .proxies
+                    .ca_bundle
+                    .as_ref()
+                    .map(|bundle| bundle.display().to_string())
+                    .unwrap_or_default();
+                // the ones that download, local files are the user's own
+                self.filter_inputs = self
+                    .sources
+                    .iter()
+                    .filter(|source| source.download_dir().is_some())
+                    .map(|source| {
+                        let filter = self
+                            .config
+                            .content_filters
+                            .get(source.id())
+                            .cloned()
+                            .unwrap_or_default();
+                        (
+                            source.id().to_owned(),
+                            (filter.sfw_only, filter.blocked_input()),
+                        )
+                    })
+                    .collect();
+                self.set_page(Page::Settings);
+                self.reclaimable = None;
+                Task::perform(
+                    async { disk_cache::reclaimable().map_err(|err| ipc::describe_error(&err)) },
+                    Message::Reclaimable,
+                )
+            }
+            Message::OpenLibrary => {
+                self.settings_return = self.page;
+                self.set_page(Page::Library);
+                self.scan_library()
+            }
+            Message::RescanLibrary => self.scan_library(),
+            Message::LibraryScanned(res) => {
+                match res {
+                    Ok(files) => {
+                        self.browser.scanned(files);
+                        return self.update(Message::ShowMoreLibrary);
+                    }
+                    Err(err) => {
+                        self.browser.scanning = false;
+                        self.user_error = Some(err);
+                    }
+                }
+                Task::none()
+            }
+            Message::ShowMoreLibrary => {
+                let missing = self.browser.show_more();
+                self.load_thumbnails(missing)
+            }
+            Message::AddLibraryDir => Task::perform(
+                async move { FileDialog::new().set_directory("~").pick_folder() },
+                Message::SelectedLibraryDir,
+            ),
+            Message::SelectedLibraryDir(dir) => {
+                let Some(dir) = dir else {
+                    return Task::none();
+                };
+                if self.config.library_dirs.contains(&dir) {
+                    return Task::none();
+                }
+                self.config.library_dirs.push(dir);
+                if let Err(err) = self.config.save() {
+                    self.user_error = Some(ipc::describe_error(&err));
+                }
+                self.scan_library()
+            }
+            Message::RemoveLibraryDir(dir) => {
+                self.config.library_dirs.retain(|d| *d != dir);
+                if let Err(err) = self.config.save() {
+                    self.user_error = Some(ipc::describe_error(&err));
+                }
+                self.scan_library()
+            }
+            Message::ApplyFromLibrary(path) | Message::ApplyRecent(path) => {
+                debug!("Applying `{}` again", path.display());
+                self.background_opts.path = path;
+                Task::batch([
+                    self.render_previews(),
+                    self.update(Message::SendBackgroundOptions),
+                ])
+            }
+            Message::OpenScripts => {
+                self.settings_return = self.page;
+                self.set_page(Page::Scripts);
+                Task::none()
+            }
+            Message::ReloadScripts => {
+                // scripts start over with an empty `this`
+                self.scripts = scripts::load_all();
+                Task::none()
+            }
+            Message::NewScript => {
+                match scripts::create() {
+                    Ok(path) => {
+                        self.user_notice =
+                            Some(format!("Created {}, edit it and reload", path.display()));
+                    }
+                    Err(err) => self.user_error = Some(ipc::describe_error(&err)),
+                }
+                self.update(Message::ReloadScripts)
+            }
+            Message::ToggleScript(name, enabled) => {
+                self.config.scripts.retain(|s| *s != name);
+                if enabled {
+                    self.config.scripts.push(name);
+                }
+                if let Err(err) = self.config.save() {
+                    self.user_error = Some(ipc::describe_error(&err));
+                }
+                Task::none()
+            }
+            Message::RunScript(name) => {
+                // runs even while automation is paused, the user asked for it
+                let monitors = self.monitors();
+                match self
+                    .scripts
+                    .iter()
+                    .find(|s| s.name == name)
+                    .and_then(|s| s.prepare(ScriptEvent::Tick, monitors))
+                {
+                    Some(run) => Self::spawn_script(run),
+                    None => Task::none(),
+                }
+            }
+            Message::ScriptTick => self.run_scripts(ScriptEvent::Tick),
+            Message::ScriptFinished(outcome) => {
+                if let Some(err) = &outcome.error {
+                    warn!("Script {} failed: {err}", outcome.script);
+                }
+                if let Some(script) = self.scripts.iter_mut().find(|s| s.name == outcome.script) {
+                    script.finish(&outcome);
+                }
+                Task::batch(outcome.actions.into_iter().map(|action| match action {
+                    ScriptAction::Apply { monitor, path } => {
+                        self.apply_automatically(Automation::Scripts, monitor, path)
+                    }
+                }))
+            }
+            Message::OpenTemplates => {
+                self.settings_return = self.page;
+                self.set_page(Page::Templates);
+                Task::none()
+            }
+            Message::ReloadTemplates => {
+                self.templates = templates::load_all();
+                // an edited template shouldn't wait for its interval
+                self.template_renders.clear();
+                Task::none()
+            }
+            Message::NewTemplate => {
+                match templates::create() {
+                    Ok(path) => {
+                        self.user_notice =
+                            Some(format!("Created {}, edit it and reload", path.display()));
+                    }
+                    Err(err) => self.user_error = Some(ipc::describe_error(&err)),
+                }
+                self.update(Message::ReloadTemplates)
+            }
+            Message::ToggleTemplate(name, enabled) => {
+                self.config.templates.retain(|t| *t != name);
+                if enabled {
+                    self.config.templates.push(name);
+                }
+                if let Err(err) = self.config.save() {
+                    self.user_error = Some(ipc::describe_error(&err));
+                }
+                Task::none()
+            }
+            Message::RenderTemplate(name) => {
+                let Some(Ok(template)) = self
+                    .templates
+                    .iter()
+                    .find(|t| t.name == name)
+                    .map(|t| t.template.clone())
+                else {
+                    return Task::none();
+                };
+                self.template_renders.insert(name.clone(), Local::now());
+                let monitor = template.monitor;
+                Task::perform(
+                    {
+                        let name = name.clone();
+                        async move {
+                            templates::render(&name, &template)
+                                .map_err(|err| ipc::describe_error(&err))
+                        }
+                    },
+                    move |res| Message::TemplateRendered(name.clone(), monitor, res),
+                )
+            }
+            Message::TemplateTick => {
+                if let Some(reason) = self.automation_paused() {
+                    debug!("Not rendering templates, automation is {reason}");
+                    return Task::none();
+                }
+                if self.ipc_handle.is_none() {
+                    return Task::none();
+                }
+                let now = Local::now();
+                let due: Vec<String> = self
+                    .templates
+                    .iter()
+                    .filter(|t| self.config.templates.contains(&t.name))
+                    .filter(|t| t.is_due(self.template_renders.get(&t.name).copied(), now))
+                    .map(|t| t.name.clone())
+                    .collect();
+                Task::batch(
+                    due.into_iter()
+                        .map(|name| self.update(Message::RenderTemplate(name))),
+                )
+            }
+            Message::TemplateRendered(name, monitor, res) => match res {
+                Ok(path) => self.apply_automatically(Automation::Templates, monitor, path),
+                Err(err) => {
+                    error!("Failed to render template {}: {:?}", name, err);
+                    self.user_error = Some(format!("Template {name}: {err}"));
+                    Task::none()
+                }
+            },
+            Message::CloseSettings => {
+                self.set_page(self.settings_return);
+                Task::none()
+            }
+            Message::ReloadConfig => self.config_reloaded(Config::load()),
+            Message::ResetConfig => self.config_reloaded(Config::reset()),
+            Message::EditHotkey(action, accelerator) => {
+                self.hotkey_inputs.set(action, accelerator);
+                // conflicts show up while typing, not only on save
+                self.user_error = self
+                    .hotkey_inputs
+                    .validate()
+                    .err()
+                    .map(|err| format!("{err}"));
+                Task::none()
+            }
+            Message::SelectMediaKeysOwner(owner) => {
+                self.media_keys_input = owner;
+                Task::none()
+            }
+            Message::SelectNotificationMode(mode) => {
+                self.notifications_input = mode;
+                Task::none()
+            }
+            Message::CheckMediaKeys => Task::perform(
+                async { hotkeys::mpris_player_running() },
+                Message::MediaPlayerRunning,
+            ),
+            Message::MediaPlayerRunning(running) => {
+                if self.config.media_keys != MediaKeysOwner::WhenNoPlayer {
+                    return Task::none();
+                }
+                if let Some(hotkeys) = &mut self.hotkeys
+                    && let Err(err) = hotkeys.set_media_keys(!running)
+                {
+                    error!("{:?}", err);
+                }
+                Task::none()
+            }
+            Message::EditQuietStart(time) => {
+                self.quiet_start_input = time;
+                Task::none()
+            }
+            Message::EditQuietEnd(time) => {
+                self.quiet_end_input = time;
+                Task::none()
+            }
+            Message::EditRotationInterval(mins) => {
+                self.rotation_interval_input = mins;
+                Task::none()
+            }
+            Message::SelectRotationOrder(order) => {
+                self.config.rotation.order = order;
+                Task::none()
+            }
+            Message::EditRotationPerMonitor(per_monitor) => {
+                self.config.rotation.per_monitor = per_monitor;
+                Task::none()
+            }
+            Message::StartRotation => {
+                let Some(choice) = self.selected_source.clone() else {
+                    self.user_error = Some("Pick a source to rotate through first".to_owned());
+                    return Task::none();
+                };
+                match self.rotation_interval_input.trim().parse::<u64>() {
+                    Ok(mins) if (1..=scheduler::MAX_INTERVAL_MINS).contains(&mins) => {
+                        self.config.rotation.interval_mins = mins
+                    }
+                    _ => {
+                        self.user_error = Some(format!(
+                            "The rotation interval should be a number of minutes like 30, at \
+                             most {}",
+                            scheduler::MAX_INTERVAL_MINS
+                        ));
+                        return Task::none();
+                    }
+                }
+                if let Err(err) = self.config.save() {
+                    self.user_error = Some(ipc::describe_error(&err));
+                }
+                let wallpapers: Vec<Wallpaper> =
+                    self.tagged_wallpapers().into_iter().cloned().collect();
+                if wallpapers.is_empty() {
+                    self.user_error = Some(format!("{} has nothing to rotate through", choice));
+                    return Task::none();
+                }
+                let seed = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos() as u64;
+                let scheduler = Scheduler::new(choice.id, wallpapers, self.config.rotation, seed);
+                debug!(
+                    "Rotating through {} wallpaper(s) every {:?}",
+                    scheduler.len(),
+                    scheduler.rotation.interval()
+                );
+                self.scheduler = Some(scheduler);
+                self.update(Message::Rotate)
+            }
+            Message::StopRotation => {
+                debug!("Stopped rotating");
+                self.scheduler = None;
+                Task::none()
+            }
+            Message::Rotate => {
+                if let Some(reason) = self.automation_paused() {
+                    debug!("Not rotating, automation is {reason}");
+                    return Task::none();
+                }
+                let targets = self.rotation_targets();
+                let mut picks = Vec::new();
+                while picks.len() < targets.len()
+                    && let Some((source_id, wallpaper)) = self.queue.pop_front()
+                {
+                    match self.sources.get(&source_id) {
+                        Some(source) => picks.push((source, wallpaper)),
+                        None => debug!(
+                            "Dropped {} from the queue, its source is gone",
+                            wallpaper.name
+                        ),
+                    }
+                }
+                if picks.len() < targets.len()
+                    && let Some(scheduler) = &mut self.scheduler
+                {
+                    match self.sources.get(&scheduler.source_id) {
+                        Some(source) => picks.extend(
+                            scheduler
+                                .next(targets.len() - picks.len())
+                                .into_iter()
+                                .map(|wallpaper| (source.clone(), wallpaper)),
+                        ),
+                        None => self.scheduler = None,
+                    }
+                }
+                Task::batch(
+                    targets
+                        .into_iter()
+                        .zip(picks)
+                        .map(|(monitor, (source, wallpaper))| {
+                            Task::perform(
+                                async move {
+                                    source
+                                        .fetch(&wallpaper)
+                                        .map_err(|err| ipc::describe_error(&err))
+                                },
+                                move |res| Message::RotationFetched(monitor, res),
+                            )
+                        }),
+                )
+            }
+            Message::QueueWallpaper(wallpaper) => {
+                let Some(choice) = &self.selected_source else {
+                    return Task::none();
+                };
+                debug!("Queued {}", wallpaper.name);
+                self.queue.push_back((choice.id.clone(), wallpaper));
+                Task::none()
+            }
+            Message::UnqueueWallpaper(at) => {
+                self.queue.remove(at);
+                Task::none()
+            }
+            Message::ClearQueue => {
+                self.queue.clear();
+                Task::none()
+            }
+            Message::RotationFetched(monitor, res) => match res {
+                Ok(path) => self.apply_automatically(Automation::Rotation, monitor, path),
+                Err(err) => {
+                    self.user_error = Some(err);
+                    Task::none()
+                }
+            },
+            Message::PauseAutomation(pause) => {
+                self.automation_paused_until = Some(pause.until(Local::now()));
+                Task::none()
+            }
+            Message::ResumeAutomation => {
+                self.automation_paused_until = None;
+                Task::none()
+            }
+            Message::VideoPlayback(paused) => {
+                let Some(ipc_handle) = self.ipc_handle.clone() else {
+                    error!("Can't control videos, connected to nothing!");
+                    return Task::none();
+                };
+                let monitors = self.background_opts.monitor_list();
+                Task::perform(
+                    async move {
+                        let res = ipc_handle.video_playback(&monitors, paused).await;
+                        (monitors, res)
+                    },
+                    move |(monitors, res)| {
+                        Message::VideoPlaybackSent(
+                            monitors,
+                            paused,
+                            res.map_err(|err| ipc::describe_error(&err)),
+                        )
+                    },
+                )
+            }
+            Message::VideoPlaybackSent(monitors, paused, res) => {
+                match res {
+                    Ok(()) => self.record_playback(&monitors, paused),
+                    Err(err) => {
+                        error!("Failed to control videos: {:?}", err);
+                        self.user_error = Some(err);
+                    }
+                }
+                Task::none()
+            }
+            Message::TogglePresentation => {
+                let Some(ipc_handle) = self.ipc_handle.clone() else {
+                    error!("Can't toggle presentation mode, connected to nothing!");
+                    return Task::none();
+                };
+                match self.presentation.clone() {
+                    Some(presentation) => {
+                        // only ended once it's left, it can be tried again
+                        // until then
+                        let restores = self
+                            .overrides
+                            .clone()
+                            .end(OverrideKind::Presentation, &self.monitor_indices());
+                        Task::perform(
+                            async move {
+                                presentation::leave(&ipc_handle, &presentation, &restores).await
+                            },
+                            |res| {
+                                Message::PresentationEnded(
+                                    res.map_err(|err| ipc::describe_error(&err)),
+                                )
+                            },
+                        )
+                    }
+                    None => {
+                        let videos_paused = self.videos_paused;
+                        let neutral = self.config.presentation_neutral_wallpaper;
+                        Task::perform(
+                            async move {
+                                presentation::enter(&ipc_handle, videos_paused, neutral).await
+                            },
+                            |res| {
+                                Message::PresentationStarted(
+                                    res.map_err(|err| ipc::describe_error(&err)),
+                                )
+                            },
+                        )
+                    }
+                }
+            }
+            Message::PresentationStarted(res) => {
+                match res {
+                    Ok(presentation) => {
+                        self.videos_paused = true;
+                        if let Some(path) = &presentation.neutral_wallpaper {
+                            self.overrides
+                                .set(OverrideKind::Presentation, None, path.clone());
+                        }
+                        self.presentation = Some(presentation);
+                    }
+                    Err(err) => {
+                        error!("Failed to enter presentation mode: {:?}", err);
+                        self.user_error = Some(err);
+                    }
+                }
+                Task::none()
+            }
+            Message::PresentationEnded(res) => {
+                match res {
+                    Ok(()) => {
+                        self.presentation = None;
+                        self.overrides
+                            .end(OverrideKind::Presentation, &self.monitor_indices());
+                        self.videos_paused = false;
+                    }
+                    Err(err) => {
+                        error!("Failed to leave presentation mode: {:?}", err);
+                        self.user_error = Some(err);
+                    }
+                }
+                Task::none()
+            }
+            Message::ToggleSnapshot => {
+                let Some(ipc_handle) = self.ipc_handle.clone() else {
+                    error!("Can't snapshot videos, connected to nothing!");
+                    return Task::none();
+                };
+                match &self.snapshot {
+                    Some(_) => {
+                        // only ended once the videos are back, it can be
+                        // tried again until then
+                        let restores = self
+                            .overrides
+                            .clone()
+                            .end(OverrideKind::Snapshot, &self.monitor_indices());
+                        Task::perform(
+                            async move { overrides::restore(&ipc_handle, &restores).await },
+                            |res| {
+                                Message::SnapshotRestored(
+                                    res.map_err(|err| ipc::describe_error(&err)),
+                                )
+                            },
+                        )
+                    }
+                    None => {
+                        Task::perform(async move { snapshot::enter(&ipc_handle).await }, |res| {
+                            Message::SnapshotTaken(res.map_err(|err| ipc::describe_error(&err)))
+                        })
+                    }
+                }
+            }
+            Message::SnapshotTaken(res) => {
+                match res {
+                    Ok(snapshot) => {
+                        for frame in &snapshot.frozen {
+                            self.overrides.set(
+                                OverrideKind::Snapshot,
+                                frame.monitor,
+                                frame.path.clone(),
+                            );
+                        }
+                        self.snapshot = Some(snapshot);
+                    }
+                    Err(err) => {
+                        error!("Failed to snapshot videos: {:?}", err);
+                        self.user_error = Some(err);
+                    }
+                }
+                Task::none()
+            }
+            Message::SnapshotRestored(res) => {
+                match res {
+                    Ok(()) => {
+                        self.snapshot = None;
+                        self.overrides
+                            .end(OverrideKind::Snapshot, &self.monitor_indices());
+                    }
+                    Err(err) => {
+                        error!("Failed to restore videos: {:?}", err);
+                        self.user_error = Some(err);
+                    }
+                }
+                Task::none()
+            }
+            Message::EditNightDimming(enabled) => {
+                self.night_dimming_input = enabled;
+                Task::none()
+            }
+            Message::EditNightStart(time) => {
+                self.night_start_input = time;
+                Task::none()
+            }
+            Message::EditNightEnd(time) => {
+                self.night_end_input = time;
+                Task::none()
+            }
+            Message::CheckNightLight => {
+                let dimming = self.config.night_dimming;
+                if !dimming.enabled && !self.night_active {
+                    return Task::none();
+                }
+                Task::perform(
+                    async move { dimming.enabled && nightlight::is_night(dimming) },
+                    Message::NightLightChecked,
+                )
+            }
+            Message::NightLightChecked(night) => {
+                // presentation/snapshot own the wallpapers for now, try again later
+                if night == self.night_active
+                    || self.presentation.is_some()
+                    || self.snapshot.is_some()
+                {
+                    return Task::none();
+                }
+                let Some(ipc_handle) = self.ipc_handle.clone() else {
+                    return Task::none();
+                };
+                debug!("Night dimming: {night}");
+                self.night_active = night;
+                let pins = self.pins.clone();
+                Task::perform(
+                    async move {
+                        for assignment in persist::state()?.assignments {
+                            if pins.is_pinned(assignment.monitor)
+                                || !nightlight::can_dim(&assignment.path)
+                            {
+                                continue;
+                            }
+                            let path = match night {
+                                true => nightlight::dim(&assignment.path)?,
+                                false => assignment.path,
+                            };
+                            // not through `SavedState::assign`, the originals stay saved
+                            ipc_handle
+                                .change_background(assignment.monitor, &path)
+                                .await?;
+                        }
+                        anyhow::Ok(())
+                    },
+                    |res| match res {
+                        Ok(()) => Message::BackgroundSent,
+                        Err(err) => Message::BackgroundSendFailed(ipc::describe_error(&err)),
+                    },
+                )
+            }
+            Message::EditActiveWindowColor(enabled) => {
+                self.active_color_input = enabled;
+                Task::none()
+            }
+            Message::SampleActiveWindow => {
+                if self.automation_paused().is_some() || self.pins.is_pinned(None) {
+                    return Task::none();
+                }
+                Task::perform(
+                    async { active_color::sample().map_err(|err| ipc::describe_error(&err)) },
+                    Message::ActiveWindowSampled,
+                )
+            }
+            Message::ActiveWindowSampled(res) => {
+                let color = match res {
+                    Ok(Some(color)) => color,
+                    Ok(None) => return Task::none(),
+                    Err(err) => {
+                        error!("Failed to sample the focused window: {:?}", err);
+                        return Task::none();
+                    }
+                };
+                let Some(color) = self.active_color.observe(color, Instant::now()) else {
+                    return Task::none();
+                };
+                debug!("Tinting the desktop with {:?}", color);
+                Task::perform(
+                    async move { active_color::render(color).map_err(|err| ipc::describe_error(&err)) },
+                    Message::ActiveColorRendered,
+                )
+            }
+            Message::ActiveColorRendered(res) => {
+                let path = match res {
+                    Ok(path) => path,
+                    Err(err) => {
+                        error!("Failed to render the tint: {:?}", err);
+                        return Task::none();
+                    }
+                };
+                // under presentation mode and the like it waits its turn
+                if !self
+                    .overrides
+                    .set(OverrideKind::ActiveColor, None, path.clone())
+                {
+                    return Task::none();
+                }
+                let Some(ipc_handle) = self.ipc_handle.clone() else {
+                    return Task::none();
+                };
+                Task::perform(
+                    // not through `SavedState::assign`, it follows the focus
+                    async move { ipc_handle.change_background(None, &path).await },
+                    |res| match res {
+                        Ok(()) => Message::BackgroundSent,
+                        Err(err) => Message::BackgroundSendFailed(ipc::describe_error(&err)),
+                    },
+                )
+            }
+            Message::EditCloseToBackground(enabled) => {
+                self.close_to_background_input = enabled;
+                Task::none()
+            }
+            Message::ShowPanel(panel, visible) => {
+                self.panels_input.set_visible(panel, visible);
+                Task::none()
+            }
+            Message::ShiftPanel(panel, up) => {
+                self.panels_input.shift(panel, up);
+                Task::none()
+            }
+            Message::EditPresentationNeutral(neutral) => {
+                self.presentation_neutral_input = neutral;
+                Task::none()
+            }
+            Message::SaveSettings => {
+                self.user_notice = None;
+                self.user_error = None;
+                if let Err(err) = self.hotkey_inputs.validate() {
+                    self.user_error = Some(format!("{err}"));
+                    return Task::none();
+                }
+                let Ok(quiet_hours) =
+                    TimeWindow::parse_inputs(&self.quiet_start_input, &self.quiet_end_input)
+                else {
+                    self.user_error =
+                        Some("Quiet hours should look like 22:00 and 07:00".to_owned());
+                    return Task::none();
+                };
+                let Ok(night_schedule) =
+                    TimeWindow::parse_inputs(&self.night_start_input, &self.night_end_input)
+                else {
+                    self.user_error =
+                        Some("The night schedule should look like 20:00 and 07:00".to_owned());
+                    return Task::none();
+                };
+                let default_monitor = match self.default_monitor_input.trim() {
+                    "" => None,
+                    number => match number.parse::<i32>() {
+                        Ok(number) if number >= 1 => Some(number - 1),
+                        _ => {
+                            self.user_error =
+                                Some("The default monitor should be a number like 1".to_owned());
+                            return Task::none();
+                        }
+                    },
+                };
+                let clear_color = match self.clear_color_input.trim() {
+                    "" => None,
+                    color => match color.parse::<HexColor>() {
+                        Ok(color) => Some(color),
+                        Err(err) => {
+                            self.user_error = Some(format!("Clear color: {err}"));
+                            return Task::none();
+                        }
+                    },
+                };
+                let (connect_timeout, handshake_timeout) = match (
+                    parse_timeout("connect", &self.connect_timeout_input),
+                    parse_timeout("handshake", &self.handshake_timeout_input),
+                ) {
+                    (Ok(connect), Ok(handshake)) => (connect, handshake),
+                    (Err(err), _) | (_, Err(err)) => {
+                        self.user_error = Some(err);
+                        return Task::none();
+                    }
+                };
+                let cache_mb = match self.cache_mb_input.trim() {
+                    "" => None,
+                    mb => match mb.parse::<usize>() {
+                        Ok(mb) if mb >= 1 => Some(mb),
+                        _ => {
+                            self.user_error = Some(
+                                "The preview cache should be a number of megabytes like 64"
+                                    .to_owned(),
+                            );
+                            return Task::none();
+                        }
+                    },
+                };
+                let disk_cache_mb = match self.disk_cache_mb_input.trim() {
+                    "" => None,
+                    mb => match mb.parse::<u64>() {
+                        Ok(mb) if mb >= 1 => Some(mb),
+                        _ => {
+                            self.user_error = Some(
+                                "The disk cache should be a number of megabytes like 512"
+                                    .to_owned(),
+                            );
+                            return Task::none();
+                        }
+                    },
+                };
+                let limit_kbps = match self.bandwidth_input.trim() {
+                    "" => None,
+                    kbps => match kbps.parse::<u64>() {
+                        Ok(kbps) if kbps >= 1 => Some(kbps),
+                        _ => {
+                            self.user_error = Some(
+                                "The download limit should be a number of KB/s like 500".to_owned(),
+                            );
+                            return Task::none();
+                        }
+                    },
+                };
+                let proxies = net::Proxies {
+                    proxy: Some(self.proxy_input.trim().to_owned())
+                        .filter(|proxy| !proxy.is_empty()),
+                    ca_bundle: Some(self.ca_bundle_input.trim())
+                        .filter(|bundle| !bundle.is_empty())
+                        .map(PathBuf::from),
+                    // only in the config file
+                    sources: self.config.proxies.sources.clone(),
+                };
+                if let Err(err) = proxies.validate() {
+                    self.user_error = Some(format!("{err}"));
+                    return Task::none();
+                }
+                let socket = self.socket_input.trim();
+                if !socket.is_empty()
+                    && let Err(err) = ipc::check_socket_path(socket)
+                {
+                    self.user_error = Some(format!("{err}"));
+                    return Task::none();
+                }
+                let limits = match (
+                    parse_limit("hashing threads", &self.workers_input),
+                    parse_limit("downloads", &self.downloads_input),
+                    parse_limit("decodes", &self.decodes_input),
+                ) {
+                    (Ok(workers), Ok(downloads), Ok(decodes)) => Limits {
+                        workers,
+                        downloads,
+                        decodes,
+                    },
+                    (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => {
+                        self.user_error = Some(err);
+                        return Task::none();
+                    }
+                };
+                if let Some(err) = rules::parse_all(&self.rule_inputs).1.into_iter().next() {
+                    self.user_error = Some(err);
+                    return Task::none();
+                }
+                let rules = self
+                    .rule_inputs
+                    .iter()
+                    .filter(|input| !input.trim().is_empty())
+                    .map(|input| input.trim().to_owned())
+                    .collect();
+                self.config.quiet_hours = quiet_hours;
+                self.config.rules = rules;
+                self.config.night_dimming = NightDimming {
+                    enabled: self.night_dimming_input,
+                    schedule: night_schedule,
+                };
+                self.config.presentation_neutral_wallpaper = self.presentation_neutral_input;
+                self.config.close_to_background = self.close_to_background_input;
+                self.config.panels = self.panels_input.clone();
+                let untint = self.config.active_window_color && !self.active_color_input;
+                self.config.active_window_color = self.active_color_input;
+                self.config.notifications = self.notifications_input;
+
+                self.config.hotkeys = self.hotkey_inputs.clone();
+                self.config.media_keys = self.media_keys_input;
+                self.config.socket =
+                    Some(self.socket_input.trim().to_owned()).filter(|socket| !socket.is_empty());
+                self.config.theme = self.theme_input;
+                self.config.start_page = self.start_page_input;
+                self.config.palette = self.palette_input;
+                self.config.default_monitor = default_monitor;
+                self.config.clear_color = clear_color;
+                self.config.wallpapers = self.wallpapers_input.clone();
+                self.config.connect_timeout = connect_timeout;
+                self.config.handshake_timeout = handshake_timeout;
+                self.config.limits = limits;
+                limits::set(limits);
+                self.config.cache_mb = cache_mb;
+                self.config.disk_cache_mb = disk_cache_mb;
+                self.config.bandwidth = net::Bandwidth {
+                    limit_kbps,
+                    sync_on_metered: self.sync_on_metered_input,
+                };
+                net::set_bandwidth(self.config.bandwidth);
+                let check_updates = self.check_updates_input && !self.config.check_updates;
+                self.config.check_updates = self.check_updates_input;
+                if !self.config.check_updates {
+                    self.update = None;
+                }
+                net::set_proxies(proxies.clone());
+                self.config.proxies = proxies;
+                self.previews.set_budget(self.config.cache_budget());
+                let filters_changed = !self.config.kiosk && {
+                    let filters: ContentFilters = self
+                        .filter_inputs
+                        .iter()
+                        .map(|(id, (sfw_only, blocked))| {
+                            let filter = ContentFilter {
+                                sfw_only: *sfw_only,
+                                blocked: ContentFilter::parse_blocked(blocked),
+                            };
+                            (id.clone(), filter)
+                        })
+                        .filter(|(_, filter)| !filter.is_empty())
+                        .collect();
+                    let changed = filters != self.config.content_filters;
+                    self.config.content_filters = filters;
+                    changed
+                };
+                sources::filter::set(self.config.content_filters.clone());
+                self.refresh_endpoints();
+                if let Err(err) = self.config.save() {
+                    self.user_error = Some(ipc::describe_error(&err));
+                    return Task::none();
+                }
+                match &mut self.hotkeys {
+                    Some(hotkeys) => match hotkeys.apply(&self.config.hotkeys) {
+                        Ok(()) => self.user_notice = Some("Settings saved".to_owned()),
+                        Err(err) => self.user_error = Some(format!("{err}")),
+                    },
+                    None => {
+                        self.user_notice =
+                            Some("Settings saved, but global hotkeys aren't available".to_owned())
+                    }
+                }
+                // what's listed may have just been filtered out
+                let relist = match self.selected_source.clone() {
+                    Some(choice) if filters_changed => self.update(Message::SelectSource(choice)),
+                    _ => Task::none(),
+                };
+                let check = match check_updates {
+                    true => Task::done(Message::CheckUpdates),
+                    false => Task::none(),
+                };
+                let untint = match untint {
+                    true => self.end_override(OverrideKind::ActiveColor),
+                    false => Task::none(),
+                };
+                Task::batch([self.sync_media_keys(), relist, check, untint])
+            }
+            Message::EditSocket(socket) => {
+                self.socket_input = socket;
+                Task::none()
+            }
+            Message::UseSocket => {
+                let socket = self.socket_input.trim().to_owned();
+                if socket.is_empty() {
+                    return self.update(Message::ResetSocket);
+                }
+                if let Err(err) = ipc::check_socket_path(&socket) {
+                    self.user_error = Some(format!("{err}"));
+                    return Task::none();
+                }
+                self.user_error = None;
+                self.config.socket = Some(socket);
+                if let Err(err) = self.config.save() {
+                    self.user_error = Some(ipc::describe_error(&err));
+                }
+                // the new socket wins over whatever was picked
+                self.selected_endpoint = None;
+                self.refresh_endpoints();
+                Task::none()
+            }
+            Message::ResetSocket => {
+                self.user_error = None;
+                self.socket_input.clear();
+                self.config.socket = None;
+                if let Err(err) = self.config.save() {
+                    self.user_error = Some(ipc::describe_error(&err));
+                }
+                self.selected_endpoint = None;
+                self.refresh_endpoints();
+                Task::none()
+            }
+            Message::SelectTheme(theme) => {
+                self.theme_input = theme;
+                Task::none()
+            }
+            Message::SelectStartPage(page) => {
+                self.start_page_input = page;
+                Task::none()
+            }
+            Message::SelectPalette(palette) => {
+                self.palette_input = palette;
+                Task::none()
+            }
+            Message::EditDefaultMonitor(monitor) => {
+                self.default_monitor_input = monitor;
+                Task::none()
+            }
+            Message::EditClearColor(color) => {
+                self.clear_color_input = color;
+                Task::none()
+            }
+            Message::EditConnectTimeout(secs) => {
+                self.connect_timeout_input = secs;
+                Task::none()
+            }
+            Message::EditHandshakeTimeout(secs) => {
+                self.handshake_timeout_input = secs;
+                Task::none()
+            }
+            Message::EditWorkers(workers) => {
+                self.workers_input = workers;
+                Task::none()
+            }
+            Message::EditDownloads(downloads) => {
+                self.downloads_input = downloads;
+                Task::none()
+            }
+            Message::EditDecodes(decodes) => {
+                self.decodes_input = decodes;
+                Task::none()
+            }
+            Message::EditCacheBudget(mb) => {
+                self.cache_mb_input = mb;
+                Task::none()
+            }
+            Message::EditDiskCacheLimit(mb) => {
+                self.disk_cache_mb_input = mb;
+                Task::none()
+            }
+            Message::EditBandwidth(kbps) => {
+                self.bandwidth_input = kbps;
+                Task::none()
+            }
+            Message::EditSyncOnMetered(sync) => {
+                self.sync_on_metered_input = sync;
+                Task::none()
+            }
+            Message::ToggleOffline(offline) => {
+                debug!("Offline mode {}", if offline { "on" } else { "off" });
+                self.config.offline = offline;
+                net::set_offline(offline);
+                if let Err(err) = self.config.save() {
+                    self.user_error = Some(ipc::describe_error(&err));
+                }
+                // whatever was skipped while offline
+                match offline {
+                    true => Task::none(),
+                    false => Task::done(Message::SyncSources),
+                }
+            }
+            Message::EditProxy(proxy) => {
+                self.proxy_input = proxy;
+                Task::none()
+            }
+            Message::EditCaBundle(bundle) => {
+                self.ca_bundle_input = bundle;
+                Task::none()
+            }
+            Message::ToggleSfwOnly(id, sfw_only) => {
+                if let Some((input, _)) = self.filter_inputs.get_mut(&id) {
+                    *input = sfw_only;
+                }
+                Task::none()
+            }
+            Message::EditBlocked(id, blocked) => {
+                if let Some((_, input)) = self.filter_inputs.get_mut(&id) {
+                    *input = blocked;
+                }
+                Task::none()
+            }
+            Message::CleanupCaches => {
+                let limit = self.config.disk_cache_limit();
+                Task::perform(
+                    async move { disk_cache::cleanup(limit).map_err(|err| ipc::describe_error(&err)) },
+                    Message::CachesCleaned,
+                )
+            }
+            Message::CachesCleaned(res) => {
+                match res {
+                    Ok(report) if report.evicted > 0 => debug!(
+                        "Evicted {} cached file(s), freed {}",
+                        report.evicted,
+                        preview::human_size(report.freed)
+                    ),
+                    Ok(_) => {}
+                    Err(err) => error!("Failed to clean up the caches: {}", err),
+                }
+                Task::none()
+            }
+            Message::Reclaimable(res) => {
+                match res {
+                    Ok(bytes) => self.reclaimable = Some(bytes),
+                    Err(err) => error!("Failed to measure the caches: {}", err),
+                }
+                Task::none()
+            }
+            Message::ClearCaches => Task::perform(
+                async { disk_cache::clear().map_err(|err| ipc::describe_error(&err)) },
+                Message::CachesCleared,
+            ),
+            Message::CachesCleared(res) => {
+                match res {
+                    Ok(report) => {
+                        self.reclaimable = Some(0);
+                        self.user_notice =
+                            Some(format!("Freed {}", preview::human_size(report.freed)));
+                    }
+                    Err(err) => {
+                        error!("Failed to clear the caches: {}", err);
+                        self.user_error = Some(err);
+                    }
+                }
+                Task::none()
+            }
+            Message::AddStartupWallpaper => {
+                if self.background_opts.path.as_os_str().is_empty() {
+                    self.user_error = Some("Select a file first!".to_owned());
+                    return Task::none();
+                }
+                for monitor in self.background_opts.targets() {
+                    self.wallpapers_input.insert(
+                        MonitorTarget::from(monitor).to_string(),
+                        self.background_opts.path.clone(),
+                    );
+                }
+                Task::none()
+            }
+            Message::RemoveStartupWallpaper(monitor) => {
+                self.wallpapers_input.remove(&monitor);
+                Task::none()
+            }
+            Message::EditRule(index, rule) => {
+                if let Some(input) = self.rule_inputs.get_mut(index) {
+                    *input = rule;
+                }
+                Task::none()
+            }
+            Message::AddRule => {
+                self.rule_inputs.push(String::new());
+                Task::none()
+            }
+            Message::RemoveRule(index) => {
+                if index < self.rule_inputs.len() {
+                    self.rule_inputs.remove(index);
+                }
+                Task::none()
+            }
+            Message::EditCheckUpdates(enabled) => {
+                self.check_updates_input = enabled;
+                Task::none()
+            }
+            Message::CheckUpdates => {
+                if !self.config.check_updates || self.config.offline {
+                    return Task::none();
+                }
+                Task::perform(
+                    async { updates::check().map_err(|err| ipc::describe_error(&err)) },
+                    Message::UpdateChecked,
+                )
+            }
+            Message::UpdateChecked(res) => {
+                match res {
+                    Ok(Some(release)) => {
+                        debug!("{} is available", release.version);
+                        self.update = Some(release);
+                    }
+                    Ok(None) => debug!("Up to date"),
+                    // not worth bothering the user about
+                    Err(err) => warn!("Failed to check for updates: {}", err),
+                }
+                Task::none()
+            }
+            Message::OpenRelease => {
+                if let Some(release) = &self.update
+                    && let Err(err) = updates::open(release)
+                {
+                    self.user_error = Some(ipc::describe_error(&err));
+                }
+                Task::none()
+            }
+            Message::DismissUpdate => {
+                self.update = None;
+                Task::none()
+            }
+            Message::CheckBattery => Task::batch([
+                Task::perform(async { rules::battery_percent() }, Message::BatteryChecked),
+                Task::perform(async { energy::discharging() }, Message::PowerChecked),
+            ]),
+            Message::RefreshLiveState => {
+                let Some(ipc_handle) = self.ipc_handle.clone() else {
+                    return Task::none();
+                };
+                if !ipc_handle
+                    .capabilities()
+                    .contains(IpcXabCapabilities::BackgroundState)
+                {
+                    return Task::none();
+                }
+                Task::perform(
+                    async move { ipc_handle.get_background_state().await },
+                    |res| Message::LiveStateReceived(res.map_err(|err| ipc::describe_error(&err))),
+                )
+            }
+            Message::LiveStateReceived(res) => {
+                match res {
+                    Ok(live) if live != self.live => {
+                        self.live = live;
+                        self.layout.selection_changed();
+                    }
+                    Ok(_) => {}
+                    Err(err) => warn!("Failed to ask xab what the monitors show: {}", err),
+                }
+                Task::none()
+            }
+            Message::PowerChecked(discharging) => {
+                if discharging != self.discharging {
+                    debug!("Running on battery: {}", discharging);
+                    self.discharging = discharging;
+                }
+                Task::none()
+            }
+            Message::EnergyEstimated(path, res) => {
+                if let Err(err) = &res {
+                    warn!("Can't estimate the battery impact: {}", err);
+                }
+                self.energy.insert(path, res);
+                self.layout.selection_changed();
+                Task::none()
+            }
+            Message::BatteryChecked(battery) => {
+                if battery == self.battery {
+                    return Task::none();
+                }
+                debug!("Battery: {:?}%", battery);
+                self.battery = battery;
+                self.run_rules(RuleEvent::BatteryChanged)
+            }
+            Message::ImportPack => Task::perform(
+                async move {
+                    FileDialog::new()
+                        .set_directory("~")
+                        .add_filter(
+                            "Wallpaper pack",
+                            &["zip", "tar", "gz", "tgz", "xz", "zst", "bz2"],
+                        )
+                        .pick_file()
+                },
+                Message::SelectedPack,
+            ),
+            Message::SelectedPack(archive) => match archive {
+                Some(archive) => {
+                    debug!("Importing pack `{}`", archive.display());
+                    self.user_notice = Some(format!("Importing {}...", archive.display()));
+                    // blocks for a while on big packs, so it gets a thread
+                    let (sender, receiver) = mpsc::unbounded();
+                    thread::spawn(move || {
+                        let last_percent = AtomicU64::new(u64::MAX);
+                        let res = library::pack::import(&archive, |progress| {
+                            // one message per percent is plenty
+                            let percent = progress.percent();
+                            if last_percent.swap(percent, Ordering::Relaxed) != percent {
+                                let _ =
+                                    sender.unbounded_send(Message::PackImportProgress(progress));
+                            }
+                        })
+                        .map(|report| {
+                            format!(
+                                "Imported {} wallpaper(s) from {}, skipped {} duplicate(s)",
+                                report.imported, report.pack, report.duplicates
+                            )
+                        })
+                        .map_err(|err| ipc::describe_error(&err));
+                        let _ = sender.unbounded_send(Message::PackImported(res));
+                    });
+                    Task::run(receiver, |message| message)
+                }
+                None => Task::none(),
+            },
+            Message::PackImportProgress(progress) => {
+                if progress.total_bytes > 0 {
+                    self.user_notice = Some(format!(
+                        "Checking for duplicates... {}%",
+                        progress.percent()
+                    ));
+                }
+                Task::none()
+            }
+            Message::PackImported(res) => {
+                match res {
+                    Ok(notice) => {
+                        debug!("{notice}");
+                        self.user_notice = Some(notice);
+                    }
+                    Err(err) => {
+                        error!("Failed to import pack: {:?}", err);
+                        self.user_error = Some(err);
+                    }
+                }
+                Task::none()
+            }
+            Message::InstallDesktopEntry => Task::perform(
+                async { desktop::install().map_err(|err| ipc::describe_error(&err)) },
+                Message::DesktopEntryInstalled,
+            ),
+            Message::DesktopEntryInstalled(res) => {
+                match res {
+                    Ok(path) => {
+                        self.user_notice = Some(format!(
+                            "Registered as a wallpaper handler ({})",
+                            path.display()
+                        ))
+                    }
+                    Err(err) => {
+                        error!("Failed to install the desktop entry: {:?}", err);
+                        self.user_error = Some(err);
+                    }
+                }
+                Task::none()
+            }
+            Message::DetachPreview => {
+                if let Some(id) = self.preview_window {
+                    return window::gain_focus(id);
+                }
+                let (id, open) = window::open(window::Settings {
+                    size: Size::new(640.0, 360.0),
+                    level: window::Level::AlwaysOnTop,
+                    ..window::Settings::default()
+                });
+                self.preview_window = Some(id);
+                self.preview_fullscreen = false;
+                open.discard()
+            }
+            Message::TogglePreviewFullscreen => {
+                let Some(id) = self.preview_window else {
+                    return Task::none();
+                };
+                // fullscreen on whatever monitor the window was dragged to
+                self.preview_fullscreen = !self.preview_fullscreen;
+                window::set_mode(
+                    id,
+                    match self.preview_fullscreen {
+                        true => window::Mode::Fullscreen,
+                        false => window::Mode::Windowed,
+                    },
+                )
+            }
+            Message::SelectSlideInterval(interval) => {
+                self.slide_interval = interval;
+                Task::none()
+            }
+            Message::SelectTransition(transition) => {
+                self.transition = transition;
+                Task::none()
+            }
+            Message::StartSlideshow => {
+                if let Some(slideshow) = &self.slideshow {
+                    return window::gain_focus(slideshow.window);
+                }
+                let Some(choice) = &self.selected_source else {
+                    return Task::none();
+                };
+
+                let wallpapers = self.previewable_wallpapers();
+                if wallpapers.is_empty() {
+                    self.user_error = Some("Nothing to preview in this source".to_owned());
+                    return Task::none();
+                }
+
+                debug!("Starting slideshow of {} wallpaper(s)", wallpapers.len());
+                let (id, open) = window::open(window::Settings {
+                    size: Size::new(960.0, 540.0),
+                    ..window::Settings::default()
+                });
+                self.slideshow = Some(Slideshow::new(
+                    id,
+                    choice.id.clone(),
+                    wallpapers,
+                    self.slide_interval,
+                    self.transition,
+                ));
+                Task::batch([open.discard(), self.fetch_slide()])
+            }
+            Message::SlideshowNext => {
+                if let Some(slideshow) = &mut self.slideshow {
+                    slideshow.advance();
+                }
+                self.fetch_slide()
+            }
+            Message::SlideshowFetched(id, res) => {
+                let Some(slideshow) = self.slideshow.as_mut().filter(|s| s.window == id) else {
+                    return Task::none();
+                };
+                match res {
+                    Ok(path) if slideshow::is_previewable(&path) => slideshow.show(&path),
+                    // keep showing the previous one until the next tick
+                    Ok(path) => debug!("Can't preview `{}`, skipping", path.display()),
+                    Err(err) => error!("Failed to fetch slide: {:?}", err),
+                }
+                Task::none()
+            }
+            // nothing to do, just makes iced redraw the fade
+            Message::SlideshowFrame => Task::none(),
+            Message::StartComparison => {
+                if let Some(comparison) = &self.comparison {
+                    return window::gain_focus(comparison.window);
+                }
+                let Some(choice) = &self.selected_source else {
+                    return Task::none();
+                };
+
+                let candidates = self.pick_candidates();
+                if candidates.len() < 2 {
+                    self.user_error = Some("Need at least two wallpapers to compare".to_owned());
+                    return Task::none();
+                }
+
+                debug!("Comparing {} wallpaper(s)", candidates.len());
+                let (id, open) = window::open(window::Settings {
+                    size: Size::new(1200.0, 480.0),
+                    ..window::Settings::default()
+                });
+                self.comparison = Some(Comparison::new(id, choice.id.clone(), candidates));
+                Task::batch([open.discard(), self.fetch_candidates()])
+            }
+            Message::ShuffleComparison => {
+                let candidates = self.pick_candidates();
+                let restore = self.stop_trying();
+                if let Some(comparison) = &mut self.comparison {
+                    comparison.replace(candidates);
+                }
+                Task::batch([restore, self.fetch_candidates()])
+            }
+            Message::ComparisonFetched(id, wallpaper, res) => {
+                let Some(comparison) = self.comparison.as_mut().filter(|c| c.window == id) else {
+                    return Task::none();
+                };
+                if let Err(err) = &res {
+                    error!("Failed to fetch {}: {:?}", wallpaper.name, err);
+                }
+                comparison.fetched(&wallpaper, res);
+                Task::none()
+            }
+            Message::TryCandidates => {
+                let Some(comparison) = &mut self.comparison else {
+                    return Task::none();
+                };
+                comparison.trying = Some(0);
+                self.try_candidate()
+            }
+            Message::TryNextCandidate => match self.comparison.as_mut().map(Comparison::try_next) {
+                Some(Some(_)) => self.try_candidate(),
+                // everyone had their turn
+                Some(None) => self.stop_trying(),
+                None => Task::none(),
+            },
+            Message::StopTrying => self.stop_trying(),
+            Message::PickCandidate(index) => {
+                let Some(comparison) = self.comparison.take() else {
+                    return Task::none();
+                };
+                let Some(path) = comparison.path(index) else {
+                    self.comparison = Some(comparison);
+                    return Task::none();
+                };
+                debug!("Picked `{}`", path.display());
+                // replaced by the pick, nothing to put back
+                self.overrides
+                    .end(OverrideKind::Trying, &self.monitor_indices());
+                self.background_opts.path = path.to_path_buf();
+                Task::batch([
+                    window::close(comparison.window),
+                    self.update(Message::SendBackgroundOptions),
+                ])
+            }
+            Message::SyncSources => {
+                if self.syncing_sources {
+                    return Task::none();
+                }
+                if let Some(reason) = self.automation_paused() {
+                    debug!("Not syncing sources, automation is {reason}");
+                    return Task::none();
+                }
+                self.syncing_sources = true;
+                let registry = self.sources.clone();
+                let sync = Task::perform(
+                    async move {
+                        sources::sync::sync_due(&registry).map_err(|err| ipc::describe_error(&err))
+                    },
+                    Message::SourcesSynced,
+                );
+                // keeps hotkeys and rules picking from what's in the channel now
+                let Some(source) = self
+                    .selected_source
+                    .as_ref()
+                    .and_then(|choice| self.sources.get(&choice.id))
+                    .filter(|source| source.follows_changes())
+                else {
+                    return sync;
+                };
+                let id = source.id().to_owned();
+                Task::batch([
+                    sync,
+                    Task::perform(
+                        async move { source.list().map_err(|err| ipc::describe_error(&err)) },
+                        move |res| Message::SourceListed(id.clone(), res),
+                    ),
+                ])
+            }
+            Message::SourcesSynced(res) => {
+                self.syncing_sources = false;
+                match persist::state() {
+                    Ok(state) => self.source_sync = state.source_sync,
+                    Err(err) => error!("Failed to reload sync state: {:?}", err),
+                }
+                match res {
+                    Ok(reports) => {
+                        for (id, report) in reports {
+                            debug!("Synced {}: {:?}", id, report);
+                        }
+                    }
+                    Err(err) => error!("Failed to sync sources: {:?}", err),
+                }
+                Task::none()
+            }
+            Message::RestartXab => {
+                let Some(ipc_handle) = self.ipc_handle.clone() else {
+                    error!("Can't restart xab, connected to nothing!");
+                    return Task::none();
+                };
+                Task::perform(async move { ipc_handle.restart_xab().await }, |res| {
+                    Message::DaemonControlSent(
+                        IpcCommands::Restart,
+                        res.map_err(|err| ipc::describe_error(&err)),
+                    )
+                })
+            }
+            Message::AskShutdownXab => {
+                self.confirm_shutdown = true;
+                Task::none()
+            }
+            Message::CancelShutdownXab => {
+                self.confirm_shutdown = false;
+                Task::none()
+            }
+            Message::ExplainCapabilities(missing) => {
+                self.explain_missing = Some(missing);
+                Task::none()
+            }
+            Message::CopyCapabilities => iced::clipboard::write(self.capabilities_report()),
+            Message::CloseCapabilities => {
+                self.explain_missing = None;
+                Task::none()
+            }
+            Message::OpenSchedule => {
+                self.schedule = Some((Horizon::default(), self.plan_schedule(Horizon::default())));
+                Task::none()
+            }
+            Message::SelectScheduleHorizon(horizon) => {
+                self.schedule = Some((horizon, self.plan_schedule(horizon)));
+                Task::none()
+            }
+            Message::CloseSchedule => {
+                self.schedule = None;
+                Task::none()
+            }
+            Message::ShutdownXab => {
+                self.confirm_shutdown = false;
+                let Some(ipc_handle) = self.ipc_handle.clone() else {
+                    error!("Can't shut xab down, connected to nothing!");
+                    return Task::none();
+                };
+                Task::perform(async move { ipc_handle.shutdown_xab().await }, |res| {
+                    Message::DaemonControlSent(
+                        IpcCommands::Shutdown,
+                        res.map_err(|err| ipc::describe_error(&err)),
+                    )
+                })
+            }
+            Message::DaemonControlSent(command, res) => {
+                if let Err(err) = res {
+                    error!("Failed to send {:?}: {:?}", command, err);
+                    self.user_error = Some(err);
+                    return Task::none();
+                }
+                let Some(ipc_handle) = &self.ipc_handle else {
+                    return Task::none();
+                };
+                ipc_handle.mark_shutting_down();
+                if command == IpcCommands::Shutdown {
+                    // it's not coming back, nothing to wait for
+                    self.shutdown = None;
+                    return self.update(Message::Disconnected);
+                }
+                // xab might've announced it already, with its own grace period
+                if self.shutdown.is_none() {
+                    self.shutdown = Some(ShutdownNotice {
+                        deadline: Instant::now() + RESTART_GRACE,
+                        path: ipc_handle.path.clone(),
+                    });
+                }
+                Task::none()
+            }
+            Message::Disconnect => match &self.ipc_handle {
+                Some(ipc_handle) => {
+                    // asked for, so don't wait for xab to come back
+                    self.shutdown = None;
+                    let ipc_clone = ipc_handle.clone();
+                    Task::perform(async move { ipc_clone.close().await }, |_| {
+                        Message::Disconnected
+                    })
+                }
+                None => {
+                    error!("Can't disconnect, connected to nothing!");
+                    Task::none()
+                }
+            },
+            Message::Disconnected => {
+                self.ipc_handle = None;
+                self.live.clear();
+                self.confirm_shutdown = false;
+                self.shared.write().monitors.clear();
+                self.arriving_monitors.clear();
+                self.night_active = false;
+                if self.shutdown.is_some() {
+                    // the tunnel still leads to where xab will be
+                    self.set_page(Page::WaitingForXab);
+                    return Task::none();
+                }
+                self.tunnel = None;
+                self.set_page(Page::Connect);
+                self.refresh_endpoints();
+                Task::none()
+            }
+        }
+    }
+
+    /// Grabs or releases the media keys according to the config
+    fn sync_media_keys(&mut self) -> Task<Message> {
+        let Some(hotkeys) = &mut self.hotkeys else {
+            return Task::none();
+        };
+        let grab = match self.config.media_keys {
+            MediaKeysOwner::Players => false,
+            MediaKeysOwner::Wallpaper => true,
+            MediaKeysOwner::WhenNoPlayer => return Task::done(Message::CheckMediaKeys),
+        };
+        if let Err(err) = hotkeys.set_media_keys(grab) {
+            error!("{:?}", err);
+            self.user_error = Some(format!("{err}"));
+        }
+        Task::none()
+    }
+
+    /// Tells the on-event hook, `input` is the event without the `hook` field
+    fn event_hook(&self, mut input: serde_json::Value) -> Task<Message> {
+        if self.config.hooks.on_event.is_none() {
+            return Task::none();
+        }
+        input["hook"] = "on-event".into();
+        Task::future(hooks::notify(self.config.hooks.on_event.clone(), input)).discard()
+    }
+
+    /// Applies `path` for rules and scripts, unless the monitor is pinned
+    fn apply_automatically(
+        &mut self,
+        by: Automation,
+        monitor: Option<i32>,
+        path: PathBuf,
+    ) -> Task<Message> {
+        let Some(ipc_handle) = self.ipc_handle.clone() else {
+            return Task::none();
+        };
+        if self.pins.is_pinned(monitor) {
+            debug!("Not applying {}, the monitor is pinned", path.display());
+            return Task::none();
+        }
+        if let Some(blocker) = self.claims.blocker(
+            by,
+            monitor,
+            &self.running_automations(),
+            &self.config.precedence,
+            &self.monitors(),
+        ) {
+            debug!("{by} left {} out, {blocker} goes first", path.display());
+            return Task::none();
+        }
+        self.claims.claim(by, monitor);
+        let hooks = self.config.hooks.clone();
+        Task::perform(
+            async move {
+                let path = hooks::apply(&ipc_handle, &hooks, monitor.as_slice(), path).await?;
+                let scaling = ipc_handle.scaling(monitor);
+                persist::update(|state| state.assign(monitor, path, scaling))
+            },
+            |res| match res {
+                Ok(()) => Message::BackgroundSent,
+                Err(err) => Message::BackgroundSendFailed(ipc::describe_error(&err)),
+            },
+        )
+    }
+
+    /// Calls the `event` handler of every enabled script
+    fn run_scripts(&self, event: ScriptEvent) -> Task<Message> {
+        if let Some(reason) = self.automation_paused() {
+            debug!("Not running scripts for {event}, automation is {reason}");
+            return Task::none();
+        }
+        if self.ipc_handle.is_none() {
+            return Task::none();
+        }
+        let monitors = self.monitors();
+        Task::batch(
+            self.scripts
+                .iter()
+                .filter(|s| self.config.scripts.contains(&s.name) && s.handles(event))
+                .filter_map(|s| s.prepare(event, monitors.clone()))
+                .map(Self::spawn_script),
+        )
+    }
+
+    /// Scripts block, so they run off the gui thread
+    fn spawn_script(run: scripts::Run) -> Task<Message> {
+        Task::perform(
+            async move { tokio::task::spawn_blocking(move || scripts::run(run)).await },
+            |res| match res {
+                Ok(outcome) => Message::ScriptFinished(outcome),
+                Err(err) => Message::BackgroundSendFailed(format!("Script panicked: {err}")),
+            },
+        )
+    }
+
+    /// Runs the actions of every rule matching `event`
+    fn run_rules(&mut self, event: RuleEvent) -> Task<Message> {
+        if let Some(reason) = self.automation_paused() {
+            debug!("Not running rules for {event}, automation is {reason}");
+            return Task::none();
+        }
+        let Some(ipc_handle) = self.ipc_handle.clone() else {
+            return Task::none();
+        };
+        let facts = Facts {
+            monitors: self.monitors().len(),
+            battery: self.battery,
+            hour: Local::now().hour(),
+        };
+
+        let actions: Vec<RuleAction> = self
+            .config
+            .parsed_rules()
+            .into_iter()
+            .filter(|rule| rule.matches(event, &facts))
+            .inspect(|rule| debug!("Rule matched: {rule}"))
+            .map(|rule| rule.action)
+            .collect();
+        Task::batch(actions.into_iter().map(|action| {
+            let ipc_handle = ipc_handle.clone();
+            match action {
+                RuleAction::PauseVideos | RuleAction::ResumeVideos => {
+                    let paused = action == RuleAction::PauseVideos;
+                    self.record_playback(&[], Some(paused));
+                    Task::perform(
+                        async move { ipc_handle.set_videos_paused(paused).await },
+                        |res| match res {
+                            Ok(()) => Message::BackgroundSent,
+                            Err(err) => Message::BackgroundSendFailed(ipc::describe_error(&err)),
+                        },
+                    )
+                }
+                RuleAction::Apply(path) => self.apply_automatically(Automation::Rules, None, path),
+                RuleAction::Clear => {
+                    let color = self.config.clear_color();
+                    Task::perform(
+                        async move { ipc_handle.clear_backgrounds(&[], color).await },
+                        |res| match res {
+                            Ok(()) => Message::BackgroundSent,
+                            Err(err) => Message::BackgroundSendFailed(ipc::describe_error(&err)),
+                        },
+                    )
+                }
+            }
+        }))
+    }
+
+    fn run_hotkey_action(&mut self, action: HotkeyAction) -> Task<Message> {
+        if action == HotkeyAction::PauseVideos {
+            let Some(ipc_handle) = self.ipc_handle.clone() else {
+                error!("Can't pause videos, connected to nothing!");
+                return Task::none();
+            };
+            self.record_playback(&[], None);
+            return Task::perform(
+                async move { ipc_handle.toggle_pause_videos().await },
+                |res| match res {
+                    Ok(()) => Message::BackgroundSent,
+                    Err(err) => Message::BackgroundSendFailed(ipc::describe_error(&err)),
+                },
+            );
+        }
+
+        if self
+            .background_opts
+            .targets()
+            .into_iter()
+            .any(|monitor| self.pins.is_pinned(monitor))
+        {
+            self.user_notice = Some(format!("{action}: the wallpaper is pinned, unpin it first"));
+            return Task::none();
+        }
+        let wallpapers = self.tagged_wallpapers();
+        if wallpapers.is_empty() {
+            self.user_error = Some(format!("{action}: pick a source first"));
+            return Task::none();
+        }
+        let len = wallpapers.len();
+        let current = wallpapers
+            .iter()
+            .position(|w| w.location == Location::Local(self.background_opts.path.clone()));
+        let index = match action {
+            HotkeyAction::NextWallpaper => current.map_or(0, |i| i + 1),
+            HotkeyAction::PreviousWallpaper => current.map_or(0, |i| i + len - 1),
+            // good enough of a random number for picking a wallpaper
+            _ => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .subsec_nanos() as usize,
+        } % len;
+
+        let wallpaper = wallpapers[index].clone();
+        // the window is probably hidden behind whatever has focus
+        let notify = self.notify("Wallpaper changed", &wallpaper.name);
+        Task::batch([
+            self.update(Message::ApplySourceWallpaper(wallpaper)),
+            notify,
+        ])
+    }
+
+    /// Desktop notification, held back during quiet hours and presentations
+    fn notify(&self, summary: &str, body: &str) -> Task<Message> {
+        if let Some(reason) = self.automation_paused() {
+            debug!("Not showing `{summary}`, notifications are {reason}");
+            return Task::none();
+        }
+        let mode = self.config.notifications;
+        let (summary, body) = (summary.to_owned(), body.to_owned());
+        Task::future(async move {
+            if let Err(err) = notify::send(mode, &summary, &body) {
+                error!("Failed to show a notification: {:?}", err);
+            }
+        })
+        .discard()
+    }
+
+    /// Wallpapers of the selected source, only the selected tag's if one is
+    fn tagged_wallpapers(&self) -> Vec<&Wallpaper> {
+        self.source_wallpapers
+            .iter()
+            .filter(|w| {
+                self.source_tag
+                    .as_ref()
+                    .is_none_or(|tag| w.tags.contains(tag))
+            })
+            .collect()
+    }
+
+    /// Wallpapers of the selected source that could be shown in a window
+    fn previewable_wallpapers(&self) -> Vec<Wallpaper> {
+        self.tagged_wallpapers()
+            .into_iter()
+            .filter(|w| match &w.location {
+                Location::Local(path) => slideshow::is_previewable(path),
+                // can't know before fetching
+                _ => true,
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn pick_candidates(&self) -> Vec<Wallpaper> {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        compare::pick_candidates(&self.previewable_wallpapers(), compare::CANDIDATES, seed)
+    }
+
+    fn fetch_candidates(&self) -> Task<Message> {
+        let Some(comparison) = &self.comparison else {
+            return Task::none();
+        };
+        let Some(source) = self.sources.get(&comparison.source_id) else {
+            return Task::none();
+        };
+
+        let id = comparison.window;
+        Task::batch(comparison.candidates.iter().map(|candidate| {
+            let source = source.clone();
+            let wallpaper = candidate.wallpaper.clone();
+            Task::perform(
+                {
+                    let wallpaper = wallpaper.clone();
+                    async move {
+                        source
+                            .fetch(&wallpaper)
+                            .map_err(|err| ipc::describe_error(&err))
+                    }
+                },
+                move |res| Message::ComparisonFetched(id, wallpaper.clone(), res),
+            )
+        }))
+    }
+
+    /// Puts the candidate whose turn it is on the desktop
+    fn try_candidate(&mut self) -> Task<Message> {
+        let Some(path) = self
+            .comparison
+            .as_ref()
+            .and_then(|c| c.path(c.trying?))
+            .map(Path::to_path_buf)
+        else {
+            // not fetched yet, it'll have to sit this turn out
+            return Task::none();
+        };
+        for target in self.background_opts.targets() {
+            self.overrides
+                .set(OverrideKind::Trying, target, path.clone());
+        }
+        self.show_on_desktop(path)
+    }
+
+    /// Ends trying candidates out, putting back what was there before
+    fn stop_trying(&mut self) -> Task<Message> {
+        let Some(comparison) = &mut self.comparison else {
+            return Task::none();
+        };
+        comparison.trying = None;
+        self.end_override(OverrideKind::Trying)
+    }
+
+    /// Changes the background without remembering it
+    fn show_on_desktop(&self, path: PathBuf) -> Task<Message> {
+        let Some(ipc_handle) = self.ipc_handle.clone() else {
+            return Task::none();
+        };
+        let monitors = self.background_opts.monitor_list();
+        Task::future(async move {
+            ipc_handle
+                .change_backgrounds(&monitors, &path)
+                .await
+                .map_err(|err| ipc::describe_error(&err))
+        })
+        .then(|res| match res {
+            Ok(()) => Task::none(),
+            Err(err) => Task::done(Message::BackgroundSendFailed(err)),
+        })
+    }
+
+    fn monitor_indices(&self) -> Vec<i32> {
+        self.monitors()
+            .iter()
+            .map(|monitor| monitor.index)
+            .collect()
+    }
+
+    /// Ends the `kind` override and puts back whatever it covered
+    fn end_override(&mut self, kind: OverrideKind) -> Task<Message> {
+        let restores = self.overrides.end(kind, &self.monitor_indices());
+        let Some(ipc_handle) = self.ipc_handle.clone() else {
+            return Task::none();
+        };
+        Task::future(async move {
+            overrides::restore(&ipc_handle, &restores)
+                .await
+                .map_err(|err| ipc::describe_error(&err))
+        })
+        .then(|res| match res {
+            Ok(()) => Task::none(),
+            Err(err) => Task::done(Message::BackgroundSendFailed(err)),
+        })
+    }
+
+    fn fetch_slide(&self) -> Task<Message> {
+        let Some(slideshow) = &self.slideshow else {
+            return Task::none();
+        };
+        let (Some(source), Some(wallpaper)) = (
+            self.sources.get(&slideshow.source_id),
+            slideshow.current_wallpaper().cloned(),
+        ) else {
+            return Task::none();
+        };
+
+        let id = slideshow.window;
+        Task::perform(
+            async move {
+                source
+                    .fetch(&wallpaper)
+                    .map_err(|err| ipc::describe_error(&err))
+            },
+            move |res| Message::SlideshowFetched(id, res),
+        )
+    }
+
+    fn build_sources_widgets(&self) -> Column<'_, Message> {
+        let choices: Vec<SourceChoice> = self
+            .sources
+            .iter()
+            .map(|source| SourceChoice {
+                id: source.id().to_owned(),
+                name: match self.config.offline && source.is_online() {
+                    true => format!("{} (unavailable offline)", source.name()),
+                    false => source.name().to_owned(),
+                },
+            })
+            .collect();
+
+        let mut wallpapers_widgets = Column::new();
+        for wallpaper in self.tagged_wallpapers() {
+            let mut row = row![
+                button(text(&wallpaper.name))
+                    .width(Length::Fill)
+                    .on_press(Message::SelectSourceWallpaper(wallpaper.clone())),
+                button("Play next")
+                    .style(button::secondary)
+                    .on_press(Message::QueueWallpaper(wallpaper.clone())),
+            ]
+            .spacing(5);
+            if let Location::Local(path) = &wallpaper.location {
+                row = row.push(button("Delete").on_press(Message::DeleteWallpaper(path.clone())));
+            }
+            wallpapers_widgets = wallpapers_widgets.push(row);
+        }
+
+        let mut tags: Vec<String> = self
+            .source_wallpapers
+            .iter()
+            .flat_map(|w| w.tags.iter().cloned())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        let channel = self.config.channels.iter().find(|dir| {
+            self.selected_source
+                .as_ref()
+                .is_some_and(|choice| choice.id == Channel::new(dir.to_path_buf()).id())
+        });
+
+        column![
+            row![
+                pick_list(choices, self.selected_source.clone(), Message::SelectSource)
+                    .placeholder("Pick a source...")
+                    .width(Length::Fill),
+                button("Follow folder").on_press(Message::FollowChannel),
+                checkbox(self.config.offline)
+                    .label("Offline")
+                    .on_toggle(Message::ToggleOffline),
+            ]
+            .spacing(5)
+            .push(
+                channel
+                    .map(|dir| button("Unfollow").on_press(Message::UnfollowChannel(dir.clone())))
+            ),
+            (!tags.is_empty()).then(|| {
+                row![
+                    pick_list(tags, self.source_tag.clone(), |tag| {
+                        Message::SelectSourceTag(Some(tag))
+                    })
+                    .placeholder("Every tag")
+                    .width(Length::Fill),
+                    button("All").on_press_maybe(
+                        self.source_tag
+                            .is_some()
+                            .then_some(Message::SelectSourceTag(None))
+                    ),
+                ]
+                .spacing(5)
+            }),
+            scrollable(wallpapers_widgets)
+                .id(WALLPAPER_LIST)
+                .on_scroll(Message::WallpapersScrolled)
+                .height(Length::Fixed(150.0)),
+            row![
+                pick_list(
+                    slideshow::INTERVALS,
+                    Some(self.slide_interval),
+                    Message::SelectSlideInterval
+                ),
+                pick_list(
+                    Transition::ALL,
+                    Some(self.transition),
+                    Message::SelectTransition
+                ),
+                button("Preview slideshow")
+                    .width(Length::Fill)
+                    .on_press_maybe(
+                        (!self.source_wallpapers.is_empty()).then_some(Message::StartSlideshow)
+                    ),
+                button("Compare").on_press_maybe(
+                    (self.source_wallpapers.len() >= 2).then_some(Message::StartComparison)
+                ),
+            ]
+            .spacing(5),
+            button("Import pack")
+                .width(Length::Fill)
+                .on_press(Message::ImportPack),
+            self.build_source_health_widgets(),
+        ]
+    }
+
+    fn save_playlists(&mut self) {
+        if let Err(err) = self.playlists.save() {
+            self.user_error = Some(ipc::describe_error(&err));
+        }
+    }
+
+    fn build_playlists_widgets(&self) -> Column<'_, Message> {
+        let playlist = self
+            .selected_playlist
+            .as_deref()
+            .and_then(|name| self.playlists.get(name));
+
+        let mut files_widgets = Column::new().spacing(5);
+        for (at, path) in playlist.iter().flat_map(|p| p.files.iter().enumerate()) {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string());
+            files_widgets = files_widgets.push(
+                row![
+                    text!(
+                        "{}{}",
+                        if self.playlist_at == Some(at) {
+                            "\u{25B6} "
+                        } else {
+                            ""
+                        },
+                        name
+                    )
+                    .width(Length::Fill),
+                    button("\u{2191}")
+                        .style(button::secondary)
+                        .on_press(Message::ShiftInPlaylist(at, true)),
+                    button("\u{2193}")
+                        .style(button::secondary)
+                        .on_press(Message::ShiftInPlaylist(at, false)),
+                    button("Remove")
+                        .style(button::secondary)
+                        .on_press(Message::RemoveFromPlaylist(at)),
+                ]
+                .spacing(5)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+
+        let has_files = playlist.is_some_and(|p| !p.files.is_empty());
+        column![
+            row![
+                pick_list(
+                    self.playlists.names(),
+                    self.selected_playlist.clone(),
+                    Message::SelectPlaylist
+                )
+                .placeholder("Pick a playlist...")
+                .width(Length::Fill),
+                button("Delete")
+                    .style(button::secondary)
+                    .on_press_maybe(playlist.is_some().then_some(Message::DeletePlaylist)),
+            ]
+            .spacing(5),
+            row![
+                text_input("New playlist name", &self.playlist_name_input)
+                    .on_input(Message::EditPlaylistName)
+                    .on_submit(Message::CreatePlaylist)
+                    .width(Length::Fill),
+                button("Create").on_press(Message::CreatePlaylist),
+            ]
+            .spacing(5),
+            scrollable(files_widgets).height(Length::Shrink),
+            row![
+                button("Add the selected file")
+                    .width(Length::Fill)
+                    .on_press_maybe(playlist.is_some().then_some(Message::AddToPlaylist)),
+                button("Previous")
+                    .on_press_maybe(has_files.then_some(Message::StepPlaylist(false))),
+                button("Next").on_press_maybe(has_files.then_some(Message::StepPlaylist(true))),
+            ]
+            .spacing(5),
+            text("Playlists are sources too, pick one above to rotate through it or preview it"),
+        ]
+        .spacing(5)
+    }
+
+    fn build_recently_deleted_widgets(&self) -> Column<'_, Message> {
+        if self.trashed.is_empty() {
+            return column![];
+        }
+        let mut trashed_widgets = Column::new().spacing(5);
+        for trashed in self.trashed.iter().rev() {
+            let mut row = row![
+                text!(
+                    "{} ({})",
+                    trashed.original.display(),
+                    trashed.deleted_at.format("%Y-%m-%d %H:%M")
+                )
+                .width(Length::Fill),
+                button("Restore").on_press(Message::RestoreTrashed(trashed.clone())),
+            ]
+            .spacing(5);
+            match &self.purge_confirm {
+                Some((target, typed)) if target == trashed => {
+                    let name = trashed.name.to_string_lossy();
+                    trashed_widgets = trashed_widgets.push(row).push(
+                        row![
+                            text_input(&format!("Type {name} to delete it for good"), typed)
+                                .on_input(Message::EditPurgeConfirm)
+                                .width(Length::Fill),
+                            button("Delete forever")
+                                .on_press_maybe((*typed == name).then_some(Message::ConfirmPurge)),
+                            button("Cancel").on_press(Message::CancelPurge),
+                        ]
+                        .spacing(5),
+                    );
+                }
+                _ => {
+                    row = row.push(
+                        button("Delete permanently").on_press(Message::StartPurge(trashed.clone())),
+                    );
+                    trashed_widgets = trashed_widgets.push(row);
+                }
+            }
+        }
+        column![card(text!["Recently deleted"], trashed_widgets)]
+    }
+
+    fn build_source_health_widgets(&self) -> Column<'_, Message> {
+        let now = SystemTime::now();
+        let mut health_widgets = Column::new().spacing(5);
+        for source in self.sources.iter() {
+            if self.config.offline && source.is_online() {
+                health_widgets = health_widgets.push(
+                    row![
+                        text(source.name().to_owned()).width(Length::Fixed(120.0)),
+                        text("unavailable in offline mode").width(Length::Fill),
+                    ]
+                    .spacing(5),
+                );
+                continue;
+            }
+            let health = SourceHealth::new(
+                self.source_scans.get(source.id()),
+                self.source_sync.get(source.id()),
+                now,
+            );
+            let mut row = row![
+                text(source.name().to_owned()).width(Length::Fixed(120.0)),
+                text(health.status).width(Length::Fill),
+            ]
+            .spacing(5);
+            if health.problem.is_some() {
+                row = row
+                    .push(button("Retry").on_press(Message::RetrySource(source.id().to_owned())));
+            }
+            health_widgets = health_widgets.push(row).push(
+                health
+                    .problem
+                    .map(|problem| self.badge(Status::Error, problem)),
+            );
+        }
+        health_widgets
+    }
+
+    /// What can be done with a monitor of the layout, after a right click or
+    /// a long press on it
+    fn build_monitor_menu(&self) -> Row<'_, Message> {
+        let Some(index) = self.monitor_menu else {
+            return row![];
+        };
+        let selected = self.background_opts.monitors.contains(&index);
+        row![
+            text!("Monitor {}", index + 1).width(Length::Fill),
+            button("Select only").on_press(Message::SelectMonitor(index)),
+            button(if selected {
+                "Remove from selection"
+            } else {
+                "Add to selection"
+            })
+            .on_press(Message::ToggleMonitor(index)),
+            button("Close")
+                .style(button::secondary)
+                .on_press(Message::CloseMonitorMenu),
+        ]
+        .spacing(5)
+        .align_y(iced::Alignment::Center)
+    }
+
+    /// The selection made on the layout, spelled out, and a way to ask xab
+    /// for the monitors again
+    fn build_monitors_widgets(&self) -> Column<'_, Message> {
+        let monitors = self.monitors();
+        let selected: Vec<String> = self
+            .background_opts
+            .monitor_list()
+            .into_iter()
+            .map(|index| {
+                match monitors
+                    .iter()
+                    .find(|monitor| monitor.index == index && monitor.name.is_some())
+                {
+                    Some(monitor) => format!("{} ({})", index + 1, monitor.label()),
+                    None => (index + 1).to_string(),
+                }
+            })
+            .collect();
+        let arriving = !self.arriving_monitors.is_empty();
+        let costly: Vec<String> = self
+            .impacts()
+            .into_iter()
+            .filter(|(_, impact)| *impact >= Impact::Medium)
+            .map(|(index, _)| (index + 1).to_string())
+            .collect();
+        let mut widgets = column![
+            row![
+                text(match selected.as_slice() {
+                    [] => "Every monitor".to_owned(),
+                    [monitor] => format!("Monitor {monitor}"),
+                    monitors => format!("Monitors {}", monitors.join(", ")),
+                })
+                .width(Length::Fill),
+                text("Click to select, ctrl+click to add, right click for more").size(12),
+                button(
+                    text(match arriving {
+                        true => "Getting the rest...",
+                        false => "Refresh",
+                    })
+                    .center(),
+                )
+                .on_press_maybe(
+                    (self.ipc_handle.is_some() && !arriving).then_some(Message::RefreshMonitors),
+                ),
+            ]
+            .spacing(5)
+            .align_y(iced::Alignment::Center)
+        ]
+        .spacing(5);
+        if self.discharging && !costly.is_empty() && self.snapshot.is_none() {
+            widgets = widgets.push(
+                row![
+                    text!(
+                        "On battery: the video on monitor {} drains it, a still image lasts longer",
+                        costly.join(", ")
+                    )
+                    .width(Length::Fill),
+                    button("Freeze videos").on_press_maybe(
+                        self.ipc_handle.is_some().then_some(Message::ToggleSnapshot)
+                    ),
+                ]
+                .spacing(5)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+        widgets
+    }
+
+    fn build_scripts_widgets(&self) -> Column<'_, Message> {
+        if self.scripts.is_empty() {
+            return column![text("No scripts yet")];
+        }
+        let mut scripts = Column::new().spacing(10);
+        for script in &self.scripts {
+            let name = script.name.clone();
+            let mut body = column![
+                row![
+                    checkbox(self.config.scripts.contains(&script.name))
+                        .label(&script.name)
+                        .on_toggle(move |enabled| Message::ToggleScript(name.clone(), enabled))
+                        .width(Length::Fill),
+                    button("Run now").on_press_maybe(
+                        (self.ipc_handle.is_some() && script.handles(ScriptEvent::Tick))
+                            .then(|| Message::RunScript(script.name.clone()))
+                    ),
+                ]
+                .spacing(5)
+            ]
+            .spacing(5);
+            if let Some(err) = script.error() {
+                body = body.push(self.badge(Status::Error, err));
+            }
+            for line in script.log.iter().rev().take(5).rev() {
+                body = body.push(text(line).size(12));
+            }
+            scripts = scripts.push(body);
+        }
+        scripts
+    }
+
+    fn build_templates_widgets(&self) -> Column<'_, Message> {
+        if self.templates.is_empty() {
+            return column![text("No templates yet")];
+        }
+        let mut templates = Column::new().spacing(10);
+        for template in &self.templates {
+            let name = template.name.clone();
+            let mut body = column![
+                row![
+                    checkbox(self.config.templates.contains(&template.name))
+                        .label(&template.name)
+                        .on_toggle(move |enabled| Message::ToggleTemplate(name.clone(), enabled))
+                        .width(Length::Fill),
+                    button("Render now").on_press_maybe(
+                        (self.ipc_handle.is_some() && template.template.is_ok())
+                            .then(|| Message::RenderTemplate(template.name.clone()))
+                    ),
+                ]
+                .spacing(5)
+            ]
+            .spacing(5);
+            match &template.template {
+                Ok(loaded) => {
+                    body = body.push(
+                        text!(
+                            "Every {} minute(s){}, {}",
+                            loaded.interval().as_secs() / 60,
+                            match loaded.agenda {
+                                Some(_) => " and when the day or the calendar changes",
+                                None => "",
+                            },
+                            match loaded.monitor {
+                                Some(monitor) => format!("on monitor {}", monitor + 1),
+                                None => "on every monitor".to_owned(),
+                            }
+                        )
+                        .size(12),
+                    )
+                }
+                Err(err) => body = body.push(self.badge(Status::Error, err)),
+            }
+            templates = templates.push(body);
+        }
+        templates
+    }
+
+    fn build_rules_widgets(&self) -> Column<'_, Message> {
+        let mut rules = Column::new().spacing(5);
+        for (i, input) in self.rule_inputs.iter().enumerate() {
+            rules = rules.push(
+                row![
+                    text_input(
+                        "when monitors change and monitors == 1 then apply /home/me/laptop.png",
+                        input
+                    )
+                    .on_input(move |rule| Message::EditRule(i, rule)),
+                    button("Remove").on_press(Message::RemoveRule(i)),
+                ]
+                .spacing(5),
+            );
+        }
+        for err in self.config.rule_errors() {
+            rules = rules.push(self.badge(Status::Error, format!("{err}, skipped")));
+        }
+        column![
+            rules,
+            text("Events (can be left out): connected, monitors change, battery changes. Values: monitors, battery, hour. Actions: pause videos, resume videos, apply <file>"),
+            button("Add rule")
+                .width(Length::Fill)
+                .on_press(Message::AddRule),
+        ]
+        .spacing(5)
+    }
+
+    fn build_hotkeys_widgets(&self) -> Element<'_, Message> {
+        let mut hotkeys_widgets = Column::new().spacing(5);
+        for &action in HotkeyAction::ALL {
+            hotkeys_widgets = hotkeys_widgets.push(
+                row![
+                    text!("{action}").width(Length::Fixed(150.0)),
+                    text_input("e.g. super+alt+n", self.hotkey_inputs.get(action))
+                        .on_input(move |accelerator| Message::EditHotkey(action, accelerator)),
+                ]
+                .spacing(5),
+            );
+        }
+        hotkeys_widgets = hotkeys_widgets.push(
+            row![
+                text("Media keys").width(Length::Fixed(150.0)),
+                pick_list(
+                    MediaKeysOwner::ALL,
+                    Some(self.media_keys_input),
+                    Message::SelectMediaKeysOwner
+                )
+                .width(Length::Fill),
+            ]
+            .spacing(5),
+        );
+        if self.hotkeys.is_none() {
+            hotkeys_widgets = hotkeys_widgets.push(text(if self.session.has_x11_desktop() {
+                "Global hotkeys aren't available in this session".to_owned()
+            } else {
+                format!(
+                    "Global hotkeys need an X11 session, this is {}",
+                    self.session
+                )
+            }));
+        }
+        card(text!["Global hotkeys"], hotkeys_widgets).into()
+    }
+
+    fn build_status_widgets(&self) -> Column<'_, Message> {
+        let mut status_widgets = Column::new();
+        if let Some(ipc_handle) = self.ipc_handle.as_ref() {
+            status_widgets = status_widgets
+                .push(self.badge(Status::Ok, format!("Connected to {}", ipc_handle.path)))
+                .push(text!(
+                    "Handshake: {:?}, IPC protocol {}",
+                    ipc_handle.handshake_latency,
+                    ipc_handle.protocol
+                ))
+                .push(
+                    match ipc_handle
+                        .capabilities()
+                        .contains(IpcXabCapabilities::Events)
+                    {
+                        true => text("Updates: live, xab sends them"),
+                        false => text!(
+                            "Updates: monitors checked every {:?}",
+                            shared::MONITOR_POLL_INTERVAL
+                        ),
+                    },
+                );
+            for (command, latency) in ipc_handle.latencies() {
+                status_widgets = status_widgets.push(text!(
+                    "{:?}: {} sent, last {:?}, avg {:?}, max {:?}",
+                    command,
+                    latency.count,
+                    latency.last,
+                    latency.average(),
+                    latency.max
+                ));
+            }
+        }
+        status_widgets.push(text!("Preview cache: {}", self.previews.stats()))
+    }
+
+    // TODO: stuff like this:
+
+    fn subscription(&self) -> Subscription<Message> {
+        let mut subscriptions = vec![
+            window::close_events().map(Message::WindowClosed),
+            Subscription::run(hotkeys::events).map(Message::HotkeyPressed),
+            Subscription::run(instance::events).map(Message::Activated),
+            iced::time::every(SOURCE_SYNC_CHECK_INTERVAL).map(|_| Message::SyncSources),
+            iced::time::every(disk_cache::CLEANUP_INTERVAL).map(|_| Message::CleanupCaches),
+            iced::event::listen_with(|event, status, id| match event {
+                iced::Event::Touch(event) => Some(Message::Touch(event)),
+                // drops on a monitor are captured by the layout
+                iced::Event::Window(window::Event::FileDropped(path))
+                    if status == iced::event::Status::Ignored =>
+                {
+                    Some(Message::FileDropped(id, path))
+                }
+                _ => None,
+            }),
+        ];
+        if self.wallpapers_kinetic.is_coasting() {
+            subscriptions.push(window::frames().map(Message::WallpapersFrame));
+        }
+        if self.config.check_updates {
+            subscriptions
+                .push(iced::time::every(updates::CHECK_INTERVAL).map(|_| Message::CheckUpdates));
+        }
+        if let Some(scheduler) = &self.scheduler {
+            subscriptions
+                .push(iced::time::every(scheduler.rotation.interval()).map(|_| Message::Rotate));
+        }
+        if let Some(ipc_handle) = &self.ipc_handle {
+            // connections that get events are told about hotplugs instead
+            if !ipc_handle
+                .capabilities()
+                .contains(IpcXabCapabilities::Events)
+            {
+                subscriptions.push(
+                    Subscription::run_with(self.shared.clone(), shared::monitor_updates)
+                        .map(Message::MonitorsChanged),
+                );
+                subscriptions.push(
+                    iced::time::every(CAPABILITY_REFRESH_INTERVAL)
+                        .map(|_| Message::RefreshCapabilities),
+                );
+            }
+            subscriptions.push(
+                Subscription::run_with(self.shared.clone(), shared::daemon_events)
+                    .map(Message::DaemonEvent),
+            );
+        }
+        match (&self.shutdown, &self.ipc_handle) {
+            (Some(_), Some(_)) => subscriptions
+                .push(iced::time::every(Duration::from_secs(1)).map(|_| Message::ShutdownTick)),
+            (Some(_), None) => subscriptions
+                .push(iced::time::every(RECONNECT_INTERVAL).map(|_| Message::Reconnect)),
+            (None, _) => {}
+        }
+        if self.config.media_keys == MediaKeysOwner::WhenNoPlayer {
+            subscriptions.push(
+                iced::time::every(MEDIA_PLAYER_CHECK_INTERVAL).map(|_| Message::CheckMediaKeys),
+            );
+        }
+        if (self.config.night_dimming.enabled || self.night_active) && self.ipc_handle.is_some() {
+            subscriptions.push(
+                iced::time::every(nightlight::CHECK_INTERVAL).map(|_| Message::CheckNightLight),
+            );
+        }
+        if self.config.active_window_color
+            && self.ipc_handle.is_some()
+            && self.session.has_x11_desktop()
+        {
+            subscriptions.push(
+                iced::time::every(active_color::SAMPLE_INTERVAL)
+                    .map(|_| Message::SampleActiveWindow),
+            );
+        }
+        if self.ipc_handle.is_some()
+            && self
+                .scripts
+                .iter()
+                .any(|s| self.config.scripts.contains(&s.name) && s.handles(ScriptEvent::Tick))
+        {
+            subscriptions
+                .push(iced::time::every(scripts::TICK_INTERVAL).map(|_| Message::ScriptTick));
+        }
+        if self.ipc_handle.is_some()
+            && self
+                .templates
+                .iter()
+                .any(|t| self.config.templates.contains(&t.name))
+        {
+            subscriptions
+                .push(iced::time::every(templates::TICK_INTERVAL).map(|_| Message::TemplateTick));
+        }
+        if self.ipc_handle.as_ref().is_some_and(|ipc_handle| {
+            let capabilities = ipc_handle.capabilities();
+            // events keep it up to date after the first ask
+            capabilities.contains(IpcXabCapabilities::BackgroundState)
+                && !capabilities.contains(IpcXabCapabilities::Events)
+        }) {
+            subscriptions
+                .push(iced::time::every(LIVE_STATE_INTERVAL).map(|_| Message::RefreshLiveState));
+        }
+        if self.config.parsed_rules().iter().any(Rule::uses_battery)
+            || self.energy.values().any(Result::is_ok)
+        {
+            subscriptions.push(
+                iced::time::every(rules::BATTERY_CHECK_INTERVAL).map(|_| Message::CheckBattery),
+            );
+        }
+        if let Some(slideshow) = &self.slideshow {
+            subscriptions.push(
+                iced::time::every(slideshow.interval.duration()).map(|_| Message::SlideshowNext),
+            );
+            if slideshow.is_transitioning() {
+                subscriptions.push(window::frames().map(|_| Message::SlideshowFrame));
+            }
+        }
+        if self.comparison.as_ref().is_some_and(|c| c.trying.is_some()) {
+            subscriptions
+                .push(iced::time::every(compare::TRY_DURATION).map(|_| Message::TryNextCandidate));
+        }
+        Subscription::batch(subscriptions)
+    }
+
+    fn refresh_endpoints(&mut self) {
+        let sockets = ipc::find_sockets();
+        debug!("Found {} xab socket(s)", sockets.len());
+        let configured = self.config.socket.as_ref().map(|path| {
+            sockets
+                .iter()
+                .find(|socket| socket.path == *path)
+                .cloned()
+                .map_or_else(|| Endpoint::Custom(path.clone()), Endpoint::Local)
+        });
+        let default = configured
+            .clone()
+            .or_else(|| ipc::default_socket(&sockets).cloned().map(Endpoint::Local));
+        self.endpoints = configured
+            .into_iter()
+            .filter(|endpoint| matches!(endpoint, Endpoint::Custom(_)))
+            .chain(sockets.into_iter().map(Endpoint::Local))
+            .chain(self.config.ssh.iter().cloned().map(Endpoint::Ssh))
+            .collect();
+        // keep the user's pick if it's still around
+        if !self
+            .selected_endpoint
+            .as_ref()
+            .is_some_and(|selected| self.endpoints.contains(selected))
+        {
+            self.selected_endpoint = default.or_else(|| self.endpoints.first().cloned());
+        }
+    }
+
+    /// What the connected xab said it can show
+    fn formats(&self) -> Option<Vec<String>> {
+        self.ipc_handle.as_ref()?.formats()
+    }
+
+    /// Whether `path` is worth sending to xab, see `media::check`. Says why
+    /// not, or what might go wrong
+    fn vet_wallpaper(&mut self, path: &Path) -> bool {
+        match media::check(path, self.formats().as_deref()) {
+            Ok(warning) => {
+                if let Some(warning) = warning {
+                    warn!("{}", warning);
+                    self.user_notice = Some(warning);
+                }
+                true
+            }
+            Err(err) => {
+                self.user_error = Some(format!("{err:#}"));
+                false
+            }
+        }
+    }
+
+    /// Says goodbye to xab, letting the queued commands out first, and
+    /// writes the saved state before exiting. The config is written as it's
+    /// changed already
+    fn exit(&mut self) -> Task<Message> {
+        let ipc_handle = self.ipc_handle.take();
+        Task::perform(
+            async move {
+                if let Some(ipc_handle) = ipc_handle {
+                    ipc_handle.close().await?;
+                }
+                persist::flush()
+            },
+            |res| Message::ReadyToExit(res.map_err(|err| ipc::describe_error(&err))),
+        )
+    }
+
+    /// Starts the file picker where this one was picked next time
+    fn remember_directory(&mut self) {
+        let directory = self.background_opts.path.parent().map(PathBuf::from);
+        if directory.is_none() || directory == self.config.last_directory {
+            return;
+        }
+        self.config.last_directory = directory;
+        if let Err(err) = self.config.save() {
+            error!("Failed to remember the directory: {:?}", err);
+        }
+    }
+
+    /// The wallpapers of the config, when connecting without anything to restore
+    fn apply_startup_wallpapers(&self) -> Task<Message> {
+        let (Some(ipc_handle), wallpapers) = (self.ipc_handle.clone(), self.config.wallpapers())
+        else {
+            return Task::none();
+        };
+        if wallpapers.is_empty() {
+            return Task::none();
+        }
+        Task::perform(
+            async move {
+                let monitors = ipc_handle.get_monitors().await;
+                for (target, path) in wallpapers {
+                    // one unplugged monitor shouldn't keep the others bare
+                    let monitor = match target.resolve(&monitors) {
+                        Ok(monitor) => monitor,
+                        Err(err) => {
+                            warn!("Skipping {}: {:#}", path.display(), err);
+                            continue;
+                        }
+                    };
+                    ipc_handle.change_background(monitor, &path).await?;
+                }
+                Ok(())
+            },
+            |res: Result<()>| {
+                Message::WallpapersRestored(res.map_err(|err| ipc::describe_error(&err)))
+            },
+        )
+    }
+
+    /// Why automatic changes (and their notifications) shouldn't happen right now
+    fn automation_paused(&self) -> Option<String> {
+        self.shared.read().automation_paused()
+    }
+
+    /// The automations that can change wallpapers right now
+    fn running_automations(&self) -> Vec<Automation> {
+        self.automation_targets()
+            .into_iter()
+            .map(|(automation, _)| automation)
+            .chain((!self.config.scripts.is_empty()).then_some(Automation::Scripts))
+            .collect()
+    }
+
+    /// The running automations and the monitors they change. Scripts pick
+    /// theirs as they go, so they're left out
+    fn automation_targets(&self) -> Vec<(Automation, Option<i32>)> {
+        let mut targets = Vec::new();
+        if self
+            .config
+            .parsed_rules()
+            .iter()
+            .any(|rule| matches!(rule.action, RuleAction::Apply(_)))
+        {
+            targets.push((Automation::Rules, None));
+        }
+        targets.extend(
+            self.templates
+                .iter()
+                .filter(|t| self.config.templates.contains(&t.name))
+                .filter_map(|t| t.template.as_ref().ok())
+                .map(|template| (Automation::Templates, template.monitor)),
+        );
+        if self.scheduler.is_some() {
+            targets.extend(
+                self.rotation_targets()
+                    .into_iter()
+                    .map(|monitor| (Automation::Rotation, monitor)),
+            );
+        }
+        targets
+    }
+
+    /// Monitors the next rotation goes to, `None` being every monitor
+    fn rotation_targets(&self) -> Vec<Option<i32>> {
+        let targets: Vec<Option<i32>> = match self.config.rotation.per_monitor {
+            true if !self.shared.read().monitors.is_empty() => self
+                .monitors()
+                .iter()
+                .map(|monitor| Some(monitor.index))
+                .collect(),
+            _ => vec![None],
+        };
+        targets
+            .into_iter()
+            .filter(|monitor| !self.pins.is_pinned(*monitor))
+            .collect()
+    }
+
+    /// Works out what automation does over `horizon` from copies, the
+    /// running rotation isn't moved along
+    fn plan_schedule(&self, horizon: Horizon) -> Plan {
+        let templates = self
+            .templates
+            .iter()
+            .filter(|t| self.config.templates.contains(&t.name))
+            .filter_map(|t| {
+                let template = t.template.as_ref().ok()?;
+                Some(TemplateSchedule {
+                    name: t.name.clone(),
+                    monitor: template.monitor,
+                    interval: template.interval(),
+                    last_render: self.template_renders.get(&t.name).copied(),
+                })
+            })
+            .collect();
+        let dimming = self.config.night_dimming;
+        timeline::plan(Inputs {
+            now: Local::now(),
+            horizon,
+            quiet_hours: self.config.quiet_hours,
+            paused_until: self.automation_paused_until,
+            rotation: self
+                .scheduler
+                .clone()
+                .map(|scheduler| (scheduler, self.rotation_targets())),
+            queued: self
+                .queue
+                .iter()
+                .map(|(_, wallpaper)| wallpaper.name.clone())
+                .collect(),
+            templates,
+            night: dimming.enabled.then_some(dimming.schedule).flatten(),
+        })
+    }
+
+    fn build_general_settings_widgets(&self) -> Column<'_, Message> {
+        let defaults = Limits::default();
+        let mut wallpapers = column![text("Wallpapers applied when connecting")].spacing(5);
+        for (monitor, path) in &self.wallpapers_input {
+            wallpapers = wallpapers.push(
+                row![
+                    text!(
+                        "{}: {}",
+                        match monitor.parse() {
+                            Ok(MonitorTarget::All) => "Every monitor".to_owned(),
+                            Ok(MonitorTarget::Primary) => "The primary monitor".to_owned(),
+                            _ => format!("Monitor {monitor}"),
+                        },
+                        path.display()
+                    )
+                    .width(Length::Fill),
+                    button("Remove").on_press(Message::RemoveStartupWallpaper(monitor.clone())),
+                ]
+                .spacing(5),
+            );
+        }
+        column![
+            row![
+                text("Socket"),
+                text_input(IPC_PATH, &self.socket_input).on_input(Message::EditSocket),
+            ]
+            .spacing(5),
+            row![
+                text("Theme"),
+                pick_list(AppTheme::ALL, Some(self.theme_input), Message::SelectTheme)
+                    .width(Length::Fill),
+            ]
+            .spacing(5),
+            row![
+                text("After connecting, show"),
+                pick_list(
+                    StartPage::ALL,
+                    Some(self.start_page_input),
+                    Message::SelectStartPage
+                )
+                .width(Length::Fill),
+            ]
+            .spacing(5),
+            checkbox(self.close_to_background_input)
+                .label(
+                    "Keep running when the window is closed, starting xab-gui again brings it back"
+                )
+                .on_toggle(Message::EditCloseToBackground),
+            row![
+                text("Status colors"),
+                pick_list(
+                    StatusPalette::ALL,
+                    Some(self.palette_input),
+                    Message::SelectPalette
+                )
+                .width(Length::Fill),
+                status::badge(Status::Ok, self.palette_input, "ok"),
+                status::badge(Status::Pending, self.palette_input, "pending"),
+                status::badge(Status::Error, self.palette_input, "error"),
+            ]
+            .spacing(5),
+            row![
+                text("Monitor selected on start"),
+                text_input("every monitor", &self.default_monitor_input)
+                    .on_input(Message::EditDefaultMonitor),
+            ]
+            .spacing(5),
+            row![
+                text("Cleared monitors show"),
+                text_input("#000000", &self.clear_color_input).on_input(Message::EditClearColor),
+            ]
+            .spacing(5),
+            row![
+                text("Give up connecting after"),
+                text_input("5", &self.connect_timeout_input).on_input(Message::EditConnectTimeout),
+                text("s, on the handshake after"),
+                text_input("5", &self.handshake_timeout_input)
+                    .on_input(Message::EditHandshakeTimeout),
+                text("s"),
+            ]
+            .spacing(5)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("At once: hashing threads"),
+                text_input(&defaults.workers().to_string(), &self.workers_input)
+                    .on_input(Message::EditWorkers),
+                text("downloads"),
+                text_input(&defaults.downloads().to_string(), &self.downloads_input)
+                    .on_input(Message::EditDownloads),
+                text("image decodes"),
+                text_input(&defaults.decodes().to_string(), &self.decodes_input)
+                    .on_input(Message::EditDecodes),
+            ]
+            .spacing(5)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Keep up to"),
+                text_input(&preview::DEFAULT_CACHE_MB.to_string(), &self.cache_mb_input)
+                    .on_input(Message::EditCacheBudget),
+                text("MB of previews and thumbnails in memory"),
+            ]
+            .spacing(5)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("and"),
+                text_input(
+                    &disk_cache::DEFAULT_LIMIT_MB.to_string(),
+                    &self.disk_cache_mb_input
+                )
+                .on_input(Message::EditDiskCacheLimit),
+                text("MB of processed wallpapers on disk"),
+                button(text(match self.reclaimable {
+                    Some(bytes) => format!("Clear caches ({})", preview::human_size(bytes)),
+                    None => "Clear caches".to_owned(),
+                }))
+                .on_press_maybe((self.reclaimable != Some(0)).then_some(Message::ClearCaches)),
+            ]
+            .spacing(5)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Download at most"),
+                text_input("unlimited", &self.bandwidth_input).on_input(Message::EditBandwidth),
+                text("KB/s"),
+                checkbox(self.sync_on_metered_input)
+                    .label("Sync sources on metered connections")
+                    .on_toggle(Message::EditSyncOnMetered),
+            ]
+            .spacing(5)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Proxy"),
+                text_input("from the environment", &self.proxy_input).on_input(Message::EditProxy),
+                text("CA bundle"),
+                text_input("the system's", &self.ca_bundle_input).on_input(Message::EditCaBundle),
+            ]
+            .spacing(5)
+            .align_y(iced::Alignment::Center),
+            checkbox(self.check_updates_input)
+                .label("Check for updates (asks GitHub once a day)")
+                .on_toggle(Message::EditCheckUpdates),
+            wallpapers,
+            button("Add the selected file for the selected monitors")
+                .width(Length::Fill)
+                .on_press(Message::AddStartupWallpaper),
+        ]
+        .spacing(10)
+    }
+
+    /// Per online source, read only in kiosk mode
+    fn build_content_filter_widgets(&self) -> Column<'_, Message> {
+        let locked = self.config.kiosk;
+        let mut filters = column![].spacing(5);
+        if locked {
+            filters = filters.push(text("Locked on this machine").size(12));
+        }
+        for (id, (sfw_only, blocked)) in &self.filter_inputs {
+            let name = self
+                .sources
+                .get(id)
+                .map(|source| source.name().to_owned())
+                .unwrap_or_else(|| id.clone());
+            let (toggle_id, edit_id) = (id.clone(), id.clone());
+            filters = filters.push(
+                row![
+                    text(name).width(Length::FillPortion(1)),
+                    checkbox(*sfw_only)
+                        .label("Safe for work only")
+                        .on_toggle_maybe((!locked).then_some(move |sfw_only| {
+                            Message::ToggleSfwOnly(toggle_id.clone(), sfw_only)
+                        })),
+                    text_input("blocked tags and keywords, comma separated", blocked)
+                        .width(Length::FillPortion(2))
+                        .on_input_maybe((!locked).then_some(move |blocked| {
+                            Message::EditBlocked(edit_id.clone(), blocked)
+                        })),
+                ]
+                .spacing(5)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+        filters
+    }
+
+    /// Lists the library directories again, thumbnails already decoded stay
+    fn scan_library(&mut self) -> Task<Message> {
+        self.browser.scanning = true;
+        let dirs = self.config.library_dirs.clone();
+        Task::perform(
+            async move { browser::scan(&dirs).map_err(|err| ipc::describe_error(&err)) },
+            Message::LibraryScanned,
+        )
+    }
+
+    /// The library page: its directories and a grid of what's in them
+    fn build_library_widgets(&self) -> Column<'_, Message> {
+        let mut dirs = column![].spacing(5);
+        for dir in &self.config.library_dirs {
+            dirs = dirs.push(
+                row![
+                    text(dir.display().to_string()).width(Length::Fill),
+                    button("Remove").on_press(Message::RemoveLibraryDir(dir.clone())),
+                ]
+                .spacing(5)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+        let tiles = self.browser.shown().iter().map(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let tile: Element<'_, Message> = match self.browser.thumbnails.get(path) {
+                Some(Some(Ok(handle))) => image(handle.clone())
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .content_fit(ContentFit::Cover)
+                    .into(),
+                Some(Some(Err(_))) => center(text(name.into_owned()).size(12)).into(),
+                _ => center(text("Loading...").size(12)).into(),
+            };
+            button(tile)
+                .padding(0)
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .style(button::text)
+                .on_press(Message::ApplyFromLibrary(path.clone()))
+                .into()
+        });
+        let status = match (self.browser.scanning, self.browser.files.len()) {
+            (true, _) => "Scanning...".to_owned(),
+            (false, 0) => "No wallpapers, add a directory to fill the library".to_owned(),
+            (false, count) => {
+                format!("{count} wallpapers, click one to apply it to the selected monitors")
+            }
+        };
+        column![
+            dirs,
+            text(status).size(12),
+            scrollable(
+                column![
+                    grid(tiles)
+                        .fluid(220)
+                        .spacing(5)
+                        .height(grid::aspect_ratio(16, 9)),
+                ]
+                .push((self.browser.shown < self.browser.files.len()).then(|| {
+                    button("Show more")
+                        .width(Length::Fill)
+                        .on_press(Message::ShowMoreLibrary)
+                }))
+                .spacing(5),
+            )
+            .height(Length::Fill),
+        ]
+        .spacing(10)
+    }
+
+    /// The crop page: a monitor picker, the image with the crop and the numbers
+    fn build_crop_widgets(&self) -> Column<'_, Message> {
+        let Some(editor) = &self.crop else {
+            return column![
+                text("Nothing to crop"),
+                button("Back")
+                    .width(Length::Fill)
+                    .on_press(Message::CloseSettings),
+            ];
+        };
+        let mut monitors = Row::new().spacing(5);
+        for monitor in self.monitors() {
+            let (width, height) = preview::monitor_size(&monitor);
+            monitors = monitors.push(
+                button(
+                    text!(
+                        "{}{}\n{width}x{height}",
+                        monitor.label(),
+                        if editor.crops.contains_key(&monitor.index) {
+                            " (cropped)"
+                        } else {
+                            ""
+                        }
+                    )
+                    .center(),
+                )
+                .width(Length::Fill)
+                .style(if monitor.index == editor.monitor {
+                    button::primary
+                } else {
+                    button::secondary
+                })
+                .on_press(Message::CropSelectMonitor(monitor.index)),
+            );
+        }
+        column![
+            text!["Crop"].size(24),
+            monitors,
+            canvas(CropView {
+                handle: &editor.handle,
+                image: editor.image,
+                crop: editor.crop(),
+                on_change: Message::CropChanged,
+            })
+            .width(Length::Fill)
+            .height(Length::Fill),
+            text("Drag to move, scroll to zoom. The crop snaps to the middle and the thirds")
+                .size(12),
+            row![
+                text("X"),
+                text_input("0", &editor.x_input).on_input(Message::EditCropX),
+                text("Y"),
+                text_input("0", &editor.y_input).on_input(Message::EditCropY),
+                text("Width"),
+                text_input("1920", &editor.width_input).on_input(Message::EditCropWidth),
+                button("Center").on_press(Message::CenterCrop),
+            ]
+            .spacing(5),
+            row![
+                button("Back")
+                    .width(Length::Fill)
+                    .on_press(Message::CloseSettings),
+                button("Copy to monitors with this resolution")
+                    .width(Length::Fill)
+                    .on_press(Message::CopyCropToSameResolution),
+                button("Apply")
+                    .width(Length::Fill)
+                    .on_press(Message::ApplyCrops),
+            ]
+            .spacing(5),
+        ]
+    }
+
+    fn build_daemon_widgets(&self) -> Column<'_, Message> {
+        let restart = button("Restart").width(Length::Fill);
+        let shutdown = button("Shut down")
+            .width(Length::Fill)
+            .style(button::danger);
+        // already on its way out
+        if self.shutdown.is_some() {
+            return column![row![restart, shutdown].spacing(5)];
+        }
+        column![
+            row![
+                self.gated(
+                    restart,
+                    Message::RestartXab,
+                    Needs::command(IpcCommands::Restart)
+                ),
+                self.gated(
+                    shutdown,
+                    Message::AskShutdownXab,
+                    Needs::command(IpcCommands::Shutdown)
+                ),
+            ]
+            .spacing(5),
+            gate::gated(
+                button("Check what xab can do").width(Length::Fill),
+                Message::RefreshCapabilities,
+                self.ipc_handle
+                    .is_none()
+                    .then(|| "Not connected to xab".to_owned()),
+            ),
+        ]
+        .spacing(5)
+    }
+
+    fn build_shutdown_dialog(&self) -> Element<'_, Message> {
+        card(
+            text("Shut xab down?"),
+            text("Every wallpaper goes away until xab is started again"),
+        )
+        .foot(
+            row![
+                button("Cancel")
+                    .width(Length::Fill)
+                    .style(button::secondary)
+                    .on_press(Message::CancelShutdownXab),
+                button("Shut down")
+                    .width(Length::Fill)
+                    .style(button::danger)
+                    .on_press(Message::ShutdownXab),
+            ]
+            .spacing(5),
+        )
+        .max_width(400.0)
+        .into()
+    }
+
+    fn build_shutdown_banner(&self) -> Column<'_, Message> {
+        let Some(notice) = &self.shutdown else {
+            return column![];
+        };
+        let left = notice.deadline.saturating_duration_since(Instant::now());
+        column![
+            text!(
+                "xab is shutting down in {}s, nothing will be sent until it's back",
+                left.as_secs()
+            ),
+            checkbox(self.restore_when_back)
+                .label("Restore my wallpapers when it's back")
+                .on_toggle(Message::EditRestoreWhenBack),
+            rule::horizontal(50),
+        ]
+        .spacing(5)
+    }
+
+    fn build_update_banner(&self) -> Column<'_, Message> {
+        let Some(release) = &self.update else {
+            return column![];
+        };
+        column![
+            text!("xab-gui {} is available", release.version),
+            column(
+                release
+                    .excerpt
+                    .iter()
+                    .map(|line| text(line).size(12).into())
+            ),
+            row![
+                button("Release notes").on_press(Message::OpenRelease),
+                button("Dismiss").on_press(Message::DismissUpdate),
+            ]
+            .spacing(5),
+            rule::horizontal(50),
+        ]
+        .spacing(5)
+    }
+
+    /// Decodes thumbnails of `paths` off the gui thread, see
+    /// `Message::ThumbnailRendered`
+    fn load_thumbnails(&mut self, paths: Vec<PathBuf>) -> Task<Message> {
+        Task::batch(paths.into_iter().map(|path| {
+            if let Some(thumbnail) = self.previews.thumbnail(&path) {
+                return Task::done(Message::ThumbnailRendered(path, Ok(thumbnail)));
+            }
+            Task::perform(
+                {
+                    let path = path.clone();
+                    async move { preview::thumbnail(&path).map_err(|err| ipc::describe_error(&err)) }
+                },
+                move |res| Message::ThumbnailRendered(path.clone(), res),
+            )
+        }))
+    }
+
+    /// Reloads the recent strip for the first selected monitor
+    /// What the layout says is on each monitor
+    fn refresh_assigned(&mut self) -> Task<Message> {
+        let assigned: Vec<(i32, PathBuf)> = match persist::state() {
+            Ok(state) => self
+                .monitors()
+                .iter()
+                .filter_map(|monitor| {
+                    let path = state.assigned(Some(monitor.index))?;
+                    Some((monitor.index, path.to_path_buf()))
+                })
+                .collect(),
+            Err(err) => {
+                warn!("{:?}", err);
+                return Task::none();
+            }
+        };
+        if assigned != self.assigned {
+            self.assigned = assigned;
+            self.layout.selection_changed();
+        }
+        Task::batch([
+            self.estimate_energy(),
+            Task::done(Message::RefreshLiveState),
+        ])
+    }
+
+    /// Follows a background someone put up, for connections that get events
+    fn live_changed(&mut self, monitor: Option<i32>, path: &Path) {
+        let indices = match monitor {
+            Some(index) => vec![index],
+            None => self.monitor_indices(),
+        };
+        for index in indices {
+            match self.live.iter_mut().find(|state| state.monitor == index) {
+                Some(state) => state.path = Some(path.to_path_buf()),
+                None => self.live.push(BackgroundState {
+                    monitor: index,
+                    path: Some(path.to_path_buf()),
+                    paused: false,
+                    scaling: self
+                        .ipc_handle
+                        .as_ref()
+                        .map_or_else(ScalingMode::default, |ipc| ipc.scaling(Some(index))),
+                }),
+            }
+        }
+        self.layout.selection_changed();
+    }
+
+    /// What's written under each monitor of the layout: what xab says it
+    /// shows, or the saved wallpaper when it can't tell
+    fn captions(&self) -> Vec<(i32, String)> {
+        let file_name = |path: &Path| {
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        };
+        self.monitors()
+            .iter()
+            .filter_map(|monitor| {
+                let index = monitor.index;
+                let Some(state) = self.live.iter().find(|state| state.monitor == index) else {
+                    let (_, path) = self.assigned.iter().find(|(i, _)| *i == index)?;
+                    return Some((index, file_name(path)));
+                };
+                let mut caption = match &state.path {
+                    Some(path) => file_name(path),
+                    None => "Nothing".to_owned(),
+                };
+                if state.paused {
+                    caption.push_str(" · paused");
+                }
+                if state.scaling != ScalingMode::default() {
+                    caption.push_str(&format!(" · {}", state.scaling));
+                }
+                Some((index, caption))
+            })
+            .collect()
+    }
+
+    /// Probes the saved video wallpapers that weren't yet
+    fn estimate_energy(&mut self) -> Task<Message> {
+        let mut videos: Vec<PathBuf> = self
+            .assigned
+            .iter()
+            .map(|(_, path)| path.clone())
+            .filter(|path| snapshot::is_video(path) && !self.energy.contains_key(path))
+            .collect();
+        videos.sort();
+        videos.dedup();
+        Task::batch(videos.into_iter().map(|video| {
+            // probed once, even if it fails
+            self.energy
+                .insert(video.clone(), Err("Probing...".to_owned()));
+            Task::perform(
+                {
+                    let video = video.clone();
+                    async move { energy::probe(&video).map_err(|err| ipc::describe_error(&err)) }
+                },
+                move |res| Message::EnergyEstimated(video.clone(), res),
+            )
+        }))
+    }
+
+    /// What the saved wallpaper of each monitor costs on battery, only
+    /// videos have one
+    fn impacts(&self) -> Vec<(i32, Impact)> {
+        self.assigned
+            .iter()
+            .filter_map(|(index, path)| {
+                let info = self.energy.get(path)?.as_ref().ok()?;
+                Some((*index, info.impact()))
+            })
+            .collect()
+    }
+
+    fn refresh_recent(&mut self) -> Task<Message> {
+        let monitor = self.background_opts.monitor_list().first().copied();
+        let estimate = self.refresh_assigned();
+        self.recent = match persist::state() {
+            Ok(state) => state
+                .recent(monitor)
+                .into_iter()
+                .filter(|path| path.exists())
+                .map(Path::to_path_buf)
+                .collect(),
+            Err(err) => {
+                warn!("{:?}", err);
+                return Task::none();
+            }
+        };
+        self.recent_thumbnails
+            .retain(|path, thumbnail| thumbnail.is_some() && self.recent.contains(path));
+        let missing: Vec<PathBuf> = self
+            .recent
+            .iter()
+            .filter(|path| !self.recent_thumbnails.contains_key(*path))
+            .cloned()
+            .collect();
+        for path in &missing {
+            self.recent_thumbnails.insert(path.clone(), None);
+        }
+        Task::batch([self.load_thumbnails(missing), estimate])
+    }
+
+    fn build_recent_widgets(&self) -> Option<Element<'_, Message>> {
+        if self.recent.is_empty() {
+            return None;
+        }
+        let tiles = self.recent.iter().map(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let tile: Element<'_, Message> = match self.recent_thumbnails.get(path) {
+                Some(Some(Ok(handle))) => image(handle.clone())
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .content_fit(ContentFit::Cover)
+                    .into(),
+                Some(Some(Err(_))) => center(text(name.into_owned()).size(12)).into(),
+                _ => center(text("Loading...").size(12)).into(),
+            };
+            button(tile)
+                .padding(0)
+                .width(Length::Fixed(128.0))
+                .height(Length::Fixed(72.0))
+                .style(button::text)
+                .on_press(Message::ApplyRecent(path.clone()))
+                .into()
+        });
+        Some(
+            card(
+                text!["Recent"],
+                scrollable(row(tiles).spacing(5)).direction(scrollable::Direction::Horizontal(
+                    scrollable::Scrollbar::default(),
+                )),
+            )
+            .into(),
+        )
+    }
+
+    /// Puts the scaling and video options of the first selected monitor in
+    /// the controls
+    fn show_monitor_options(&mut self) {
+        if let Some(ipc_handle) = &self.ipc_handle {
+            let monitor = self.background_opts.monitor_list().first().copied();
+            self.background_opts.scaling = ipc_handle.scaling(monitor);
+            self.background_opts.video = ipc_handle.video_options(monitor);
+        }
+    }
+
+    /// Keeps track of what `IpcHandle::video_playback` did
+    fn record_playback(&mut self, monitors: &[i32], paused: Option<bool>) {
+        if monitors.is_empty() {
+            self.videos_paused = paused.unwrap_or(!self.videos_paused);
+            self.paused_monitors.clear();
+            return;
+        }
+        let all: HashSet<i32> = self.monitors().iter().map(|m| m.index).collect();
+        if self.videos_paused {
+            self.videos_paused = false;
+            self.paused_monitors = all.clone();
+        }
+        for &monitor in monitors {
+            let paused = paused.unwrap_or(!self.paused_monitors.contains(&monitor));
+            match paused {
+                true => self.paused_monitors.insert(monitor),
+                false => self.paused_monitors.remove(&monitor),
+            };
+        }
+        if self.paused_monitors == all {
+            self.videos_paused = true;
+            self.paused_monitors.clear();
+        }
+    }
+
+    /// A state with its icon, in the configured palette
+    fn badge<'a>(&self, status: Status, label: impl text::IntoFragment<'a>) -> Row<'a, Message> {
+        status::badge(status, self.config.palette, label)
+    }
+
+    /// `control` sending `on_press`, disabled with a tooltip when the
+    /// connected xab can't do it
+    fn gated<'a>(
+        &self,
+        control: Button<'a, Message>,
+        on_press: Message,
+        needs: Needs,
+    ) -> Element<'a, Message> {
+        let unmet = match &self.ipc_handle {
+            Some(ipc_handle) => needs.unmet(ipc_handle.capabilities()),
+            None => Some("Not connected to xab".to_owned()),
+        };
+        match self.explain_button(needs) {
+            Some(explain) => row![gate::gated(control, on_press, unmet), explain]
+                .spacing(2)
+                .into(),
+            None => gate::gated(control, on_press, unmet),
+        }
+    }
+
+    /// A button opening `build_capabilities_dialog` when the connected xab is
+    /// too old for `needs`
+    fn explain_button<'a>(&self, needs: Needs) -> Option<Element<'a, Message>> {
+        let missing = needs.missing(self.ipc_handle.as_ref()?.capabilities());
+        (!missing.is_empty()).then(|| {
+            button(text("?").center())
+                .style(button::secondary)
+                .on_press(Message::ExplainCapabilities(missing))
+                .into()
+        })
+    }
+
+    /// What the connected xab said it can do, for bug reports
+    fn capabilities_report(&self) -> String {
+        let Some(ipc_handle) = &self.ipc_handle else {
+            return "Not connected to xab".to_owned();
+        };
+        let mut report = format!(
+            "xab at {}, IPC protocol {}, capabilities {:#x}\n",
+            ipc_handle.path,
+            ipc_handle.protocol,
+            ipc_handle.capabilities().bits()
+        );
+        for &capability in gate::KNOWN {
+            let has = ipc_handle.capabilities().contains(capability);
+            report += &format!(
+                "{} {}\n",
+                if has { "yes" } else { "no " },
+                gate::describe(capability)
+            );
+        }
+        report
+    }
+
+    fn build_capabilities_dialog(&self, missing: IpcXabCapabilities) -> Element<'_, Message> {
+        let have = self
+            .ipc_handle
+            .as_ref()
+            .map(|ipc_handle| ipc_handle.capabilities())
+            .unwrap_or(IpcXabCapabilities::None);
+        let mut list = column![].spacing(2);
+        for &capability in gate::KNOWN {
+            let status = match (have.contains(capability), missing.intersects(capability)) {
+                (true, _) => Status::Ok,
+                (false, true) => Status::Error,
+                (false, false) => Status::Pending,
+            };
+            list = list.push(self.badge(status, gate::describe(capability)));
+        }
+        let needed: Vec<&str> = missing.iter().map(gate::describe).collect();
+        let header = match &self.ipc_handle {
+            Some(ipc_handle) => format!(
+                "Connected to {} (IPC protocol {}, capabilities {:#x})",
+                ipc_handle.path,
+                ipc_handle.protocol,
+                have.bits()
+            ),
+            None => "Not connected to xab".to_owned(),
+        };
+        card(
+            text("This xab is too old for that"),
+            column![
+                text(header),
+                text!(
+                    "It needs a newer xab that can do {}. Once xab is upgraded, reconnect and \
+                     it turns on by itself",
+                    needed.join(" and ")
+                ),
+                text("What this xab told us it can do:"),
+                list,
+            ]
+            .spacing(10),
+        )
+        .foot(
+            row![
+                button("Copy details")
+                    .width(Length::Fill)
+                    .style(button::secondary)
+                    .on_press(Message::CopyCapabilities),
+                button("Close")
+                    .width(Length::Fill)
+                    .on_press(Message::CloseCapabilities),
+            ]
+            .spacing(5),
+        )
+        .max_width(500.0)
+        .into()
+    }
+
+    fn build_schedule_dialog<'a>(&self, horizon: Horizon, plan: &'a Plan) -> Element<'a, Message> {
+        let mut monitors: Vec<Option<i32>> = plan.entries.iter().map(|e| e.monitor).collect();
+        monitors.sort();
+        monitors.dedup();
+
+        let mut columns = row![].spacing(15);
+        for monitor in monitors {
+            let mut entries = column![
+                text(match monitor {
+                    Some(index) => format!("Monitor {index}"),
+                    None => "Every monitor".to_owned(),
+                })
+                .size(16)
+            ]
+            .spacing(2);
+            let mut day = None;
+            for entry in plan.entries.iter().filter(|e| e.monitor == monitor) {
+                if day != Some(entry.at.date_naive()) {
+                    day = Some(entry.at.date_naive());
+                    entries = entries.push(text(entry.at.format("%A %-d %B").to_string()).size(12));
+                }
+                entries = entries.push(text!("{}  {}", entry.at.format("%H:%M"), entry.what));
+            }
+            columns = columns.push(entries.width(Length::Fixed(240.0)));
+        }
+
+        let mut notes = column![].spacing(2);
+        if plan.entries.is_empty() {
+            notes = notes.push(text(
+                "Nothing is scheduled, start a rotation or turn on a template",
+            ));
+        }
+        if plan.truncated > 0 {
+            notes = notes.push(text!("And {} more after that", plan.truncated));
+        }
+        if self.presentation.is_some() {
+            notes = notes.push(text("Nothing happens until presentation mode is left"));
+        }
+        notes = notes.push(text(
+            "Rules aren't on it, they run when monitors, the battery or the connection change",
+        ));
+
+        card(
+            text("What automation will do"),
+            column![
+                pick_list(Horizon::ALL, Some(horizon), Message::SelectScheduleHorizon),
+                scrollable(columns)
+                    .direction(scrollable::Direction::Both {
+                        vertical: scrollable::Scrollbar::default(),
+                        horizontal: scrollable::Scrollbar::default(),
+                    })
+                    .height(Length::Fixed(400.0)),
+                notes,
+            ]
+            .spacing(10),
+        )
+        .foot(
+            button("Close")
+                .width(Length::Fill)
+                .on_press(Message::CloseSchedule),
+        )
+        .max_width(800.0)
+        .into()
+    }
+
+    /// Only for videos, and only when xab can do something with them
+    fn build_video_options_widgets(&self) -> Option<Element<'_, Message>> {
+        let ipc_handle = self.ipc_handle.as_ref()?;
+        if !ipc_handle
+            .capabilities()
+            .contains(IpcXabCapabilities::Video)
+            || !snapshot::is_video(&self.background_opts.path)
+        {
+            return None;
+        }
+        let header = button(text(match self.video_options_open {
+            true => "Video options ▾",
+            false => "Video options ▸",
+        }))
+        .style(button::text)
+        .on_press(Message::ToggleVideoOptions);
+        if !self.video_options_open {
+            return Some(header.into());
+        }
+        let video = self.background_opts.video;
+        let body = column![
+            row![
+                checkbox(video.looping)
+                    .label("Loop")
+                    .on_toggle(move |looping| Message::EditVideo(VideoOptions {
+                        looping,
+                        ..video
+                    })),
+                checkbox(video.muted)
+                    .label("Mute")
+                    .on_toggle(move |muted| Message::EditVideo(VideoOptions { muted, ..video })),
+            ]
+            .spacing(10),
+            row![
+                text("Volume").width(Length::Fixed(60.0)),
+                slider(0..=100, video.volume, move |volume| {
+                    Message::EditVideo(VideoOptions { volume, ..video })
+                }),
+                text!("{}%", video.volume).width(Length::Fixed(50.0)),
+            ]
+            .spacing(5),
+            row![
+                text("Speed").width(Length::Fixed(60.0)),
+                slider(VideoOptions::RATES, video.rate, move |rate| {
+                    Message::EditVideo(VideoOptions { rate, ..video })
+                })
+                .step(25u16),
+                text!("{:.2}x", f32::from(video.rate) / 100.0).width(Length::Fixed(50.0)),
+            ]
+            .spacing(5),
+            text("Applied with the next background").size(12),
+        ]
+        .spacing(5);
+        Some(card(header, body).into())
+    }
+
+    fn build_playback_widgets(&self) -> Column<'_, Message> {
+        let state = if self.videos_paused {
+            "Paused".to_owned()
+        } else if self.paused_monitors.is_empty() {
+            "Playing".to_owned()
+        } else {
+            let mut monitors: Vec<i32> = self.paused_monitors.iter().copied().collect();
+            monitors.sort_unstable();
+            let monitors: Vec<String> = monitors.iter().map(|m| (m + 1).to_string()).collect();
+            format!("Paused on monitors {}", monitors.join(", "))
+        };
+        let monitors = self.background_opts.monitor_list();
+        let target = match monitors.as_slice() {
+            [] => "every monitor".to_owned(),
+            [monitor] => format!("monitor {}", monitor + 1),
+            monitors => format!("{} monitors", monitors.len()),
+        };
+        let needs = match monitors.is_empty() {
+            true => Needs::default(),
+            false => Needs::capabilities(IpcXabCapabilities::PerMonitorPlayback),
+        };
+        column![
+            text(state),
+            row![
+                self.gated(button("Pause"), Message::VideoPlayback(Some(true)), needs),
+                self.gated(button("Resume"), Message::VideoPlayback(Some(false)), needs),
+                self.gated(button("Toggle"), Message::VideoPlayback(None), needs),
+                text(format!("on {target}")),
+            ]
+            .spacing(5),
+        ]
+        .spacing(5)
+    }
+
+    fn build_automation_widgets(&self) -> Column<'_, Message> {
+        let mut pause_buttons = row![text("Pause for")].spacing(5);
+        for &pause in PauseFor::ALL {
+            pause_buttons = pause_buttons
+                .push(button(text(pause.to_string())).on_press(Message::PauseAutomation(pause)));
+        }
+        if self.automation_paused_until.is_some() {
+            pause_buttons =
+                pause_buttons.push(button("Resume").on_press(Message::ResumeAutomation));
+        }
+        column![
+            button(if self.presentation.is_some() {
+                "Leave presentation mode"
+            } else {
+                "Presentation mode"
+            })
+            .width(Length::Fill)
+            .on_press(Message::TogglePresentation),
+            button(if self.snapshot.is_some() {
+                "Restore video wallpapers"
+            } else {
+                "Freeze video wallpapers (for screen sharing)"
+            })
+            .width(Length::Fill)
+            .on_press(Message::ToggleSnapshot),
+            text(match self.automation_paused() {
+                Some(reason) => format!("Automatic changes are {reason}"),
+                None => "Automatic changes are running".to_owned(),
+            }),
+            self.build_conflicts_widgets(),
+            pause_buttons,
+            button("Preview schedule")
+                .width(Length::Fill)
+                .style(button::secondary)
+                .on_press(Message::OpenSchedule),
+            rule::horizontal(1),
+            row![
+                text("Rotate every"),
+                text_input("30", &self.rotation_interval_input)
+                    .width(Length::Fixed(60.0))
+                    .on_input_maybe(
+                        self.scheduler
+                            .is_none()
+                            .then_some(Message::EditRotationInterval)
+                    ),
+                text("min"),
+                pick_list(
+                    RotationOrder::ALL,
+                    Some(self.config.rotation.order),
+                    Message::SelectRotationOrder
+                ),
+                checkbox(self.config.rotation.per_monitor)
+                    .label("Each monitor its own")
+                    .on_toggle_maybe(
+                        self.scheduler
+                            .is_none()
+                            .then_some(Message::EditRotationPerMonitor)
+                    ),
+            ]
+            .spacing(5)
+            .align_y(iced::Alignment::Center),
+            match &self.scheduler {
+                Some(scheduler) => row![
+                    text!(
+                        "Rotating through {} wallpaper(s) of {}",
+                        scheduler.len(),
+                        self.sources
+                            .get(&scheduler.source_id)
+                            .map(|source| source.name().to_owned())
+                            .unwrap_or_default()
+                    )
+                    .width(Length::Fill),
+                    button("Next").on_press(Message::Rotate),
+                    button("Stop").on_press(Message::StopRotation),
+                ],
+                None => row![
+                    button("Rotate through the selected source")
+                        .width(Length::Fill)
+                        .on_press_maybe(
+                            self.selected_source
+                                .is_some()
+                                .then_some(Message::StartRotation)
+                        ),
+                ]
+                .push(
+                    (!self.queue.is_empty())
+                        .then(|| button("Play the next queued").on_press(Message::Rotate)),
+                ),
+            }
+            .spacing(5)
+            .align_y(iced::Alignment::Center),
+            self.build_queue_widgets(),
+        ]
+        .spacing(5)
+    }
+
+    /// Automations that would fight over a monitor and which one wins
+    fn build_conflicts_widgets(&self) -> Column<'_, Message> {
+        let monitors = self.monitors();
+        let conflicts = coordinator::conflicts(
+            &self.automation_targets(),
+            &self.config.precedence,
+            &monitors,
+        );
+        let mut conflicts_widgets = Column::new().spacing(5);
+        for conflict in conflicts {
+            let Some((winner, losers)) = conflict.automations.split_first() else {
+                continue;
+            };
+            let losers: Vec<String> = losers
+                .iter()
+                .map(|a| a.to_string().to_lowercase())
+                .collect();
+            conflicts_widgets = conflicts_widgets.push(text!(
+                "{winner} and {} both change {}, {} wins (see [precedence] in the config)",
+                losers.join(" and "),
+                match conflict.monitor {
+                    Some(monitor) => format!("monitor {}", monitor + 1),
+                    None => "every monitor".to_owned(),
+                },
+                winner.to_string().to_lowercase(),
+            ));
+        }
+        conflicts_widgets
+    }
+
+    fn build_queue_widgets(&self) -> Column<'_, Message> {
+        if self.queue.is_empty() {
+            return column![];
+        }
+        let mut queue_widgets = Column::new().spacing(5).push(
+            row![
+                text!("Up next, {} queued", self.queue.len()).width(Length::Fill),
+                button("Clear")
+                    .style(button::secondary)
+                    .on_press(Message::ClearQueue),
+            ]
+            .spacing(5)
+            .align_y(iced::Alignment::Center),
+        );
+        for (at, (_, wallpaper)) in self.queue.iter().enumerate() {
+            queue_widgets = queue_widgets.push(
+                row![
+                    text!("{}. {}", at + 1, wallpaper.name).width(Length::Fill),
+                    button("Remove")
+                        .style(button::secondary)
+                        .on_press(Message::UnqueueWallpaper(at)),
+                ]
+                .spacing(5)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+        queue_widgets
+    }
+
+    /// Keeps a handle on `connect` for the cancel button
+    fn cancelable_connect(&mut self, connect: Task<Message>) -> Task<Message> {
+        let (connect, handle) = connect.abortable();
+        self.connecting = Some(handle.abort_on_drop());
+        connect
+    }
+
+    fn connect_ipc(path: String, timeouts: Timeouts) -> Task<Message> {
+        Task::perform(
+            async move { IpcHandle::with_timeouts(&path, timeouts).await },
+            |res| match res {
+                Ok(ipc_handle) => Message::Connected(Arc::new(ipc_handle)),
+                Err(err) => Message::ConnectionFailed(ipc::describe_error(&err)),
+            },
+        )
+    }
+
+    /// Monitors shown in the layout
+    fn monitors(&self) -> Vec<Monitor> {
+        match self.shared.read().monitors.as_slice() {
+            [] if !self.arriving_monitors.is_empty() => self.arriving_monitors.clone(),
+            [] => vec![Monitor::fullscreen()],
+            monitors => monitors.to_vec(),
+        }
+    }
+
+    /// Previews of the selected file on the monitors it would go to
+    fn preview_keys(&self) -> Vec<PreviewKey> {
+        if self.background_opts.path.as_os_str().is_empty() {
+            return Vec::new();
+        }
+        let selected = &self.background_opts.monitors;
+        self.monitors()
+            .iter()
+            .filter(|monitor| selected.is_empty() || selected.contains(&monitor.index))
+            .map(|monitor| {
+                PreviewKey::new(
+                    monitor,
+                    self.background_opts.scaling,
+                    &self.background_opts.path,
+                )
+            })
+            .collect()
+    }
+
+    /// Renders whatever previews aren't cached yet
+    fn render_previews(&mut self) -> Task<Message> {
+        // whatever's cached might be for another file or monitor
+        self.layout.selection_changed();
+        let keys: Vec<PreviewKey> = self
+            .preview_keys()
+            .into_iter()
+            .filter(|key| self.previews.start(key))
+            .collect();
+        let previews = Task::batch(keys.into_iter().map(|key| {
+            Task::perform(
+                {
+                    let key = key.clone();
+                    async move { preview::render(&key).map_err(|err| ipc::describe_error(&err)) }
+                },
+                move |res| Message::PreviewRendered(key.clone(), res),
+            )
+        }));
+        Task::batch([self.render_thumbnail(), previews])
+    }
+
+    /// Decodes the selected file for the preview pane, unless it's done already
+    fn render_thumbnail(&mut self) -> Task<Message> {
+        let path = self.background_opts.path.clone();
+        if path.as_os_str().is_empty()
+            || self
+                .thumbnail
+                .as_ref()
+                .is_some_and(|(selected, _)| *selected == path)
+        {
+            return Task::none();
+        }
+        self.credit = provenance::of(&path).unwrap_or_else(|err| {
+            warn!("{:?}", err);
+            None
+        });
+        if let Some(thumbnail) = self.previews.thumbnail(&path) {
+            self.thumbnail = Some((path, Some(Ok(thumbnail))));
+            return Task::none();
+        }
+        self.thumbnail = Some((path.clone(), None));
+        Task::perform(
+            {
+                let path = path.clone();
+                async move { preview::thumbnail(&path).map_err(|err| ipc::describe_error(&err)) }
+            },
+            move |res| Message::ThumbnailRendered(path.clone(), res),
+        )
+    }
+
+    /// The selected file with a caption, nothing until one is selected
+    fn build_thumbnail_widgets(&self) -> Option<Element<'_, Message>> {
+        let (path, thumbnail) = self
+            .thumbnail
+            .as_ref()
+            .filter(|(path, _)| *path == self.background_opts.path)?;
+        let (preview, caption): (Element<'_, Message>, String) = match thumbnail {
+            Some(Ok(thumbnail)) => (
+                image(thumbnail.handle.clone())
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .content_fit(ContentFit::Contain)
+                    .into(),
+                thumbnail.caption(path),
+            ),
+            Some(Err(_)) => (
+                center(text("Can't preview this file")).into(),
+                path.display().to_string(),
+            ),
+            None => (
+                center(text("Loading...")).into(),
+                path.display().to_string(),
+            ),
+        };
+        Some(
+            column![
+                container(preview)
+                    .width(Length::Fill)
+                    .height(Length::Fixed(180.0))
+                    .style(|_| container::background(Color::BLACK)),
+                text(caption).size(12),
+            ]
+            .push(self.credit.as_ref().map(|credit| {
+                row![
+                    text(credit.attribution()).size(12).width(Length::Fill),
+                    button(text("Copy credit").size(12))
+                        .style(button::secondary)
+                        .on_press(Message::CopyCredit),
+                ]
+                .spacing(5)
+                .align_y(iced::Alignment::Center)
+            }))
+            .spacing(5)
+            .into(),
+        )
+    }
+
+    /// The crop follows the numeric inputs once they're numbers again
+    fn edit_crop_inputs(&mut self, edit: impl FnOnce(&mut CropEditor)) -> Task<Message> {
+        if let Some(editor) = &mut self.crop {
+            edit(editor);
+            if let Some(crop) = editor.parse_inputs() {
+                editor.crops.insert(editor.monitor, crop);
+            }
+        }
+        Task::none()
+    }
+
+    /// Whether every selected monitor is pinned
+    fn selection_pinned(&self) -> bool {
+        self.background_opts
+            .targets()
+            .into_iter()
+            .all(|monitor| self.pins.is_pinned(monitor))
+    }
+
+    /// After the selected monitors got something new, or are about to
+    fn invalidate_selected_previews(&mut self) {
+        for monitor in self.background_opts.targets() {
+            self.previews.invalidate_monitor(monitor.unwrap_or(-1));
+        }
+    }
+
+    fn notify_choose_monitor(&mut self) {
+        self.user_notice = Some(format!(
+            "Pick a monitor for {}, then apply",
+            self.background_opts.path.display()
+        ));
+    }
+
+    /// Swaps in the config reloaded or reset from the settings
+    fn config_reloaded(&mut self, loaded: Result<Config>) -> Task<Message> {
+        let config = match loaded {
+            Ok(config) => config,
+            Err(err) => {
+                self.user_error = Some(format!("{err:#}"));
+                return Task::none();
+            }
+        };
+        limits::set(config.limits);
+        net::set_bandwidth(config.bandwidth);
+        net::set_proxies(config.proxies.clone());
+        net::set_offline(config.offline);
+        sources::filter::set(config.content_filters.clone());
+        if let Some(hotkeys) = &mut self.hotkeys
+            && let Err(err) = hotkeys.apply(&config.hotkeys)
+        {
+            error!("Failed to register hotkeys: {:?}", err);
+        }
+        self.config = config;
+        self.previews.set_budget(self.config.cache_budget());
+        self.refresh_endpoints();
+
+        // the inputs start over from the new config
+        let back = self.settings_return;
+        let task = self.update(Message::OpenSettings);
+        self.settings_return = back;
+        self.user_notice = Some("Config loaded".to_owned());
+        task
+    }
+
+    fn set_page(&mut self, page: Page) {
+        self.page = page;
+        self.user_error = None;
+        self.user_notice = None;
+        let last_page = match page {
+            Page::Connected => StartPage::Monitors,
+            Page::Library if self.ipc_handle.is_some() => StartPage::Library,
+            _ => return,
+        };
+        if last_page != self.last_page {
+            self.last_page = last_page;
+            if let Err(err) = persist::update(|state| state.last_page = last_page) {
+                warn!("Failed to remember the page: {:?}", err);
+            }
+        }
+    }
+
+    fn title(&self, window: window::Id) -> String {
+        match &self.slideshow {
+            Some(slideshow) if slideshow.window == window => "xab gui - slideshow".to_owned(),
+            _ if self.comparison.as_ref().is_some_and(|c| c.window == window) => {
+                "xab gui - compare".to_owned()
+            }
+            _ if self.preview_window == Some(window) => "xab gui - preview".to_owned(),
+            _ => match (&self.ipc_handle, &self.selected_endpoint) {
+                (
+                    Some(_),
+                    Some(Endpoint::Local(XabSocket {
+                        display: Some(display),
+                        ..
+                    })),
+                ) => format!("xab gui - {display}"),
+                (Some(_), Some(Endpoint::Ssh(profile))) => format!("xab gui - {}", profile.host),
+                _ => "xab gui".to_owned(),
+            },
+        }
+    }
+
+    fn view(&self, window: window::Id) -> Element<'_, Message> {
+        match &self.slideshow {
+            Some(slideshow) if slideshow.window == window => Self::view_slideshow(slideshow),
+            _ => match &self.comparison {
+                Some(comparison) if comparison.window == window => self.view_comparison(comparison),
+                _ if self.preview_window == Some(window) => self.view_detached_preview(),
+                _ if self.confirm_shutdown => modal(self.view_main(), self.build_shutdown_dialog()),
+                _ => match (self.explain_missing, &self.schedule) {
+                    (Some(missing), _) => {
+                        modal(self.view_main(), self.build_capabilities_dialog(missing))
+                    }
+                    (None, Some((horizon, plan))) => {
+                        modal(self.view_main(), self.build_schedule_dialog(*horizon, plan))
+                    }
+                    (None, None) => self.view_main().into(),
+                },
+            },
+        }
+    }
+
+    fn view_comparison<'a>(&self, comparison: &'a Comparison) -> Element<'a, Message> {
+        let mut candidates = row![].spacing(10);
+        for (index, candidate) in comparison.candidates.iter().enumerate() {
+            let preview: Element<'_, Message> = match (&candidate.handle, &candidate.path) {
+                (Some(handle), _) => image(handle.clone())
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .content_fit(ContentFit::Cover)
+                    .into(),
+                (None, Some(path)) => {
+                    center(text!("Can't preview `{}` yet", path.display())).into()
+                }
+                (None, None) => match &candidate.error {
+                    Some(err) => center(self.badge(Status::Error, err)).into(),
+                    None => center(self.badge(Status::Pending, "Fetching...")).into(),
+                },
+            };
+            let on_desktop = comparison.trying == Some(index);
+
+            candidates = candidates.push(
+                column![
+                    container(preview)
+                        .width(Length::Fill)
+                        .height(Length::Fill)
+                        .style(|_| container::background(Color::BLACK)),
+                    text(&candidate.wallpaper.name),
+                    text(if on_desktop {
+                        "On your desktop now"
+                    } else {
+                        ""
+                    }),
+                    button("Pick this one").width(Length::Fill).on_press_maybe(
+                        candidate
+                            .path
+                            .is_some()
+                            .then_some(Message::PickCandidate(index))
+                    ),
+                ]
+                .spacing(5)
+                .width(Length::Fill),
+            );
+        }
+
+        let try_button = match comparison.trying {
+            Some(_) => button("Stop trying").on_press(Message::StopTrying),
+            None => button(text!(
+                "Try each on the desktop for {}s",
+                compare::TRY_DURATION.as_secs()
+            ))
+            .on_press_maybe(self.ipc_handle.is_some().then_some(Message::TryCandidates)),
+        };
+
+        column![
+            candidates.height(Length::Fill),
+            row![
+                try_button,
+                button("Shuffle").on_press(Message::ShuffleComparison),
+            ]
+            .spacing(5),
+        ]
+        .spacing(10)
+        .padding(10)
+        .into()
+    }
+
+    fn view_detached_preview(&self) -> Element<'_, Message> {
+        let path = &self.background_opts.path;
+        let preview: Element<'_, Message> = if path.as_os_str().is_empty() {
+            center(text("Select a file to preview it here")).into()
+        } else if slideshow::is_previewable(path) {
+            image(path.clone())
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .content_fit(ContentFit::Cover)
+                .into()
+        } else {
+            center(text!("Can't preview `{}` yet", path.display())).into()
+        };
+
+        // click to fill the monitor the window is on, click again to go back
+        mouse_area(
+            container(preview)
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .style(|_| container::background(Color::BLACK)),
+        )
+        .on_press(Message::TogglePreviewFullscreen)
+        .into()
+    }
+
+    fn view_slideshow(slideshow: &Slideshow) -> Element<'_, Message> {
+        let progress = slideshow.progress();
+        let mut slides = stack![];
+        if let Some(previous) = slideshow.previous.clone().filter(|_| progress < 1.0) {
+            slides = slides.push(
+                image(previous)
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .content_fit(ContentFit::Cover)
+                    .opacity(1.0 - progress),
+            );
+        }
+        if let Some(current) = slideshow.current.clone() {
+            slides = slides.push(
+                image(current)
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .content_fit(ContentFit::Cover)
+                    .opacity(progress),
+            );
+        }
+
+        container(slides)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(|_| container::background(Color::BLACK))
+            .into()
+    }
+
+    fn build_panels_widgets(&self) -> Column<'_, Message> {
+        let arranged = self.panels_input.arranged();
+        let mut panels = column![].spacing(5);
+        for (at, &panel) in arranged.iter().enumerate() {
+            let visible = self.panels_input.is_visible(panel);
+            panels = panels.push(
+                row![
+                    checkbox(visible)
+                        .label(panel.to_string())
+                        .on_toggle_maybe(
+                            panel
+                                .hideable()
+                                .then_some(move |visible| Message::ShowPanel(panel, visible))
+                        )
+                        .width(Length::Fill),
+                    button("Up")
+                        .on_press_maybe((at > 0).then_some(Message::ShiftPanel(panel, true))),
+                    button("Down").on_press_maybe(
+                        (at + 1 < arranged.len()).then_some(Message::ShiftPanel(panel, false))
+                    ),
+                ]
+                .spacing(5)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+        panels
+    }
+
+    /// One of the panels of the connected page, None if it has nothing to
+    /// show right now
+    fn build_panel(&self, panel: Panel) -> Option<Element<'_, Message>> {
+        let pinned = self.selection_pinned();
+        Some(match panel {
+            Panel::Preview => self.build_thumbnail_widgets()?,
+            Panel::Monitors => card(
+                text(format!(
+                    "{}{}",
+                    if self.choosing_monitor {
+                        "Monitors - pick one for the opened file"
+                    } else {
+                        "Monitors"
+                    },
+                    if pinned { " \u{1F4CC}" } else { "" }
+                )),
+                column![
+                    canvas(LayoutView {
+                        monitors: self.monitors(),
+                        selected: &self.background_opts.monitors,
+                        previews: self
+                            .preview_keys()
+                            .iter()
+                            .filter_map(|key| {
+                                Some((key.monitor, self.previews.get(key)?.clone()))
+                            })
+                            .collect(),
+                        captions: self.captions(),
+                        impacts: self.impacts(),
+                        caches: &self.layout,
+                        on_select: Message::SelectMonitor,
+                        on_toggle: Message::ToggleMonitor,
+                        on_menu: Message::OpenMonitorMenu,
+                        on_drop: Message::DroppedOnMonitor,
+                    })
+                    .width(Length::Fill)
+                    .height(Length::Fixed(160.0)),
+                    self.build_monitor_menu(),
+                    self.build_monitors_widgets(),
+                    row![
+                        pick_list(
+                            ScalingMode::ALL,
+                            Some(self.background_opts.scaling),
+                            Message::SelectScaling
+                        ),
+                        // only the preview follows it otherwise
+                        self.explain_button(Needs::capabilities(IpcXabCapabilities::Scaling)),
+                        button(if pinned { "Unpin" } else { "Pin" }).on_press(Message::TogglePin),
+                    ]
+                    .spacing(5)
+                ]
+                .spacing(5),
+            )
+            .into(),
+            Panel::Apply => row![
+                button("Apply")
+                    .width(Length::Fill)
+                    .on_press(Message::SendBackgroundOptions),
+                button("Clear").style(button::secondary).on_press_maybe(
+                    self.ipc_handle
+                        .is_some()
+                        .then_some(Message::ClearBackground)
+                ),
+                self.gated(
+                    button("Span across monitors"),
+                    Message::SpanBackground,
+                    Needs::capabilities(
+                        IpcXabCapabilities::Monitors | IpcXabCapabilities::CustomPositioning
+                    )
+                ),
+            ]
+            .spacing(5)
+            .push(self.apply_status.map(|status| {
+                self.badge(
+                    status,
+                    match status {
+                        Status::Ok => "Applied",
+                        Status::Pending => "Applying...",
+                        Status::Error => "Apply failed",
+                    },
+                )
+            }))
+            .into(),
+            Panel::Recent => self.build_recent_widgets()?,
+            Panel::Sources => card(text!["Sources"], self.build_sources_widgets()).into(),
+            Panel::Playlists => card(text!["Playlists"], self.build_playlists_widgets()).into(),
+            Panel::RecentlyDeleted => self.build_recently_deleted_widgets().into(),
+            Panel::Video => self.build_video_options_widgets()?,
+            Panel::Playback => card(text!["Playback"], self.build_playback_widgets()).into(),
+            Panel::Automation => card(text!["Automation"], self.build_automation_widgets()).into(),
+            Panel::Status => card(text!["Status"], self.build_status_widgets()).into(),
+            Panel::Daemon => card(text!["Daemon"], self.build_daemon_widgets()).into(),
+        })
+    }
+
+    fn view_main(&self) -> Column<'_, Message> {
+        match self.page {
+            Page::Connect => column![
+                self.build_update_banner(),
+                image("res/logo.webp").content_fit(ContentFit::Cover),
+                row![
+                    pick_list(
+                        self.endpoints.as_slice(),
+                        self.selected_endpoint.as_ref(),
+                        Message::SelectEndpoint
+                    )
+                    .placeholder("xab isn't running")
+                    .width(Length::Fill),
+                    button("Refresh").on_press(Message::RefreshSockets),
+                ]
+                .spacing(5),
+                row![
+                    text_input(IPC_PATH, &self.socket_input)
+                        .on_input(Message::EditSocket)
+                        .on_submit(Message::UseSocket),
+                    button("Use socket").on_press(Message::UseSocket),
+                    button("Reset to default").on_press_maybe(
+                        self.config.socket.is_some().then_some(Message::ResetSocket)
+                    ),
+                ]
+                .spacing(5),
+                button("Connect")
+                    .width(Length::Fill)
+                    .on_press(Message::Connect),
+                button("Settings")
+                    .width(Length::Fill)
+                    .on_press(Message::OpenSettings),
+            ]
+            .push(
+                self.session
+                    .warning()
+                    .map(|w| column![rule::horizontal(50), text(w)]),
+            )
+            .push(
+                self.user_error
+                    .as_ref()
+                    .map(|e| column![rule::horizontal(50), self.badge(Status::Error, e)]),
+            )
+            .padding(20),
+            Page::Connecting => column![
+                self.badge(Status::Pending, "Connecting..."),
+                button("Cancel")
+                    .width(Length::Fill)
+                    .on_press(Message::CancelConnect),
+            ]
+            .spacing(10)
+            .push(
+                self.user_error
+                    .as_ref()
+                    .map(|e| column![rule::horizontal(50), self.badge(Status::Error, e)]),
+            )
+            .padding(20),
+            Page::WaitingForXab => column![
+                self.badge(
+                    Status::Pending,
+                    "xab shut down, waiting for it to come back..."
+                ),
+                checkbox(self.restore_when_back)
+                    .label("Restore my wallpapers when it's back")
+                    .on_toggle(Message::EditRestoreWhenBack),
+                button("Stop waiting")
+                    .width(Length::Fill)
+                    .on_press(Message::StopWaiting),
+            ]
+            .spacing(10)
+            .padding(20),
+            Page::Connected => column![
+                self.build_shutdown_banner(),
+                self.build_update_banner(),
+                image("res/logo.webp").content_fit(ContentFit::Cover),
+                row![
+                    button("Select file")
+                        .width(Length::Fill)
+                        .on_press(Message::SelectFileForBackground),
+                    self.gated(
+                        button("Crop"),
+                        Message::OpenCropEditor,
+                        Needs::capabilities(IpcXabCapabilities::Monitors)
+                    ),
+                    button("Detach preview").on_press(Message::DetachPreview),
+                ]
+                .spacing(5),
+                column(
+                    self.config
+                        .panels
+                        .visible()
+                        .into_iter()
+                        .filter_map(|panel| self.build_panel(panel))
+                ),
+                row![
+                    button("Settings")
+                        .width(Length::Fill)
+                        .on_press(Message::OpenSettings),
+                    button("Scripts")
+                        .width(Length::Fill)
+                        .on_press(Message::OpenScripts),
+                    button("Templates")
+                        .width(Length::Fill)
+                        .on_press(Message::OpenTemplates),
+                    button("Library")
+                        .width(Length::Fill)
+                        .on_press(Message::OpenLibrary),
+                ]
+                .spacing(5),
+                button("Disconnect")
+                    .width(Length::Fill)
+                    .on_press(Message::Disconnect),
+            ]
+            .push(
+                self.user_notice
+                    .as_ref()
+                    .map(|n| column![rule::horizontal(50), text(n)]),
+            )
+            .push(
+                self.user_error
+                    .as_ref()
+                    .map(|e| column![rule::horizontal(50), self.badge(Status::Error, e)]),
+            )
+            .padding(20),
+            Page::Settings => column![
+                text!["Settings"].size(24),
+                card(text!["General"], self.build_general_settings_widgets()),
+                card(text!["Connected page"], self.build_panels_widgets()),
+                card(
+                    text!["Content filters"],
+                    self.build_content_filter_widgets()
+                ),
+                self.build_hotkeys_widgets(),
+                card(
+                    text!["Quiet hours"],
+                    row![
+                        text("No automatic changes from"),
+                        text_input("22:00", &self.quiet_start_input)
+                            .on_input(Message::EditQuietStart),
+                        text("to"),
+                        text_input("07:00", &self.quiet_end_input).on_input(Message::EditQuietEnd),
+                    ]
+                    .spacing(5)
+                ),
+                card(
+                    text!["Presentation mode"],
+                    checkbox(self.presentation_neutral_input)
+                        .label("Cover the desktop with a neutral wallpaper")
+                        .on_toggle(Message::EditPresentationNeutral),
+                ),
+                card(
+                    text!["Notifications"],
+                    pick_list(
+                        NotificationMode::ALL,
+                        Some(self.notifications_input),
+                        Message::SelectNotificationMode
+                    )
+                    .width(Length::Fill),
+                ),
+                card(
+                    text!["Night dimming"],
+                    column![
+                        checkbox(self.night_dimming_input)
+                            .label("Dim and warm up wallpapers at night")
+                            .on_toggle(Message::EditNightDimming),
+                        row![
+                            text("Without a desktop night light, from"),
+                            text_input("20:00", &self.night_start_input)
+                                .on_input(Message::EditNightStart),
+                            text("to"),
+                            text_input("07:00", &self.night_end_input)
+                                .on_input(Message::EditNightEnd),
+                        ]
+                        .spacing(5),
+                    ]
+                    .spacing(5)
+                ),
+                card(text!["Rules"], self.build_rules_widgets()),
+                card(
+                    text!["Experimental"],
+                    checkbox(self.active_color_input)
+                        .label("Match the wallpaper to the focused window's icon color")
+                        .on_toggle(Message::EditActiveWindowColor),
+                ),
+                card(
+                    text!["Desktop integration"],
+                    button("Register as image/video handler")
+                        .width(Length::Fill)
+                        .on_press(Message::InstallDesktopEntry),
+                ),
+                row![
+                    button("Back")
+                        .width(Length::Fill)
+                        .on_press(Message::CloseSettings),
+                    button("Save")
+                        .width(Length::Fill)
+                        .on_press(Message::SaveSettings),
+                ]
+                .spacing(5),
+            ]
+            .push(self.config.load_error.as_ref().map(|err| {
+                card(
+                    text!["Config"],
+                    column![
+                        self.badge(Status::Error, format!("Failed to load, not saved: {err}")),
+                        row![
+                            button("Reload")
+                                .width(Length::Fill)
+                                .on_press(Message::ReloadConfig),
+                            button("Reset (keeps a .bak)")
+                                .width(Length::Fill)
+                                .on_press(Message::ResetConfig),
+                        ]
+                        .spacing(5),
+                    ]
+                    .spacing(5),
+                )
+            }))
+            .push(
+                self.user_notice
+                    .as_ref()
+                    .map(|n| column![rule::horizontal(50), text(n)]),
+            )
+            .push(
+                self.user_error
+                    .as_ref()
+                    .map(|e| column![rule::horizontal(50), self.badge(Status::Error, e)]),
+            )
+            .spacing(10)
+            .padding(20),
+            Page::Scripts => column![
+                text!["Scripts"].size(24),
+                text!("Rhai scripts from {}", scripts::dir().display()),
+                scrollable(self.build_scripts_widgets()).height(Length::Fill),
+                row![
+                    button("Back")
+                        .width(Length::Fill)
+                        .on_press(Message::CloseSettings),
+                    button("Reload")
+                        .width(Length::Fill)
+                        .on_press(Message::ReloadScripts),
+                    button("New script")
+                        .width(Length::Fill)
+                        .on_press(Message::NewScript),
+                ]
+                .spacing(5),
+            ]
+            .push(
+                self.user_notice
+                    .as_ref()
+                    .map(|n| column![rule::horizontal(50), text(n)]),
+            )
+            .push(
+                self.user_error
+                    .as_ref()
+                    .map(|e| column![rule::horizontal(50), self.badge(Status::Error, e)]),
+            )
+            .spacing(10)
+            .padding(20),
+            Page::Templates => column![
+                text!["Templates"].size(24),
+                text!("Generated wallpapers from {}", templates::dir().display()),
+                scrollable(self.build_templates_widgets()).height(Length::Fill),
+                row![
+                    button("Back")
+                        .width(Length::Fill)
+                        .on_press(Message::CloseSettings),
+                    button("Reload")
+                        .width(Length::Fill)
+                        .on_press(Message::ReloadTemplates),
+                    button("New template")
+                        .width(Length::Fill)
+                        .on_press(Message::NewTemplate),
+                ]
+                .spacing(5),
+            ]
+            .push(
+                self.user_notice
+                    .as_ref()
+                    .map(|n| column![rule::horizontal(50), text(n)]),
+            )
+            .push(
+                self.user_error
+                    .as_ref()
+                    .map(|e| column![rule::horizontal(50), self.badge(Status::Error, e)]),
+            )
+            .spacing(10)
+            .padding(20),
+            Page::Library => column![
+                text!["Library"].size(24),
+                self.build_library_widgets().height(Length::Fill),
+                row![
+                    button("Back")
+                        .width(Length::Fill)
+                        .on_press(Message::CloseSettings),
+                    button("Rescan")
+                        .width(Length::Fill)
+                        .on_press_maybe((!self.browser.scanning).then_some(Message::RescanLibrary)),
+                    button("Add directory")
+                        .width(Length::Fill)
+                        .on_press(Message::AddLibraryDir),
+                ]
+                .spacing(5),
+            ]
+            .push(
+                self.user_notice
+                    .as_ref()
+                    .map(|n| column![rule::horizontal(50), text(n)]),
+            )
+            .push(
+                self.user_error
+                    .as_ref()
+                    .map(|e| column![rule::horizontal(50), self.badge(Status::Error, e)]),
+            )
+            .spacing(10)
+            .padding(20),
+            Page::Crop => self
+                .build_crop_widgets()
+                .push(
+                    self.user_notice
+                        .as_ref()
+                        .map(|n| column![rule::horizontal(50), text(n)]),
+                )
+                .push(
+                    self.user_error
+                        .as_ref()
+                        .map(|e| column![rule::horizontal(50), self.badge(Status::Error, e)]),
+                )
+                .spacing(10)
+                .padding(20),
+        }
+    }
+    fn theme(&self, _window: window::Id) -> iced::Theme {
+        self.config.theme.theme()
+    }
+}
+
+/// Seconds typed into a timeout setting, None if it's left empty
+fn parse_timeout(name: &str, input: &str) -> Result<Option<u64>, String> {
+    match input.trim() {
+        "" => Ok(None),
+        secs => match secs.parse::<u64>() {
+            Ok(secs) if secs >= 1 => Ok(Some(secs)),
+            _ => Err(format!(
+                "The {name} timeout should be a number of seconds like 5"
+            )),
+        },
+    }
+}
+
+/// A concurrency limit typed into the settings, None if it's left empty
+fn parse_limit(name: &str, input: &str) -> Result<Option<usize>, String> {
+    match input.trim() {
+        "" => Ok(None),
+        limit => match limit.parse::<usize>() {
+            Ok(limit) if limit >= 1 => Ok(Some(limit)),
+            _ => Err(format!("The number of {name} should be a number like 4")),
+        },
+    }
+}
+
+/// `dialog` over a dimmed `base`, which can't be clicked while it's open
+fn modal<'a>(
+    base: impl Into<Element<'a, Message>>,
+    dialog: impl Into<Element<'a, Message>>,
+) -> Element<'a, Message> {
+    stack![
+        base.into(),
+        opaque(
+            center(opaque(dialog)).style(|_| container::background(Color {
+                a: 0.6,
+                ..Color::BLACK
+            }))
+        ),
+    ]
+    .into()
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    // only enable debug for our crate
+    // logs go to stderr so `--json` output can be piped
+    Subscriber::builder()
+        .with_env_filter(EnvFilter::new("xab_gui=debug"))
+        .with_writer(std::io::stderr)
+        .init();
+
+    match cli.command {
+        Some(Command::ApplySaved { exit }) => {
+            cli::apply_saved()?;
+            if exit {
+                return Ok(());
+            }
+        }
+        Some(Command::Status) => return cli::status(cli.json),
+        Some(Command::ImportPack { archive }) => return cli::import_pack(&archive),
+        Some(Command::Credits) => return cli::credits(cli.json),
+        Some(Command::SetBackground { file, monitor }) => {
+            return cli::set_background(&file, monitor);
+        }
+        Some(Command::ClearBackground { monitor }) => return cli::clear_background(monitor),
+        Some(Command::ListMonitors) => return cli::list_monitors(cli.json),
+        Some(Command::Pause) => return cli::pause_videos(Some(true)),
+        Some(Command::Resume) => return cli::pause_videos(Some(false)),
+        Some(Command::TogglePause) => return cli::pause_videos(None),
+        None => {}
+    }
+
+    let activation = Activation::from_env(&cli.files);
+    match instance::forward(&activation) {
+        Ok(true) => return Ok(()),
+        Ok(false) => instance::claim()?,
+        Err(err) => error!("Failed to reach the running instance: {:?}", err),
+    }
+
+    debug!("Initializing iced application");
+    // a daemon instead of an application so we can have more than one window
+    let result = iced::daemon(move || App::new(activation.clone()), App::update, App::view)
+        .title(App::title)
+        .theme(App::theme)
+        .subscription(App::subscription)
+        .run();
+    instance::release();
+    if let Err(err) = persist::flush() {
+        error!("{:?}", err);
+    }
+    result?;
     debug!("bye");
     Ok(())
 }
@@ -0,0 +1,101 @@
+use anyhow::{Context, Result, anyhow};
+use std::{fs::File, io::Read, path::Path};
+
+use crate::sources::local::WALLPAPER_EXTENSIONS;
+
+/// Formats told apart by their first bytes, and the extensions they go by
+const FORMATS: &[(&str, &[&str])] = &[
+    ("PNG", &["png"]),
+    ("JPEG", &["jpg", "jpeg"]),
+    ("GIF", &["gif"]),
+    ("WebP", &["webp"]),
+    ("BMP", &["bmp"]),
+    ("MP4", &["mp4", "m4v"]),
+    ("QuickTime", &["mov"]),
+    ("Matroska", &["mkv", "webm"]),
+    ("AVI", &["avi"]),
+];
+
+/// Enough for every signature in `sniff_bytes`
+const HEAD_SIZE: usize = 16;
+
+/// The format in `FORMATS` the magic bytes at the start of a file belong to
+fn sniff_bytes(head: &[u8]) -> Option<&'static str> {
+    // RIFF and ISO media files say what they are a few bytes in
+    let riff = head.starts_with(b"RIFF").then(|| head.get(8..12)).flatten();
+    let ftyp = (head.get(4..8) == Some(b"ftyp"))
+        .then(|| head.get(8..10))
+        .flatten();
+    let format = match (riff, ftyp) {
+        (Some(b"WEBP"), _) => "WebP",
+        (Some(b"AVI "), _) => "AVI",
+        (_, Some(b"qt")) => "QuickTime",
+        (_, Some(_)) => "MP4",
+        _ if head.starts_with(b"\x89PNG") => "PNG",
+        _ if head.starts_with(b"\xff\xd8\xff") => "JPEG",
+        _ if head.starts_with(b"GIF8") => "GIF",
+        _ if head.starts_with(b"BM") => "BMP",
+        _ if head.starts_with(b"\x1a\x45\xdf\xa3") => "Matroska",
+        _ => return None,
+    };
+    Some(format)
+}
+
+fn extensions(format: &str) -> &'static [&'static str] {
+    FORMATS
+        .iter()
+        .find(|(name, _)| *name == format)
+        .map(|(_, extensions)| *extensions)
+        .unwrap_or_default()
+}
+
+/// What the first bytes of `path` say it is, None if they don't say
+pub fn sniff(path: &Path) -> Result<Option<&'static str>> {
+    let mut head = Vec::with_capacity(HEAD_SIZE);
+    File::open(path)
+        .and_then(|file| file.take(HEAD_SIZE as u64).read_to_end(&mut head))
+        .with_context(|| format!("Failed to read `{}`", path.display()))?;
+    Ok(sniff_bytes(&head))
+}
+
+/// Whether xab can show `path`, going by what it is and the extensions xab
+/// said it can show (`IpcHandle::formats`), or the usual ones when it
+/// didn't. Errors if it surely can't, a warning if it might not
+pub fn check(path: &Path, supported: Option<&[String]>) -> Result<Option<String>> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string());
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let sniffed = sniff(path)?;
+    // a misnamed file still goes by what it is
+    let goes_by: Vec<&str> = match sniffed {
+        Some(format) => extensions(format).to_vec(),
+        None => vec![extension.as_str()],
+    };
+
+    let misnamed = sniffed
+        .filter(|format| !extensions(format).contains(&extension.as_str()))
+        .map(|format| format!("{name} is really a {format} file"));
+
+    match (supported, sniffed) {
+        (Some(supported), _) if !goes_by.iter().any(|ext| supported.iter().any(|s| s == ext)) => {
+            Err(anyhow!(
+                "This xab can't show {}, it only takes {}",
+                match sniffed {
+                    Some(format) => format!("{format} files like {name}"),
+                    None => name,
+                },
+                supported.join(", ")
+            ))
+        }
+        (Some(_), _) | (None, Some(_)) => Ok(misnamed),
+        (None, None) if WALLPAPER_EXTENSIONS.contains(&extension.as_str()) => Ok(Some(format!(
+            "{name} doesn't look like a {extension} file, xab might not show it"
+        ))),
+        (None, None) => Err(anyhow!("{name} isn't an image or video xab knows")),
+    }
+}
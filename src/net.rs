@@ -0,0 +1,339 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        Mutex, MutexGuard,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+use tracing::{debug, warn};
+
+// shelling out to curl is a lot less code (and binary size) than pulling in an
+// http client + tls stack, and it already knows about proxies and the like
+
+/// On top of the proxy environment variables (`https_proxy`, `no_proxy`, ...)
+/// curl follows anyway, set from the config
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proxies {
+    /// Used instead of the environment's, like `http://proxy:3128` or
+    /// `socks5h://localhost:1080`
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// By source id, an empty one goes around every proxy
+    #[serde(default)]
+    pub sources: BTreeMap<String, String>,
+    /// Pem file of certificate authorities, for networks that intercept tls.
+    /// Replaces the system's, so it should have the public ones too
+    #[serde(default)]
+    pub ca_bundle: Option<PathBuf>,
+}
+
+impl Proxies {
+    pub fn validate(&self) -> Result<()> {
+        match &self.ca_bundle {
+            Some(bundle) if !bundle.is_file() => {
+                Err(anyhow!("There's no CA bundle at {}", bundle.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+static PROXIES: Mutex<Proxies> = Mutex::new(Proxies {
+    proxy: None,
+    sources: BTreeMap::new(),
+    ca_bundle: None,
+});
+
+pub fn set_proxies(proxies: Proxies) {
+    *PROXIES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = proxies;
+}
+
+/// curl set up for requests of `source`
+fn curl(source: &str) -> Command {
+    let proxies = PROXIES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut curl = Command::new("curl");
+    curl.args(["--fail", "--silent", "--show-error", "--location"]);
+    match proxies.sources.get(source).or(proxies.proxy.as_ref()) {
+        Some(proxy) if proxy.is_empty() => curl.args(["--noproxy", "*"]),
+        Some(proxy) => curl.arg("--proxy").arg(proxy),
+        None => &mut curl,
+    };
+    if let Some(bundle) = &proxies.ca_bundle {
+        curl.arg("--cacert").arg(bundle);
+    }
+    curl
+}
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Set from the config, nothing touches the network while it's on
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::SeqCst);
+}
+
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::SeqCst)
+}
+
+fn ensure_online(url: &str) -> Result<()> {
+    match is_offline() {
+        true => Err(anyhow!("Not fetching {url}, offline mode is on")),
+        false => Ok(()),
+    }
+}
+
+/// GETs `url` for `source` and returns the body
+pub fn get(source: &str, url: &str) -> Result<Vec<u8>> {
+    ensure_online(url)?;
+    debug!("GET {url}");
+    let output = curl(source)
+        .arg(url)
+        .output()
+        .with_context(|| "Failed to run curl, is it installed?")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to GET {url}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// How downloads share the connection, set from the config
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bandwidth {
+    /// Kilobytes a second for every download together, unset is as fast as
+    /// the connection goes
+    #[serde(default)]
+    pub limit_kbps: Option<u64>,
+    /// Background syncs pause on metered connections (hotspots and such)
+    /// unless this is set
+    #[serde(default)]
+    pub sync_on_metered: bool,
+}
+
+static BANDWIDTH: Mutex<Bandwidth> = Mutex::new(Bandwidth {
+    limit_kbps: None,
+    sync_on_metered: false,
+});
+/// Downloads running right now, they split the limit between them
+static DOWNLOADING: AtomicUsize = AtomicUsize::new(0);
+
+fn lock() -> MutexGuard<'static, Bandwidth> {
+    BANDWIDTH
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn set_bandwidth(bandwidth: Bandwidth) {
+    *lock() = bandwidth;
+}
+
+/// Whether NetworkManager thinks the connection is metered, false when
+/// there's no NetworkManager to ask
+pub fn is_metered() -> bool {
+    let output = Command::new("busctl")
+        .args([
+            "--system",
+            "get-property",
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+            "Metered",
+        ])
+        .output();
+    match output {
+        // `u 1`, 1 is yes and 3 is a guessed yes, see NMMetered
+        Ok(output) if output.status.success() => matches!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            "u 1" | "u 3"
+        ),
+        Ok(output) => {
+            debug!(
+                "Can't ask NetworkManager about metering: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            false
+        }
+        Err(err) => {
+            debug!("Can't ask NetworkManager about metering: {err}");
+            false
+        }
+    }
+}
+
+/// Whether background downloads should stop for now
+pub fn should_pause_syncs() -> bool {
+    !lock().sync_on_metered && is_metered()
+}
+
+/// Counts as a running download until dropped
+struct Downloading;
+
+impl Downloading {
+    fn start() -> (Self, usize) {
+        let running = DOWNLOADING.fetch_add(1, Ordering::SeqCst) + 1;
+        (Self, running)
+    }
+}
+
+impl Drop for Downloading {
+    fn drop(&mut self) {
+        DOWNLOADING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// curl's exit code for servers that ignore range requests, or send the whole
+/// file again because it changed since
+const CURL_RANGE_ERROR: i32 = 33;
+
+/// What a download got so far, `foo.jpg.part`, with the url it came from and
+/// the server's ETag next to it so a resume never mixes two files
+struct Partial {
+    path: PathBuf,
+    url: PathBuf,
+    etag: PathBuf,
+}
+
+const PARTIAL_SUFFIXES: &[&str] = &[".part", ".part.url", ".part.etag"];
+
+/// Whether `path` belongs to a download that hasn't finished
+pub fn is_partial(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy())
+        .is_some_and(|name| PARTIAL_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)))
+}
+
+impl Partial {
+    fn new(dest: &Path) -> Self {
+        let with = |suffix: &str| {
+            let mut name = dest.file_name().unwrap_or_default().to_os_string();
+            name.push(suffix);
+            dest.with_file_name(name)
+        };
+        Self {
+            path: with(PARTIAL_SUFFIXES[0]),
+            url: with(PARTIAL_SUFFIXES[1]),
+            etag: with(PARTIAL_SUFFIXES[2]),
+        }
+    }
+
+    /// Drops what's there unless it came from `url`, returns the ETag to
+    /// resume it with
+    fn resume(&self, url: &str) -> Option<String> {
+        if std::fs::read_to_string(&self.url).ok().as_deref() != Some(url) {
+            self.remove();
+            let _ = std::fs::write(&self.url, url);
+            return None;
+        }
+        // weak ones can't be used with If-Range
+        std::fs::read_to_string(&self.etag)
+            .ok()
+            .map(|etag| etag.trim().to_owned())
+            .filter(|etag| !etag.is_empty() && !etag.starts_with("W/"))
+    }
+
+    fn remove(&self) {
+        for path in [&self.path, &self.url, &self.etag] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Downloads `url` into `dest`, going through a temporary file so a failed
+/// download never leaves a half written wallpaper behind. What a failed
+/// download got is kept and picked up by the next try
+pub fn download(source: &str, url: &str, dest: &Path) -> Result<()> {
+    ensure_online(url)?;
+    debug!("Downloading {url} to {}", dest.display());
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let partial = Partial::new(dest);
+    let etag = partial.resume(url);
+    let (_downloading, running) = Downloading::start();
+    let mut curl = curl(source);
+    curl.args(["--continue-at", "-"])
+        .arg("--etag-save")
+        .arg(&partial.etag);
+    // a changed file comes whole instead of being appended to the old one
+    if let Some(etag) = etag {
+        curl.arg("--header").arg(format!("If-Range: {etag}"));
+    }
+    // split when the download starts, the ones after it don't slow it down
+    if let Some(limit) = lock().limit_kbps {
+        let share = (limit / running as u64).max(1);
+        curl.arg("--limit-rate").arg(format!("{share}K"));
+    }
+    let output = curl
+        .arg("--output")
+        .arg(&partial.path)
+        .arg(url)
+        .output()
+        .with_context(|| "Failed to run curl, is it installed?")?;
+
+    if !output.status.success() {
+        let got_nothing = std::fs::metadata(&partial.path).is_ok_and(|m| m.len() == 0);
+        if output.status.code() == Some(CURL_RANGE_ERROR) {
+            warn!("{url} can't be resumed, starting over next time");
+            partial.remove();
+        } else if got_nothing {
+            partial.remove();
+        }
+        return Err(anyhow!(
+            "Failed to download {url}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    std::fs::rename(&partial.path, dest)
+        .with_context(|| format!("Failed to move download to {}", dest.display()))?;
+    partial.remove();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partials_keep_the_whole_name() {
+        let partial = Partial::new(Path::new("/cache/foo.jpg"));
+        assert_eq!(partial.path, Path::new("/cache/foo.jpg.part"));
+        assert_ne!(partial.path, Partial::new(Path::new("/cache/foo.png")).path);
+        for path in [&partial.path, &partial.url, &partial.etag] {
+            assert!(is_partial(path));
+        }
+        assert!(!is_partial(Path::new("/cache/foo.jpg")));
+    }
+
+    #[test]
+    fn partials_only_resume_the_same_url() {
+        let dir = std::env::temp_dir().join(format!("xab-gui-net-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let partial = Partial::new(&dir.join("foo.jpg"));
+        std::fs::write(&partial.path, b"half").unwrap();
+
+        // nothing says where it's from
+        let unknown = (partial.resume("https://a/foo.jpg"), partial.path.exists());
+        std::fs::write(&partial.path, b"half").unwrap();
+        std::fs::write(&partial.etag, "\"abc\"\n").unwrap();
+        let same = (partial.resume("https://a/foo.jpg"), partial.path.exists());
+        let other = (partial.resume("https://b/foo.jpg"), partial.path.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(unknown, (None, false));
+        assert_eq!(same, (Some("\"abc\"".to_owned()), true));
+        assert_eq!(other, (None, false));
+    }
+}
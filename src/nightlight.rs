@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+use tracing::debug;
+
+use crate::{automation::TimeWindow, disk_cache, limits, xdg};
+
+/// How often the night light state is looked at
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Multiplied into every pixel, dims everything and blue the most
+const WARM_DIM: [f32; 3] = [0.85, 0.72, 0.55];
+
+/// ```toml
+/// [night_dimming]
+/// enabled = true
+/// # only used when the desktop doesn't have a night light
+/// schedule = { start = "20:00", end = "07:00" }
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NightDimming {
+    pub enabled: bool,
+    pub schedule: Option<TimeWindow>,
+}
+
+/// Whether GNOME's night light is currently tinting the screen, None if there's
+/// no GNOME settings daemon to ask
+fn gnome_night_light() -> Option<bool> {
+    let output = Command::new("dbus-send")
+        .args([
+            "--session",
+            "--print-reply",
+            "--dest=org.gnome.SettingsDaemon.Color",
+            "/org/gnome/SettingsDaemon/Color",
+            "org.freedesktop.DBus.Properties.Get",
+            "string:org.gnome.SettingsDaemon.Color",
+            "string:NightLightActive",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // variant boolean true
+    let reply = String::from_utf8_lossy(&output.stdout);
+    Some(reply.contains("boolean true"))
+}
+
+/// The desktop's night light if it has one, otherwise the schedule
+pub fn is_night(dimming: NightDimming) -> bool {
+    if let Some(active) = gnome_night_light() {
+        return active;
+    }
+    dimming
+        .schedule
+        .is_some_and(|schedule| schedule.contains(Local::now().time()))
+}
+
+/// Only formats the image crate is built with, videos are left alone
+pub fn can_dim(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext.to_lowercase().as_str(), "png" | "jpg" | "jpeg"))
+}
+
+/// A warmer, dimmer copy of `path` in the cache
+pub fn dim(path: &Path) -> Result<PathBuf> {
+    // the modification time too, so an edited wallpaper isn't served stale
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .hash(&mut hasher);
+    let dir = xdg::cache_dir().join("nightlight");
+    let dest = dir.join(format!("{:016x}.png", hasher.finish()));
+    if disk_cache::hit(&dest) {
+        return Ok(dest);
+    }
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let _decoding = limits::decode_permit();
+    let mut image = image::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?
+        .into_rgb8();
+    for pixel in image.pixels_mut() {
+        for (channel, factor) in pixel.0.iter_mut().zip(WARM_DIM) {
+            *channel = (*channel as f32 * factor).round() as u8;
+        }
+    }
+    image
+        .save(&dest)
+        .with_context(|| format!("Failed to save {}", dest.display()))?;
+    debug!("Dimmed {} to {}", path.display(), dest.display());
+    Ok(dest)
+}
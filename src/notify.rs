@@ -0,0 +1,93 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{fmt, process::Command};
+use tracing::debug;
+
+/// When wallpaper change notifications are shown
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationMode {
+    /// Unless the desktop is in do not disturb
+    #[default]
+    FollowDesktop,
+    /// Even in do not disturb
+    Always,
+    Never,
+}
+
+impl NotificationMode {
+    pub const ALL: &[Self] = &[Self::FollowDesktop, Self::Always, Self::Never];
+}
+
+impl fmt::Display for NotificationMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::FollowDesktop => "Unless do not disturb is on",
+            Self::Always => "Always",
+            Self::Never => "Never",
+        })
+    }
+}
+
+fn output_of(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Asks the usual suspects whether the desktop is in do not disturb
+pub fn do_not_disturb() -> bool {
+    // the notification spec's own property (kde, newer servers)
+    let inhibited = output_of(
+        "dbus-send",
+        &[
+            "--session",
+            "--print-reply",
+            "--dest=org.freedesktop.Notifications",
+            "/org/freedesktop/Notifications",
+            "org.freedesktop.DBus.Properties.Get",
+            "string:org.freedesktop.Notifications",
+            "string:Inhibited",
+        ],
+    );
+    if inhibited.is_some_and(|reply| reply.contains("boolean true")) {
+        return true;
+    }
+    // gnome
+    if output_of(
+        "gsettings",
+        &["get", "org.gnome.desktop.notifications", "show-banners"],
+    )
+    .is_some_and(|banners| banners == "false")
+    {
+        return true;
+    }
+    // dunst
+    output_of("dunstctl", &["is-paused"]).is_some_and(|paused| paused == "true")
+}
+
+/// Shows a desktop notification, unless `mode` or the desktop says not to
+pub fn send(mode: NotificationMode, summary: &str, body: &str) -> Result<()> {
+    match mode {
+        NotificationMode::Never => return Ok(()),
+        NotificationMode::FollowDesktop if do_not_disturb() => {
+            debug!("Do not disturb is on, not showing `{summary}`");
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let output = Command::new("notify-send")
+        .args(["--app-name=xab-gui", "--icon=xab-gui", summary, body])
+        .output()
+        .with_context(|| "Failed to run notify-send, is it installed?")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "notify-send failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+//! Lossless serde for paths, use with `#[serde(with = "crate::os_path")]`
+//!
+//! Paths are bytes on unix, the ones that aren't valid UTF-8 (old latin-1 or
+//! shift-jis file names, ...) are stored as `{ raw = [...] }` instead of
+//! failing to save or being mangled by a lossy conversion
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    ffi::OsStr,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    Utf8(String),
+    Raw { raw: Vec<u8> },
+}
+
+impl From<&Path> for Stored {
+    fn from(path: &Path) -> Self {
+        match path.to_str() {
+            Some(path) => Self::Utf8(path.to_owned()),
+            None => Self::Raw {
+                raw: path.as_os_str().as_bytes().to_vec(),
+            },
+        }
+    }
+}
+
+impl From<Stored> for PathBuf {
+    fn from(stored: Stored) -> Self {
+        match stored {
+            Stored::Utf8(path) => PathBuf::from(path),
+            Stored::Raw { raw } => PathBuf::from(OsStr::from_bytes(&raw)),
+        }
+    }
+}
+
+/// `path` the way it's stored, for json put together by hand
+pub fn stored(path: &Path) -> impl Serialize + use<> {
+    Stored::from(path)
+}
+
+pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    Stored::from(path).serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    Stored::deserialize(deserializer).map(PathBuf::from)
+}
+
+/// Same thing for a list of paths
+pub mod vec {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(paths.iter().map(|path| Stored::from(path.as_path())))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<PathBuf>, D::Error> {
+        Ok(Vec::<Stored>::deserialize(deserializer)?
+            .into_iter()
+            .map(PathBuf::from)
+            .collect())
+    }
+}
+
+/// Same thing for an optional path
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        path: &Option<PathBuf>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        path.as_deref().map(Stored::from).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<PathBuf>, D::Error> {
+        Ok(Option::<Stored>::deserialize(deserializer)?.map(PathBuf::from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Paths {
+        #[serde(with = "crate::os_path")]
+        path: PathBuf,
+        #[serde(with = "crate::os_path::vec")]
+        paths: Vec<PathBuf>,
+        #[serde(default, with = "crate::os_path::option")]
+        maybe: Option<PathBuf>,
+    }
+
+    fn latin1() -> PathBuf {
+        PathBuf::from(OsStr::from_bytes(b"/wallpapers/caf\xe9.png"))
+    }
+
+    #[test]
+    fn paths_survive_a_round_trip() {
+        let paths = Paths {
+            path: latin1(),
+            paths: vec![PathBuf::from("/wallpapers/forêt.jpg"), latin1()],
+            maybe: Some(latin1()),
+        };
+        let saved = toml::to_string(&paths).unwrap();
+        assert!(saved.contains("raw") && !saved.contains('\u{fffd}'));
+        assert!(saved.contains("\"/wallpapers/forêt.jpg\""));
+        assert_eq!(toml::from_str::<Paths>(&saved).unwrap(), paths);
+        let nothing = Paths {
+            maybe: None,
+            ..paths
+        };
+        let saved = toml::to_string(&nothing).unwrap();
+        assert_eq!(toml::from_str::<Paths>(&saved).unwrap(), nothing);
+
+        let json = serde_json::to_value(stored(&latin1())).unwrap();
+        assert_eq!(
+            json["raw"].as_array().unwrap().len(),
+            latin1().as_os_str().len()
+        );
+        assert_eq!(
+            serde_json::to_value(stored(Path::new("/a.png"))).unwrap(),
+            "/a.png"
+        );
+    }
+
+    #[test]
+    fn old_state_files_still_load() {
+        let state: crate::state::SavedState = toml::from_str(
+            r#"
+            [[assignments]]
+            monitor = 0
+            path = "/home/me/Pictures/forest.jpg"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            state.assignments[0].path,
+            Path::new("/home/me/Pictures/forest.jpg")
+        );
+
+        let paths: Paths = toml::from_str(
+            r#"path = "/a.png"
+paths = []"#,
+        )
+        .unwrap();
+        assert_eq!(paths.maybe, None);
+    }
+}
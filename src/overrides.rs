@@ -0,0 +1,195 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use tracing::debug;
+
+use crate::{ipc::IpcHandle, persist};
+
+/// Temporary states that put up wallpapers of their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverrideKind {
+    Presentation,
+    /// Video wallpapers frozen to a still frame
+    Snapshot,
+    /// Comparison candidates tried out on the desktop
+    Trying,
+    /// Tinted after the focused window
+    ActiveColor,
+}
+
+#[derive(Debug, Clone)]
+struct Override {
+    kind: OverrideKind,
+    /// `None` being every monitor
+    monitor: Option<i32>,
+    path: PathBuf,
+}
+
+/// What a monitor goes back to once an override ends, `None` being its
+/// saved wallpaper
+pub type Restore = (Option<i32>, Option<PathBuf>);
+
+/// Temporary wallpapers, oldest first. Each one covers what was there
+/// before it, so ending one only changes the monitors it was on top of and
+/// puts back whatever is under it, be it an older override or the saved
+/// wallpaper
+#[derive(Debug, Default, Clone)]
+pub struct Overrides(Vec<Override>);
+
+impl Overrides {
+    /// What `monitor` shows, `None` if it's the saved wallpaper
+    fn visible(&self, monitor: Option<i32>) -> Option<PathBuf> {
+        self.0
+            .iter()
+            .rev()
+            .find(|o| o.monitor == monitor || o.monitor.is_none())
+            .map(|o| o.path.clone())
+    }
+
+    /// Puts `path` up on `monitor` for `kind`, replacing what `kind` had
+    /// there without moving it in the stack. False if something newer covers
+    /// any of it, it's only worth sending once true
+    pub fn set(&mut self, kind: OverrideKind, monitor: Option<i32>, path: PathBuf) -> bool {
+        let at = match self
+            .0
+            .iter()
+            .position(|o| o.kind == kind && o.monitor == monitor)
+        {
+            Some(at) => {
+                self.0[at].path = path;
+                at
+            }
+            None => {
+                self.0.push(Override {
+                    kind,
+                    monitor,
+                    path,
+                });
+                self.0.len() - 1
+            }
+        };
+        // one on every monitor is partly covered by any newer one
+        !self.0[at + 1..]
+            .iter()
+            .any(|o| monitor.is_none() || o.monitor.is_none() || o.monitor == monitor)
+    }
+
+    /// Ends every override of `kind`, returns what has to be put back. Every
+    /// monitor comes first, `monitors` are the ones xab knows about
+    pub fn end(&mut self, kind: OverrideKind, monitors: &[i32]) -> Vec<Restore> {
+        let ended: Vec<Option<i32>> = self
+            .0
+            .iter()
+            .filter(|o| o.kind == kind)
+            .map(|o| o.monitor)
+            .collect();
+        let targets: Vec<Option<i32>> = match ended.contains(&None) {
+            true => std::iter::once(None)
+                .chain(monitors.iter().copied().map(Some))
+                .collect(),
+            false => ended,
+        };
+        let before: Vec<Option<PathBuf>> = targets.iter().map(|&t| self.visible(t)).collect();
+        self.0.retain(|o| o.kind != kind);
+
+        let mut restores: Vec<Restore> = Vec::new();
+        for (target, before) in targets.into_iter().zip(before) {
+            let after = self.visible(target);
+            let needed = match restores.first() {
+                // every monitor was just put back, only the ones that show
+                // something else need to go again
+                Some((None, everywhere)) => after != *everywhere,
+                _ => after != before,
+            };
+            if needed {
+                restores.push((target, after));
+            }
+        }
+        debug!("Ended {:?} overrides, putting back {:?}", kind, restores);
+        restores
+    }
+}
+
+/// Puts back what `Overrides::end` returned, in order
+pub async fn restore(ipc_handle: &IpcHandle, restores: &[Restore]) -> Result<()> {
+    if restores.is_empty() {
+        return Ok(());
+    }
+    let state = persist::state()?;
+    for (monitor, path) in restores {
+        match (monitor, path) {
+            (_, Some(path)) => ipc_handle.change_background(*monitor, path).await?,
+            (None, None) => {
+                for assignment in &state.assignments {
+                    ipc_handle
+                        .change_background_scaled(
+                            assignment.monitor,
+                            &assignment.path,
+                            assignment.scaling,
+                        )
+                        .await?;
+                }
+            }
+            (Some(_), None) => {
+                if let Some(path) = state.assigned(*monitor) {
+                    ipc_handle.change_background(*monitor, path).await?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(name: &str) -> PathBuf {
+        PathBuf::from(name)
+    }
+
+    #[test]
+    fn newer_overrides_cover_older_ones() {
+        let mut overrides = Overrides::default();
+        assert!(overrides.set(OverrideKind::ActiveColor, None, path("tint")));
+        assert!(overrides.set(OverrideKind::Trying, Some(1), path("candidate")));
+        // still under the candidate on monitor 1
+        assert!(!overrides.set(OverrideKind::ActiveColor, None, path("tint2")));
+        assert!(overrides.set(OverrideKind::Trying, Some(1), path("candidate2")));
+        // another monitor isn't covered
+        assert!(overrides.set(OverrideKind::Snapshot, Some(0), path("frame")));
+        assert_eq!(overrides.visible(Some(0)), Some(path("frame")));
+        assert_eq!(overrides.visible(Some(1)), Some(path("candidate2")));
+        assert_eq!(overrides.visible(Some(2)), Some(path("tint2")));
+    }
+
+    #[test]
+    fn ending_puts_back_whats_under() {
+        let mut overrides = Overrides::default();
+        overrides.set(OverrideKind::ActiveColor, None, path("tint"));
+        overrides.set(OverrideKind::Trying, Some(1), path("candidate"));
+
+        assert_eq!(
+            overrides.end(OverrideKind::Trying, &[0, 1]),
+            [(Some(1), Some(path("tint")))]
+        );
+        assert_eq!(
+            overrides.end(OverrideKind::ActiveColor, &[0, 1]),
+            [(None, None)]
+        );
+        assert!(overrides.end(OverrideKind::ActiveColor, &[0, 1]).is_empty());
+    }
+
+    #[test]
+    fn ending_an_older_one_leaves_newer_ones_up() {
+        let mut overrides = Overrides::default();
+        overrides.set(OverrideKind::Presentation, None, path("neutral"));
+        overrides.set(OverrideKind::Trying, Some(1), path("candidate"));
+
+        // every monitor goes back, then monitor 1 gets the candidate again
+        assert_eq!(
+            overrides.end(OverrideKind::Presentation, &[0, 1]),
+            [(None, None), (Some(1), Some(path("candidate")))]
+        );
+        assert!(overrides.set(OverrideKind::ActiveColor, None, path("tint")));
+    }
+}
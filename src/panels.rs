@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The parts of the connected page that can be moved around or hidden
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Panel {
+    /// Thumbnail of the selected file
+    Preview,
+    Monitors,
+    /// Apply, clear and span, can't be hidden
+    Apply,
+    Recent,
+    Sources,
+    Playlists,
+    RecentlyDeleted,
+    Video,
+    Playback,
+    Automation,
+    Status,
+    Daemon,
+}
+
+impl Panel {
+    /// The order they come in unless rearranged
+    pub const ALL: &[Self] = &[
+        Self::Preview,
+        Self::Monitors,
+        Self::Apply,
+        Self::Recent,
+        Self::Sources,
+        Self::Playlists,
+        Self::RecentlyDeleted,
+        Self::Video,
+        Self::Playback,
+        Self::Automation,
+        Self::Status,
+        Self::Daemon,
+    ];
+
+    pub fn hideable(self) -> bool {
+        self != Self::Apply
+    }
+}
+
+impl fmt::Display for Panel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Preview => "Preview",
+            Self::Monitors => "Monitors",
+            Self::Apply => "Apply buttons",
+            Self::Recent => "Recent wallpapers",
+            Self::Sources => "Sources",
+            Self::Playlists => "Playlists",
+            Self::RecentlyDeleted => "Recently deleted",
+            Self::Video => "Video options",
+            Self::Playback => "Playback",
+            Self::Automation => "Automation",
+            Self::Status => "Status",
+            Self::Daemon => "Daemon",
+        })
+    }
+}
+
+/// How the connected page is arranged, kept in the config
+///
+/// ```toml
+/// [panels]
+/// order = ["monitors", "preview", "apply"]
+/// hidden = ["daemon", "status"]
+/// ```
+///
+/// Panels missing from `order`, like ones added in newer versions, go after
+/// the others in their usual order
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Panels {
+    #[serde(default)]
+    pub order: Vec<Panel>,
+    #[serde(default)]
+    pub hidden: Vec<Panel>,
+}
+
+impl Panels {
+    /// Every panel, in the order they're shown
+    pub fn arranged(&self) -> Vec<Panel> {
+        let mut arranged: Vec<Panel> = Vec::new();
+        for &panel in self.order.iter().chain(Panel::ALL) {
+            if !arranged.contains(&panel) {
+                arranged.push(panel);
+            }
+        }
+        arranged
+    }
+
+    /// The panels shown, in order
+    pub fn visible(&self) -> Vec<Panel> {
+        self.arranged()
+            .into_iter()
+            .filter(|panel| self.is_visible(*panel))
+            .collect()
+    }
+
+    pub fn is_visible(&self, panel: Panel) -> bool {
+        !panel.hideable() || !self.hidden.contains(&panel)
+    }
+
+    pub fn set_visible(&mut self, panel: Panel, visible: bool) {
+        self.hidden.retain(|hidden| *hidden != panel);
+        if !visible && panel.hideable() {
+            self.hidden.push(panel);
+        }
+    }
+
+    /// Moves `panel` one place up (or down), nothing at either end
+    pub fn shift(&mut self, panel: Panel, up: bool) {
+        let mut arranged = self.arranged();
+        let Some(at) = arranged.iter().position(|p| *p == panel) else {
+            return;
+        };
+        let to = match up {
+            true => at.checked_sub(1),
+            false => Some(at + 1).filter(|to| *to < arranged.len()),
+        };
+        if let Some(to) = to {
+            arranged.swap(at, to);
+            self.order = arranged;
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use anyhow::{Context, Result, anyhow};
+use std::{
+    sync::{Condvar, Mutex, MutexGuard, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{debug, error};
+
+use crate::state::SavedState;
+
+/// Rapid changes (dragging, reordering, ...) are written at most this often
+pub const MIN_WRITE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+struct Inner {
+    /// Loaded on first use, the file is only read once per process
+    state: Option<SavedState>,
+    dirty: bool,
+    last_write: Option<Instant>,
+}
+
+/// The saved state of this process, every change goes through here so nobody
+/// reads a file that's about to be overwritten
+struct Persist {
+    inner: Mutex<Inner>,
+    changed: Condvar,
+    /// Held while writing, taken before `inner` so a stale snapshot can't be
+    /// written over a newer one
+    writing: Mutex<()>,
+}
+
+fn persist() -> &'static Persist {
+    static PERSIST: OnceLock<Persist> = OnceLock::new();
+    PERSIST.get_or_init(|| {
+        if let Err(err) = thread::Builder::new()
+            .name("xab-state-writer".to_owned())
+            .spawn(|| persist().run())
+        {
+            error!("Failed to start the state writer, changes are saved on exit: {err}");
+        }
+        Persist {
+            inner: Mutex::default(),
+            changed: Condvar::new(),
+            writing: Mutex::default(),
+        }
+    })
+}
+
+impl Persist {
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// The loaded state, loading it if needed
+    fn loaded(&self) -> Result<MutexGuard<'_, Inner>> {
+        let mut inner = self.lock();
+        if inner.state.is_none() {
+            inner.state = Some(SavedState::load()?);
+        }
+        Ok(inner)
+    }
+
+    fn run(&self) {
+        loop {
+            let wait = {
+                let inner = self
+                    .changed
+                    .wait_while(self.lock(), |inner| !inner.dirty)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                inner
+                    .last_write
+                    .map(|last| MIN_WRITE_INTERVAL.saturating_sub(last.elapsed()))
+                    .unwrap_or_default()
+            };
+            // let more changes pile up, flush() may beat us to it
+            thread::sleep(wait);
+
+            let _writing = self
+                .writing
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let snapshot = {
+                let mut inner = self.lock();
+                if !inner.dirty {
+                    continue;
+                }
+                inner.dirty = false;
+                inner.last_write = Some(Instant::now());
+                inner.state.clone()
+            };
+
+            if let Some(state) = snapshot
+                && let Err(err) = state.save()
+            {
+                error!("Failed to save state: {:?}", err);
+                // try again with the next change (or on exit)
+                self.lock().dirty = true;
+                thread::sleep(MIN_WRITE_INTERVAL);
+            }
+        }
+    }
+}
+
+/// A copy of the saved state
+pub fn state() -> Result<SavedState> {
+    let inner = persist().loaded()?;
+    inner
+        .state
+        .clone()
+        .ok_or_else(|| anyhow!("Saved state went missing"))
+}
+
+/// Changes the saved state, it's written to disk shortly after
+pub fn update<T>(change: impl FnOnce(&mut SavedState) -> T) -> Result<T> {
+    let persist = persist();
+    let mut inner = persist.loaded()?;
+    let state = inner
+        .state
+        .as_mut()
+        .ok_or_else(|| anyhow!("Saved state went missing"))?;
+    let result = change(state);
+    inner.dirty = true;
+    persist.changed.notify_all();
+    Ok(result)
+}
+
+/// Writes pending changes right away, for when the process is about to exit
+pub fn flush() -> Result<()> {
+    let persist = persist();
+    let _writing = persist
+        .writing
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut inner = persist.lock();
+    if !inner.dirty {
+        return Ok(());
+    }
+    if let Some(state) = &inner.state {
+        state
+            .save()
+            .with_context(|| "Failed to save state on exit")?;
+        debug!("Flushed saved state");
+    }
+    inner.dirty = false;
+    inner.last_write = Some(Instant::now());
+    Ok(())
+}
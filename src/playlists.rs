@@ -0,0 +1,203 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, error};
+
+use crate::{state::write_atomic, xdg};
+
+/// A named set of wallpapers, played in the order they're in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Playlist {
+    pub name: String,
+    #[serde(default, with = "crate::os_path::vec")]
+    pub files: Vec<PathBuf>,
+}
+
+impl Playlist {
+    /// Adds `path` at the end, nothing if it's already in
+    pub fn add(&mut self, path: PathBuf) -> bool {
+        if self.files.contains(&path) {
+            return false;
+        }
+        self.files.push(path);
+        true
+    }
+
+    pub fn remove(&mut self, at: usize) -> Option<PathBuf> {
+        (at < self.files.len()).then(|| self.files.remove(at))
+    }
+
+    /// Moves the file at `at` one place up (or down), nothing at either end
+    pub fn shift(&mut self, at: usize, up: bool) {
+        let to = match up {
+            true => at.checked_sub(1),
+            false => Some(at + 1),
+        };
+        if let Some(to) = to.filter(|to| *to < self.files.len() && at < self.files.len()) {
+            self.files.swap(at, to);
+        }
+    }
+
+    /// The file after (or before) `at` and where it is, wrapping around at
+    /// either end
+    pub fn step(&self, at: usize, forward: bool) -> Option<(usize, &Path)> {
+        let len = self.files.len();
+        if len == 0 {
+            return None;
+        }
+        let to = match forward {
+            true => (at + 1) % len,
+            false => (at + len - 1) % len,
+        };
+        Some((to, &self.files[to]))
+    }
+}
+
+/// Every playlist, kept in `$XDG_DATA_HOME/xab-gui/playlists.toml`
+///
+/// ```toml
+/// [[playlists]]
+/// name = "Nature"
+/// files = ["/home/me/Pictures/forest.jpg", "/home/me/Videos/waves.mp4"]
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Playlists {
+    #[serde(default)]
+    pub playlists: Vec<Playlist>,
+    /// Why the file couldn't be loaded, it isn't saved over then
+    #[serde(skip)]
+    pub load_error: Option<String>,
+}
+
+impl Playlists {
+    pub fn path() -> PathBuf {
+        xdg::data_dir().join("playlists.toml")
+    }
+
+    /// A missing file is treated as no playlists
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read playlists at {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse playlists at {}", path.display()))
+    }
+
+    /// `load`, no playlists if the file is broken. Those remember why so
+    /// they're never saved over the user's
+    pub fn load_or_default() -> Self {
+        Self::load().unwrap_or_else(|err| {
+            error!("Failed to load playlists: {:?}", err);
+            Self {
+                load_error: Some(format!("{err:#}")),
+                ..Self::default()
+            }
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(err) = &self.load_error {
+            return Err(anyhow!(
+                "Not saving over the playlists that failed to load ({err}), fix {} and restart",
+                path.display()
+            ));
+        }
+        write_atomic(&path, toml::to_string_pretty(self)?.as_bytes())
+            .with_context(|| format!("Failed to write playlists to {}", path.display()))?;
+        debug!("Saved playlists to {}", path.display());
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Playlist> {
+        self.playlists.iter().find(|p| p.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Playlist> {
+        self.playlists.iter_mut().find(|p| p.name == name)
+    }
+
+    /// An empty playlist called `name`
+    pub fn create(&mut self, name: &str) -> Result<()> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("Give the playlist a name first"));
+        }
+        if self.get(name).is_some() {
+            return Err(anyhow!("There's a playlist called {name} already"));
+        }
+        self.playlists.push(Playlist {
+            name: name.to_owned(),
+            files: Vec::new(),
+        });
+        Ok(())
+    }
+
+    pub fn delete(&mut self, name: &str) {
+        self.playlists.retain(|p| p.name != name);
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.playlists.iter().map(|p| p.name.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn of(files: &[&str]) -> Playlist {
+        Playlist {
+            name: "test".to_owned(),
+            files: files.iter().map(PathBuf::from).collect(),
+        }
+    }
+
+    #[test]
+    fn steps_around_both_ends() {
+        let playlist = of(&["a", "b", "c"]);
+        assert_eq!(playlist.step(0, true), Some((1, Path::new("b"))));
+        assert_eq!(playlist.step(2, true), Some((0, Path::new("a"))));
+        assert_eq!(playlist.step(0, false), Some((2, Path::new("c"))));
+        assert_eq!(playlist.step(1, false), Some((0, Path::new("a"))));
+        assert_eq!(of(&[]).step(0, true), None);
+    }
+
+    #[test]
+    fn shifts_within_bounds() {
+        let mut playlist = of(&["a", "b", "c"]);
+        playlist.shift(1, true);
+        assert_eq!(playlist, of(&["b", "a", "c"]));
+        playlist.shift(1, false);
+        assert_eq!(playlist, of(&["b", "c", "a"]));
+        // nothing at either end or past it
+        playlist.shift(0, true);
+        playlist.shift(2, false);
+        playlist.shift(5, true);
+        assert_eq!(playlist, of(&["b", "c", "a"]));
+    }
+
+    #[test]
+    fn removes_only_whats_there() {
+        let mut playlist = of(&["a", "b"]);
+        assert_eq!(playlist.remove(2), None);
+        assert_eq!(playlist.remove(0), Some(PathBuf::from("a")));
+        assert_eq!(playlist, of(&["b"]));
+    }
+
+    #[test]
+    fn playlists_that_failed_to_load_arent_saved() {
+        let playlists = Playlists {
+            load_error: Some("expected `]`".to_owned()),
+            ..Playlists::default()
+        };
+        assert!(playlists.save().is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use tracing::debug;
+
+use crate::{
+    ipc::IpcHandle,
+    overrides::{self, Restore},
+    sources::{
+        Location, Wallpaper, WallpaperSource,
+        gradient::{Gradients, NEUTRAL},
+    },
+};
+
+/// What presentation mode changed, so exactly that is undone afterwards
+#[derive(Debug, Default, Clone)]
+pub struct Presentation {
+    /// Videos that were already paused stay paused
+    pub paused_videos: bool,
+    /// Put up as an `OverrideKind::Presentation`
+    pub neutral_wallpaper: Option<PathBuf>,
+}
+
+pub async fn enter(
+    ipc_handle: &IpcHandle,
+    videos_paused: bool,
+    neutral_wallpaper: bool,
+) -> Result<Presentation> {
+    let mut presentation = Presentation::default();
+    if !videos_paused {
+        ipc_handle.set_videos_paused(true).await?;
+        presentation.paused_videos = true;
+    }
+    if neutral_wallpaper {
+        let path = Gradients.fetch(&Wallpaper {
+            name: NEUTRAL.to_owned(),
+            location: Location::Generated(NEUTRAL.to_owned()),
+            tags: Vec::new(),
+            provenance: None,
+        })?;
+        // not through `SavedState::assign`, it's not meant to stick
+        ipc_handle.change_background(None, &path).await?;
+        presentation.neutral_wallpaper = Some(path);
+    }
+    debug!("Entered presentation mode: {:?}", presentation);
+    Ok(presentation)
+}
+
+/// `restores` are what ending the presentation override left to put back
+pub async fn leave(
+    ipc_handle: &IpcHandle,
+    presentation: &Presentation,
+    restores: &[Restore],
+) -> Result<()> {
+    overrides::restore(ipc_handle, restores).await?;
+    if presentation.paused_videos {
+        ipc_handle.set_videos_paused(false).await?;
+    }
+    debug!("Left presentation mode");
+    Ok(())
+}
@@ -0,0 +1,426 @@
+use anyhow::{Context, Result};
+use iced::widget::image::Handle;
+use image::{
+    DynamicImage, RgbaImage,
+    imageops::{self, FilterType},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+use crate::{
+    ipc_spec::{Monitor, ScalingMode},
+    limits, snapshot, thumbnails,
+};
+
+/// Width of the rendered previews, the height follows the monitor
+pub const PREVIEW_WIDTH: u32 = 320;
+/// Bounds of the thumbnail of the selected file, the aspect ratio is kept
+const THUMBNAIL_SIZE: (u32, u32) = (480, 270);
+/// For monitors that don't know their size (fullscreen)
+const FALLBACK_SIZE: (u32, u32) = (1920, 1080);
+
+/// Everything a composited preview depends on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PreviewKey {
+    pub monitor: i32,
+    pub size: (u32, u32),
+    pub mode: ScalingMode,
+    pub path: PathBuf,
+}
+
+/// Size of `monitor`, guessed for the fullscreen one
+pub fn monitor_size(monitor: &Monitor) -> (u32, u32) {
+    match (monitor.width, monitor.height) {
+        (0, _) | (_, 0) => FALLBACK_SIZE,
+        size => size,
+    }
+}
+
+impl PreviewKey {
+    pub fn new(monitor: &Monitor, mode: ScalingMode, path: &Path) -> Self {
+        Self {
+            monitor: monitor.index,
+            size: monitor_size(monitor),
+            mode,
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+fn open(path: &Path) -> Result<DynamicImage> {
+    // videos are previewed by their first frame
+    let path = match snapshot::is_video(path) {
+        true => snapshot::extract_frame(path)?,
+        false => path.to_path_buf(),
+    };
+    image::open(&path).with_context(|| format!("Failed to open {}", path.display()))
+}
+
+fn resize(image: &DynamicImage, width: u32, height: u32) -> RgbaImage {
+    imageops::resize(image, width.max(1), height.max(1), FilterType::Triangle)
+}
+
+/// The wallpaper as `key.mode` would put it on the monitor, scaled down to `PREVIEW_WIDTH`
+pub fn render(key: &PreviewKey) -> Result<Handle> {
+    let _decoding = limits::decode_permit();
+    let wallpaper = open(&key.path)?;
+    let (monitor_width, monitor_height) = key.size;
+    let scale = PREVIEW_WIDTH as f32 / monitor_width as f32;
+    let (width, height) = (
+        PREVIEW_WIDTH,
+        (monitor_height as f32 * scale).round().max(1.0) as u32,
+    );
+    let (source_width, source_height) = (wallpaper.width() as f32, wallpaper.height() as f32);
+    let scaled_by = |factor: f32| {
+        resize(
+            &wallpaper,
+            (source_width * factor).round() as u32,
+            (source_height * factor).round() as u32,
+        )
+    };
+    let centered = |image: &RgbaImage| {
+        (
+            (width as i64 - image.width() as i64) / 2,
+            (height as i64 - image.height() as i64) / 2,
+        )
+    };
+
+    let mut preview = RgbaImage::from_pixel(width, height, image::Rgba([0, 0, 0, 255]));
+    match key.mode {
+        ScalingMode::Stretch => preview = resize(&wallpaper, width, height),
+        ScalingMode::Fill => {
+            let scaled =
+                scaled_by((width as f32 / source_width).max(height as f32 / source_height));
+            let (x, y) = centered(&scaled);
+            imageops::overlay(&mut preview, &scaled, x, y);
+        }
+        ScalingMode::Fit => {
+            let scaled =
+                scaled_by((width as f32 / source_width).min(height as f32 / source_height));
+            let (x, y) = centered(&scaled);
+            imageops::overlay(&mut preview, &scaled, x, y);
+        }
+        ScalingMode::Center => {
+            let scaled = scaled_by(scale);
+            let (x, y) = centered(&scaled);
+            imageops::overlay(&mut preview, &scaled, x, y);
+        }
+        ScalingMode::Tile => {
+            let tile = scaled_by(scale);
+            for y in (0..height).step_by(tile.height() as usize) {
+                for x in (0..width).step_by(tile.width() as usize) {
+                    imageops::overlay(&mut preview, &tile, x as i64, y as i64);
+                }
+            }
+        }
+    }
+
+    Ok(Handle::from_rgba(
+        preview.width(),
+        preview.height(),
+        preview.into_raw(),
+    ))
+}
+
+/// The selected file on its own, with what the caption says about it
+#[derive(Debug, Clone)]
+pub struct Thumbnail {
+    pub handle: Handle,
+    /// Of the file, not the thumbnail
+    pub width: u32,
+    pub height: u32,
+    pub bytes: u64,
+    /// Videos are shown by their first frame
+    pub video: bool,
+}
+
+impl Thumbnail {
+    /// Like `forest.jpg - 3840x2160, 2.4 MB`
+    pub fn caption(&self, path: &Path) -> String {
+        format!(
+            "{} - {}x{}, {}{}",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            self.width,
+            self.height,
+            human_size(self.bytes),
+            if self.video { ", first frame" } else { "" }
+        )
+    }
+}
+
+/// Sizes as they're shown everywhere, like `1.5 MB`
+pub fn human_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "KB", "MB", "GB"] {
+        if size < 1000.0 {
+            return format!("{size:.1} {unit}");
+        }
+        size /= 1000.0;
+    }
+    format!("{size:.1} TB")
+}
+
+/// Decodes `path` (or a frame of it) into a thumbnail for the connected page
+pub fn thumbnail(path: &Path) -> Result<Thumbnail> {
+    let bytes = path
+        .metadata()
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .len();
+    // big folders would be decoded all over again every launch otherwise
+    let (thumbnail, width, height) = match thumbnails::load(path) {
+        Some(stored) => (stored.image, stored.width, stored.height),
+        None => {
+            let _decoding = limits::decode_permit();
+            let wallpaper = open(path)?;
+            let (width, height) = (wallpaper.width(), wallpaper.height());
+            let thumbnail = wallpaper
+                .thumbnail(THUMBNAIL_SIZE.0, THUMBNAIL_SIZE.1)
+                .into_rgba8();
+            if let Err(err) = thumbnails::store(path, &thumbnail, width, height) {
+                warn!("{:?}", err);
+            }
+            (thumbnail, width, height)
+        }
+    };
+    Ok(Thumbnail {
+        handle: Handle::from_rgba(thumbnail.width(), thumbnail.height(), thumbnail.into_raw()),
+        width,
+        height,
+        bytes,
+        video: snapshot::is_video(path),
+    })
+}
+
+/// Memory the previews and thumbnails may take when it isn't configured
+pub const DEFAULT_CACHE_MB: usize = 64;
+
+#[derive(Debug)]
+struct Cached<T> {
+    value: T,
+    bytes: usize,
+    /// Of `PreviewCache::clock`, the smallest is evicted first
+    used: u64,
+}
+
+fn handle_bytes(handle: &Handle) -> usize {
+    match handle {
+        Handle::Rgba { pixels, .. } => pixels.len(),
+        Handle::Bytes(_, bytes) => bytes.len(),
+        Handle::Path(..) => 0,
+    }
+}
+
+/// Rendered previews and thumbnails, so redrawing the layout or going back
+/// to a file doesn't scale images again. The least recently used ones are
+/// dropped once they take more than the budget
+#[derive(Debug)]
+pub struct PreviewCache {
+    previews: HashMap<PreviewKey, Cached<Handle>>,
+    thumbnails: HashMap<PathBuf, Cached<Thumbnail>>,
+    /// Being rendered right now, not requested twice
+    pending: HashSet<PreviewKey>,
+    budget: usize,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl Default for PreviewCache {
+    fn default() -> Self {
+        Self {
+            previews: HashMap::new(),
+            thumbnails: HashMap::new(),
+            pending: HashSet::new(),
+            budget: DEFAULT_CACHE_MB * 1024 * 1024,
+            clock: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+}
+
+/// What the cache holds, for the status card
+#[derive(Debug, Clone, Copy)]
+pub struct CacheStats {
+    pub previews: usize,
+    pub thumbnails: usize,
+    pub bytes: usize,
+    pub budget: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+impl fmt::Display for CacheStats {
+    /// Like `12 previews, 3 thumbnails, 4.2 MB of 67.1 MB, 80% hits, 5 evicted`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} previews, {} thumbnails, {} of {}, {}% hits, {} evicted",
+            self.previews,
+            self.thumbnails,
+            human_size(self.bytes as u64),
+            human_size(self.budget as u64),
+            (self.hits * 100)
+                .checked_div(self.hits + self.misses)
+                .unwrap_or(0),
+            self.evictions
+        )
+    }
+}
+
+impl PreviewCache {
+    /// In bytes, evicts right away if it shrunk
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict();
+    }
+
+    pub fn get(&self, key: &PreviewKey) -> Option<&Handle> {
+        self.previews.get(key).map(|cached| &cached.value)
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Marks `key` as being rendered, false if it's cached or already on its way
+    pub fn start(&mut self, key: &PreviewKey) -> bool {
+        let now = self.tick();
+        if let Some(cached) = self.previews.get_mut(key) {
+            cached.used = now;
+            self.hits += 1;
+            return false;
+        }
+        self.misses += 1;
+        self.pending.insert(key.clone())
+    }
+
+    pub fn finish(&mut self, key: PreviewKey, handle: Option<Handle>) {
+        self.pending.remove(&key);
+        if let Some(handle) = handle {
+            let used = self.tick();
+            let bytes = handle_bytes(&handle);
+            self.previews.insert(
+                key,
+                Cached {
+                    value: handle,
+                    bytes,
+                    used,
+                },
+            );
+            self.evict();
+        }
+    }
+
+    /// The cached thumbnail of `path`, counts as using it
+    pub fn thumbnail(&mut self, path: &Path) -> Option<Thumbnail> {
+        let now = self.tick();
+        match self.thumbnails.get_mut(path) {
+            Some(cached) => {
+                cached.used = now;
+                self.hits += 1;
+                Some(cached.value.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert_thumbnail(&mut self, path: PathBuf, thumbnail: Thumbnail) {
+        let used = self.tick();
+        let bytes = handle_bytes(&thumbnail.handle);
+        self.thumbnails.insert(
+            path,
+            Cached {
+                value: thumbnail,
+                bytes,
+                used,
+            },
+        );
+        self.evict();
+    }
+
+    /// Drops the previews of `monitor` (-1 for every monitor), e.g. after
+    /// something was assigned to it or its scaling mode changed
+    pub fn invalidate_monitor(&mut self, monitor: i32) {
+        self.previews
+            .retain(|key, _| monitor != -1 && key.monitor != monitor);
+    }
+
+    fn bytes(&self) -> usize {
+        self.previews
+            .values()
+            .map(|cached| cached.bytes)
+            .sum::<usize>()
+            + self
+                .thumbnails
+                .values()
+                .map(|cached| cached.bytes)
+                .sum::<usize>()
+    }
+
+    /// Least recently used first, but the newest entry always stays so a
+    /// tiny budget still shows something
+    fn evict(&mut self) {
+        let mut bytes = self.bytes();
+        while bytes > self.budget && self.previews.len() + self.thumbnails.len() > 1 {
+            let oldest_preview = self
+                .previews
+                .iter()
+                .min_by_key(|(_, cached)| cached.used)
+                .map(|(key, cached)| (cached.used, key.clone()));
+            let oldest_thumbnail = self
+                .thumbnails
+                .iter()
+                .min_by_key(|(_, cached)| cached.used)
+                .map(|(path, cached)| (cached.used, path.clone()));
+            let freed = match (oldest_preview, oldest_thumbnail) {
+                (Some((preview_used, key)), Some((thumbnail_used, _)))
+                    if preview_used < thumbnail_used =>
+                {
+                    self.previews.remove(&key).map(|cached| cached.bytes)
+                }
+                (Some((_, key)), None) => self.previews.remove(&key).map(|cached| cached.bytes),
+                (_, Some((_, path))) => self.thumbnails.remove(&path).map(|cached| cached.bytes),
+                (None, None) => None,
+            };
+            bytes -= freed.unwrap_or_default();
+            self.evictions += 1;
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            previews: self.previews.len(),
+            thumbnails: self.thumbnails.len(),
+            bytes: self.bytes(),
+            budget: self.budget,
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_read_like_people_write_them() {
+        assert_eq!(human_size(512), "512.0 B");
+        assert_eq!(human_size(1_500_000), "1.5 MB");
+        assert_eq!(human_size(500 * 1024 * 1024 * 1024), "536.9 GB");
+        assert_eq!(human_size(2_000_000_000_000), "2.0 TB");
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing::debug;
+
+use crate::xdg;
+
+/// Where a downloaded wallpaper came from, so it can be credited
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Page the wallpaper was found on
+    pub origin: String,
+    pub author: Option<String>,
+    pub license: Option<String>,
+}
+
+impl Provenance {
+    /// One line to put under a screenshot
+    pub fn attribution(&self) -> String {
+        let by = match &self.author {
+            Some(author) => format!(" by {author}"),
+            None => String::new(),
+        };
+        let license = match &self.license {
+            Some(license) => format!(" ({license})"),
+            None => String::new(),
+        };
+        format!("Wallpaper{by}{license}, {}", self.origin)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    #[serde(with = "crate::os_path")]
+    path: PathBuf,
+    #[serde(flatten)]
+    provenance: Provenance,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    #[serde(default)]
+    entries: Vec<Entry>,
+}
+
+/// Sources fetch in parallel, every update reads and writes the whole index
+static WRITING: Mutex<()> = Mutex::new(());
+
+fn index_path() -> PathBuf {
+    xdg::data_dir().join("provenance.toml")
+}
+
+fn load() -> Result<Index> {
+    let path = index_path();
+    if !path.exists() {
+        return Ok(Index::default());
+    }
+    let contents =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Remembers where `path` was downloaded from, forgetting files that were
+/// evicted since
+pub fn record(path: &Path, provenance: &Provenance) -> Result<()> {
+    let _writing = WRITING
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut index = load()?;
+    index
+        .entries
+        .retain(|entry| entry.path != path && entry.path.exists());
+    index.entries.push(Entry {
+        path: path.to_owned(),
+        provenance: provenance.clone(),
+    });
+
+    let index_path = index_path();
+    if let Some(parent) = index_path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(&index_path, toml::to_string_pretty(&index)?)
+        .with_context(|| format!("Failed to write {}", index_path.display()))?;
+    debug!("Recorded where {} came from", path.display());
+    Ok(())
+}
+
+/// Where `path` came from, None for files that weren't downloaded
+pub fn of(path: &Path) -> Result<Option<Provenance>> {
+    Ok(load()?
+        .entries
+        .into_iter()
+        .find(|entry| entry.path == path)
+        .map(|entry| entry.provenance))
+}
@@ -0,0 +1,408 @@
+use anyhow::{Result, anyhow};
+use std::{fmt, fs, path::PathBuf, str::FromStr, time::Duration};
+
+/// How often to check the battery, only while a rule cares about it
+pub const BATTERY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Something a rule reacts to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleEvent {
+    Connected,
+    MonitorsChanged,
+    BatteryChanged,
+}
+
+impl RuleEvent {
+    fn parse(s: &str) -> Result<Self> {
+        match s {
+            "connected" => Ok(Self::Connected),
+            "monitors change" | "monitors changed" => Ok(Self::MonitorsChanged),
+            "battery changes" | "battery changed" => Ok(Self::BatteryChanged),
+            _ => Err(anyhow!(
+                "Unknown event `{s}`, try `connected`, `monitors change` or `battery changes`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RuleEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Connected => "connected",
+            Self::MonitorsChanged => "monitors change",
+            Self::BatteryChanged => "battery changes",
+        })
+    }
+}
+
+/// What conditions can look at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fact {
+    Monitors,
+    /// Percent, conditions on it are false without a battery
+    Battery,
+    Hour,
+}
+
+impl Fact {
+    fn parse(s: &str) -> Result<Self> {
+        match s {
+            "monitors" | "count" => Ok(Self::Monitors),
+            "battery" => Ok(Self::Battery),
+            "hour" => Ok(Self::Hour),
+            _ => Err(anyhow!(
+                "Unknown value `{s}`, try `monitors`, `battery` or `hour`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Fact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Monitors => "monitors",
+            Self::Battery => "battery",
+            Self::Hour => "hour",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    Ge,
+    Gt,
+}
+
+impl Op {
+    /// Longest first so `<=` isn't read as `<`
+    const ALL: &[(&str, Self)] = &[
+        ("<=", Self::Le),
+        (">=", Self::Ge),
+        ("==", Self::Eq),
+        ("!=", Self::Ne),
+        ("<", Self::Lt),
+        (">", Self::Gt),
+        ("=", Self::Eq),
+    ];
+
+    fn eval(self, left: i64, right: i64) -> bool {
+        match self {
+            Self::Lt => left < right,
+            Self::Le => left <= right,
+            Self::Eq => left == right,
+            Self::Ne => left != right,
+            Self::Ge => left >= right,
+            Self::Gt => left > right,
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Ge => ">=",
+            Self::Gt => ">",
+        })
+    }
+}
+
+/// `battery < 20%`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub fact: Fact,
+    pub op: Op,
+    pub value: i64,
+}
+
+impl Condition {
+    fn parse(s: &str) -> Result<Self> {
+        let (at, token, op) = Op::ALL
+            .iter()
+            .filter_map(|(token, op)| Some((s.find(token)?, *token, *op)))
+            .min_by_key(|(at, token, _)| (*at, usize::MAX - token.len()))
+            .ok_or_else(|| anyhow!("`{s}` isn't a comparison like `monitors == 1`"))?;
+        let value = s[at + token.len()..].trim().trim_end_matches('%').trim();
+        Ok(Self {
+            fact: Fact::parse(s[..at].trim())?,
+            op,
+            value: value
+                .parse()
+                .map_err(|_| anyhow!("`{value}` isn't a whole number"))?,
+        })
+    }
+
+    pub fn holds(&self, facts: &Facts) -> bool {
+        let value = match self.fact {
+            Fact::Monitors => facts.monitors as i64,
+            Fact::Battery => match facts.battery {
+                Some(percent) => percent.into(),
+                None => return false,
+            },
+            Fact::Hour => facts.hour.into(),
+        };
+        self.op.eval(value, self.value)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.fact, self.op, self.value)?;
+        if self.fact == Fact::Battery {
+            f.write_str("%")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleAction {
+    PauseVideos,
+    ResumeVideos,
+    /// On every monitor
+    Apply(PathBuf),
+    /// Every monitor, see `Config::clear_color`
+    Clear,
+}
+
+impl RuleAction {
+    fn parse(s: &str) -> Result<Self> {
+        match s {
+            "pause videos" => Ok(Self::PauseVideos),
+            "resume videos" => Ok(Self::ResumeVideos),
+            "clear" => Ok(Self::Clear),
+            _ => match s.strip_prefix("apply ").map(str::trim) {
+                Some(path) if !path.is_empty() => Ok(Self::Apply(PathBuf::from(path))),
+                _ => Err(anyhow!(
+                    "Unknown action `{s}`, try `pause videos`, `resume videos`, `clear` or `apply <file>`"
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PauseVideos => f.write_str("pause videos"),
+            Self::ResumeVideos => f.write_str("resume videos"),
+            Self::Apply(path) => write!(f, "apply {}", path.display()),
+            Self::Clear => f.write_str("clear"),
+        }
+    }
+}
+
+/// When the monitors change and there's only one left, use the laptop wallpaper:
+///
+/// ```toml
+/// rules = [
+///     "when monitors change and monitors == 1 then apply /home/me/laptop.png",
+///     "when battery < 20% then pause videos",
+/// ]
+/// ```
+///
+/// Kept as strings in the config, see `parse_all`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    /// `None` for rules that only have conditions, checked on every event
+    pub event: Option<RuleEvent>,
+    /// All of them have to hold
+    pub conditions: Vec<Condition>,
+    pub action: RuleAction,
+}
+
+impl Rule {
+    pub fn matches(&self, event: RuleEvent, facts: &Facts) -> bool {
+        self.event.is_none_or(|own| own == event) && self.conditions.iter().all(|c| c.holds(facts))
+    }
+
+    /// The battery only gets checked if a rule needs it
+    pub fn uses_battery(&self) -> bool {
+        self.event == Some(RuleEvent::BatteryChanged)
+            || self.conditions.iter().any(|c| c.fact == Fact::Battery)
+    }
+}
+
+/// The rules in `inputs` that parse, and why each of the others didn't.
+/// Blank ones are left out
+pub fn parse_all(inputs: &[String]) -> (Vec<Rule>, Vec<String>) {
+    let mut rules = Vec::new();
+    let mut errors = Vec::new();
+    for (i, input) in inputs.iter().enumerate() {
+        if input.trim().is_empty() {
+            continue;
+        }
+        match input.parse() {
+            Ok(rule) => rules.push(rule),
+            Err(err) => errors.push(format!("Rule {}: {err}", i + 1)),
+        }
+    }
+    (rules, errors)
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let rest = s
+            .strip_prefix("when ")
+            .ok_or_else(|| anyhow!("Rules start with `when`"))?;
+        let (when, then) = rest
+            .split_once(" then ")
+            .ok_or_else(|| anyhow!("Rules need a `then <action>`"))?;
+
+        let mut parts = when.split(" and ").map(str::trim);
+        let first = parts.next().unwrap_or_default();
+        let (event, mut conditions) = match RuleEvent::parse(first) {
+            Ok(event) => (Some(event), Vec::new()),
+            // the event can be left out, the first part is a condition then
+            Err(_) if Op::ALL.iter().any(|(token, _)| first.contains(token)) => {
+                (None, vec![Condition::parse(first)?])
+            }
+            Err(err) => return Err(err),
+        };
+        for part in parts {
+            conditions.push(Condition::parse(part)?);
+        }
+        Ok(Self {
+            event,
+            conditions,
+            action: RuleAction::parse(then.trim())?,
+        })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = self
+            .event
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        parts.extend(self.conditions.iter().map(ToString::to_string));
+        write!(f, "when {} then {}", parts.join(" and "), self.action)
+    }
+}
+
+/// What conditions are checked against
+#[derive(Debug, Clone, Copy)]
+pub struct Facts {
+    pub monitors: usize,
+    pub battery: Option<u8>,
+    pub hour: u32,
+}
+
+/// Charge of the first battery, `None` on machines without one
+pub fn battery_percent() -> Option<u8> {
+    let mut batteries: Vec<PathBuf> = fs::read_dir("/sys/class/power_supply")
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            fs::read_to_string(path.join("type")).is_ok_and(|kind| kind.trim() == "Battery")
+        })
+        .collect();
+    batteries.sort();
+    fs::read_to_string(batteries.first()?.join("capacity"))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rules() {
+        let rule: Rule = "when monitors change and monitors == 1 then apply /home/me/laptop.png"
+            .parse()
+            .unwrap();
+        assert_eq!(rule.event, Some(RuleEvent::MonitorsChanged));
+        assert_eq!(
+            rule.conditions,
+            [Condition {
+                fact: Fact::Monitors,
+                op: Op::Eq,
+                value: 1
+            }]
+        );
+        assert_eq!(
+            rule.action,
+            RuleAction::Apply(PathBuf::from("/home/me/laptop.png"))
+        );
+
+        let rule: Rule = "when hour >= 20 and hour <= 23 then clear".parse().unwrap();
+        assert_eq!(rule.conditions[0].op, Op::Ge);
+        assert_eq!(rule.conditions[1].op, Op::Le);
+        assert_eq!(rule.action, RuleAction::Clear);
+    }
+
+    #[test]
+    fn event_can_be_left_out() {
+        let rule: Rule = "when battery < 20% then pause videos".parse().unwrap();
+        assert_eq!(rule.event, None);
+        assert!(rule.uses_battery());
+        let low = Facts {
+            monitors: 1,
+            battery: Some(15),
+            hour: 12,
+        };
+        assert!(rule.matches(RuleEvent::BatteryChanged, &low));
+        assert!(rule.matches(RuleEvent::Connected, &low));
+        let charged = Facts {
+            battery: Some(80),
+            ..low
+        };
+        assert!(!rule.matches(RuleEvent::BatteryChanged, &charged));
+    }
+
+    #[test]
+    fn displays_what_it_parses() {
+        for input in [
+            "when battery changes and battery < 20% then pause videos",
+            "when battery < 20% then pause videos",
+            "when connected then resume videos",
+            "when monitors change and monitors != 2 then apply /a b.png",
+        ] {
+            let rule: Rule = input.parse().unwrap();
+            assert_eq!(rule.to_string(), input);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_rules() {
+        for input in [
+            "monitors == 1 then clear",
+            "when monitors == 1",
+            "when lunch then clear",
+            "when battery < low then clear",
+            "when connected then dance",
+            "when temperature > 30 then clear",
+        ] {
+            assert!(input.parse::<Rule>().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn bad_rules_are_skipped() {
+        let inputs = [
+            "when connected then clear".to_owned(),
+            "when lunch then clear".to_owned(),
+            " ".to_owned(),
+            "when battery < 20% then pause videos".to_owned(),
+        ];
+        let (rules, errors) = parse_all(&inputs);
+        assert_eq!(rules.len(), 2);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("Rule 2: "));
+    }
+}
@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, time::Duration};
+
+use crate::sources::Wallpaper;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RotationOrder {
+    /// The way the source lists them
+    #[default]
+    Ordered,
+    /// Every wallpaper once per round, in a new order each round
+    Shuffle,
+}
+
+impl RotationOrder {
+    pub const ALL: &[Self] = &[Self::Ordered, Self::Shuffle];
+}
+
+impl fmt::Display for RotationOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ordered => "In order",
+            Self::Shuffle => "Shuffled",
+        })
+    }
+}
+
+/// How the desktop is rotated through a source, kept in the config
+///
+/// ```toml
+/// [rotation]
+/// interval_mins = 30
+/// order = "shuffle"
+/// per_monitor = true
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rotation {
+    pub interval_mins: u64,
+    #[serde(default)]
+    pub order: RotationOrder,
+    /// Every monitor gets a wallpaper of its own instead of sharing one
+    #[serde(default)]
+    pub per_monitor: bool,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self {
+            interval_mins: 30,
+            order: RotationOrder::default(),
+            per_monitor: false,
+        }
+    }
+}
+
+/// A year, longer intervals are cut down to it
+pub const MAX_INTERVAL_MINS: u64 = 365 * 24 * 60;
+
+impl Rotation {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_mins.clamp(1, MAX_INTERVAL_MINS) * 60)
+    }
+}
+
+/// Puts the next wallpapers of a source on the desktop every
+/// `Rotation::interval`, started and stopped from the automation card
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    pub source_id: String,
+    pub rotation: Rotation,
+    wallpapers: Vec<Wallpaper>,
+    /// Indices into `wallpapers`, in the order of the current round
+    round: Vec<usize>,
+    next: usize,
+    seed: u64,
+}
+
+impl Scheduler {
+    pub fn new(
+        source_id: String,
+        wallpapers: Vec<Wallpaper>,
+        rotation: Rotation,
+        seed: u64,
+    ) -> Self {
+        let mut scheduler = Self {
+            source_id,
+            rotation,
+            round: (0..wallpapers.len()).collect(),
+            wallpapers,
+            next: 0,
+            seed: seed | 1,
+        };
+        scheduler.shuffle();
+        scheduler
+    }
+
+    pub fn len(&self) -> usize {
+        self.wallpapers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wallpapers.is_empty()
+    }
+
+    fn shuffle(&mut self) {
+        if self.rotation.order != RotationOrder::Shuffle {
+            return;
+        }
+        for i in (1..self.round.len()).rev() {
+            // xorshift, good enough for wallpapers
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 7;
+            self.seed ^= self.seed << 17;
+            self.round.swap(i, self.seed as usize % (i + 1));
+        }
+    }
+
+    /// The next `count` wallpapers, one for each monitor being rotated.
+    /// They're only the same when the source has fewer than `count`
+    pub fn next(&mut self, count: usize) -> Vec<Wallpaper> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        (0..count)
+            .map(|_| {
+                if self.next >= self.round.len() {
+                    self.next = 0;
+                    self.shuffle();
+                }
+                self.next += 1;
+                self.wallpapers[self.round[self.next - 1]].clone()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::Location;
+
+    fn scheduler(order: RotationOrder, seed: u64) -> Scheduler {
+        let wallpapers = (0..10)
+            .map(|i| Wallpaper {
+                name: i.to_string(),
+                location: Location::Local(format!("{i}.png").into()),
+                tags: Vec::new(),
+                provenance: None,
+            })
+            .collect();
+        let rotation = Rotation {
+            order,
+            ..Rotation::default()
+        };
+        Scheduler::new("test".to_owned(), wallpapers, rotation, seed)
+    }
+
+    fn names(wallpapers: Vec<Wallpaper>) -> Vec<String> {
+        wallpapers.into_iter().map(|w| w.name).collect()
+    }
+
+    fn sorted(mut names: Vec<String>) -> Vec<String> {
+        names.sort_by_key(|name| name.parse::<u32>().unwrap());
+        names
+    }
+
+    #[test]
+    fn ordered_keeps_the_sources_order() {
+        let mut scheduler = scheduler(RotationOrder::Ordered, 42);
+        let all: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+        assert_eq!(names(scheduler.next(10)), all);
+        assert_eq!(names(scheduler.next(3)), all[..3]);
+    }
+
+    #[test]
+    fn shuffle_shows_every_wallpaper_once_a_round() {
+        let all: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+        let mut scheduler = scheduler(RotationOrder::Shuffle, 42);
+        let rounds: Vec<Vec<String>> = (0..4).map(|_| names(scheduler.next(10))).collect();
+        for round in &rounds {
+            assert_eq!(sorted(round.clone()), all);
+        }
+        assert!(rounds.iter().any(|round| *round != all));
+        assert!(rounds.windows(2).any(|pair| pair[0] != pair[1]));
+
+        // split across calls too
+        let mut scheduler = self::scheduler(RotationOrder::Shuffle, 42);
+        let mut round = names(scheduler.next(4));
+        round.extend(names(scheduler.next(6)));
+        assert_eq!(round, rounds[0]);
+
+        let mut other = self::scheduler(RotationOrder::Shuffle, 7);
+        assert_ne!(names(other.next(10)), rounds[0]);
+    }
+
+    #[test]
+    fn empty_sources_give_nothing() {
+        let mut scheduler = Scheduler::new("test".to_owned(), Vec::new(), Rotation::default(), 42);
+        assert!(scheduler.next(2).is_empty());
+    }
+
+    #[test]
+    fn intervals_are_kept_sane() {
+        let interval = |interval_mins| {
+            Rotation {
+                interval_mins,
+                ..Rotation::default()
+            }
+            .interval()
+        };
+        assert_eq!(interval(0), Duration::from_secs(60));
+        assert_eq!(interval(30), Duration::from_secs(30 * 60));
+        assert_eq!(
+            interval(u64::MAX),
+            Duration::from_secs(MAX_INTERVAL_MINS * 60)
+        );
+    }
+}
@@ -0,0 +1,405 @@
+use anyhow::{Context, Result};
+use chrono::{Datelike, Local, Timelike};
+use rhai::{AST, Array, CallFnOptions, Dynamic, Engine, Map, Scope};
+use std::{
+    collections::VecDeque,
+    fmt, fs,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, error};
+
+use crate::{ipc_spec::Monitor, library::Library, xdg};
+
+/// How often `on_tick` is called
+pub const TICK_INTERVAL: Duration = Duration::from_secs(60);
+/// Keeps a runaway loop from hanging the script thread
+const MAX_OPERATIONS: u64 = 1_000_000;
+/// Keep a script from eating the memory, in bytes
+const MAX_STRING_SIZE: usize = 64 * 1024;
+/// Big enough for `library()` of a large library
+const MAX_ARRAY_SIZE: usize = 100_000;
+/// Lines of output kept per script
+const LOG_LINES: usize = 50;
+
+/// Written by the "New script" button
+pub const TEMPLATE: &str = r#"// Called every minute, `this` is kept between calls
+fn on_tick() {
+    if this.last_hour == hour() {
+        return;
+    }
+    this.last_hour = hour();
+
+    let wallpapers = library();
+    if wallpapers.len() > 0 {
+        let pick = wallpapers[random(wallpapers.len())];
+        print(`switching to ${pick.name}`);
+        apply(pick.path);
+    }
+}
+
+// also: on_connected(), on_monitors_changed()
+// api: monitors(), library(), apply(path), apply(monitor, path),
+//      hour(), minute(), weekday(), random(max), print(text)
+"#;
+
+/// Things scripts react to, by defining a function with the handler's name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptEvent {
+    Tick,
+    Connected,
+    MonitorsChanged,
+}
+
+impl ScriptEvent {
+    pub fn handler(self) -> &'static str {
+        match self {
+            Self::Tick => "on_tick",
+            Self::Connected => "on_connected",
+            Self::MonitorsChanged => "on_monitors_changed",
+        }
+    }
+}
+
+impl fmt::Display for ScriptEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.handler())
+    }
+}
+
+/// What a script asked for, done by the gui once the script returns
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptAction {
+    Apply { monitor: Option<i32>, path: PathBuf },
+}
+
+/// A `.rhai` file from the scripts directory
+#[derive(Debug)]
+pub struct Script {
+    pub name: String,
+    pub path: PathBuf,
+    ast: Result<AST, String>,
+    /// `this` of the handlers
+    memory: Map,
+    pub log: VecDeque<String>,
+}
+
+impl Script {
+    pub fn load(path: PathBuf) -> Self {
+        let name = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let ast = fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))
+            .and_then(|source| {
+                Engine::new()
+                    .compile(source)
+                    .map_err(|err| format!("{err}"))
+            });
+        Self {
+            name,
+            path,
+            ast,
+            memory: Map::new(),
+            log: VecDeque::new(),
+        }
+    }
+
+    /// Why it can't run, if it can't
+    pub fn error(&self) -> Option<&str> {
+        self.ast.as_ref().err().map(String::as_str)
+    }
+
+    pub fn handles(&self, event: ScriptEvent) -> bool {
+        self.ast
+            .as_ref()
+            .is_ok_and(|ast| ast.iter_functions().any(|f| f.name == event.handler()))
+    }
+
+    /// Everything a run needs, so it can happen on another thread
+    pub fn prepare(&self, event: ScriptEvent, monitors: Vec<Monitor>) -> Option<Run> {
+        Some(Run {
+            script: self.name.clone(),
+            ast: self.ast.as_ref().ok()?.clone(),
+            memory: self.memory.clone(),
+            event,
+            monitors,
+        })
+    }
+
+    /// Takes in what a run left behind
+    pub fn finish(&mut self, outcome: &Outcome) {
+        self.memory = outcome.memory.clone();
+        self.log.extend(outcome.log.iter().cloned());
+        if let Some(err) = &outcome.error {
+            self.log.push_back(format!("error: {err}"));
+        }
+        while self.log.len() > LOG_LINES {
+            self.log.pop_front();
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/xab-gui/scripts`
+pub fn dir() -> PathBuf {
+    xdg::config_dir().join("scripts")
+}
+
+/// Every script in `dir()`, sorted by name
+pub fn load_all() -> Vec<Script> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+        .collect();
+    paths.sort();
+    paths.into_iter().map(Script::load).collect()
+}
+
+/// Writes `TEMPLATE` to a new file in `dir()`
+pub fn create() -> Result<PathBuf> {
+    let dir = dir();
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let mut path = dir.join("new-script.rhai");
+    let mut suffix = 2;
+    while path.exists() {
+        path = dir.join(format!("new-script-{suffix}.rhai"));
+        suffix += 1;
+    }
+    fs::write(&path, TEMPLATE).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+pub struct Run {
+    script: String,
+    ast: AST,
+    memory: Map,
+    event: ScriptEvent,
+    monitors: Vec<Monitor>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub script: String,
+    pub actions: Vec<ScriptAction>,
+    memory: Map,
+    log: Vec<String>,
+    pub error: Option<String>,
+}
+
+fn monitor_map(monitor: &Monitor) -> Dynamic {
+    let mut map = Map::new();
+    map.insert("index".into(), i64::from(monitor.index).into());
+    map.insert("primary".into(), monitor.primary.into());
+    map.insert("x".into(), i64::from(monitor.x).into());
+    map.insert("y".into(), i64::from(monitor.y).into());
+    map.insert("width".into(), i64::from(monitor.width).into());
+    map.insert("height".into(), i64::from(monitor.height).into());
+    map.into()
+}
+
+fn library_entries() -> Array {
+    let library = Library::load().unwrap_or_else(|err| {
+        error!("Failed to load the library for a script: {:?}", err);
+        Library::default()
+    });
+    library
+        .entries
+        .iter()
+        .map(|entry| {
+            let mut map = Map::new();
+            map.insert(
+                "path".into(),
+                entry.path.to_string_lossy().into_owned().into(),
+            );
+            map.insert("name".into(), entry.name.clone().into());
+            map.insert(
+                "tags".into(),
+                entry
+                    .tags
+                    .iter()
+                    .cloned()
+                    .map(Dynamic::from)
+                    .collect::<Array>()
+                    .into(),
+            );
+            map.insert(
+                "pack".into(),
+                entry.pack.clone().map_or(Dynamic::UNIT, Dynamic::from),
+            );
+            map.into()
+        })
+        .collect()
+}
+
+/// Only what's registered here is reachable, rhai itself can't touch files,
+/// the network or other processes
+fn engine(
+    run: &Run,
+    actions: Arc<Mutex<Vec<ScriptAction>>>,
+    log: Arc<Mutex<Vec<String>>>,
+) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.set_max_array_size(MAX_ARRAY_SIZE);
+
+    let print_log = log.clone();
+    engine.on_print(move |line| lock(&print_log).push(line.to_owned()));
+    engine.on_debug(move |line, _, _| lock(&log).push(line.to_owned()));
+
+    let monitors: Array = run.monitors.iter().map(monitor_map).collect();
+    engine.register_fn("monitors", move || monitors.clone());
+    engine.register_fn("library", library_entries);
+
+    let apply_all = actions.clone();
+    engine.register_fn("apply", move |path: &str| {
+        lock(&apply_all).push(ScriptAction::Apply {
+            monitor: None,
+            path: PathBuf::from(path),
+        });
+    });
+    engine.register_fn("apply", move |monitor: i64, path: &str| {
+        lock(&actions).push(ScriptAction::Apply {
+            monitor: i32::try_from(monitor).ok(),
+            path: PathBuf::from(path),
+        });
+    });
+
+    engine.register_fn("hour", || i64::from(Local::now().hour()));
+    engine.register_fn("minute", || i64::from(Local::now().minute()));
+    // 0 is monday
+    engine.register_fn("weekday", || {
+        i64::from(Local::now().weekday().num_days_from_monday())
+    });
+    engine.register_fn("random", |max: i64| {
+        // good enough of a random number for picking a wallpaper
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .subsec_nanos();
+        i64::from(nanos) % max.max(1)
+    });
+    engine
+}
+
+/// Calls the handler of `run.event`, blocks until the script returns
+pub fn run(mut run: Run) -> Outcome {
+    debug!("Running {} of {}", run.event, run.script);
+    let actions = Arc::default();
+    let log = Arc::default();
+    let engine = engine(&run, Arc::clone(&actions), Arc::clone(&log));
+
+    let mut this: Dynamic = std::mem::take(&mut run.memory).into();
+    let res = engine.call_fn_with_options::<Dynamic>(
+        CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut this),
+        &mut Scope::new(),
+        &run.ast,
+        run.event.handler(),
+        (),
+    );
+    drop(engine);
+
+    Outcome {
+        script: run.script,
+        // half of what a failed script wanted isn't worth doing
+        actions: match res {
+            Ok(_) => take(actions),
+            Err(_) => Vec::new(),
+        },
+        // a script could've replaced `this` with something that isn't a map
+        memory: this.try_cast::<Map>().unwrap_or_default(),
+        log: take(log),
+        error: res.err().map(|err| err.to_string()),
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn take<T>(shared: Arc<Mutex<Vec<T>>>) -> Vec<T> {
+    std::mem::take(&mut *lock(&shared))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(source: &str) -> Script {
+        Script {
+            name: "test".to_owned(),
+            path: PathBuf::new(),
+            ast: Engine::new()
+                .compile(source)
+                .map_err(|err| format!("{err}")),
+            memory: Map::new(),
+            log: VecDeque::new(),
+        }
+    }
+
+    fn tick(script: &mut Script) -> Outcome {
+        let outcome = run(script.prepare(ScriptEvent::Tick, Vec::new()).unwrap());
+        script.finish(&outcome);
+        outcome
+    }
+
+    #[test]
+    fn runs_handlers_and_keeps_this() {
+        let mut script = script(
+            r#"
+            fn on_tick() {
+                if this.runs == () { this.runs = 0; }
+                this.runs += 1;
+                print(`run ${this.runs}`);
+                apply(1, "/a.png");
+            }
+            "#,
+        );
+        assert!(script.handles(ScriptEvent::Tick));
+        assert!(!script.handles(ScriptEvent::Connected));
+
+        tick(&mut script);
+        let outcome = tick(&mut script);
+        assert_eq!(outcome.error, None);
+        assert_eq!(
+            outcome.actions,
+            [ScriptAction::Apply {
+                monitor: Some(1),
+                path: PathBuf::from("/a.png")
+            }]
+        );
+        assert_eq!(script.log, ["run 1", "run 2"]);
+    }
+
+    #[test]
+    fn failed_runs_do_nothing() {
+        let mut script = script(
+            r#"
+            fn on_tick() {
+                apply("/a.png");
+                let s = "x";
+                loop { s += s; }
+            }
+            "#,
+        );
+        let outcome = tick(&mut script);
+        assert!(outcome.error.is_some());
+        assert!(outcome.actions.is_empty());
+        assert!(script.log.back().unwrap().starts_with("error: "));
+    }
+
+    #[test]
+    fn runaway_loops_are_stopped() {
+        let outcome = tick(&mut script("fn on_tick() { loop {} }"));
+        assert!(outcome.error.is_some());
+    }
+}
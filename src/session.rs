@@ -0,0 +1,64 @@
+use std::{env, fmt};
+
+/// The kind of graphical session we're running in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SessionKind {
+    #[default]
+    X11,
+    /// With or without an XWayland server to run xab on
+    Wayland { xwayland: bool },
+    /// No display at all (tty, ssh without forwarding, ...)
+    Unknown,
+}
+
+impl fmt::Display for SessionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::X11 => "X11",
+            Self::Wayland { xwayland: true } => "Wayland (XWayland)",
+            Self::Wayland { xwayland: false } => "Wayland",
+            Self::Unknown => "unknown",
+        })
+    }
+}
+
+fn var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+impl SessionKind {
+    pub fn detect() -> Self {
+        let wayland = var("WAYLAND_DISPLAY").is_some()
+            || var("XDG_SESSION_TYPE").is_some_and(|kind| kind == "wayland");
+        let x11 = var("DISPLAY").is_some();
+        match (wayland, x11) {
+            (true, xwayland) => Self::Wayland { xwayland },
+            (false, true) => Self::X11,
+            (false, false) => Self::Unknown,
+        }
+    }
+
+    /// Key grabs, and anything else that needs to see the whole X11 desktop
+    ///
+    /// on XWayland they only see events while an X11 window has focus
+    pub fn has_x11_desktop(&self) -> bool {
+        *self == Self::X11
+    }
+
+    /// Why xab might not work here, shown on the connect page
+    pub fn warning(&self) -> Option<&'static str> {
+        match self {
+            Self::X11 => None,
+            Self::Wayland { xwayland: true } => Some(
+                "Wayland session: xab draws on the X11 root window, so it only works \
+                 on the XWayland display (most compositors don't show it). \
+                 Global hotkeys are disabled.",
+            ),
+            Self::Wayland { xwayland: false } => Some(
+                "Wayland session without XWayland: xab needs an X server, \
+                 so it can't run here. Use an X11 session or start XWayland.",
+            ),
+            Self::Unknown => Some("No display found, is this a graphical session?"),
+        }
+    }
+}
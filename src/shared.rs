@@ -0,0 +1,101 @@
+use chrono::{DateTime, Local};
+use iced::futures::{SinkExt, Stream, StreamExt};
+use std::{
+    hash::{Hash, Hasher},
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::Duration,
+};
+use tracing::debug;
+
+use crate::{
+    automation::{self, TimeWindow},
+    ipc::IpcHandle,
+    ipc_spec::{IpcEvent, Monitor},
+    state::Pins,
+};
+
+/// How often the monitor list is asked for while connected
+pub const MONITOR_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The part of the app background subscriptions need to see, kept up to date
+/// by `update()`, subscriptions only read it
+#[derive(Debug, Default)]
+pub struct SharedState {
+    pub ipc: Option<Arc<IpcHandle>>,
+    pub monitors: Vec<Monitor>,
+    pub pins: Pins,
+    pub quiet_hours: Option<TimeWindow>,
+    pub automation_paused_until: Option<DateTime<Local>>,
+    pub presenting: bool,
+}
+
+impl SharedState {
+    /// Why automatic changes (and their notifications) shouldn't happen right now
+    pub fn automation_paused(&self) -> Option<String> {
+        if self.presenting {
+            return Some("paused for presentation mode".to_owned());
+        }
+        automation::paused_reason(self.quiet_hours, self.automation_paused_until, Local::now())
+    }
+}
+
+/// Cheap to clone, identifies the subscriptions using it by the allocation
+#[derive(Debug, Clone, Default)]
+pub struct Shared(Arc<RwLock<SharedState>>);
+
+impl Shared {
+    pub fn read(&self) -> RwLockReadGuard<'_, SharedState> {
+        self.0
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, SharedState> {
+        self.0
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Hash for Shared {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state);
+    }
+}
+
+/// Yields the monitor list whenever it's different from the shared one
+pub fn monitor_updates(shared: &Shared) -> impl Stream<Item = Vec<Monitor>> + use<> {
+    let shared = shared.clone();
+    iced::stream::channel(1, async move |mut output| {
+        loop {
+            // never hold the lock across an await
+            let ipc = shared.read().ipc.clone();
+            if let Some(ipc) = ipc {
+                let monitors = ipc.get_monitors().await;
+                if monitors != shared.read().monitors {
+                    debug!("Monitors changed: {:?}", monitors);
+                    if output.send(monitors).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            tokio::time::sleep(MONITOR_POLL_INTERVAL).await;
+        }
+    })
+}
+
+/// Events xab sends over the current connection
+pub fn daemon_events(shared: &Shared) -> impl Stream<Item = IpcEvent> + use<> {
+    let shared = shared.clone();
+    iced::stream::channel(16, async move |mut output| {
+        let events = shared.read().ipc.as_ref().and_then(|ipc| ipc.take_events());
+        let Some(mut events) = events else {
+            return;
+        };
+        while let Some(event) = events.next().await {
+            if output.send(event).await.is_err() {
+                break;
+            }
+        }
+    })
+}
@@ -0,0 +1,146 @@
+use iced::{widget::image, window};
+use std::{
+    fmt,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::sources::Wallpaper;
+
+/// How long a fade between two wallpapers takes
+pub const FADE_DURATION: Duration = Duration::from_millis(800);
+
+pub const INTERVALS: &[SlideInterval] = &[
+    SlideInterval(5),
+    SlideInterval(10),
+    SlideInterval(30),
+    SlideInterval(60),
+];
+
+/// Seconds each wallpaper stays on screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlideInterval(pub u64);
+
+impl Default for SlideInterval {
+    fn default() -> Self {
+        Self(10)
+    }
+}
+
+impl SlideInterval {
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs(self.0)
+    }
+}
+
+impl fmt::Display for SlideInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}s", self.0)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Cut,
+    #[default]
+    Fade,
+}
+
+impl Transition {
+    pub const ALL: &[Self] = &[Self::Cut, Self::Fade];
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Cut => "Cut",
+            Self::Fade => "Fade",
+        })
+    }
+}
+
+/// Can't preview videos (yet), so they're skipped
+pub fn is_previewable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            matches!(
+                ext.to_lowercase().as_str(),
+                "png" | "jpg" | "jpeg" | "webp" | "bmp" | "gif"
+            )
+        })
+}
+
+/// A preview window cycling through wallpapers without touching the desktop
+#[derive(Debug)]
+pub struct Slideshow {
+    pub window: window::Id,
+    pub source_id: String,
+    pub wallpapers: Vec<Wallpaper>,
+    pub index: usize,
+    pub interval: SlideInterval,
+    pub transition: Transition,
+    pub current: Option<image::Handle>,
+    pub previous: Option<image::Handle>,
+    transition_started: Option<Instant>,
+}
+
+impl Slideshow {
+    pub fn new(
+        window: window::Id,
+        source_id: String,
+        wallpapers: Vec<Wallpaper>,
+        interval: SlideInterval,
+        transition: Transition,
+    ) -> Self {
+        Self {
+            window,
+            source_id,
+            wallpapers,
+            index: 0,
+            interval,
+            transition,
+            current: None,
+            previous: None,
+            transition_started: None,
+        }
+    }
+
+    pub fn current_wallpaper(&self) -> Option<&Wallpaper> {
+        self.wallpapers.get(self.index)
+    }
+
+    /// Moves to the next wallpaper and returns it, wrapping around at the end
+    pub fn advance(&mut self) -> Option<&Wallpaper> {
+        if self.wallpapers.is_empty() {
+            return None;
+        }
+        self.index = (self.index + 1) % self.wallpapers.len();
+        self.current_wallpaper()
+    }
+
+    /// Shows `path` as the current slide, starting the transition
+    pub fn show(&mut self, path: &Path) {
+        self.previous = self.current.take();
+        self.current = Some(image::Handle::from_path(path));
+        self.transition_started = match (self.transition, &self.previous) {
+            (Transition::Fade, Some(_)) => Some(Instant::now()),
+            _ => None,
+        };
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.transition_started
+            .is_some_and(|started| started.elapsed() < FADE_DURATION)
+    }
+
+    /// Opacity of the current slide, 0..=1
+    pub fn progress(&self) -> f32 {
+        match self.transition_started {
+            Some(started) => {
+                (started.elapsed().as_secs_f32() / FADE_DURATION.as_secs_f32()).min(1.0)
+            }
+            None => 1.0,
+        }
+    }
+}
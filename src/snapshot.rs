@@ -0,0 +1,102 @@
+use anyhow::{Context, Result, anyhow};
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tracing::{debug, error};
+
+use crate::{disk_cache, ipc::IpcHandle, persist, state::Assignment, xdg};
+
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "webm", "mov", "avi"];
+
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Video wallpapers replaced by a still frame, each one put up as an
+/// `OverrideKind::Snapshot`
+#[derive(Debug, Default, Clone)]
+pub struct Snapshot {
+    /// The frames and the monitors they're on
+    pub frozen: Vec<Assignment>,
+}
+
+/// Grabs a frame of `video` with ffmpeg, cached per video
+///
+/// NOTE: xab can't tell us where playback is, so this is the first frame
+pub fn extract_frame(video: &Path) -> Result<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    video.hash(&mut hasher);
+    let dir = xdg::cache_dir().join("snapshots");
+    let dest = dir.join(format!("{:016x}.png", hasher.finish()));
+    if disk_cache::hit(&dest) {
+        return Ok(dest);
+    }
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let output = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-i"])
+        .arg(video)
+        .args(["-frames:v", "1"])
+        .arg(&dest)
+        .output()
+        .with_context(|| "Failed to run ffmpeg, is it installed?")?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&dest);
+        return Err(anyhow!(
+            "Failed to grab a frame of {}: {}",
+            video.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    debug!(
+        "Grabbed a frame of {} to {}",
+        video.display(),
+        dest.display()
+    );
+    Ok(dest)
+}
+
+/// Swaps every saved video wallpaper for a still frame of it
+pub async fn enter(ipc_handle: &IpcHandle) -> Result<Snapshot> {
+    let videos: Vec<Assignment> = persist::state()?
+        .assignments
+        .into_iter()
+        .filter(|a| is_video(&a.path))
+        .collect();
+    if videos.is_empty() {
+        return Err(anyhow!("There are no video wallpapers to freeze"));
+    }
+
+    // grab everything first so a failing ffmpeg doesn't leave half of them frozen
+    let frames = videos
+        .iter()
+        .map(|video| extract_frame(&video.path))
+        .collect::<Result<Vec<_>>>()?;
+    let mut frozen = Vec::new();
+    for (video, frame) in videos.iter().zip(frames) {
+        // not through `SavedState::assign`, it's not meant to stick
+        if let Err(err) = ipc_handle.change_background(video.monitor, &frame).await {
+            // the ones frozen already go back to playing
+            for video in &videos[..frozen.len()] {
+                if let Err(err) = ipc_handle
+                    .change_background_scaled(video.monitor, &video.path, video.scaling)
+                    .await
+                {
+                    error!("Failed to put back {}: {:?}", video.path.display(), err);
+                }
+            }
+            return Err(err);
+        }
+        frozen.push(Assignment {
+            monitor: video.monitor,
+            path: frame,
+            scaling: ipc_handle.scaling(video.monitor),
+        });
+    }
+    Ok(Snapshot { frozen })
+}
@@ -0,0 +1,134 @@
+use anyhow::{Result, anyhow};
+use std::{path::PathBuf, sync::Arc};
+
+pub mod channel;
+pub mod daily;
+pub mod filter;
+pub mod gradient;
+pub mod health;
+pub mod library;
+pub mod local;
+pub mod online;
+pub mod playlist;
+pub mod sync;
+
+use crate::provenance::Provenance;
+use sync::SyncPolicy;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Location {
+    Local(PathBuf),
+    /// Needs to be fetched before it can be applied
+    Remote(String),
+    /// Rendered by the source when fetched
+    Generated(String),
+}
+
+/// A single wallpaper offered by a source
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Wallpaper {
+    pub name: String,
+    pub location: Location,
+    /// Set by sources that know more than a name, like the library, a channel
+    /// or wallhaven
+    pub tags: Vec<String>,
+    /// Recorded when downloaded, see `provenance`
+    pub provenance: Option<Provenance>,
+}
+
+/// Somewhere wallpapers come from, to add a new kind of source implement this
+/// in a new module and register it in `Registry::with_builtin`
+///
+/// NOTE: `list` and `fetch` may block on the disk or network, call them from a task
+pub trait WallpaperSource: Send + Sync {
+    /// Stable id, used to refer to the source from saved state
+    fn id(&self) -> &str;
+
+    /// Shown in the ui
+    fn name(&self) -> &str;
+
+    fn list(&self) -> Result<Vec<Wallpaper>>;
+
+    /// Where fetched wallpapers end up, only sources that download stuff have one
+    fn download_dir(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// Whether it needs the network, those are unavailable in offline mode
+    fn is_online(&self) -> bool {
+        self.download_dir().is_some()
+    }
+
+    /// Sources with a policy are synced in the background
+    fn default_sync_policy(&self) -> Option<SyncPolicy> {
+        None
+    }
+
+    /// Sources whose contents change on their own are listed again when
+    /// sources are synced, so automatic picks see what was added
+    fn follows_changes(&self) -> bool {
+        false
+    }
+
+    /// Makes sure the wallpaper exists locally and returns its path
+    fn fetch(&self, wallpaper: &Wallpaper) -> Result<PathBuf> {
+        match &wallpaper.location {
+            Location::Local(path) => Ok(path.clone()),
+            Location::Remote(what) | Location::Generated(what) => {
+                Err(anyhow!("{} doesn't know how to fetch {what}", self.name()))
+            }
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct Registry {
+    sources: Vec<Arc<dyn WallpaperSource>>,
+}
+
+impl std::fmt::Debug for Registry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.sources.iter().map(|source| source.id()))
+            .finish()
+    }
+}
+
+impl Registry {
+    pub fn with_builtin() -> Self {
+        let mut registry = Self::default();
+        registry.register(library::LibrarySource);
+        registry.register(local::LocalDirectory::pictures());
+        registry.register(online::Wallhaven::default());
+        registry.register(daily::BingDaily);
+        registry.register(gradient::Gradients);
+        registry
+    }
+
+    /// Builtin sources plus a channel per directory
+    pub fn with_channels(dirs: &[PathBuf]) -> Self {
+        let mut registry = Self::with_builtin();
+        for dir in dirs {
+            registry.register(channel::Channel::new(dir.clone()));
+        }
+        registry
+    }
+
+    /// Replaces any source with the same id
+    pub fn register(&mut self, source: impl WallpaperSource + 'static) {
+        self.sources.retain(|s| s.id() != source.id());
+        self.sources.push(Arc::new(filter::Filtered(source)));
+    }
+
+    pub fn unregister(&mut self, id: &str) {
+        self.sources.retain(|s| s.id() != id);
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<dyn WallpaperSource>> {
+        self.sources.iter().find(|s| s.id() == id).cloned()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn WallpaperSource>> {
+        self.sources.iter()
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+use super::{Location, Wallpaper, WallpaperSource, local::is_wallpaper};
+
+/// A whole directory tree, every subfolder a wallpaper is in becomes one of
+/// its tags. Listed again every now and then so new files get picked up
+pub struct Channel {
+    id: String,
+    name: String,
+    pub dir: PathBuf,
+}
+
+impl Channel {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            id: format!("channel:{}", dir.display()),
+            name: format!(
+                "{} (channel)",
+                dir.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| dir.display().to_string())
+            ),
+            dir,
+        }
+    }
+}
+
+/// Hidden folders are skipped and symlinked folders aren't followed, so a
+/// link back up the tree can't loop forever
+fn walk(dir: &Path, tags: &[String], wallpapers: &mut Vec<Wallpaper>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            let mut tags = tags.to_vec();
+            tags.push(name);
+            // one unreadable folder shouldn't hide the rest of the channel
+            if let Err(err) = walk(&path, &tags, wallpapers) {
+                warn!("Skipping {}: {:?}", path.display(), err);
+            }
+        } else if is_wallpaper(&path) {
+            wallpapers.push(Wallpaper {
+                name: tags
+                    .iter()
+                    .chain([&name])
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                location: Location::Local(path),
+                tags: tags.to_vec(),
+                provenance: None,
+            });
+        }
+    }
+    Ok(())
+}
+
+impl WallpaperSource for Channel {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn list(&self) -> Result<Vec<Wallpaper>> {
+        let mut wallpapers = Vec::new();
+        walk(&self.dir, &[], &mut wallpapers)?;
+        wallpapers.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(wallpapers)
+    }
+
+    fn follows_changes(&self) -> bool {
+        true
+    }
+}
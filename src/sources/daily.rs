@@ -0,0 +1,115 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use std::path::PathBuf;
+use tracing::warn;
+
+use super::{Location, Wallpaper, WallpaperSource, sync::SyncPolicy};
+use crate::{
+    net,
+    provenance::{self, Provenance},
+    xdg,
+};
+
+const ARCHIVE_URL: &str = "https://www.bing.com/HPImageArchive.aspx?format=js&idx=0&n=8";
+
+#[derive(Deserialize)]
+struct ArchiveReply {
+    images: Vec<ArchiveImage>,
+}
+
+#[derive(Deserialize)]
+struct ArchiveImage {
+    url: String,
+    startdate: String,
+    title: String,
+    /// Like "Some place (© Photographer/Agency)"
+    #[serde(default)]
+    copyright: String,
+    #[serde(default)]
+    copyrightlink: String,
+}
+
+/// The photographer out of the copyright notice
+fn author(copyright: &str) -> Option<String> {
+    let (_, author) = copyright.rsplit_once('©')?;
+    let author = author.trim().trim_end_matches(')').trim();
+    (!author.is_empty()).then(|| author.to_owned())
+}
+
+/// Bing's image of the day (and the week before it)
+pub struct BingDaily;
+
+impl BingDaily {
+    fn dir() -> PathBuf {
+        xdg::cache_dir().join("sources").join("bing-daily")
+    }
+}
+
+impl WallpaperSource for BingDaily {
+    fn id(&self) -> &str {
+        "bing-daily"
+    }
+
+    fn name(&self) -> &str {
+        "Bing image of the day"
+    }
+
+    fn download_dir(&self) -> Option<PathBuf> {
+        Some(Self::dir())
+    }
+
+    fn default_sync_policy(&self) -> Option<SyncPolicy> {
+        Some(SyncPolicy {
+            interval_hours: 24,
+            max_mb_per_day: 50,
+            max_items: 14,
+            max_age_days: 0,
+        })
+    }
+
+    fn list(&self) -> Result<Vec<Wallpaper>> {
+        let body = net::get(self.id(), ARCHIVE_URL)?;
+        let reply: ArchiveReply =
+            serde_json::from_slice(&body).with_context(|| "Failed to parse bing reply")?;
+
+        Ok(reply
+            .images
+            .into_iter()
+            .map(|image| Wallpaper {
+                name: format!("{} - {}", image.startdate, image.title),
+                location: Location::Remote(format!("https://www.bing.com{}", image.url)),
+                tags: Vec::new(),
+                provenance: Some(Provenance {
+                    author: author(&image.copyright),
+                    origin: match image.copyrightlink.is_empty() {
+                        true => "https://www.bing.com".to_owned(),
+                        false => image.copyrightlink,
+                    },
+                    license: Some("all rights reserved".to_owned()),
+                }),
+            })
+            .collect())
+    }
+
+    fn fetch(&self, wallpaper: &Wallpaper) -> Result<PathBuf> {
+        let Location::Remote(url) = &wallpaper.location else {
+            return Err(anyhow!("Not a bing wallpaper: {:?}", wallpaper.location));
+        };
+        // names start with the date, which is unique per image
+        let date = wallpaper.name.split(' ').next().unwrap_or_default();
+
+        let dest = Self::dir().join(format!("{date}.jpg"));
+        if !dest.exists() {
+            net::download(self.id(), url, &dest)?;
+            // the wallpaper is there either way, it just won't be credited
+            if let Some(Err(err)) = wallpaper
+                .provenance
+                .as_ref()
+                .map(|provenance| provenance::record(&dest, provenance))
+            {
+                warn!("{:?}", err);
+            }
+        }
+        Ok(dest)
+    }
+}
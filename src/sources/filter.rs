@@ -0,0 +1,109 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+};
+
+use super::{Wallpaper, WallpaperSource, sync::SyncPolicy};
+
+/// Tags sources put on what isn't safe for work
+const UNSAFE_TAGS: &[&str] = &["sketchy", "nsfw"];
+
+/// What a source may list, set per source in the config
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentFilter {
+    /// Drops whatever the source tags as sketchy or nsfw
+    #[serde(default)]
+    pub sfw_only: bool,
+    /// Tags and keywords, matched against tags and names ignoring case
+    #[serde(default)]
+    pub blocked: Vec<String>,
+}
+
+impl ContentFilter {
+    pub fn is_empty(&self) -> bool {
+        !self.sfw_only && self.blocked.is_empty()
+    }
+
+    pub fn allows(&self, wallpaper: &Wallpaper) -> bool {
+        let tags: Vec<String> = wallpaper.tags.iter().map(|t| t.to_lowercase()).collect();
+        if self.sfw_only && tags.iter().any(|tag| UNSAFE_TAGS.contains(&tag.as_str())) {
+            return false;
+        }
+        let name = wallpaper.name.to_lowercase();
+        !self.blocked.iter().any(|blocked| {
+            let blocked = blocked.to_lowercase();
+            name.contains(&blocked) || tags.contains(&blocked)
+        })
+    }
+
+    /// `blocked` as the settings show it
+    pub fn blocked_input(&self) -> String {
+        self.blocked.join(", ")
+    }
+
+    pub fn parse_blocked(input: &str) -> Vec<String> {
+        input
+            .split(',')
+            .map(str::trim)
+            .filter(|blocked| !blocked.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+}
+
+/// By source id
+pub type ContentFilters = BTreeMap<String, ContentFilter>;
+
+static CURRENT: Mutex<ContentFilters> = Mutex::new(BTreeMap::new());
+
+fn lock() -> MutexGuard<'static, ContentFilters> {
+    CURRENT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Whatever the config says, set when it's loaded or saved
+pub fn set(filters: ContentFilters) {
+    *lock() = filters;
+}
+
+/// Every registered source is wrapped in this, so nothing a filter drops
+/// shows up in the ui or gets synced and applied in the background
+pub struct Filtered<S>(pub S);
+
+impl<S: WallpaperSource> WallpaperSource for Filtered<S> {
+    fn id(&self) -> &str {
+        self.0.id()
+    }
+
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn list(&self) -> Result<Vec<Wallpaper>> {
+        let mut wallpapers = self.0.list()?;
+        if let Some(filter) = lock().get(self.id()) {
+            wallpapers.retain(|wallpaper| filter.allows(wallpaper));
+        }
+        Ok(wallpapers)
+    }
+
+    fn download_dir(&self) -> Option<PathBuf> {
+        self.0.download_dir()
+    }
+
+    fn default_sync_policy(&self) -> Option<SyncPolicy> {
+        self.0.default_sync_policy()
+    }
+
+    fn follows_changes(&self) -> bool {
+        self.0.follows_changes()
+    }
+
+    fn fetch(&self, wallpaper: &Wallpaper) -> Result<PathBuf> {
+        self.0.fetch(wallpaper)
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::{Context, Result, anyhow};
+use image::{ImageBuffer, Rgb};
+use std::{fs, path::PathBuf};
+
+use super::{Location, Wallpaper, WallpaperSource};
+use crate::{disk_cache, xdg};
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+
+/// Plain gray, used by presentation mode
+pub const NEUTRAL: &str = "Neutral";
+
+/// name, top color, bottom color
+const PRESETS: &[(&str, [u8; 3], [u8; 3])] = &[
+    (NEUTRAL, [0x3c, 0x3c, 0x3c], [0x3c, 0x3c, 0x3c]),
+    ("Sunset", [0xff, 0x7e, 0x5f], [0x2b, 0x19, 0x3d]),
+    ("Ocean", [0x00, 0x9f, 0xfd], [0x0a, 0x1a, 0x3a]),
+    ("Forest", [0x5a, 0xa4, 0x69], [0x0f, 0x2a, 0x1d]),
+    ("Night", [0x2c, 0x3e, 0x50], [0x00, 0x00, 0x00]),
+    ("Peach", [0xff, 0xd1, 0xb3], [0xf6, 0x8a, 0x8a]),
+];
+
+/// Vertical two color gradients rendered locally
+pub struct Gradients;
+
+/// Linearly interpolates between `top` and `bottom`, `t` is 0..=1
+pub fn lerp_color(top: [u8; 3], bottom: [u8; 3], t: f32) -> Rgb<u8> {
+    Rgb(std::array::from_fn(|i| {
+        (top[i] as f32 + (bottom[i] as f32 - top[i] as f32) * t).round() as u8
+    }))
+}
+
+impl WallpaperSource for Gradients {
+    fn id(&self) -> &str {
+        "gradients"
+    }
+
+    fn name(&self) -> &str {
+        "Gradients"
+    }
+
+    fn list(&self) -> Result<Vec<Wallpaper>> {
+        Ok(PRESETS
+            .iter()
+            .map(|(name, _, _)| Wallpaper {
+                name: name.to_string(),
+                location: Location::Generated(name.to_string()),
+                tags: Vec::new(),
+                provenance: None,
+            })
+            .collect())
+    }
+
+    fn fetch(&self, wallpaper: &Wallpaper) -> Result<PathBuf> {
+        let Location::Generated(name) = &wallpaper.location else {
+            return Err(anyhow!("Not a gradient: {:?}", wallpaper.location));
+        };
+        let (_, top, bottom) = PRESETS
+            .iter()
+            .find(|(preset, _, _)| preset == name)
+            .ok_or_else(|| anyhow!("Unknown gradient: {name}"))?;
+
+        render(&name.to_lowercase(), *top, *bottom)
+    }
+}
+
+/// A single color, what a cleared monitor shows when xab can't clear one
+pub fn solid(color: [u8; 3]) -> Result<PathBuf> {
+    render(
+        &format!("solid-{:02x}{:02x}{:02x}", color[0], color[1], color[2]),
+        color,
+        color,
+    )
+}
+
+/// Renders a gradient into the cache as `<name>-<size>.png`, once
+pub fn render(name: &str, top: [u8; 3], bottom: [u8; 3]) -> Result<PathBuf> {
+    let dir = xdg::cache_dir().join("sources").join("gradients");
+    let dest = dir.join(format!("{name}-{WIDTH}x{HEIGHT}.png"));
+    if disk_cache::hit(&dest) {
+        return Ok(dest);
+    }
+
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    ImageBuffer::from_fn(WIDTH, HEIGHT, |_, y| {
+        lerp_color(top, bottom, y as f32 / (HEIGHT - 1) as f32)
+    })
+    .save(&dest)
+    .with_context(|| format!("Failed to save gradient to {}", dest.display()))?;
+    Ok(dest)
+}
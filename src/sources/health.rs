@@ -0,0 +1,79 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::sync::SourceSync;
+
+/// Outcome of the last time a source was listed
+#[derive(Debug, Clone)]
+pub struct Scan {
+    pub at: SystemTime,
+    /// Amount of wallpapers or why listing failed
+    pub items: Result<usize, String>,
+}
+
+/// `3 min ago`, `2 h ago`, ...
+fn ago(then: SystemTime, now: SystemTime) -> String {
+    let secs = now.duration_since(then).unwrap_or_default().as_secs();
+    match secs {
+        0..60 => "just now".to_owned(),
+        60..3600 => format!("{} min ago", secs / 60),
+        3600..86400 => format!("{} h ago", secs / 3600),
+        _ => format!("{} days ago", secs / 86400),
+    }
+}
+
+/// What the sources card says about a source
+#[derive(Debug, Clone, Default)]
+pub struct SourceHealth {
+    pub status: String,
+    /// Why the source looks empty (or stale), None if it's fine
+    pub problem: Option<String>,
+}
+
+impl SourceHealth {
+    pub fn new(scan: Option<&Scan>, sync: Option<&SourceSync>, now: SystemTime) -> Self {
+        let mut status = Vec::new();
+        let mut problems = Vec::new();
+        match scan {
+            Some(Scan { at, items: Ok(0) }) => {
+                status.push(format!("empty, scanned {}", ago(*at, now)));
+            }
+            Some(Scan {
+                at,
+                items: Ok(items),
+            }) => {
+                status.push(format!("{items} wallpaper(s), scanned {}", ago(*at, now)));
+            }
+            Some(Scan {
+                items: Err(err), ..
+            }) => problems.push(err.clone()),
+            None => status.push("not scanned yet".to_owned()),
+        }
+
+        if let Some(sync) = sync {
+            match sync.ledger.last_sync {
+                Some(last_sync) => status.push(format!(
+                    "synced {}",
+                    ago(UNIX_EPOCH + std::time::Duration::from_secs(last_sync), now)
+                )),
+                None => status.push("never synced".to_owned()),
+            }
+            if sync.ledger.paused {
+                status.push("paused on a metered connection".to_owned());
+            }
+            if let Some(err) = &sync.ledger.last_error {
+                problems.push(format!("last sync failed: {err}"));
+            }
+            if sync.ledger.quota_exhausted(&sync.policy, now) {
+                problems.push(format!(
+                    "daily download quota ({} MB) used up",
+                    sync.policy.max_mb_per_day
+                ));
+            }
+        }
+
+        Self {
+            status: status.join(", "),
+            problem: (!problems.is_empty()).then(|| problems.join("\n")),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+
+use super::{Location, Wallpaper, WallpaperSource};
+use crate::library::Library;
+
+/// Everything imported into the library
+pub struct LibrarySource;
+
+impl WallpaperSource for LibrarySource {
+    fn id(&self) -> &str {
+        "library"
+    }
+
+    fn name(&self) -> &str {
+        "Library"
+    }
+
+    fn list(&self) -> Result<Vec<Wallpaper>> {
+        Ok(Library::load()?
+            .entries
+            .into_iter()
+            .map(|entry| Wallpaper {
+                name: match entry.pack {
+                    Some(pack) => format!("{} ({pack})", entry.name),
+                    None => entry.name,
+                },
+                location: Location::Local(entry.path),
+                tags: entry.tags,
+                provenance: None,
+            })
+            .collect())
+    }
+}
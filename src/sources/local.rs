@@ -0,0 +1,74 @@
+use anyhow::{Context, Result};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use super::{Location, Wallpaper, WallpaperSource};
+
+/// Extensions xab can (probably) render
+pub const WALLPAPER_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "webp", "bmp", "gif", "mp4", "mkv", "webm", "mov", "avi",
+];
+
+/// By the extension, the file isn't looked at
+pub fn is_wallpaper(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| WALLPAPER_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Wallpapers in a directory on disk (not recursive)
+pub struct LocalDirectory {
+    id: String,
+    name: String,
+    pub dir: PathBuf,
+}
+
+impl LocalDirectory {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            id: format!("local:{}", dir.display()),
+            name: dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| dir.display().to_string()),
+            dir,
+        }
+    }
+
+    /// `~/Pictures`
+    pub fn pictures() -> Self {
+        Self::new(PathBuf::from(env::var_os("HOME").unwrap_or_default()).join("Pictures"))
+    }
+}
+
+impl WallpaperSource for LocalDirectory {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn list(&self) -> Result<Vec<Wallpaper>> {
+        let mut wallpapers: Vec<Wallpaper> = fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read {}", self.dir.display()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| is_wallpaper(path))
+            .map(|path| Wallpaper {
+                name: path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                location: Location::Local(path),
+                tags: Vec::new(),
+                provenance: None,
+            })
+            .collect();
+        wallpapers.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(wallpapers)
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use std::path::PathBuf;
+use tracing::warn;
+
+use super::{Location, Wallpaper, WallpaperSource, sync::SyncPolicy};
+use crate::{
+    net,
+    provenance::{self, Provenance},
+    xdg,
+};
+
+const SEARCH_URL: &str = "https://wallhaven.cc/api/v1/search";
+
+#[derive(Deserialize)]
+struct SearchReply {
+    data: Vec<SearchItem>,
+}
+
+#[derive(Deserialize)]
+struct SearchItem {
+    id: String,
+    /// The wallpaper's page on wallhaven
+    url: String,
+    path: String,
+    resolution: String,
+    /// general, anime or people
+    category: String,
+    /// sfw, sketchy or nsfw
+    purity: String,
+}
+
+/// SFW toplist from wallhaven.cc
+pub struct Wallhaven {
+    /// Appended to the search url, see https://wallhaven.cc/help/api
+    pub query: String,
+}
+
+impl Default for Wallhaven {
+    fn default() -> Self {
+        Self {
+            query: "sorting=toplist&categories=100&purity=100".to_owned(),
+        }
+    }
+}
+
+impl Wallhaven {
+    fn dir() -> PathBuf {
+        xdg::cache_dir().join("sources").join("wallhaven")
+    }
+}
+
+impl WallpaperSource for Wallhaven {
+    fn id(&self) -> &str {
+        "wallhaven"
+    }
+
+    fn name(&self) -> &str {
+        "Wallhaven"
+    }
+
+    fn download_dir(&self) -> Option<PathBuf> {
+        Some(Self::dir())
+    }
+
+    fn default_sync_policy(&self) -> Option<SyncPolicy> {
+        Some(SyncPolicy {
+            interval_hours: 24,
+            max_mb_per_day: 200,
+            max_items: 50,
+            max_age_days: 30,
+        })
+    }
+
+    fn list(&self) -> Result<Vec<Wallpaper>> {
+        let body = net::get(self.id(), &format!("{SEARCH_URL}?{}", self.query))?;
+        let reply: SearchReply =
+            serde_json::from_slice(&body).with_context(|| "Failed to parse wallhaven reply")?;
+
+        Ok(reply
+            .data
+            .into_iter()
+            .map(|item| Wallpaper {
+                name: format!("{} ({})", item.id, item.resolution),
+                location: Location::Remote(item.path),
+                tags: vec![item.category, item.purity],
+                // the search api leaves out uploaders, licenses are up to them
+                provenance: Some(Provenance {
+                    origin: item.url,
+                    author: None,
+                    license: None,
+                }),
+            })
+            .collect())
+    }
+
+    fn fetch(&self, wallpaper: &Wallpaper) -> Result<PathBuf> {
+        let Location::Remote(url) = &wallpaper.location else {
+            return Err(anyhow!(
+                "Not a wallhaven wallpaper: {:?}",
+                wallpaper.location
+            ));
+        };
+        let file_name = url
+            .rsplit('/')
+            .next()
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| anyhow!("Weird wallhaven url: {url}"))?;
+
+        let dest = Self::dir().join(file_name);
+        if !dest.exists() {
+            net::download(self.id(), url, &dest)?;
+            // the wallpaper is there either way, it just won't be credited
+            if let Some(Err(err)) = wallpaper
+                .provenance
+                .as_ref()
+                .map(|provenance| provenance::record(&dest, provenance))
+            {
+                warn!("{:?}", err);
+            }
+        }
+        Ok(dest)
+    }
+}
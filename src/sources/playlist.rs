@@ -0,0 +1,59 @@
+use anyhow::{Result, anyhow};
+
+use super::{Location, Wallpaper, WallpaperSource};
+use crate::playlists::Playlists;
+
+/// A playlist, so it can be rotated through and previewed like any source
+pub struct PlaylistSource {
+    id: String,
+    name: String,
+    playlist: String,
+}
+
+impl PlaylistSource {
+    pub fn new(playlist: &str) -> Self {
+        Self {
+            id: Self::id_of(playlist),
+            name: format!("{playlist} (playlist)"),
+            playlist: playlist.to_owned(),
+        }
+    }
+
+    pub fn id_of(playlist: &str) -> String {
+        format!("playlist:{playlist}")
+    }
+}
+
+impl WallpaperSource for PlaylistSource {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn list(&self) -> Result<Vec<Wallpaper>> {
+        let playlists = Playlists::load()?;
+        let playlist = playlists
+            .get(&self.playlist)
+            .ok_or_else(|| anyhow!("The {} playlist is gone", self.playlist))?;
+        Ok(playlist
+            .files
+            .iter()
+            .map(|path| Wallpaper {
+                name: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.display().to_string()),
+                location: Location::Local(path.clone()),
+                tags: Vec::new(),
+                provenance: None,
+            })
+            .collect())
+    }
+
+    fn follows_changes(&self) -> bool {
+        true
+    }
+}
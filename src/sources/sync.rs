@@ -0,0 +1,288 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, warn};
+
+use super::{Registry, WallpaperSource};
+use crate::{limits, net, persist};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// How often and how much an online source is allowed to download
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncPolicy {
+    pub interval_hours: u64,
+    pub max_mb_per_day: u64,
+    /// Max amount of downloads kept on disk, older ones get evicted
+    pub max_items: usize,
+    /// Downloads older than this get evicted, 0 keeps them forever
+    pub max_age_days: u64,
+}
+
+impl SyncPolicy {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_hours * 60 * 60)
+    }
+}
+
+/// What a source already did, so quotas hold across restarts
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SyncLedger {
+    /// Unix timestamps
+    pub last_sync: Option<u64>,
+    /// Days since the unix epoch that `downloaded_today` counts
+    pub quota_day: u64,
+    pub downloaded_today: u64,
+    pub last_error: Option<String>,
+    /// The last sync stopped for a metered connection, it goes on (partial
+    /// downloads included) once the connection isn't
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// Per-source sync settings and bookkeeping
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceSync {
+    pub policy: SyncPolicy,
+    #[serde(default)]
+    pub ledger: SyncLedger,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SyncReport {
+    pub downloaded: usize,
+    pub downloaded_bytes: u64,
+    pub evicted: usize,
+    pub quota_hit: bool,
+    pub paused: bool,
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl SyncLedger {
+    pub fn is_due(&self, policy: &SyncPolicy, now: SystemTime) -> bool {
+        match self.last_sync {
+            Some(last_sync) => {
+                unix_secs(now).saturating_sub(last_sync) >= policy.interval().as_secs()
+            }
+            None => true,
+        }
+    }
+
+    /// Whether today's downloads already used up the daily quota
+    pub fn quota_exhausted(&self, policy: &SyncPolicy, now: SystemTime) -> bool {
+        self.quota_day == unix_secs(now) / SECS_PER_DAY
+            && self.downloaded_today >= policy.max_mb_per_day * 1024 * 1024
+    }
+
+    fn roll_day(&mut self, now: SystemTime) {
+        let today = unix_secs(now) / SECS_PER_DAY;
+        if self.quota_day != today {
+            self.quota_day = today;
+            self.downloaded_today = 0;
+        }
+    }
+}
+
+fn files_in(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                // half finished downloads aren't ours to evict
+                .filter(|path| path.is_file() && !net::is_partial(path))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Downloads new wallpapers of `source` within the quota, then evicts old ones
+pub fn sync(
+    source: &dyn WallpaperSource,
+    sync: &mut SourceSync,
+    keep: &HashSet<PathBuf>,
+    now: SystemTime,
+) -> Result<SyncReport> {
+    let dir = source
+        .download_dir()
+        .ok_or_else(|| anyhow!("{} doesn't download anything", source.name()))?;
+    let policy = &sync.policy;
+    let ledger = &mut sync.ledger;
+    ledger.roll_day(now);
+
+    let mut report = SyncReport::default();
+    let existing: HashSet<PathBuf> = files_in(&dir).into_iter().collect();
+    let wallpapers: Vec<_> = source.list()?.into_iter().take(policy.max_items).collect();
+    // the quota is only checked between batches, so it can be overshot by a
+    // batch's worth of downloads
+    for batch in wallpapers.chunks(limits::current().downloads()) {
+        if net::should_pause_syncs() {
+            warn!(
+                "Pausing the sync of {}, the connection is metered",
+                source.name()
+            );
+            report.paused = true;
+            break;
+        }
+        if ledger.downloaded_today >= policy.max_mb_per_day * 1024 * 1024 {
+            warn!("{} hit its daily download quota", source.name());
+            report.quota_hit = true;
+            break;
+        }
+
+        let fetched: Vec<Result<PathBuf>> = thread::scope(|scope| {
+            batch
+                .iter()
+                .map(|wallpaper| scope.spawn(|| source.fetch(wallpaper)))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err(anyhow!("Fetching a wallpaper panicked")))
+                })
+                .collect()
+        });
+        for path in fetched {
+            let path = path?;
+            if !existing.contains(&path) {
+                let size = fs::metadata(&path).map(|m| m.len()).unwrap_or_default();
+                ledger.downloaded_today += size;
+                report.downloaded += 1;
+                report.downloaded_bytes += size;
+            }
+        }
+    }
+    // paused syncs stay due, so they're picked up as soon as they can
+    if !report.paused {
+        ledger.last_sync = Some(unix_secs(now));
+    }
+    ledger.paused = report.paused;
+
+    report.evicted = cleanup(&dir, policy, keep, now)?;
+    debug!("Synced {}: {:?}", source.name(), report);
+    Ok(report)
+}
+
+/// Evicts downloads beyond `max_items` (oldest first) or older than
+/// `max_age_days`, never touching anything in `keep`
+pub fn cleanup(
+    dir: &Path,
+    policy: &SyncPolicy,
+    keep: &HashSet<PathBuf>,
+    now: SystemTime,
+) -> Result<usize> {
+    let mut files: Vec<(PathBuf, SystemTime)> = files_in(dir)
+        .into_iter()
+        .map(|path| {
+            let modified = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .unwrap_or(UNIX_EPOCH);
+            (path, modified)
+        })
+        .collect();
+    // newest first
+    files.sort_by_key(|(_, modified)| Reverse(*modified));
+
+    let max_age = Duration::from_secs(policy.max_age_days * SECS_PER_DAY);
+    let mut evicted = 0;
+    for (i, (path, modified)) in files.iter().enumerate() {
+        let too_many = i >= policy.max_items;
+        let too_old =
+            policy.max_age_days != 0 && now.duration_since(*modified).unwrap_or_default() > max_age;
+        if (too_many || too_old) && !keep.contains(path) {
+            debug!("Evicting {}", path.display());
+            fs::remove_file(path).with_context(|| format!("Failed to evict {}", path.display()))?;
+            evicted += 1;
+        }
+    }
+    Ok(evicted)
+}
+
+/// Syncs every source that's due and saves the updated ledgers
+pub fn sync_due(registry: &Registry) -> Result<Vec<(String, SyncReport)>> {
+    let now = SystemTime::now();
+    sync_matching(registry, now, |_, sync| {
+        sync.ledger.is_due(&sync.policy, now)
+    })
+}
+
+/// Syncs `id` now, due or not (e.g. to retry after an error)
+pub fn sync_now(registry: &Registry, id: &str) -> Result<Vec<(String, SyncReport)>> {
+    sync_matching(registry, SystemTime::now(), |source, _| source.id() == id)
+}
+
+fn sync_matching(
+    registry: &Registry,
+    now: SystemTime,
+    should_sync: impl Fn(&dyn WallpaperSource, &SourceSync) -> bool,
+) -> Result<Vec<(String, SyncReport)>> {
+    // not even an attempt, so everything's due once offline mode is off
+    if net::is_offline() {
+        debug!("Not syncing, offline mode is on");
+        return Ok(Vec::new());
+    }
+    let mut state = persist::state()?;
+    // don't pull the rug from under the current backgrounds
+    let keep: HashSet<PathBuf> = state.assignments.iter().map(|a| a.path.clone()).collect();
+
+    let mut reports = Vec::new();
+    let mut synced = Vec::new();
+    for source in registry.iter() {
+        let Some(default_policy) = source.default_sync_policy() else {
+            continue;
+        };
+        let sync_state = state
+            .source_sync
+            .entry(source.id().to_owned())
+            .or_insert_with(|| SourceSync {
+                policy: default_policy,
+                ledger: SyncLedger::default(),
+            });
+        if !should_sync(source.as_ref(), sync_state) {
+            continue;
+        }
+        synced.push(source.id().to_owned());
+
+        match sync(source.as_ref(), sync_state, &keep, now) {
+            Ok(report) => {
+                sync_state.ledger.last_error = None;
+                reports.push((source.id().to_owned(), report));
+            }
+            Err(err) => {
+                warn!("Failed to sync {}: {:?}", source.name(), err);
+                // still counts as an attempt, otherwise an offline source gets hammered
+                sync_state.ledger.last_sync = Some(unix_secs(now));
+                sync_state.ledger.last_error = Some(format!("{:#}", err));
+            }
+        }
+    }
+
+    // only the ledgers of the sources synced here, other things (policies,
+    // another sync) might've changed in the meantime
+    persist::update(|saved| {
+        for id in synced {
+            let Some(sync_state) = state.source_sync.remove(&id) else {
+                continue;
+            };
+            match saved.source_sync.get_mut(&id) {
+                Some(saved) => saved.ledger = sync_state.ledger,
+                None => {
+                    saved.source_sync.insert(id, sync_state);
+                }
+            }
+        }
+    })?;
+    Ok(reports)
+}
@@ -0,0 +1,210 @@
+use anyhow::{Context, Result, anyhow};
+use image::imageops::FilterType;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+use crate::{disk_cache, ipc_spec::Monitor, limits, xdg};
+
+/// Pixels hidden behind the bezels between two monitors, keyed by their
+/// numbers like `"1-2"` (in either order)
+pub type Bezels = BTreeMap<String, u32>;
+
+fn bezel(bezels: &Bezels, a: i32, b: i32) -> u32 {
+    bezels
+        .iter()
+        .find(|(gap, _)| {
+            gap.split_once('-')
+                .and_then(|(first, second)| {
+                    Some((
+                        first.trim().parse::<i32>().ok()? - 1,
+                        second.trim().parse::<i32>().ok()? - 1,
+                    ))
+                })
+                .is_some_and(|gap| gap == (a, b) || gap == (b, a))
+        })
+        .map_or(0, |(_, width)| *width)
+}
+
+/// Where the monitors are once the bezels are counted in, by index
+fn spread(monitors: &[Monitor], bezels: &Bezels) -> Vec<(i32, u32, u32)> {
+    // a gap moves everything past its edge, gaps sharing an edge (like
+    // in a 2x2 grid) only once
+    let mut vertical_edges: HashMap<u32, u32> = HashMap::new();
+    let mut horizontal_edges: HashMap<u32, u32> = HashMap::new();
+    for a in monitors {
+        for b in monitors {
+            let width = bezel(bezels, a.index, b.index);
+            if width == 0 {
+                continue;
+            }
+            let overlaps_vertically = a.y < b.y + b.height && b.y < a.y + a.height;
+            let overlaps_horizontally = a.x < b.x + b.width && b.x < a.x + a.width;
+            if overlaps_vertically && a.x + a.width <= b.x {
+                let edge = vertical_edges.entry(b.x).or_default();
+                *edge = (*edge).max(width);
+            }
+            if overlaps_horizontally && a.y + a.height <= b.y {
+                let edge = horizontal_edges.entry(b.y).or_default();
+                *edge = (*edge).max(width);
+            }
+        }
+    }
+    let shift = |edges: &HashMap<u32, u32>, at: u32| -> u32 {
+        edges
+            .iter()
+            .filter(|(edge, _)| **edge <= at)
+            .map(|(_, width)| width)
+            .sum()
+    };
+    monitors
+        .iter()
+        .map(|monitor| {
+            (
+                monitor.index,
+                monitor.x + shift(&vertical_edges, monitor.x),
+                monitor.y + shift(&horizontal_edges, monitor.y),
+            )
+        })
+        .collect()
+}
+
+/// Cuts `path` into a piece per monitor as if it covered all of them, the
+/// parts behind the bezels left out. Pieces are cached
+pub fn render(monitors: &[Monitor], path: &Path, bezels: &Bezels) -> Result<Vec<(i32, PathBuf)>> {
+    if monitors.len() < 2 || monitors.iter().any(|m| m.width == 0 || m.height == 0) {
+        return Err(anyhow!(
+            "Spanning needs at least two monitors with a known layout"
+        ));
+    }
+
+    let spread = spread(monitors, bezels);
+    let left = spread.iter().map(|(_, x, _)| *x).min().unwrap_or_default();
+    let top = spread.iter().map(|(_, _, y)| *y).min().unwrap_or_default();
+    let sizes: HashMap<i32, (u32, u32)> = monitors
+        .iter()
+        .map(|monitor| (monitor.index, (monitor.width, monitor.height)))
+        .collect();
+    let (width, height) = spread
+        .iter()
+        .fold((0, 0), |(width, height), (index, x, y)| {
+            let (w, h) = sizes[index];
+            (width.max(x - left + w), height.max(y - top + h))
+        });
+
+    // the layout and the modification time too, so an edited wallpaper isn't served stale
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .hash(&mut hasher);
+    spread.hash(&mut hasher);
+    monitors
+        .iter()
+        .map(|monitor| (monitor.width, monitor.height))
+        .collect::<Vec<_>>()
+        .hash(&mut hasher);
+    let dir = xdg::cache_dir().join("span");
+    let prefix = format!("{:016x}", hasher.finish());
+    let pieces: Vec<(i32, PathBuf)> = spread
+        .iter()
+        .map(|(index, _, _)| (*index, dir.join(format!("{prefix}-{index}.png"))))
+        .collect();
+    if pieces.iter().all(|(_, piece)| disk_cache::hit(piece)) {
+        return Ok(pieces);
+    }
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let _decoding = limits::decode_permit();
+    let image = image::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?
+        .resize_to_fill(width, height, FilterType::Triangle);
+    for ((index, x, y), (_, piece)) in spread.iter().zip(&pieces) {
+        let (w, h) = sizes[index];
+        image
+            .crop_imm(x - left, y - top, w, h)
+            .save(piece)
+            .with_context(|| format!("Failed to save {}", piece.display()))?;
+    }
+    debug!(
+        "Spanned {} across {} monitors ({}x{})",
+        path.display(),
+        pieces.len(),
+        width,
+        height
+    );
+    Ok(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(index: i32, x: u32, y: u32) -> Monitor {
+        Monitor {
+            index,
+            x,
+            y,
+            width: 1920,
+            height: 1080,
+            ..Monitor::fullscreen()
+        }
+    }
+
+    fn bezels(gaps: &[(&str, u32)]) -> Bezels {
+        gaps.iter()
+            .map(|(gap, width)| (gap.to_string(), *width))
+            .collect()
+    }
+
+    #[test]
+    fn bezels_go_by_monitor_number_either_way() {
+        let bezels = bezels(&[("1-2", 40), (" 3 - 2 ", 10), ("2-x", 5)]);
+        assert_eq!(bezel(&bezels, 0, 1), 40);
+        assert_eq!(bezel(&bezels, 1, 0), 40);
+        assert_eq!(bezel(&bezels, 1, 2), 10);
+        assert_eq!(bezel(&bezels, 0, 2), 0);
+    }
+
+    #[test]
+    fn bezels_push_the_monitors_past_them() {
+        let side_by_side = [monitor(0, 0, 0), monitor(1, 1920, 0)];
+        assert_eq!(
+            spread(&side_by_side, &Bezels::new()),
+            [(0, 0, 0), (1, 1920, 0)]
+        );
+        assert_eq!(
+            spread(&side_by_side, &bezels(&[("1-2", 40)])),
+            [(0, 0, 0), (1, 1960, 0)]
+        );
+
+        // the two gaps on the middle edge of a 2x2 grid count once
+        let grid = [
+            monitor(0, 0, 0),
+            monitor(1, 1920, 0),
+            monitor(2, 0, 1080),
+            monitor(3, 1920, 1080),
+        ];
+        let bezels = bezels(&[("1-2", 40), ("3-4", 30), ("1-3", 20)]);
+        assert_eq!(
+            spread(&grid, &bezels),
+            [(0, 0, 0), (1, 1960, 0), (2, 0, 1100), (3, 1960, 1100)]
+        );
+    }
+
+    #[test]
+    fn spanning_needs_a_layout() {
+        let path = Path::new("wallpaper.png");
+        assert!(render(&[monitor(0, 0, 0)], path, &Bezels::new()).is_err());
+        let unknown = Monitor {
+            width: 0,
+            ..monitor(1, 1920, 0)
+        };
+        assert!(render(&[monitor(0, 0, 0), unknown], path, &Bezels::new()).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+use crate::{
+    config::StartPage, ipc_spec::ScalingMode, sources::sync::SourceSync, trash::Trashed, xdg,
+};
+
+/// Bumped whenever the layout of `SavedState` changes in a way old versions
+/// can't read, files without a version are from before versioning (0)
+pub const STATE_VERSION: u32 = 1;
+/// Wallpapers remembered per monitor, see `SavedState::recent`
+pub const HISTORY_LEN: usize = 12;
+
+/// Writes to a temporary file next to `path` and renames it over `path`, so
+/// a crash leaves either the old or the new file but never half of one
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let tmp = dir.join(format!(
+        ".{}.tmp-{}",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        std::process::id()
+    ));
+    let write = || -> Result<()> {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        // make the rename itself durable
+        File::open(dir)?.sync_all()?;
+        Ok(())
+    };
+    write().inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+/// A background that was applied to a monitor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assignment {
+    /// `None` means the background wasn't applied to a specific monitor
+    pub monitor: Option<i32>,
+    #[serde(with = "crate::os_path")]
+    pub path: PathBuf,
+    /// Fill for the ones saved before it was remembered
+    #[serde(default)]
+    pub scaling: ScalingMode,
+}
+
+/// Monitors whose wallpaper automatic changes (hotkeys, schedules, ...) leave
+/// alone, -1 pins every monitor
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Pins(BTreeSet<i32>);
+
+impl Pins {
+    /// Whether changing the background of `monitor` would replace a pinned one
+    pub fn is_pinned(&self, monitor: Option<i32>) -> bool {
+        match monitor {
+            // every monitor would be changed
+            None => !self.0.is_empty(),
+            Some(monitor) => self.0.contains(&monitor) || self.0.contains(&-1),
+        }
+    }
+
+    pub fn set(&mut self, monitor: Option<i32>, pinned: bool) {
+        let monitor = monitor.unwrap_or(-1);
+        if pinned {
+            self.0.insert(monitor);
+        } else if monitor == -1 {
+            // unpinning "all monitors" unpins every one of them
+            self.0.clear();
+        } else {
+            self.0.remove(&monitor);
+        }
+    }
+}
+
+/// Stuff that should survive a restart of the gui (or of xab), unlike `Config`
+/// this changes all the time without the user asking for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedState {
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub assignments: Vec<Assignment>,
+    /// Keyed by source id
+    #[serde(default)]
+    pub source_sync: BTreeMap<String, SourceSync>,
+    #[serde(default)]
+    pub pinned: Pins,
+    /// Shown under "Recently deleted"
+    #[serde(default)]
+    pub trashed: Vec<Trashed>,
+    /// Every assignment, oldest first, see `HISTORY_LEN`
+    #[serde(default)]
+    pub history: Vec<Assignment>,
+    /// Monitors or library, whichever was open last
+    #[serde(default)]
+    pub last_page: StartPage,
+}
+
+impl Default for SavedState {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            assignments: Vec::new(),
+            source_sync: BTreeMap::new(),
+            pinned: Pins::default(),
+            trashed: Vec::new(),
+            history: Vec::new(),
+            last_page: StartPage::default(),
+        }
+    }
+}
+
+impl SavedState {
+    pub fn file_path() -> PathBuf {
+        xdg::state_dir().join("state.toml")
+    }
+
+    /// Loads the saved state, a missing file is treated as an empty state
+    pub fn load() -> Result<Self> {
+        let path = Self::file_path();
+        if !path.exists() {
+            debug!("No saved state at {}", path.display());
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read saved state at {}", path.display()))?;
+        let mut state: Self = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse saved state at {}", path.display()))?;
+        state.migrate()?;
+        Ok(state)
+    }
+
+    fn migrate(&mut self) -> Result<()> {
+        if self.version > STATE_VERSION {
+            return Err(anyhow!(
+                "Saved state is from a newer xab-gui (version {}, this one understands {})",
+                self.version,
+                STATE_VERSION
+            ));
+        }
+        if self.version < STATE_VERSION {
+            // 0 -> 1: only the version field was added
+            debug!(
+                "Migrating saved state from version {} to {}",
+                self.version, STATE_VERSION
+            );
+            self.version = STATE_VERSION;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::file_path();
+        write_atomic(&path, toml::to_string_pretty(self)?.as_bytes())
+            .with_context(|| format!("Failed to write saved state to {}", path.display()))?;
+        debug!("Saved state to {}", path.display());
+        Ok(())
+    }
+
+    /// Remembers `path` as the background of `monitor`, replacing the previous one
+    pub fn assign(&mut self, monitor: Option<i32>, path: PathBuf, scaling: ScalingMode) {
+        match monitor {
+            // a background for every monitor overrides all of the per-monitor ones
+            None => self.assignments.clear(),
+            Some(_) => self.assignments.retain(|a| a.monitor != monitor),
+        }
+        self.history
+            .retain(|a| a.monitor != monitor || a.path != path);
+        self.history.push(Assignment {
+            monitor,
+            path: path.clone(),
+            scaling,
+        });
+        let kept = self.history.iter().filter(|a| a.monitor == monitor).count();
+        let mut extra = kept.saturating_sub(HISTORY_LEN);
+        self.history.retain(|a| {
+            let drop = extra > 0 && a.monitor == monitor;
+            extra -= drop as usize;
+            !drop
+        });
+        self.assignments.push(Assignment {
+            monitor,
+            path,
+            scaling,
+        });
+    }
+
+    /// What was on `monitor` before, newest first, `None` for every monitor's
+    pub fn recent(&self, monitor: Option<i32>) -> Vec<&Path> {
+        let mut seen = BTreeSet::new();
+        self.history
+            .iter()
+            .rev()
+            .filter(|a| monitor.is_none() || a.monitor == monitor || a.monitor.is_none())
+            .map(|a| a.path.as_path())
+            .filter(|path| seen.insert(*path))
+            .take(HISTORY_LEN)
+            .collect()
+    }
+
+    /// What was last applied to `monitor`, either to it or to every monitor
+    pub fn assigned(&self, monitor: Option<i32>) -> Option<&Path> {
+        self.assignments
+            .iter()
+            .rev()
+            .find(|a| a.monitor == monitor || a.monitor.is_none())
+            .map(|a| a.path.as_path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assignments_remember_their_scaling() {
+        let mut state = SavedState::default();
+        state.assign(Some(0), PathBuf::from("/a.png"), ScalingMode::Fit);
+        state.assign(None, PathBuf::from("/b.png"), ScalingMode::Tile);
+        assert_eq!(state.assignments.len(), 1);
+        assert_eq!(state.assignments[0].scaling, ScalingMode::Tile);
+
+        let saved = toml::to_string(&state).unwrap();
+        let loaded: SavedState = toml::from_str(&saved).unwrap();
+        assert_eq!(loaded.assignments[0].scaling, ScalingMode::Tile);
+    }
+
+    #[test]
+    fn older_assignments_are_filled() {
+        let assignment: Assignment = toml::from_str("monitor = 1\npath = \"/a.png\"").unwrap();
+        assert_eq!(assignment.scaling, ScalingMode::Fill);
+    }
+}
@@ -0,0 +1,97 @@
+use iced::{
+    Color,
+    widget::{Row, row, text, text::IntoFragment},
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Colors of the state indicators. Every state has its own icon too, so
+/// none of them is told apart by hue alone
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusPalette {
+    /// Green, amber and red
+    #[default]
+    Default,
+    /// Okabe-Ito's bluish green, orange and vermillion, for protanopia and
+    /// deuteranopia
+    OkabeIto,
+    /// Teal, grey and magenta, for tritanopia
+    Tritan,
+    /// The theme's text color, only the icons tell states apart
+    Monochrome,
+}
+
+impl StatusPalette {
+    pub const ALL: &[Self] = &[
+        Self::Default,
+        Self::OkabeIto,
+        Self::Tritan,
+        Self::Monochrome,
+    ];
+}
+
+impl fmt::Display for StatusPalette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Default => "Default",
+            Self::OkabeIto => "Red-green safe (Okabe-Ito)",
+            Self::Tritan => "Blue-yellow safe",
+            Self::Monochrome => "Monochrome",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Connected, applied, healthy
+    Ok,
+    /// Connecting, applying
+    Pending,
+    Error,
+}
+
+impl Status {
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::Ok => "✔",
+            Self::Pending => "◔",
+            Self::Error => "✖",
+        }
+    }
+
+    /// None leaves the theme's text color
+    pub fn color(self, palette: StatusPalette) -> Option<Color> {
+        let rgb = match (palette, self) {
+            (StatusPalette::Monochrome, _) => return None,
+            (StatusPalette::Default, Self::Ok) => 0x2e_b8_4f,
+            (StatusPalette::Default, Self::Pending) => 0xe8_a3_17,
+            (StatusPalette::Default, Self::Error) => 0xe0_3c_3c,
+            (StatusPalette::OkabeIto, Self::Ok) => 0x00_9e_73,
+            (StatusPalette::OkabeIto, Self::Pending) => 0xe6_9f_00,
+            (StatusPalette::OkabeIto, Self::Error) => 0xd5_5e_00,
+            (StatusPalette::Tritan, Self::Ok) => 0x00_99_99,
+            (StatusPalette::Tritan, Self::Pending) => 0x99_99_99,
+            (StatusPalette::Tritan, Self::Error) => 0xcc_33_77,
+        };
+        Some(Color::from_rgb8(
+            (rgb >> 16) as u8,
+            (rgb >> 8) as u8,
+            rgb as u8,
+        ))
+    }
+}
+
+/// `label` after the state's icon, both in the state's color
+pub fn badge<'a, Message: 'a>(
+    status: Status,
+    palette: StatusPalette,
+    label: impl IntoFragment<'a>,
+) -> Row<'a, Message> {
+    let color = status.color(palette);
+    row![
+        text(status.icon()).color_maybe(color),
+        text(label).color_maybe(color),
+    ]
+    .spacing(5)
+}
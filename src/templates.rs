@@ -0,0 +1,667 @@
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont, point};
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Datelike, Local};
+use image::{ImageBuffer, Rgb, RgbImage, imageops::FilterType};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    time::Duration,
+};
+use tracing::debug;
+
+use crate::{disk_cache, limits, sources::gradient::lerp_color, xdg};
+
+pub mod agenda;
+pub mod system;
+
+/// How often templates are looked at, each one renders on its own schedule
+pub const TICK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// `{quote}` picks one of these a day, unless the template has its own
+const QUOTES: &[&str] = &[
+    "Simplicity is prerequisite for reliability.",
+    "Make it work, make it right, make it fast.",
+    "The best way to predict the future is to invent it.",
+    "Premature optimization is the root of all evil.",
+    "Talk is cheap. Show me the code.",
+    "First, solve the problem. Then, write the code.",
+    "Perfection is achieved when there is nothing left to take away.",
+];
+
+/// Written by the "New template" button
+pub const TEMPLATE: &str = r##"# Rendered every `every_minutes` and applied, to `monitor` or all of them
+every_minutes = 60
+# monitor = 0
+top = "#2c3e50"
+# leave out for a solid color
+bottom = "#000000"
+# or draw on a wallpaper instead
+# base = "/home/me/Pictures/mountains.jpg"
+# font = "/usr/share/fonts/TTF/DejaVuSans.ttf"
+# quotes = ["one a day", "from this list"]
+
+# placeholders: {date} {time} {weekday} {uptime} {quote}
+#               {hostname} {ip} {kernel} {disk}
+# x and y are the center of the text, 0..1 of the width and height
+[[text]]
+text = "{weekday}"
+y = 0.4
+size = 140
+
+[[text]]
+text = "{date}, up {uptime}"
+y = 0.55
+size = 48
+color = "#cccccc"
+
+[[text]]
+text = "{quote}"
+y = 0.9
+size = 28
+color = "#999999"
+
+# today's events, rendered again when the day or the file changes
+# x and y are the top left corner
+# [agenda]
+# file = "/home/me/.local/share/calendar.ics"
+# x = 0.05
+# y = 0.1
+# size = 32
+
+# hostname, address, kernel and disk usage in a corner, handy on lab machines
+# lower every_minutes to keep it fresh
+# [system]
+# corner = "bottom-right"
+# disk = "/"
+# size = 24
+# lines = ["{hostname}", "{ip}", "Linux {kernel}", "Disk {disk}"]
+"##;
+
+/// `#rrggbb`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct HexColor(pub [u8; 3]);
+
+impl FromStr for HexColor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let hex = s.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(anyhow!("`{s}` isn't a color, they look like #2c3e50"));
+        }
+        let channel = |i: usize| {
+            u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+                .map_err(|_| anyhow!("`{s}` isn't a color, they look like #2c3e50"))
+        };
+        Ok(Self([channel(0)?, channel(1)?, channel(2)?]))
+    }
+}
+
+impl TryFrom<String> for HexColor {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<HexColor> for String {
+    fn from(color: HexColor) -> Self {
+        color.to_string()
+    }
+}
+
+impl fmt::Display for HexColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b] = self.0;
+        write!(f, "#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+fn default_every_minutes() -> u64 {
+    60
+}
+
+/// A year, `every_minutes` past it is cut down to it
+pub const MAX_EVERY_MINUTES: u64 = 365 * 24 * 60;
+
+fn default_width() -> u32 {
+    1920
+}
+
+fn default_height() -> u32 {
+    1080
+}
+
+fn default_center() -> f32 {
+    0.5
+}
+
+fn default_text_size() -> f32 {
+    48.0
+}
+
+fn default_text_color() -> HexColor {
+    HexColor([0xff, 0xff, 0xff])
+}
+
+fn default_top() -> HexColor {
+    HexColor([0x3c, 0x3c, 0x3c])
+}
+
+fn default_agenda_corner() -> f32 {
+    0.05
+}
+
+fn default_agenda_size() -> f32 {
+    32.0
+}
+
+fn default_agenda_empty() -> String {
+    "Nothing today".to_owned()
+}
+
+fn default_system_margin() -> f32 {
+    0.03
+}
+
+fn default_system_size() -> f32 {
+    24.0
+}
+
+fn default_system_disk() -> PathBuf {
+    PathBuf::from("/")
+}
+
+fn default_system_lines() -> Vec<String> {
+    ["{hostname}", "{ip}", "Linux {kernel}", "Disk {disk}"]
+        .map(str::to_owned)
+        .to_vec()
+}
+
+/// A line of text, `{placeholders}` are filled in when rendering
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextItem {
+    pub text: String,
+    #[serde(default = "default_center")]
+    pub x: f32,
+    #[serde(default = "default_center")]
+    pub y: f32,
+    /// Pixels
+    #[serde(default = "default_text_size")]
+    pub size: f32,
+    #[serde(default = "default_text_color")]
+    pub color: HexColor,
+}
+
+/// Today's events from an ics file, a line each
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgendaItem {
+    pub file: PathBuf,
+    /// Top left corner, 0..1 of the width and height
+    #[serde(default = "default_agenda_corner")]
+    pub x: f32,
+    #[serde(default = "default_agenda_corner")]
+    pub y: f32,
+    #[serde(default = "default_agenda_size")]
+    pub size: f32,
+    #[serde(default = "default_text_color")]
+    pub color: HexColor,
+    /// Shown on days without events, nothing is shown if it's empty
+    #[serde(default = "default_agenda_empty")]
+    pub empty: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// Lines about the machine stacked in a corner
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemItem {
+    #[serde(default)]
+    pub corner: Corner,
+    /// Space to the edges, 0..1 of the height
+    #[serde(default = "default_system_margin")]
+    pub margin: f32,
+    #[serde(default = "default_system_size")]
+    pub size: f32,
+    #[serde(default = "default_text_color")]
+    pub color: HexColor,
+    /// Anywhere on the filesystem `{disk}` is about
+    #[serde(default = "default_system_disk")]
+    pub disk: PathBuf,
+    #[serde(default = "default_system_lines")]
+    pub lines: Vec<String>,
+}
+
+/// A wallpaper generated from a `.toml` file in `dir()`, see `TEMPLATE`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    #[serde(default = "default_every_minutes")]
+    pub every_minutes: u64,
+    /// All of them if unset
+    pub monitor: Option<i32>,
+    #[serde(default = "default_width")]
+    pub width: u32,
+    #[serde(default = "default_height")]
+    pub height: u32,
+    #[serde(default = "default_top")]
+    pub top: HexColor,
+    /// Same as `top` if unset
+    pub bottom: Option<HexColor>,
+    /// Drawn on instead of the colors, cropped to fill
+    pub base: Option<PathBuf>,
+    pub agenda: Option<AgendaItem>,
+    pub system: Option<SystemItem>,
+    /// Asks fontconfig for a sans-serif font if unset
+    pub font: Option<PathBuf>,
+    #[serde(default)]
+    pub quotes: Vec<String>,
+    #[serde(default, rename = "text")]
+    pub texts: Vec<TextItem>,
+}
+
+impl Template {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.every_minutes.clamp(1, MAX_EVERY_MINUTES) * 60)
+    }
+}
+
+/// A template file, loaded or not
+#[derive(Debug)]
+pub struct TemplateFile {
+    pub name: String,
+    pub path: PathBuf,
+    pub template: Result<Template, String>,
+}
+
+impl TemplateFile {
+    pub fn load(path: PathBuf) -> Self {
+        let name = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let template = fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))
+            .and_then(|contents| toml::from_str(&contents).map_err(|err| format!("{err}")));
+        Self {
+            name,
+            path,
+            template,
+        }
+    }
+
+    /// Loaded, and never rendered or its interval passed since. Agendas
+    /// also change with the day and when their calendar is edited
+    pub fn is_due(&self, last_render: Option<DateTime<Local>>, now: DateTime<Local>) -> bool {
+        let template = match &self.template {
+            Ok(template) => template,
+            Err(_) => return false,
+        };
+        let Some(at) = last_render else {
+            return true;
+        };
+        if (now - at).to_std().unwrap_or_default() >= template.interval() {
+            return true;
+        }
+        template.agenda.as_ref().is_some_and(|agenda| {
+            at.date_naive() != now.date_naive()
+                || agenda::modified(&agenda.file).is_some_and(|modified| modified > at)
+        })
+    }
+}
+
+/// `$XDG_CONFIG_HOME/xab-gui/templates`
+pub fn dir() -> PathBuf {
+    xdg::config_dir().join("templates")
+}
+
+/// Every template in `dir()`, sorted by name
+pub fn load_all() -> Vec<TemplateFile> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+    paths.into_iter().map(TemplateFile::load).collect()
+}
+
+/// Writes `TEMPLATE` to a new file in `dir()`
+pub fn create() -> Result<PathBuf> {
+    let dir = dir();
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let mut path = dir.join("new-template.toml");
+    let mut suffix = 2;
+    while path.exists() {
+        path = dir.join(format!("new-template-{suffix}.toml"));
+        suffix += 1;
+    }
+    fs::write(&path, TEMPLATE).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Like `3d 4h 12m`, from `/proc/uptime`
+fn uptime() -> String {
+    let Some(secs) = fs::read_to_string("/proc/uptime")
+        .ok()
+        .and_then(|uptime| uptime.split_whitespace().next()?.parse::<f64>().ok())
+    else {
+        return "?".to_owned();
+    };
+    let minutes = secs as u64 / 60;
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    match days {
+        0 => format!("{hours}h {minutes}m"),
+        _ => format!("{days}d {hours}h {minutes}m"),
+    }
+}
+
+/// Fills in the `{placeholders}` of `text`
+pub fn expand(text: &str, quotes: &[String]) -> String {
+    let now = Local::now();
+    let mut expanded = text
+        .replace("{date}", &now.format("%B %-d, %Y").to_string())
+        .replace("{time}", &now.format("%H:%M").to_string())
+        .replace("{weekday}", &now.format("%A").to_string());
+    if expanded.contains("{uptime}") {
+        expanded = expanded.replace("{uptime}", &uptime());
+    }
+    if expanded.contains("{hostname}") {
+        expanded = expanded.replace("{hostname}", &system::hostname());
+    }
+    if expanded.contains("{ip}") {
+        expanded = expanded.replace("{ip}", &system::ip());
+    }
+    if expanded.contains("{kernel}") {
+        expanded = expanded.replace("{kernel}", &system::kernel());
+    }
+    if expanded.contains("{disk}") {
+        expanded = expanded.replace("{disk}", &system::disk(Path::new("/")));
+    }
+    if expanded.contains("{quote}") {
+        let day = now.ordinal0() as usize;
+        let quote = match quotes {
+            [] => QUOTES[day % QUOTES.len()],
+            quotes => quotes[day % quotes.len()].as_str(),
+        };
+        expanded = expanded.replace("{quote}", quote);
+    }
+    expanded
+}
+
+/// What fontconfig thinks a sans-serif font is
+fn default_font() -> Result<PathBuf> {
+    let output = Command::new("fc-match")
+        .args(["--format=%{file}", "sans-serif"])
+        .output()
+        .with_context(|| "Failed to run fc-match, set `font` in the template")?;
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    if !output.status.success() || path.is_empty() {
+        return Err(anyhow!(
+            "fc-match found no font, set `font` in the template"
+        ));
+    }
+    Ok(PathBuf::from(path))
+}
+
+fn load_font(path: Option<&PathBuf>) -> Result<FontVec> {
+    let path = match path {
+        Some(path) => path.clone(),
+        None => default_font()?,
+    };
+    let data = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    FontVec::try_from_vec(data).with_context(|| format!("{} isn't a font", path.display()))
+}
+
+/// Lays out a single line of `text` at the origin, returns it with its width
+fn layout(font: &FontVec, text: &str, size: f32) -> (Vec<ab_glyph::Glyph>, f32) {
+    let font = font.as_scaled(PxScale::from(size));
+    let mut glyphs = Vec::new();
+    let mut caret = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += font.kern(previous, id);
+        }
+        glyphs.push(id.with_scale_and_position(size, point(caret, font.ascent())));
+        caret += font.h_advance(id);
+        previous = Some(id);
+    }
+    (glyphs, caret)
+}
+
+/// Width of a single line of `text`
+pub fn text_width(font: &FontVec, text: &str, size: f32) -> f32 {
+    layout(font, text, size).1
+}
+
+/// Height of a line of text, including the gap to the next one
+pub fn line_height(font: &FontVec, size: f32) -> f32 {
+    let font = font.as_scaled(PxScale::from(size));
+    font.height() + font.line_gap()
+}
+
+/// Draws a single line of `text` with its top left corner at `origin`
+pub fn draw_text(
+    image: &mut RgbImage,
+    font: &FontVec,
+    text: &str,
+    size: f32,
+    origin: (f32, f32),
+    color: [u8; 3],
+) {
+    let (glyphs, _) = layout(font, text, size);
+    for glyph in glyphs {
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|x, y, coverage| {
+            let x = origin.0 + bounds.min.x + x as f32;
+            let y = origin.1 + bounds.min.y + y as f32;
+            if x < 0.0 || y < 0.0 {
+                return;
+            }
+            let Some(pixel) = image.get_pixel_mut_checked(x as u32, y as u32) else {
+                return;
+            };
+            for (channel, text) in pixel.0.iter_mut().zip(color) {
+                *channel =
+                    (*channel as f32 + (text as f32 - *channel as f32) * coverage).round() as u8;
+            }
+        });
+    }
+}
+
+/// Same as `draw_text`, centered on `center` instead
+pub fn draw_text_centered(
+    image: &mut RgbImage,
+    font: &FontVec,
+    text: &str,
+    size: f32,
+    center: (f32, f32),
+    color: [u8; 3],
+) {
+    let (_, width) = layout(font, text, size);
+    let height = font.as_scaled(PxScale::from(size)).height();
+    let origin = (center.0 - width / 2.0, center.1 - height / 2.0);
+    draw_text(image, font, text, size, origin, color);
+}
+
+/// Stacks `lines` in the corner `item` asks for, right aligned on the right side
+fn draw_system(image: &mut RgbImage, font: &FontVec, item: &SystemItem, lines: &[String]) {
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let margin = item.margin * height;
+    let line_height = line_height(font, item.size);
+    let mut y = match item.corner {
+        Corner::TopLeft | Corner::TopRight => margin,
+        Corner::BottomLeft | Corner::BottomRight => {
+            height - margin - line_height * lines.len() as f32
+        }
+    };
+    for line in lines {
+        let x = match item.corner {
+            Corner::TopLeft | Corner::BottomLeft => margin,
+            Corner::TopRight | Corner::BottomRight => {
+                width - margin - text_width(font, line, item.size)
+            }
+        };
+        draw_text(image, font, line, item.size, (x, y), item.color.0);
+        y += line_height;
+    }
+}
+
+/// Renders `template` into the cache, the same text renders to the same file
+pub fn render(name: &str, template: &Template) -> Result<PathBuf> {
+    let texts: Vec<String> = template
+        .texts
+        .iter()
+        .map(|item| expand(&item.text, &template.quotes))
+        .collect();
+
+    let agenda: Vec<String> = match &template.agenda {
+        Some(agenda) => {
+            let entries = agenda::on(&agenda.file, Local::now().date_naive())?;
+            match entries.is_empty() {
+                true => vec![agenda.empty.clone()],
+                false => entries.iter().map(ToString::to_string).collect(),
+            }
+        }
+        None => Vec::new(),
+    };
+
+    let system: Vec<String> = match &template.system {
+        Some(item) => {
+            let disk = system::disk(&item.disk);
+            item.lines
+                .iter()
+                .map(|line| expand(&line.replace("{disk}", &disk), &template.quotes))
+                .collect()
+        }
+        None => Vec::new(),
+    };
+
+    let mut hasher = DefaultHasher::new();
+    format!("{template:?}").hash(&mut hasher);
+    texts.hash(&mut hasher);
+    agenda.hash(&mut hasher);
+    system.hash(&mut hasher);
+    // an edited base wallpaper shouldn't be served stale
+    template
+        .base
+        .as_ref()
+        .and_then(|base| {
+            fs::metadata(base)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .hash(&mut hasher);
+    let dir = xdg::cache_dir().join("templates");
+    let prefix = format!("{name}-");
+    let dest = dir.join(format!("{prefix}{:016x}.png", hasher.finish()));
+    if disk_cache::hit(&dest) {
+        return Ok(dest);
+    }
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let (width, height) = (template.width.max(1), template.height.max(1));
+    let _decoding = limits::decode_permit();
+    let mut image: RgbImage = match &template.base {
+        Some(base) => image::open(base)
+            .with_context(|| format!("Failed to open {}", base.display()))?
+            .resize_to_fill(width, height, FilterType::Triangle)
+            .into_rgb8(),
+        None => {
+            let top = template.top.0;
+            let bottom = template.bottom.unwrap_or(template.top).0;
+            ImageBuffer::from_fn(width, height, |_, y| match height {
+                1 => Rgb(top),
+                _ => lerp_color(top, bottom, y as f32 / (height - 1) as f32),
+            })
+        }
+    };
+    if !texts
+        .iter()
+        .chain(&agenda)
+        .chain(&system)
+        .all(String::is_empty)
+    {
+        let font = load_font(template.font.as_ref())?;
+        for (item, text) in template.texts.iter().zip(&texts) {
+            draw_text_centered(
+                &mut image,
+                &font,
+                text,
+                item.size,
+                (item.x * width as f32, item.y * height as f32),
+                item.color.0,
+            );
+        }
+        if let Some(item) = &template.agenda {
+            let (x, mut y) = (item.x * width as f32, item.y * height as f32);
+            for line in &agenda {
+                draw_text(&mut image, &font, line, item.size, (x, y), item.color.0);
+                y += line_height(&font, item.size);
+            }
+        }
+        if let Some(item) = &template.system {
+            draw_system(&mut image, &font, item, &system);
+        }
+    }
+    image
+        .save(&dest)
+        .with_context(|| format!("Failed to save {}", dest.display()))?;
+    debug!("Rendered template {} to {}", name, dest.display());
+
+    // older renders of the same template are never used again
+    for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path != dest
+            && path.file_name().is_some_and(|file| {
+                // not `<name>-2-...`, that's another template
+                file.to_string_lossy()
+                    .strip_prefix(&prefix)
+                    .is_some_and(|rest| rest.len() == "0123456789abcdef.png".len())
+            })
+        {
+            let _ = fs::remove_file(path);
+        }
+    }
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals_are_kept_sane() {
+        let interval = |every_minutes| {
+            let mut template: Template = toml::from_str("").unwrap();
+            template.every_minutes = every_minutes;
+            template.interval()
+        };
+        assert_eq!(interval(0), Duration::from_secs(60));
+        assert_eq!(interval(60), Duration::from_secs(60 * 60));
+        assert_eq!(
+            interval(u64::MAX),
+            Duration::from_secs(MAX_EVERY_MINUTES * 60)
+        );
+    }
+}
@@ -0,0 +1,416 @@
+use anyhow::{Context, Result};
+use chrono::{
+    DateTime, Datelike, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use std::{fmt, fs, path::Path};
+
+/// An event happening on the day asked for
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Entry {
+    /// None for all day events
+    pub start: Option<NaiveTime>,
+    pub summary: String,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.start {
+            Some(start) => write!(f, "{}  {}", start.format("%H:%M"), self.summary),
+            None => write!(f, "All day  {}", self.summary),
+        }
+    }
+}
+
+/// `DTSTART`/`DTEND`, times with a `TZID` are taken as local time
+#[derive(Debug, Clone, Copy)]
+enum When {
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+}
+
+impl When {
+    fn parse(params: &str, value: &str) -> Option<Self> {
+        let date_only = value.len() == 8
+            || (params.contains("VALUE=DATE") && !params.contains("VALUE=DATE-TIME"));
+        if date_only {
+            return NaiveDate::parse_from_str(value, "%Y%m%d")
+                .ok()
+                .map(Self::Date);
+        }
+        let (value, utc) = match value.strip_suffix('Z') {
+            Some(value) => (value, true),
+            None => (value, false),
+        };
+        let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+        Some(Self::DateTime(match utc {
+            true => Utc
+                .from_utc_datetime(&time)
+                .with_timezone(&Local)
+                .naive_local(),
+            false => time,
+        }))
+    }
+
+    fn date(self) -> NaiveDate {
+        match self {
+            Self::Date(date) => date,
+            Self::DateTime(time) => time.date(),
+        }
+    }
+}
+
+/// `MO`, `TU`, ..., `1MO` and friends are for monthly rules so the number is dropped
+fn weekday(day: &str) -> Option<Weekday> {
+    match day.trim_start_matches(|c: char| c == '+' || c == '-' || c.is_ascii_digit()) {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// The part of `RRULE` that's understood
+#[derive(Debug, Clone)]
+struct Recurrence {
+    frequency: Frequency,
+    interval: u32,
+    until: Option<NaiveDate>,
+    /// Occurrences in all, the first one included
+    count: Option<u32>,
+    /// Only for weekly rules, the start's weekday if empty
+    by_day: Vec<Weekday>,
+}
+
+impl Recurrence {
+    fn parse(value: &str) -> Option<Self> {
+        let mut frequency = None;
+        let mut recurrence = Self {
+            frequency: Frequency::Daily,
+            interval: 1,
+            until: None,
+            count: None,
+            by_day: Vec::new(),
+        };
+        for part in value.split(';') {
+            let Some((key, value)) = part.split_once('=') else {
+                continue;
+            };
+            match key {
+                "FREQ" => {
+                    frequency = Some(match value {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return None,
+                    })
+                }
+                "INTERVAL" => recurrence.interval = value.parse().unwrap_or(1).max(1),
+                "UNTIL" => recurrence.until = When::parse("", value).map(When::date),
+                "COUNT" => recurrence.count = value.parse().ok(),
+                "BYDAY" => recurrence.by_day = value.split(',').filter_map(weekday).collect(),
+                _ => {}
+            }
+        }
+        recurrence.frequency = frequency?;
+        Some(recurrence)
+    }
+
+    fn occurs(&self, first: NaiveDate, day: NaiveDate) -> bool {
+        if day < first || self.until.is_some_and(|until| day > until) || !self.matches(first, day) {
+            return false;
+        }
+        // excluded occurrences count too
+        self.count.is_none_or(|count| {
+            first
+                .iter_days()
+                .take_while(|date| *date <= day)
+                .filter(|date| self.matches(first, *date))
+                .count()
+                <= count as usize
+        })
+    }
+
+    /// Whether `day` fits the rule, leaving out where it ends
+    fn matches(&self, first: NaiveDate, day: NaiveDate) -> bool {
+        let interval = i64::from(self.interval);
+        match self.frequency {
+            Frequency::Daily => (day - first).num_days() % interval == 0,
+            Frequency::Weekly => {
+                let monday = |date: NaiveDate| {
+                    date - Days::new(u64::from(date.weekday().num_days_from_monday()))
+                };
+                let weeks = (monday(day) - monday(first)).num_days() / 7;
+                let on_day = match self.by_day.as_slice() {
+                    [] => day.weekday() == first.weekday(),
+                    days => days.contains(&day.weekday()),
+                };
+                weeks % interval == 0 && on_day
+            }
+            Frequency::Monthly => {
+                let months =
+                    (day.year() - first.year()) * 12 + day.month() as i32 - first.month() as i32;
+                day.day() == first.day() && i64::from(months) % interval == 0
+            }
+            Frequency::Yearly => {
+                day.month() == first.month()
+                    && day.day() == first.day()
+                    && i64::from(day.year() - first.year()) % interval == 0
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Event {
+    uid: String,
+    summary: String,
+    start: Option<When>,
+    end: Option<When>,
+    recurrence: Option<Recurrence>,
+    /// `EXDATE`s, and the days of occurrences changed on their own
+    skipped: Vec<NaiveDate>,
+    /// Set on an occurrence changed on its own, the day it replaces
+    recurrence_id: Option<NaiveDate>,
+    cancelled: bool,
+}
+
+impl Event {
+    fn occurs(&self, day: NaiveDate) -> bool {
+        let Some(start) = self.start else {
+            return false;
+        };
+        if self.cancelled || self.skipped.contains(&day) {
+            return false;
+        }
+        let first = start.date();
+        if let Some(recurrence) = &self.recurrence {
+            return recurrence.occurs(first, day);
+        }
+        let last = match self.end {
+            // the end date of all day events is exclusive
+            Some(When::Date(end)) => end.pred_opt().unwrap_or(end).max(first),
+            Some(When::DateTime(end)) if end.time() == NaiveTime::MIN => {
+                end.date().pred_opt().unwrap_or(first).max(first)
+            }
+            Some(When::DateTime(end)) => end.date(),
+            None => first,
+        };
+        first <= day && day <= last
+    }
+
+    fn entry(&self, day: NaiveDate) -> Entry {
+        Entry {
+            // an event that started yesterday lasts all day today
+            start: match self.start {
+                Some(When::DateTime(start)) if start.date() == day || self.recurrence.is_some() => {
+                    Some(start.time())
+                }
+                _ => None,
+            },
+            summary: self.summary.clone(),
+        }
+    }
+}
+
+/// Undoes ics escaping, newlines become spaces since entries are one line
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", " ")
+        .replace("\\N", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+fn parse(ics: &str) -> Vec<Event> {
+    // long lines are folded into ones starting with a space or a tab
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_owned()),
+        }
+    }
+
+    let mut events = Vec::new();
+    let mut event: Option<Event> = None;
+    let mut nested = 0u32;
+    for line in &lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (name, params) = name.split_once(';').unwrap_or((name, ""));
+        match (name, value) {
+            ("BEGIN", "VEVENT") => event = Some(Event::default()),
+            ("END", "VEVENT") => events.extend(event.take()),
+            // alarms have summaries of their own
+            ("BEGIN", _) if event.is_some() => nested += 1,
+            ("END", _) if nested > 0 => nested -= 1,
+            _ => {}
+        }
+        let Some(event) = event.as_mut().filter(|_| nested == 0) else {
+            continue;
+        };
+        match name {
+            "UID" => event.uid = value.to_owned(),
+            "SUMMARY" => event.summary = unescape(value),
+            "DTSTART" => event.start = When::parse(params, value),
+            "DTEND" => event.end = When::parse(params, value),
+            "RRULE" => event.recurrence = Recurrence::parse(value),
+            "EXDATE" => event.skipped.extend(
+                value
+                    .split(',')
+                    .filter_map(|value| When::parse(params, value))
+                    .map(When::date),
+            ),
+            "RECURRENCE-ID" => event.recurrence_id = When::parse(params, value).map(When::date),
+            "STATUS" => event.cancelled = value == "CANCELLED",
+            _ => {}
+        }
+    }
+
+    // an occurrence changed on its own takes the place of the one the rule
+    // would've put there
+    let replaced: Vec<(String, NaiveDate)> = events
+        .iter()
+        .filter_map(|event| Some((event.uid.clone(), event.recurrence_id?)))
+        .collect();
+    for event in &mut events {
+        if event.recurrence.is_some() && event.recurrence_id.is_none() {
+            event.skipped.extend(
+                replaced
+                    .iter()
+                    .filter(|(uid, _)| *uid == event.uid)
+                    .map(|(_, day)| *day),
+            );
+        }
+    }
+    events
+}
+
+/// Events of `day` in the calendar at `path`, all day ones first
+pub fn on(path: &Path, day: NaiveDate) -> Result<Vec<Entry>> {
+    let ics =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut entries: Vec<Entry> = parse(&ics)
+        .iter()
+        .filter(|event| event.occurs(day))
+        .map(|event| event.entry(day))
+        .collect();
+    entries.sort_by(|a, b| {
+        a.start
+            .cmp(&b.start)
+            .then_with(|| a.summary.cmp(&b.summary))
+    });
+    Ok(entries)
+}
+
+/// When the calendar was last changed, None if it can't be told
+pub fn modified(path: &Path) -> Option<DateTime<Local>> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(DateTime::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(ics: &str, day: &str) -> Vec<String> {
+        let day = NaiveDate::parse_from_str(day, "%Y-%m-%d").unwrap();
+        parse(ics)
+            .iter()
+            .filter(|event| event.occurs(day))
+            .map(|event| event.entry(day).to_string())
+            .collect()
+    }
+
+    const STANDUP: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Google Inc//Google Calendar 70.9054//EN\r
+BEGIN:VEVENT\r
+DTSTART;TZID=Europe/Berlin:20240902T093000\r
+DTEND;TZID=Europe/Berlin:20240902T094500\r
+RRULE:FREQ=WEEKLY;WKST=MO;COUNT=6;BYDAY=MO,WE\r
+EXDATE;TZID=Europe/Berlin:20240904T093000\r
+UID:standup-1@google.com\r
+SUMMARY:Standup\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+DESCRIPTION:This is an event reminder\r
+TRIGGER:-P0DT0H10M0S\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;TZID=Europe/Berlin:20240911T110000\r
+DTEND;TZID=Europe/Berlin:20240911T111500\r
+RECURRENCE-ID;TZID=Europe/Berlin:20240911T093000\r
+UID:standup-1@google.com\r
+SUMMARY:Standup (moved)\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;TZID=Europe/Berlin:20240916T093000\r
+RECURRENCE-ID;TZID=Europe/Berlin:20240916T093000\r
+UID:standup-1@google.com\r
+STATUS:CANCELLED\r
+SUMMARY:Standup\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn counts_occurrences() {
+        // mon 2, wed 4 (excluded), mon 9, wed 11 (moved), mon 16
+        // (cancelled), wed 18 is the sixth and last
+        assert_eq!(entries(STANDUP, "2024-09-02"), ["09:30  Standup"]);
+        assert_eq!(entries(STANDUP, "2024-09-09"), ["09:30  Standup"]);
+        assert_eq!(entries(STANDUP, "2024-09-18"), ["09:30  Standup"]);
+        assert!(entries(STANDUP, "2024-09-23").is_empty());
+    }
+
+    #[test]
+    fn skips_excluded_dates() {
+        assert!(entries(STANDUP, "2024-09-04").is_empty());
+    }
+
+    #[test]
+    fn moved_occurrences_replace_the_rules() {
+        assert_eq!(entries(STANDUP, "2024-09-11"), ["11:00  Standup (moved)"]);
+        assert!(entries(STANDUP, "2024-09-16").is_empty());
+    }
+
+    #[test]
+    fn reads_all_day_and_yearly_events() {
+        let ics = "BEGIN:VCALENDAR
+BEGIN:VEVENT
+DTSTART;VALUE=DATE:20200315
+DTEND;VALUE=DATE:20200316
+RRULE:FREQ=YEARLY
+EXDATE;VALUE=DATE:20230315
+SUMMARY:Ada's birthday\\, again
+UID:birthday
+END:VEVENT
+END:VCALENDAR
+";
+        assert_eq!(
+            entries(ics, "2024-03-15"),
+            ["All day  Ada's birthday, again"]
+        );
+        assert!(entries(ics, "2023-03-15").is_empty());
+        assert!(entries(ics, "2024-03-16").is_empty());
+    }
+}
@@ -0,0 +1,53 @@
+use std::{fs, net::UdpSocket, path::Path, process::Command};
+
+use crate::preview::human_size;
+
+/// From `/proc/sys/kernel/hostname`
+pub fn hostname() -> String {
+    proc_value("/proc/sys/kernel/hostname")
+}
+
+/// From `/proc/sys/kernel/osrelease`, like `6.9.1-arch1-1`
+pub fn kernel() -> String {
+    proc_value("/proc/sys/kernel/osrelease")
+}
+
+fn proc_value(path: &str) -> String {
+    fs::read_to_string(path)
+        .map(|value| value.trim().to_owned())
+        .unwrap_or_else(|_| "?".to_owned())
+}
+
+/// The address outgoing traffic would leave from
+pub fn ip() -> String {
+    // connecting a udp socket only picks a route, nothing is sent
+    UdpSocket::bind(("0.0.0.0", 0))
+        .and_then(|socket| {
+            socket.connect(("1.1.1.1", 53))?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|_| "offline".to_owned())
+}
+
+/// Like `41% of 468G`, for the filesystem `path` is on
+pub fn disk(path: &Path) -> String {
+    let Ok(output) = Command::new("df").arg("-Pk").arg(path).output() else {
+        return "?".to_owned();
+    };
+    // Filesystem 1024-blocks Used Available Capacity Mounted on
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let fields: Vec<&str> = stdout
+        .lines()
+        .nth(1)
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect();
+    match (
+        fields.get(1).and_then(|size| size.parse::<u64>().ok()),
+        fields.get(4),
+    ) {
+        (Some(size), Some(capacity)) => format!("{capacity} of {}", human_size(size * 1024)),
+        _ => "?".to_owned(),
+    }
+}
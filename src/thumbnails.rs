@@ -0,0 +1,90 @@
+use anyhow::{Context, Result, anyhow};
+use image::{ImageFormat, RgbaImage, codecs::jpeg::JpegEncoder};
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+};
+use tracing::{debug, warn};
+
+use crate::{disk_cache, xdg};
+
+/// Under `xdg::cache_dir`, `disk_cache` keeps it within the limit
+pub const DIR: &str = "thumbs";
+const QUALITY: u8 = 85;
+
+/// A thumbnail decoded from the disk
+pub struct Stored {
+    pub image: RgbaImage,
+    /// Of the file it was made from
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Where the thumbnail of `path` goes, the modification time is part of the
+/// key so edited files get a new one. None when the file can't be read
+fn cache_path(path: &Path) -> Option<PathBuf> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    modified.hash(&mut hasher);
+    Some(
+        xdg::cache_dir()
+            .join(DIR)
+            .join(format!("{:016x}.thumb", hasher.finish())),
+    )
+}
+
+/// The size of the original followed by a jpeg of the thumbnail
+fn decode(bytes: &[u8]) -> Result<Stored> {
+    let (Some(width), Some(height), Some(jpeg)) = (bytes.get(..4), bytes.get(4..8), bytes.get(8..))
+    else {
+        return Err(anyhow!("Truncated thumbnail"));
+    };
+    Ok(Stored {
+        image: image::load_from_memory_with_format(jpeg, ImageFormat::Jpeg)?.into_rgba8(),
+        width: u32::from_be_bytes(width.try_into()?),
+        height: u32::from_be_bytes(height.try_into()?),
+    })
+}
+
+/// The thumbnail made of `path` last time, if it's still the same file
+pub fn load(path: &Path) -> Option<Stored> {
+    let dest = cache_path(path)?;
+    if !disk_cache::hit(&dest) {
+        return None;
+    }
+    match fs::read(&dest)
+        .map_err(anyhow::Error::from)
+        .and_then(|bytes| decode(&bytes))
+    {
+        Ok(stored) => Some(stored),
+        Err(err) => {
+            // made again and overwritten
+            warn!("Broken thumbnail {}: {:?}", dest.display(), err);
+            None
+        }
+    }
+}
+
+/// Keeps `thumbnail` of `path` (`width`x`height`) for next time
+pub fn store(path: &Path, thumbnail: &RgbaImage, width: u32, height: u32) -> Result<()> {
+    let Some(dest) = cache_path(path) else {
+        return Ok(());
+    };
+    let mut bytes = [width.to_be_bytes(), height.to_be_bytes()].concat();
+    let rgb = image::DynamicImage::ImageRgba8(thumbnail.clone()).into_rgb8();
+    JpegEncoder::new_with_quality(&mut bytes, QUALITY)
+        .encode_image(&rgb)
+        .with_context(|| format!("Failed to encode the thumbnail of {}", path.display()))?;
+
+    let dir = xdg::cache_dir().join(DIR);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    fs::write(&dest, bytes).with_context(|| format!("Failed to write {}", dest.display()))?;
+    debug!(
+        "Stored the thumbnail of {} in {}",
+        path.display(),
+        dest.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use chrono::{DateTime, Days, Local, NaiveTime, TimeDelta};
+use std::{fmt, time::Duration};
+
+use crate::{
+    automation::{self, TimeWindow},
+    scheduler::Scheduler,
+    templates,
+};
+
+/// A rotation every few minutes fills a week with thousands of changes, the
+/// rest is summed up
+pub const MAX_ENTRIES: usize = 400;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Horizon {
+    #[default]
+    Day,
+    Week,
+}
+
+impl Horizon {
+    pub const ALL: &[Self] = &[Self::Day, Self::Week];
+
+    fn length(self) -> TimeDelta {
+        match self {
+            Self::Day => TimeDelta::days(1),
+            Self::Week => TimeDelta::days(7),
+        }
+    }
+}
+
+impl fmt::Display for Horizon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Day => "Next 24 hours",
+            Self::Week => "Next 7 days",
+        })
+    }
+}
+
+/// Something the automation is going to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub at: DateTime<Local>,
+    /// `None` being every monitor
+    pub monitor: Option<i32>,
+    pub what: String,
+}
+
+/// A template that's rendered and applied on its own schedule
+#[derive(Debug, Clone)]
+pub struct TemplateSchedule {
+    pub name: String,
+    pub monitor: Option<i32>,
+    pub interval: Duration,
+    pub last_render: Option<DateTime<Local>>,
+}
+
+/// Everything the plan is worked out from, copied off the gui so nothing
+/// real moves along
+#[derive(Debug, Clone)]
+pub struct Inputs {
+    pub now: DateTime<Local>,
+    pub horizon: Horizon,
+    pub quiet_hours: Option<TimeWindow>,
+    pub paused_until: Option<DateTime<Local>>,
+    /// The rotation that's running and the monitors it goes to
+    pub rotation: Option<(Scheduler, Vec<Option<i32>>)>,
+    /// Names of the queued wallpapers, played before the rotation's
+    pub queued: Vec<String>,
+    pub templates: Vec<TemplateSchedule>,
+    /// Night dimming's own schedule, GNOME's night light isn't predictable
+    pub night: Option<TimeWindow>,
+}
+
+/// What's planned, oldest first, and how many more didn't fit in
+/// `MAX_ENTRIES`
+pub struct Plan {
+    pub entries: Vec<Entry>,
+    pub truncated: usize,
+}
+
+impl Inputs {
+    fn paused(&self, at: DateTime<Local>) -> bool {
+        automation::paused_reason(self.quiet_hours, self.paused_until, at).is_some()
+    }
+
+    /// The first template tick from `at` on that isn't paused
+    fn next_unpaused(&self, mut at: DateTime<Local>, end: DateTime<Local>) -> DateTime<Local> {
+        let tick = TimeDelta::from_std(templates::TICK_INTERVAL).unwrap_or(TimeDelta::minutes(1));
+        while at < end && self.paused(at) {
+            at += tick;
+        }
+        at
+    }
+}
+
+/// `window`'s start and end over the days `from..to` touches
+fn window_edges(
+    window: TimeWindow,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+) -> Vec<(DateTime<Local>, bool)> {
+    let at = |day: chrono::NaiveDate, time: NaiveTime| {
+        day.and_time(time).and_local_timezone(Local).earliest()
+    };
+    let mut edges = Vec::new();
+    let mut day = from.date_naive();
+    while day <= to.date_naive() {
+        edges.extend(at(day, window.start).map(|start| (start, true)));
+        edges.extend(at(day, window.end).map(|end| (end, false)));
+        let Some(next) = day.checked_add_days(Days::new(1)) else {
+            break;
+        };
+        day = next;
+    }
+    edges.retain(|(at, _)| from <= *at && *at < to);
+    edges
+}
+
+/// What rotation, templates, quiet hours and night dimming will do over
+/// `inputs.horizon`. Rules react to events, they're not on it
+pub fn plan(inputs: Inputs) -> Plan {
+    let end = inputs.now + inputs.horizon.length();
+    let mut entries = Vec::new();
+
+    if let Some((mut scheduler, targets)) = inputs.rotation.clone() {
+        let interval =
+            TimeDelta::from_std(scheduler.rotation.interval()).unwrap_or(TimeDelta::minutes(30));
+        let mut at = inputs.now + interval;
+        let mut queued = inputs.queued.iter().cloned();
+        // a rotation that's skipped doesn't move along either
+        while at < end && entries.len() <= MAX_ENTRIES * 2 {
+            if !inputs.paused(at) {
+                let mut picks: Vec<String> = queued.by_ref().take(targets.len()).collect();
+                let rest = scheduler.next(targets.len() - picks.len());
+                picks.extend(rest.into_iter().map(|wallpaper| wallpaper.name));
+                for (monitor, what) in targets.iter().zip(picks) {
+                    entries.push(Entry {
+                        at,
+                        monitor: *monitor,
+                        what,
+                    });
+                }
+            }
+            at += interval;
+        }
+    }
+
+    for template in &inputs.templates {
+        let interval = TimeDelta::from_std(template.interval).unwrap_or(TimeDelta::hours(1));
+        let mut at = match template.last_render {
+            Some(last) => (last + interval).max(inputs.now),
+            None => inputs.now,
+        };
+        loop {
+            at = inputs.next_unpaused(at, end);
+            if at >= end {
+                break;
+            }
+            entries.push(Entry {
+                at,
+                monitor: template.monitor,
+                what: format!("Template {}", template.name),
+            });
+            at += interval;
+        }
+    }
+
+    if let Some(quiet) = inputs.quiet_hours {
+        for (at, start) in window_edges(quiet, inputs.now, end) {
+            entries.push(Entry {
+                at,
+                monitor: None,
+                what: match start {
+                    true => "Quiet hours start, nothing changes".to_owned(),
+                    false => "Quiet hours end".to_owned(),
+                },
+            });
+        }
+    }
+
+    if let Some(night) = inputs.night {
+        for (at, start) in window_edges(night, inputs.now, end) {
+            entries.push(Entry {
+                at,
+                monitor: None,
+                what: match start {
+                    true => "Dimmed for the night".to_owned(),
+                    false => "Back from dimming".to_owned(),
+                },
+            });
+        }
+    }
+
+    entries.sort_by_key(|entry| entry.at);
+    let truncated = entries.len().saturating_sub(MAX_ENTRIES);
+    entries.truncate(MAX_ENTRIES);
+    Plan { entries, truncated }
+}
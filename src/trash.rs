@@ -0,0 +1,256 @@
+//! Deleting wallpapers through the freedesktop trash (`$XDG_DATA_HOME/Trash`),
+//! so file managers can restore them too
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+use crate::{
+    library::{Library, LibraryEntry},
+    persist, xdg,
+};
+
+/// Something we moved to the trash, remembered so the gui can restore it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trashed {
+    #[serde(with = "crate::os_path")]
+    pub original: PathBuf,
+    /// File name in `Trash/files`
+    #[serde(with = "crate::os_path")]
+    pub name: PathBuf,
+    pub deleted_at: DateTime<Local>,
+    /// Put back into the library on restore
+    pub library_entry: Option<LibraryEntry>,
+}
+
+impl Trashed {
+    fn file(&self) -> PathBuf {
+        trash_dir().join("files").join(&self.name)
+    }
+
+    fn info(&self) -> PathBuf {
+        info_path(&self.name)
+    }
+
+    /// False once the trash was emptied from somewhere else
+    pub fn exists(&self) -> bool {
+        self.file().symlink_metadata().is_ok()
+    }
+}
+
+pub fn trash_dir() -> PathBuf {
+    xdg::data_home().join("Trash")
+}
+
+fn info_path(name: &Path) -> PathBuf {
+    let mut info = name.as_os_str().to_owned();
+    info.push(".trashinfo");
+    trash_dir().join("info").join(info)
+}
+
+/// Paths in `.trashinfo` files are url-escaped bytes
+fn escape(path: &Path) -> String {
+    path.as_os_str()
+        .as_bytes()
+        .iter()
+        .map(|&byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// `photo.jpg`, `photo.2.jpg`, `photo.3.jpg`, ...
+fn candidate_name(path: &Path, attempt: u32) -> PathBuf {
+    let file_name = PathBuf::from(path.file_name().unwrap_or_default());
+    if attempt == 1 {
+        return file_name;
+    }
+    let mut name = file_name.file_stem().unwrap_or_default().to_owned();
+    name.push(format!(".{attempt}"));
+    if let Some(ext) = file_name.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    PathBuf::from(name)
+}
+
+/// Moves `path` to the trash
+pub fn trash(path: &Path, library_entry: Option<LibraryEntry>) -> Result<Trashed> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} isn't a file", path.display()))?;
+    // only the parent, a symlink goes to the trash and not what it points to
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let original = parent
+        .canonicalize()
+        .with_context(|| format!("Failed to find {}", path.display()))?
+        .join(file_name);
+    original
+        .symlink_metadata()
+        .with_context(|| format!("Failed to find {}", path.display()))?;
+    let dir = trash_dir();
+    for sub in ["files", "info"] {
+        fs::create_dir_all(dir.join(sub))
+            .with_context(|| format!("Failed to create {}", dir.join(sub).display()))?;
+    }
+
+    let deleted_at = Local::now();
+    // creating the info file reserves the name, see the trash spec
+    let (name, mut info) = (1..=u32::MAX)
+        .map(|attempt| candidate_name(&original, attempt))
+        .find_map(|name| {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(info_path(&name))
+            {
+                Ok(info) => Some(Ok((name, info))),
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => None,
+                Err(err) => Some(Err(err)),
+            }
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "Every name for {} is taken in the trash",
+                original.display()
+            )
+        })?
+        .with_context(|| "Failed to create the trash info file")?;
+    let trashed = Trashed {
+        original,
+        name,
+        deleted_at,
+        library_entry,
+    };
+
+    let moved = write!(
+        info,
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        escape(&trashed.original),
+        deleted_at.format("%Y-%m-%dT%H:%M:%S")
+    )
+    .map_err(anyhow::Error::from)
+    .and_then(|()| fs::rename(&trashed.original, trashed.file()).map_err(Into::into));
+    if let Err(err) = moved {
+        let _ = fs::remove_file(trashed.info());
+        return Err(
+            match err.downcast_ref::<std::io::Error>().map(|e| e.kind()) {
+                Some(ErrorKind::CrossesDevices) => anyhow!(
+                    "{} is on another drive than the trash, delete it from a file manager",
+                    trashed.original.display()
+                ),
+                _ => err.context(format!("Failed to trash {}", trashed.original.display())),
+            },
+        );
+    }
+    debug!(
+        "Trashed {} as {}",
+        trashed.original.display(),
+        trashed.name.display()
+    );
+    Ok(trashed)
+}
+
+/// Trashes a wallpaper, taking it out of the library too
+pub fn delete_wallpaper(path: &Path) -> Result<Trashed> {
+    let mut library = Library::load()?;
+    let entry = library.remove(path);
+    let trashed = trash(path, entry.clone())?;
+    if entry.is_some() {
+        library.save()?;
+    }
+    persist::update(|state| state.trashed.push(trashed.clone()))?;
+    Ok(trashed)
+}
+
+/// Moves it back to where it was
+pub fn restore(trashed: &Trashed) -> Result<()> {
+    if trashed.original.symlink_metadata().is_ok() {
+        return Err(anyhow!(
+            "Can't restore, something else is at {} now",
+            trashed.original.display()
+        ));
+    }
+    if let Some(parent) = trashed.original.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::rename(trashed.file(), &trashed.original)
+        .with_context(|| format!("Failed to restore {}", trashed.original.display()))?;
+    let _ = fs::remove_file(trashed.info());
+
+    if let Some(entry) = &trashed.library_entry {
+        let mut library = Library::load()?;
+        library.add(entry.clone());
+        library.save()?;
+    }
+    persist::update(|state| state.trashed.retain(|t| t != trashed))?;
+    debug!("Restored {}", trashed.original.display());
+    Ok(())
+}
+
+/// Deletes it for good
+pub fn purge(trashed: &Trashed) -> Result<()> {
+    let file = trashed.file();
+    if file.is_dir() {
+        fs::remove_dir_all(&file)
+    } else {
+        fs::remove_file(&file)
+    }
+    .or_else(|err| match err.kind() {
+        ErrorKind::NotFound => Ok(()),
+        _ => Err(err),
+    })
+    .with_context(|| format!("Failed to delete {}", file.display()))?;
+    let _ = fs::remove_file(trashed.info());
+    persist::update(|state| state.trashed.retain(|t| t != trashed))?;
+    debug!("Purged {}", trashed.original.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    #[test]
+    fn escapes_like_urls() {
+        assert_eq!(
+            escape(Path::new("/home/me/Pictures/my photo.jpg")),
+            "/home/me/Pictures/my%20photo.jpg"
+        );
+        assert_eq!(escape(Path::new("/a/50%_~b")), "/a/50%25_~b");
+        // bytes that aren't utf-8 get through as they are
+        assert_eq!(
+            escape(Path::new(OsStr::from_bytes(b"/caf\xe9.png"))),
+            "/caf%E9.png"
+        );
+    }
+
+    #[test]
+    fn numbers_names_before_the_extension() {
+        let path = Path::new("/home/me/photo.jpg");
+        assert_eq!(candidate_name(path, 1), Path::new("photo.jpg"));
+        assert_eq!(candidate_name(path, 2), Path::new("photo.2.jpg"));
+        assert_eq!(
+            candidate_name(Path::new("/home/me/notes"), 3),
+            Path::new("notes.3")
+        );
+        assert_eq!(
+            candidate_name(Path::new("/home/me/.hidden"), 2),
+            Path::new(".hidden.2")
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    io::{BufRead, BufReader, Read},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{debug, error};
+
+use crate::{ipc_spec::IPC_PATH, xdg};
+
+/// How long ssh gets to log in and set up the forward
+pub const TUNNEL_TIMEOUT: Duration = Duration::from_secs(15);
+
+fn default_remote_socket() -> String {
+    IPC_PATH.to_owned()
+}
+
+/// An xab on another machine, reached through `ssh -L`
+///
+/// ```toml
+/// [[ssh]]
+/// name = "living room"
+/// host = "me@htpc"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SshProfile {
+    pub name: String,
+    /// Anything ssh accepts, `user@host` or a Host from ~/.ssh/config
+    pub host: String,
+    #[serde(default = "default_remote_socket")]
+    pub remote_socket: String,
+    /// Forward through an already running ControlMaster instead of logging in again
+    pub control_socket: Option<PathBuf>,
+}
+
+impl fmt::Display for SshProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (ssh)", self.name)
+    }
+}
+
+/// A forwarded socket, the forward is torn down on drop
+#[derive(Debug)]
+pub struct Tunnel {
+    pub profile: SshProfile,
+    pub local_socket: PathBuf,
+    /// None when the forward lives in a ControlMaster
+    child: Option<Child>,
+}
+
+fn forward_spec(local_socket: &std::path::Path, remote_socket: &str) -> String {
+    format!("{}:{}", local_socket.display(), remote_socket)
+}
+
+impl Tunnel {
+    /// Blocks until the local socket is there, or ssh gives up
+    pub fn open(profile: &SshProfile) -> Result<Self> {
+        let local_socket = xdg::runtime_dir().join(format!(
+            "xab-gui-tunnel-{}-{}.sock",
+            std::process::id(),
+            profile
+                .name
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        ));
+        let forward = forward_spec(&local_socket, &profile.remote_socket);
+        let _ = std::fs::remove_file(&local_socket);
+
+        if let Some(control_socket) = &profile.control_socket {
+            let output = Command::new("ssh")
+                .arg("-S")
+                .arg(control_socket)
+                // a host starting with `-` is still only a host
+                .args(["-O", "forward", "-L", &forward, "--", &profile.host])
+                .output()
+                .with_context(|| "Failed to run ssh, is it installed?")?;
+            if !output.status.success() {
+                return Err(anyhow!(
+                    "ssh -O forward failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+            debug!(
+                "Forwarding {} through {}",
+                forward,
+                control_socket.display()
+            );
+            return Ok(Self {
+                profile: profile.clone(),
+                local_socket,
+                child: None,
+            });
+        }
+
+        let mut child = Command::new("ssh")
+            .args([
+                "-N",
+                // there's no terminal to ask for a password on
+                "-o",
+                "BatchMode=yes",
+                "-o",
+                "ExitOnForwardFailure=yes",
+                "-o",
+                "StreamLocalBindUnlink=yes",
+                "-L",
+                &forward,
+                "--",
+                &profile.host,
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| "Failed to run ssh, is it installed?")?;
+
+        let started = Instant::now();
+        while !local_socket.exists() {
+            if let Some(status) = child.try_wait()? {
+                let mut stderr = String::new();
+                if let Some(mut pipe) = child.stderr.take() {
+                    let _ = pipe.read_to_string(&mut stderr);
+                }
+                return Err(anyhow!("ssh exited ({status}): {}", stderr.trim()));
+            }
+            if started.elapsed() > TUNNEL_TIMEOUT {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(anyhow!(
+                    "Timed out waiting for ssh to forward {}",
+                    profile.remote_socket
+                ));
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        debug!("Forwarding {} to {}", forward, profile.host);
+        // read for as long as ssh runs, a full pipe would stall it
+        if let Some(stderr) = child.stderr.take() {
+            thread::spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    debug!("ssh: {line}");
+                }
+            });
+        }
+
+        Ok(Self {
+            profile: profile.clone(),
+            local_socket,
+            child: Some(child),
+        })
+    }
+}
+
+impl Drop for Tunnel {
+    fn drop(&mut self) {
+        debug!("Closing the tunnel to {}", self.profile.host);
+        match (&mut self.child, &self.profile.control_socket) {
+            (Some(child), _) => {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            (None, Some(control_socket)) => {
+                let forward = forward_spec(&self.local_socket, &self.profile.remote_socket);
+                let result = Command::new("ssh")
+                    .arg("-S")
+                    .arg(control_socket)
+                    .args(["-O", "cancel", "-L", &forward, "--", &self.profile.host])
+                    .output();
+                if let Err(err) = result {
+                    error!("Failed to cancel the forward: {err}");
+                }
+            }
+            (None, None) => {}
+        }
+        let _ = std::fs::remove_file(&self.local_socket);
+    }
+}
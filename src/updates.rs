@@ -0,0 +1,80 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{process::Command, time::Duration};
+use tracing::debug;
+
+use crate::net;
+
+/// Only asked when the user turned update checks on, nothing but the
+/// request itself is sent
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/MrCatNerd/xab-gui/releases/latest";
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Lines of the release notes shown in the banner
+const EXCERPT_LINES: usize = 4;
+
+#[derive(Deserialize)]
+struct LatestRelease {
+    tag_name: String,
+    html_url: String,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+/// A release newer than this build
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub version: String,
+    pub url: String,
+    /// The first few lines of the release notes
+    pub excerpt: Vec<String>,
+}
+
+/// `v1.2.3` into [1, 2, 3], anything after a `-` (pre-releases) is ignored
+fn version_numbers(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split('-')
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+fn excerpt(notes: &str) -> Vec<String> {
+    notes
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .take(EXCERPT_LINES)
+        .map(str::to_owned)
+        .collect()
+}
+
+/// The latest release if it's newer than this build
+pub fn check() -> Result<Option<Release>> {
+    let body = net::get("updates", LATEST_RELEASE_URL)?;
+    let latest: LatestRelease =
+        serde_json::from_slice(&body).with_context(|| "Failed to parse the latest release")?;
+    let current = env!("CARGO_PKG_VERSION");
+    debug!("Latest release is {}, this is {current}", latest.tag_name);
+    if version_numbers(&latest.tag_name) <= version_numbers(current) {
+        return Ok(None);
+    }
+    Ok(Some(Release {
+        excerpt: excerpt(latest.body.as_deref().unwrap_or_default()),
+        version: latest.tag_name,
+        url: latest.html_url,
+    }))
+}
+
+/// Opens the release page in the browser
+pub fn open(release: &Release) -> Result<()> {
+    let mut child = Command::new("xdg-open")
+        .arg(&release.url)
+        .spawn()
+        .with_context(|| "Failed to run xdg-open")?;
+    // reaped off the gui thread, some browsers keep it around
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use std::{env, path::PathBuf};
+
+/// Resolves an XDG base directory, falling back to `$HOME/<fallback>` if the variable is unset
+fn base_dir(var: &str, fallback: &str) -> PathBuf {
+    env::var_os(var)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env::var_os("HOME").unwrap_or_default()).join(fallback))
+}
+
+/// `$XDG_STATE_HOME/xab-gui`
+pub fn state_dir() -> PathBuf {
+    base_dir("XDG_STATE_HOME", ".local/state").join("xab-gui")
+}
+
+/// `$XDG_CACHE_HOME/xab-gui`
+pub fn cache_dir() -> PathBuf {
+    base_dir("XDG_CACHE_HOME", ".cache").join("xab-gui")
+}
+
+/// `$XDG_DATA_HOME` itself, for files other apps look for
+pub fn data_home() -> PathBuf {
+    base_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// `$XDG_DATA_HOME/xab-gui`
+pub fn data_dir() -> PathBuf {
+    data_home().join("xab-gui")
+}
+
+/// `$XDG_CONFIG_HOME/xab-gui`
+pub fn config_dir() -> PathBuf {
+    base_dir("XDG_CONFIG_HOME", ".config").join("xab-gui")
+}
+
+/// `$XDG_RUNTIME_DIR`, falling back to the temp dir
+pub fn runtime_dir() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir)
+}